@@ -0,0 +1,31 @@
+use std::env;
+use std::fs::File;
+use std::path::PathBuf;
+
+use weaklink_build::{Config, SymbolStub};
+
+fn main() {
+    println!("cargo:rustc-env=TARGET={}", env::var("TARGET").unwrap());
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    // Two stub libraries for the same dylib: one calling through the symbol table, the other patched.
+    for (name, patched) in [("table", false), ("patched", true)] {
+        let stub = SymbolStub {
+            import_name: "add_0".to_string(),
+            export_name: format!("{name}_add_0"),
+            ..SymbolStub::default()
+        };
+        let group_name = format!("{name}_calls");
+        let mut config = Config::new(&format!("{name}_stub"));
+        config.add_symbol_group(&group_name, [stub]).unwrap();
+        if patched {
+            config.patched_groups = vec![group_name];
+        }
+
+        let source_path = out_dir.join(format!("{name}_stubs.rs"));
+        let mut source = File::create(&source_path).unwrap();
+        config.generate_source(&mut source);
+        println!("cargo:rerun-if-changed={}", source_path.display());
+    }
+}
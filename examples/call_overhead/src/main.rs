@@ -0,0 +1,79 @@
+//! Measures the overhead of calls via weaklink stubs, compared to calls via function pointers obtained from `dlsym`.
+//!
+//! This is an informal measurement: each kind of call is timed once, in a loop, without warm-up or statistics, so
+//! the results vary from run to run, and between machines.
+//!
+//! Run with `cargo run --release -p call_overhead [iterations]`.
+
+#[allow(clippy::all)]
+mod table_stubs {
+    include!(concat!(env!("OUT_DIR"), "/table_stubs.rs"));
+}
+
+#[allow(clippy::all)]
+mod patched_stubs {
+    include!(concat!(env!("OUT_DIR"), "/patched_stubs.rs"));
+}
+
+use std::env;
+use std::ffi::CString;
+use std::hint::black_box;
+use std::time::Instant;
+
+extern "C" {
+    fn table_add_0(a: u32) -> u32;
+    fn patched_add_0(a: u32) -> u32;
+}
+
+const DEFAULT_ITERATIONS: u32 = 100_000_000;
+
+fn main() {
+    let iterations = match env::args().nth(1) {
+        Some(arg) => arg.parse().expect("Invalid number of iterations"),
+        None => DEFAULT_ITERATIONS,
+    };
+
+    let path = weaklink_build::locate::dylib("exporter").unwrap();
+    let handle = weaklink::loading::load_library(&path).unwrap();
+    let name = CString::new("add_0").unwrap();
+    let address = weaklink::loading::find_symbol(handle, &name).unwrap();
+    let direct: extern "C" fn(u32) -> u32 = unsafe { std::mem::transmute(address) };
+
+    for library in [&table_stubs::table_stub, &patched_stubs::patched_stub] {
+        library.set_handle(handle);
+    }
+    table_stubs::table_calls.resolve_global().unwrap();
+    patched_stubs::patched_calls.resolve_global().unwrap();
+
+    let results = [
+        ("direct", measure(iterations, |a| direct(a))),
+        ("stub", measure(iterations, |a| unsafe { table_add_0(a) })),
+        ("patched stub", measure(iterations, |a| unsafe { patched_add_0(a) })),
+    ];
+    for (name, ns_per_call) in results {
+        println!("{name:>14}: {ns_per_call:.3} ns/call");
+    }
+}
+
+// Returns the average duration of a call in nanoseconds.
+fn measure(iterations: u32, f: impl Fn(u32) -> u32) -> f64 {
+    let start = Instant::now();
+    let mut a = 0;
+    for _ in 0..iterations {
+        a = f(black_box(a));
+    }
+    black_box(a);
+    start.elapsed().as_nanos() as f64 / iterations as f64
+}
+
+#[test]
+fn test_main() {
+    let path = weaklink_build::locate::dylib("exporter").unwrap();
+    table_stubs::table_stub.load_from(&path).unwrap();
+    patched_stubs::patched_stub.load_from(&path).unwrap();
+    table_stubs::table_calls.resolve_global().unwrap();
+    patched_stubs::patched_calls.resolve_global().unwrap();
+    assert_eq!(unsafe { table_add_0(1) }, 1);
+    assert_eq!(unsafe { patched_add_0(1) }, 1);
+    assert!(measure(10, |a| unsafe { patched_add_0(a) }).is_finite());
+}
@@ -9,23 +9,27 @@ fn main() -> Result<(), Error> {
     let args = Vec::from_iter(env::args());
     let exports = dylib_exports(Path::new(&args[1]))?;
     for exp in &exports {
-        println!("{} {:?}", exp.name, exp.section);
+        println!("{} {:?} {:?}", exp.name, exp.section, exp.kind);
     }
     Ok(())
 }
 
 #[test]
 fn test_exports() {
-    let path = utils::find_deps_dylib("exporter").unwrap();
+    let path = weaklink_build::locate::dylib("exporter").unwrap();
     let exports = dylib_exports(&path).unwrap();
 
     let mut adds_found = 0;
     for exp in exports {
-        println!("name={}, section={:?}", exp.name, exp.section);
+        println!("name={}, section={:?}, kind={:?}", exp.name, exp.section, exp.kind);
         let exp_name = exp.name.trim_start_matches('_').trim_end_matches(|c: char| c.is_numeric());
         if exp_name == "add_" {
+            assert_eq!(exp.kind, weaklink_build::exports::ExportKind::Code);
             adds_found += 1;
         }
+        if exp_name == "plugin_version" {
+            assert_eq!(exp.kind, weaklink_build::exports::ExportKind::Data);
+        }
     }
     assert_eq!(adds_found, 10);
 }
@@ -1,12 +1,23 @@
 use std::env;
 use std::path::Path;
 
-use weaklink_build::imports::archive_imports;
+use weaklink_build::imports::{archive_import_sites, archive_imports};
 
 type Error = Box<dyn std::error::Error>;
 
+// Usage: dump_imports <archive> [<symbol>]
+// If a symbol is specified, prints the sites referencing it instead.
 fn main() -> Result<(), Error> {
     let args = Vec::from_iter(env::args());
+    if let Some(symbol) = args.get(2) {
+        let sites = archive_import_sites(Path::new(&args[1]))?;
+        for site in sites.get(symbol).into_iter().flatten() {
+            let object = site.object.as_deref().unwrap_or("");
+            let function = site.function.as_deref().unwrap_or("?");
+            println!("{object} {} {function}", site.section);
+        }
+        return Ok(());
+    }
     let imports = archive_imports(Path::new(&args[1]))?;
     for imp in &imports {
         println!("{}", imp.name);
@@ -16,7 +27,7 @@ fn main() -> Result<(), Error> {
 
 #[test]
 fn test_imports() {
-    let path = utils::find_latest_deps_artifact(|name| name.contains("importer") && name.ends_with(".rlib")).unwrap();
+    let path = weaklink_build::locate::rlib("importer").unwrap();
     let imports = archive_imports(&path).unwrap();
 
     let mut adds_found = 0;
@@ -29,3 +40,71 @@ fn test_imports() {
     }
     assert_eq!(adds_found, 10);
 }
+
+#[test]
+fn test_parsed_imports() {
+    let path = weaklink_build::locate::rlib("importer").unwrap();
+    let data = weaklink_build::ParsedBinary::read(&path).unwrap();
+    let binary = weaklink_build::ParsedBinary::parse(&data).unwrap();
+    let mut names = Vec::from_iter(binary.imports().unwrap().into_iter().map(|imp| imp.name));
+    let mut expected = Vec::from_iter(archive_imports(&path).unwrap().into_iter().map(|imp| imp.name));
+    names.sort();
+    expected.sort();
+    assert_eq!(names, expected);
+    assert!(binary.exports().is_err());
+}
+
+#[test]
+fn test_plugin_candidates() {
+    use weaklink_build::imports::{plugin_candidates, Import, ImportCategory};
+
+    let imports = [
+        "memcpy",
+        "pthread_create",
+        "__rust_alloc",
+        "__udivti3",
+        "_Unwind_Resume",
+        "add_1",
+        "plugin_init",
+    ];
+    let imports = Vec::from_iter(imports.iter().map(|name| Import { name: name.to_string() }));
+    let categories = Vec::from_iter(imports.iter().map(|imp| imp.category_for("x86_64-unknown-linux-gnu")));
+    use ImportCategory::*;
+    assert_eq!(categories, [System, System, Runtime, Runtime, Runtime, Other, Other]);
+    let mac_import = Import {
+        name: "_memcpy".to_string(),
+    };
+    assert_eq!(mac_import.category_for("aarch64-apple-darwin"), System);
+
+    let candidates = plugin_candidates(imports.clone(), &[]).unwrap();
+    assert_eq!(
+        Vec::from_iter(candidates.iter().map(|imp| imp.name.as_str())),
+        ["add_1", "plugin_init"]
+    );
+
+    // Symbols of the exporter are no longer candidates if it is considered a system library.
+    let exporter = weaklink_build::locate::dylib("exporter").unwrap();
+    let candidates = plugin_candidates(imports, &[&exporter]).unwrap();
+    assert_eq!(
+        Vec::from_iter(candidates.iter().map(|imp| imp.name.as_str())),
+        ["plugin_init"]
+    );
+}
+
+#[test]
+fn test_import_sites() {
+    let path = weaklink_build::locate::rlib("importer").unwrap();
+    let sites = archive_import_sites(&path).unwrap();
+    assert_eq!(sites.len(), archive_imports(&path).unwrap().len());
+
+    for (name, sites) in sites {
+        println!("{name}: {sites:?}");
+        let caller = match name.trim_start_matches('_') {
+            "add_0" | "add_1" | "add_2" | "add_3" | "add_4" => "addition1",
+            _ => "addition2",
+        };
+        assert!(sites
+            .iter()
+            .any(|site| site.function.as_ref().is_some_and(|function| function.contains(caller))));
+    }
+}
@@ -1,7 +1,10 @@
 pub use seq_macro::seq;
+use std::ffi::{c_char, c_void, CStr};
+use std::ptr;
 
 seq! {N in 0..10 {
     #[no_mangle]
+    #[allow(clippy::identity_op)]
     pub extern "C" fn add_~N(a: u32) -> u32 {
         a + N
     }
@@ -9,3 +12,80 @@ seq! {N in 0..10 {
 
 #[no_mangle]
 pub static SOMEDATA: i32 = 123;
+
+weaklink_export::plugin_version!("2.3");
+
+weaklink_export::export_manifest! {
+    version: "2.3",
+    groups: ["base", "extended", "signed", "drifted"],
+}
+
+/// Registration function, which serves symbols that aren't in the dynamic symbol table.
+///
+/// # Safety
+/// `name` must point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn plugin_get_api(name: *const c_char) -> *const c_void {
+    let fns = seq!(N in 0..10 { [#(add_~N as *const c_void,)*] });
+    let name = CStr::from_ptr(name).to_bytes();
+    let index = name
+        .strip_prefix(b"hidden_add_")
+        .and_then(|n| std::str::from_utf8(n).ok()?.parse::<usize>().ok());
+    match index.and_then(|index| fns.get(index)) {
+        Some(address) => *address,
+        None => ptr::null(),
+    }
+}
+
+// Table of functions, for plugin ABIs that return a struct of pointers instead of exporting each function.
+static VTABLE: [extern "C" fn(u32) -> u32; 3] = [add_5, add_6, add_8];
+
+#[no_mangle]
+pub extern "C" fn plugin_vtable() -> *const *const c_void {
+    VTABLE.as_ptr() as *const *const c_void
+}
+
+/// A COM-style object, whose first member points to its table of methods.
+#[repr(C)]
+pub struct Object {
+    methods: &'static [extern "C" fn(&Object, u32) -> u32; 2],
+    base: u32,
+}
+
+extern "C" fn object_add(object: &Object, a: u32) -> u32 {
+    object.base + a
+}
+
+extern "C" fn object_mul(object: &Object, a: u32) -> u32 {
+    object.base * a
+}
+
+static OBJECT: Object = Object {
+    methods: &[object_add, object_mul],
+    base: 100,
+};
+
+// Factory of the object, in the manner of `DllGetClassObject`.
+#[no_mangle]
+pub extern "C" fn plugin_create_object() -> *const c_void {
+    &OBJECT as *const Object as *const c_void
+}
+
+/// Reports the signature hashes of the exports, with `add_1` having drifted from what hosts expect.
+///
+/// # Safety
+/// `name` must point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn plugin_signature_hash(name: *const c_char) -> u64 {
+    match CStr::from_ptr(name).to_bytes() {
+        b"add_1" => weaklink_export::signature_hash("fn(u64) -> u64"),
+        name if name.starts_with(b"add_") => weaklink_export::signature_hash("fn(u32) -> u32"),
+        _ => 0,
+    }
+}
+
+// Signatures of the groups of `signed_stub`, with `drifted` having changed since hosts were built.
+weaklink_export::export_signatures! {
+    "signed" => { "add_3" => "fn(u32) -> u32", "add_4" => "fn(u32) -> u32" },
+    "drifted" => { "add_5" => "fn(u32, u32) -> u32" },
+}
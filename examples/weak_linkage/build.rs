@@ -1,18 +1,19 @@
 use std::collections::HashSet;
 use std::env;
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use weaklink_build::{exports, imports};
-use weaklink_build::{Config, SymbolStub};
+use weaklink_build::{Config, LibcFlavor, SymbolStub, RUNTIME_SYMBOLS};
 
 fn main() {
     println!("cargo:rustc-env=TARGET={}", std::env::var("TARGET").unwrap());
 
-    let path = utils::find_deps_dylib("exporter").unwrap();
+    let path = weaklink_build::locate::dylib("exporter").unwrap();
+    let dylib_file_name = path.file_name().unwrap().to_str().unwrap().to_string();
     let exports = exports::dylib_exports(&path).unwrap();
 
-    let path = utils::find_latest_deps_artifact(|name| name.contains("importer") && name.ends_with(".rlib")).unwrap();
+    let path = weaklink_build::locate::rlib("importer").unwrap();
     let imports = imports::archive_imports(&path).unwrap();
 
     let imports_str = HashSet::<String>::from_iter(imports.iter().map(|i| i.name.clone()));
@@ -21,22 +22,194 @@ fn main() {
     let mut stubs = exports
         .into_iter()
         .filter(|e| common.contains(&e.name))
-        .map(|e| SymbolStub::new(&e.name))
+        .map(|e| match e.name.as_str() {
+            "add_9" => SymbolStub::new(&e.name).deprecated_since("2.0"),
+            "add_0" | "add_1" => SymbolStub::new(&e.name).signature("fn(u32) -> u32"),
+            _ => SymbolStub::new(&e.name),
+        })
         .collect::<Vec<_>>();
     stubs.push(SymbolStub::new_data("get_SOMEDATA", "SOMEDATA"));
 
     println!("cargo:warning=Found {} common symbols", stubs.len());
 
     let mut config = Config::new("exporter_stub");
+    config.deny_symbols(RUNTIME_SYMBOLS.iter().copied());
     config.add_symbol_group("base", stubs).unwrap();
 
     let missing = vec![SymbolStub::new("foo"), SymbolStub::new_data("get_bar", "bar")];
     config.add_symbol_group("missing", missing).unwrap();
+    config.add_symbol_group("optional", [SymbolStub::new("add_9")]).unwrap();
+    config.set_min_version("optional", "2.1").unwrap();
+    config.group("extended").add("add_1").add_optional("foo").depends_on("base");
+    config.group("needs_missing").depends_on("missing");
+    config.add_symbols([SymbolStub::new("add_2")]).unwrap();
+    config.group("native").add("native_present");
+    config.group("native_missing").add("native_absent");
+    config.group("extras").add("extra_api");
+    config.derive_groups_from_archives(&[("importer", &path)]).unwrap();
+    config.add_name_variant("unmarked", "no_such_marker").unwrap();
+    config.add_name_variant("renamed", "add_0").unwrap();
+    let variant_add = SymbolStub::new("variant_add").variant("unmarked", "add_5").variant("renamed", "add_4");
+    config.add_symbol_group("variants", [variant_add]).unwrap();
+    config.group_cfg("extras", "feature = \"extras\"").unwrap();
+    config.hot_groups = vec!["missing".to_string()];
+    config.patched_groups = vec!["base".to_string()];
+    config.required_groups = vec!["extended".to_string(), "base".to_string()];
+    if !env::var("TARGET").unwrap().contains("windows") {
+        config.native_weak_groups = vec!["native".to_string(), "native_missing".to_string()];
+    }
+    config.group_sections = true;
+    config.c_api = true;
+    config.dlopen_names = vec![dylib_file_name.clone()];
+    config.configure_hook = Some("crate::configure_exporter".to_string());
+    config.signature_query = Some("plugin_signature_hash".to_string());
+    config.group_name_tables = true;
+    config.dependencies = vec![dylib_file_name.clone()];
 
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    // Imports of the importer left without stubs, which `test_check_imports` expects none of.
+    let unstubbed = config.check_imports(&[&path]).unwrap();
+    let unstubbed = unstubbed.iter().map(|import| format!("{}\n", import.name)).collect::<String>();
+    std::fs::write(out_dir.join("unstubbed_imports.txt"), unstubbed).unwrap();
     let source_path = out_dir.join("stubs.rs");
     let mut source = File::create(&source_path).unwrap();
     config.generate_source(&mut source);
+    config.generate_c_header(&mut File::create(out_dir.join("stubs.h")).unwrap());
+    // C client of the management API, compiled against the header, and linked to the stubs.  See `test_c_client`.
+    cc::Build::new().file("src/c_client.c").include(&out_dir).compile("c_client");
+    println!("cargo:rerun-if-changed=src/c_client.c");
+    config.generate_bindings(&mut File::create(out_dir.join("stub_bindings.rs")).unwrap());
+    config.generate_manifest(&mut File::create(out_dir.join("stubs.manifest")).unwrap());
+    let mut call_graph_file = File::create(out_dir.join("call_graph.json")).unwrap();
+    config.generate_call_graph(&[&path], &mut call_graph_file).unwrap();
     println!("cargo:rerun-if-changed={}", source_path.display());
     println!("cargo:warning=Generated {}", source_path.display());
+
+    // The same library under other export names, with compressed symbol names.
+    let mut config = fixture("compressed");
+    config.compress_names = true;
+    generate(&config, &out_dir);
+
+    // Likewise, with hashed symbol names, which are set from the manifest at runtime.
+    let mut config = fixture("hashed");
+    config.hash_names = true;
+    generate(&config, &out_dir);
+    config.generate_manifest(&mut File::create(out_dir.join("hashed_stubs.manifest")).unwrap());
+
+    // Likewise, with obfuscated symbol names, whose key is supplied at runtime.
+    let mut config = fixture("obfuscated");
+    config.obfuscation_key = Some(0x5eed_0bf5_ca7e_d000);
+    generate(&config, &out_dir);
+
+    // Symbols that aren't in the dynamic symbol table, but are served by a registration function.
+    let mut config = Config::new("bootstrap_stub");
+    let stubs = (0..10).map(|i| SymbolStub::new_data(&format!("bootstrap_add_{i}"), &format!("hidden_add_{i}")));
+    config.add_symbol_group("bootstrapped", stubs).unwrap();
+    config.bootstrap_export = Some("plugin_get_api".to_string());
+    generate(&config, &out_dir);
+
+    // Functions obtained from a struct returned by the library, which are called through their stubs as usual.
+    let mut config = Config::new("vtable_stub");
+    let stubs = [5, 6, 8].iter().enumerate();
+    let stubs = stubs.map(|(index, n)| SymbolStub::new(&format!("vtable_add_{n}")).vtable_slot("plugin_vtable", index));
+    config.add_symbol_group("vtable", stubs).unwrap();
+    generate(&config, &out_dir);
+
+    // Methods of an interface created by a factory callback.
+    let mut config = Config::new("interface_stub");
+    let stubs = ["object_add", "object_mul"].iter().enumerate();
+    let stubs = stubs.map(|(index, name)| SymbolStub::new(name).interface_slot("object", index));
+    config.add_symbol_group("object_methods", stubs).unwrap();
+    generate(&config, &out_dir);
+
+    // Symbols that are all resolved once the library is loaded, some of which are missing.
+    let mut config = fixture("eager");
+    config.group("absent").add_data("eager_foo", "foo").add_data("eager_bar", "bar");
+    config.group("needs_absent").add_data("eager_add_1", "add_1").depends_on("absent");
+    config.eager_binding = true;
+    generate(&config, &out_dir);
+
+    // Symbols of the C library that the program is linked against, looked up via the main program.
+    let mut config = Config::main_program("main_stub");
+    config.group("environment").add_data("main_getenv", "getenv");
+    config.group("unavailable").add_data("main_unavailable", "weaklink_no_such_function");
+    generate(&config, &out_dir);
+
+    // Symbols of the vDSO, whose names depend on the architecture.
+    let mut config = Config::vdso("vdso_stub");
+    let clock_gettime = match env::var("CARGO_CFG_TARGET_ARCH").unwrap().as_str() {
+        "aarch64" => "__kernel_clock_gettime",
+        _ => "__vdso_clock_gettime",
+    };
+    config.group("time").add_data("vdso_clock_gettime", clock_gettime);
+    generate(&config, &out_dir);
+
+    // Symbols bound to a version of their name, looked up in glibc, whose base version depends on the architecture.
+    let mut config = Config::main_program("versioned_stub");
+    let base_version = match env::var("CARGO_CFG_TARGET_ARCH").unwrap().as_str() {
+        "x86_64" => "GLIBC_2.2.5",
+        "aarch64" | "powerpc64" => "GLIBC_2.17",
+        "riscv64" => "GLIBC_2.27",
+        "loongarch64" => "GLIBC_2.36",
+        "arm" => "GLIBC_2.4",
+        _ => "GLIBC_2.0",
+    };
+    let version = SymbolStub::new_data("versioned_libc_version", "gnu_get_libc_version").version(base_version);
+    config.group("versioned").add_stub(version);
+    let release = SymbolStub::new_data("future_libc_release", "gnu_get_libc_release").version("GLIBC_999");
+    config.group("future_version").add_stub(release);
+    generate(&config, &out_dir);
+
+    // Names of the library built for the C library of the target, and of one built for another C library.
+    let mut config = fixture("libc");
+    let target_env = env::var("CARGO_CFG_TARGET_ENV").unwrap();
+    let (flavor, other_flavor) = match (env::var("CARGO_CFG_TARGET_OS").unwrap().as_str(), target_env.as_str()) {
+        ("linux", "gnu") => (LibcFlavor::Glibc, LibcFlavor::Musl),
+        ("linux", "musl") => (LibcFlavor::Musl, LibcFlavor::Glibc),
+        _ => (LibcFlavor::Other, LibcFlavor::Glibc),
+    };
+    config.add_dylib_names_for(other_flavor, ["{other_path}"]);
+    config.add_dylib_names_for(flavor, ["{path}"]);
+    generate(&config, &out_dir);
+    println!("cargo:rustc-env=WEAKLINK_LIBC_FLAVOR={}", flavor.tag());
+
+    // Library closed while resolutions race with the shutdown.
+    let mut config = fixture("closing");
+    config.group("racing").add_data("closing_racing_add_1", "add_1");
+    generate(&config, &out_dir);
+
+    // Copy of the library loaded in a namespace of its own.
+    let mut config = fixture("isolated");
+    config.dylib_names = vec!["{path}".to_string()];
+    generate(&config, &out_dir);
+
+    // Library that legacy code opens by itself, which the dlopen shim redirects to the stubs.
+    let mut config = fixture("shim");
+    config.dlopen_names = vec![dylib_file_name];
+    generate(&config, &out_dir);
+
+    // Groups whose signatures are checked against the table exported by the library.
+    let mut config = Config::new("signed_stub");
+    let stub =
+        |n: u32| SymbolStub::new_data(&format!("signed_add_{n}"), &format!("add_{n}")).signature("fn(u32) -> u32");
+    config.add_symbol_group("signed", [stub(3), stub(4)]).unwrap();
+    config.add_symbol_group("drifted", [stub(5)]).unwrap();
+    config.signature_handshake = true;
+    config.dylib_names = vec!["{plugin_dir}/{plugin_file}".to_string()];
+    generate(&config, &out_dir);
+}
+
+// Config of a library wrapping the exporter, for the test of a single feature, with data stubs of `add_0` to `add_9`
+// in a group named after the feature.  Stubs are exported under the feature's name, to keep them unique.
+fn fixture(feature: &str) -> Config {
+    let mut config = Config::new(&format!("{feature}_stub"));
+    let stubs = (0..10).map(|i| SymbolStub::new_data(&format!("{feature}_add_{i}"), &format!("add_{i}")));
+    config.add_symbol_group(feature, stubs).unwrap();
+    config
+}
+
+// Generates the stubs of a library, e.g. `compressed_stub`, into the file that `main.rs` includes, e.g.
+// `compressed_stubs.rs`.
+fn generate(config: &Config, out_dir: &Path) {
+    config.generate_source(&mut File::create(out_dir.join(format!("{}s.rs", config.name))).unwrap());
 }
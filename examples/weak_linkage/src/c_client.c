@@ -0,0 +1,20 @@
+/* Client of the management API of the stubs, compiled against the generated header.  See `test_c_client`. */
+
+#include <stddef.h>
+
+#include "stubs.h"
+
+/* Loads the library from `path` and resolves its groups, returning 0 on success, or the number of the failed check. */
+int c_client_run(const char *path) {
+    /* No default names are configured, so the library is only found at `path`. */
+    if (exporter_stub_load()) return 1;
+    if (exporter_stub_last_error() == NULL || exporter_stub_last_error_code() == 0) return 2;
+    if (!exporter_stub_load_from(path)) return 3;
+    if (exporter_stub_last_error() != NULL || exporter_stub_last_error_code() != 0) return 4;
+    if (!exporter_stub_resolve_base()) return 5;
+    if (get_SOMEDATA() == NULL) return 6;
+    if (exporter_stub_resolve_missing()) return 7;
+    if (exporter_stub_last_error() == NULL || exporter_stub_last_error_code() == 0) return 8;
+    if (weaklink_dlerror() != NULL) return 9;
+    return 0;
+}
@@ -1,13 +1,176 @@
+#[allow(clippy::all)]
 mod stubs {
     include!(concat!(env!("OUT_DIR"), "/stubs.rs"));
 }
+mod stub_bindings {
+    include!(concat!(env!("OUT_DIR"), "/stub_bindings.rs"));
+}
+
+// Declares the modules of the stubs that `build.rs` generates for the tests of single features.
+macro_rules! fixture_stubs {
+    ($($module:ident),*) => {
+        $(
+            #[allow(clippy::all)]
+            mod $module {
+                include!(concat!(env!("OUT_DIR"), "/", stringify!($module), ".rs"));
+            }
+        )*
+    };
+}
+
+fixture_stubs!(
+    compressed_stubs,
+    hashed_stubs,
+    obfuscated_stubs,
+    bootstrap_stubs,
+    vtable_stubs,
+    interface_stubs,
+    eager_stubs,
+    main_stubs,
+    vdso_stubs,
+    libc_stubs,
+    versioned_stubs,
+    closing_stubs,
+    isolated_stubs,
+    signed_stubs,
+    shim_stubs
+);
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use weaklink::{ErrorCode, ErrorExt};
+
+// Bound to the stubs' weak reference in place of a library export.
+#[no_mangle]
+pub extern "C" fn native_present() -> u32 {
+    42
+}
+
+// Number of calls of the configuration hook of the stubs.
+static CONFIGURED: AtomicUsize = AtomicUsize::new(0);
+
+// Runs on first use of the stubs, before any of the settings below.
+fn configure_exporter(options: &mut weaklink::Options) {
+    CONFIGURED.fetch_add(1, Ordering::Relaxed);
+    assert!(options.auto_load && options.deprecation_handler.is_none());
+    options.dylib_paths.push(Path::new("missing").join("exporter.dylib"));
+    // Set by `test_init_required` for its subprocess, which loads the library by the configured paths.
+    if let Some(path) = std::env::var_os("WEAKLINK_INIT_REQUIRED") {
+        options.dylib_paths.push(path.into());
+    }
+}
 
 fn main() {
     println!("Starting");
+    stubs::exporter_stub.init_checked();
+    assert_eq!(CONFIGURED.load(Ordering::Relaxed), 0);
+
+    static DEPRECATED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    stubs::exporter_stub.set_deprecation_handler(|library, symbol, since| {
+        DEPRECATED.lock().unwrap().push(format!("{library}: {symbol:?} since {since}"));
+    });
+    assert_eq!(CONFIGURED.load(Ordering::Relaxed), 1);
+    assert!(stubs::exporter_stub.configure_once(configure_exporter).is_err());
+    static MISMATCHED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    stubs::exporter_stub.set_signature_mismatch_handler(|_, symbol, expected, found| {
+        assert_eq!(expected, weaklink::signature_hash("fn(u32) -> u32"));
+        assert_eq!(found, weaklink::signature_hash("fn(u64) -> u64"));
+        MISMATCHED.lock().unwrap().push(symbol.to_string_lossy().into_owned());
+    });
+    static FILTERED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    stubs::exporter_stub.set_address_filter(|symbol, address| {
+        FILTERED.lock().unwrap().push(symbol.to_string_lossy().into_owned());
+        address
+    });
+    static EVENTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    stubs::exporter_stub.set_resolution_hook(|event| {
+        let event = match event {
+            weaklink::HookEvent::Load { error: None, .. } => "loaded".to_string(),
+            weaklink::HookEvent::Load { error: Some(_), .. } => "load failed".to_string(),
+            weaklink::HookEvent::Resolved { symbol, .. } => format!("resolved {}", symbol.to_string_lossy()),
+            weaklink::HookEvent::Failed { symbol, .. } => format!("failed {}", symbol.to_string_lossy()),
+            _ => return,
+        };
+        EVENTS.lock().unwrap().push(event);
+    });
+
+    // Test loading a group's dylib on demand, without loading the library's default one
+    let path = weaklink_build::locate::dylib("exporter").unwrap();
+    assert!(stubs::optional.resolve_loading_from(&path.with_file_name("missing.so")).is_err());
+    let err = stubs::optional.resolve_loading_from(&path).err().unwrap();
+    assert_eq!(err.code(), ErrorCode::VersionTooOld);
+    let err = err.downcast_ref::<weaklink::VersionTooOld>().unwrap();
+    assert_eq!((err.required, err.found.as_deref()), ("2.1", None));
+    stubs::exporter_stub.set_plugin_version("2.0.9");
+    assert!(stubs::optional.resolve_loading_from(&path).is_err());
+    stubs::exporter_stub.set_plugin_version("2.10");
+    assert!(stubs::optional.resolve_loading_from(&path).is_ok());
+    assert!(stubs::optional.resolve().is_ok());
+    assert!(stubs::exporter_stub.handle().is_none());
+    assert_eq!(*DEPRECATED.lock().unwrap(), [r#"exporter_stub: "add_9" since 2.0"#]);
+    assert_eq!(*FILTERED.lock().unwrap(), ["add_9"]);
 
-    let path = utils::find_deps_dylib("exporter").unwrap();
+    // Test natively weak-linked symbols, which don't require loading the library
+    #[cfg(not(windows))]
+    {
+        let token = stubs::native.resolve().unwrap();
+        assert_eq!(
+            stubs::exporter_stub.symbol_address(stubs::sym_index::native_present),
+            Some(native_present as *const () as usize)
+        );
+        drop(token);
+        assert!(stubs::native_missing.resolve().is_err());
+        assert!(stubs::exporter_stub.handle().is_none());
+    }
+
+    let recording = Path::new(concat!(env!("OUT_DIR"), "/recording.txt"));
+    stubs::exporter_stub.record_to(recording).unwrap();
+
+    // Test that implicit loading can be disabled
+    stubs::exporter_stub.set_auto_load(false);
+    assert!(stubs::base.resolve().is_err());
+
+    let path = weaklink_build::locate::dylib("exporter").unwrap();
     println!("Loading {}", path.display());
-    stubs::exporter_stub.load_from(&path).unwrap();
+    let elsewhere = path.parent().unwrap().join("elsewhere").join(path.file_name().unwrap());
+    assert!(stubs::exporter_stub.load_with_dependencies(&elsewhere).is_err());
+    assert!(stubs::exporter_stub.handle().is_none());
+
+    // Test rejecting the library by its manifest, before loading it
+    stubs::exporter_stub.set_manifest_check(|manifest| match manifest.provides("missing") {
+        true => Ok(()),
+        false => Err("the plugin doesn't provide the missing group".into()),
+    });
+    let err = stubs::exporter_stub.load_with_dependencies(&path).err().unwrap();
+    assert_eq!(err.code(), ErrorCode::PluginRejected);
+    assert!(stubs::exporter_stub.handle().is_none());
+    stubs::exporter_stub.set_manifest_check(|manifest| match manifest.provides("base") {
+        true => Ok(()),
+        false => Err("the plugin doesn't provide the base group".into()),
+    });
+
+    // Test rejecting the library once loaded, which unloads it before its handle is published
+    stubs::exporter_stub.set_post_load_check(|handle| {
+        weaklink::loading::find_symbol(handle, &std::ffi::CString::new("plugin_status")?).map(drop)
+    });
+    let err = stubs::exporter_stub.load_with_dependencies(&path).err().unwrap();
+    assert_eq!(err.code(), ErrorCode::PluginRejected);
+    assert!(err.to_string().contains("failed its post-load check"), "{err}");
+    assert!(stubs::exporter_stub.handle().is_none());
+    stubs::exporter_stub.set_post_load_check(|handle| {
+        weaklink::loading::find_symbol(handle, &std::ffi::CString::new("add_0")?).map(drop)
+    });
+    stubs::exporter_stub.load_with_dependencies(&path).unwrap();
+    stubs::exporter_stub.pin().unwrap();
+    #[cfg(unix)]
+    assert_eq!(
+        weaklink::loading::module_version(stubs::exporter_stub.handle().unwrap()).as_deref(),
+        Some("2.3")
+    );
+    assert_eq!(stubs::exporter_stub.plugin_version().as_deref(), Some("2.10"));
+    stubs::exporter_stub.set_validity_checks(true);
 
     // Test scoped resolution
     let token = stubs::base.resolve().unwrap();
@@ -15,17 +178,357 @@ fn main() {
     println!("result 1: {}", result);
     drop(token);
 
-    #[cfg(feature = "checked")]
-    assert_eq!(unsafe { importer::get_SOMEDATA() }, std::ptr::null());
+    // Test that signature drift of the plugin is reported, in debug builds
+    let expected: &[&str] = if cfg!(debug_assertions) { &["add_1"] } else { &[] };
+    assert_eq!(*MISMATCHED.lock().unwrap(), expected);
+
+    // Test token utilities
+    let token = stubs::base.resolve().unwrap();
+    let clone = token.clone();
+    drop(token);
+    assert_eq!(importer::addition1(0), 10);
+    assert!(!clone.is_permanent());
+    let weak = clone.downgrade();
+    let token = weak.upgrade().unwrap();
+    assert!(std::ptr::eq(token.group(), &stubs::base));
+    assert_ne!(unsafe { importer::get_SOMEDATA() }, std::ptr::null());
+    let data = unsafe { stub_bindings::get_SOMEDATA() } as *const i32;
+    assert_eq!(data, unsafe { importer::get_SOMEDATA() });
+    drop(token);
+
+    // Test using a token on another thread
+    let token = stubs::base.resolve().unwrap();
+    let result = std::thread::spawn(move || {
+        let result = importer::addition1(0);
+        drop(token);
+        result
+    });
+    assert_eq!(result.join().unwrap(), 10);
 
     // Test resolve_global()
-    stubs::base.resolve().unwrap().mark_permanent();
+    stubs::base.resolve_global().unwrap();
+    assert!(stubs::base.resolve().unwrap().is_permanent());
     let result = importer::addition2(0);
     println!("result 2: {}", result);
 
-    // Test resolution of missing symbols
+    // Test name variants, of which the first one whose marker the dylib exports is selected
+    assert_eq!(stubs::exporter_stub.name_variant(), Some("renamed"));
+    stubs::variants.resolve_global().unwrap();
+    let address = stubs::exporter_stub.symbol_address(stubs::sym_index::variant_add).unwrap();
+    let variant_add: extern "C" fn(u32) -> u32 = unsafe { std::mem::transmute(address) };
+    assert_eq!(variant_add(1), 5);
+
+    // Test that resolved stubs have been patched into direct jumps
+    #[cfg(all(target_arch = "x86_64", target_os = "linux", not(feature = "checked")))]
+    {
+        let code = unsafe { std::slice::from_raw_parts(importer::add_0 as *const u8, 2) };
+        assert!(matches!(code, [0xE9, _] | [0xFF, 0x25]));
+    }
+
+    // Test resolution of missing symbols, and retrieval of the failures
+    stubs::exporter_stub.clear_recent_errors();
     assert!(stubs::missing.resolve().is_err());
+    let failure = stubs::exporter_stub.last_error().unwrap();
+    assert_eq!(failure.code, ErrorCode::SymbolNotFound);
+    let failures = stubs::exporter_stub.recent_errors();
+    assert_eq!(failures[0].symbol, "foo");
+    let events = EVENTS.lock().unwrap().clone();
+    for event in ["load failed", "loaded", "resolved add_0", "failed foo"] {
+        assert!(events.iter().any(|e| e == event), "{event} not in {events:?}");
+    }
+    assert_eq!(failures[0].sym_index, stubs::sym_index::foo);
+    assert!(failures[0].message.contains("foo"));
+
+    // Test closure-style resolution
+    let values = vec![1, 2, 3];
+    let result = stubs::base.if_resolved(move || values.into_iter().map(importer::addition1).sum::<u32>());
+    assert_eq!(result.unwrap(), 36);
+    let result = stubs::base.try_if_resolved(|| Err::<(), _>("plugin call failed"));
+    assert!(matches!(
+        result,
+        Err(weaklink::GroupCallError::Call("plugin call failed"))
+    ));
+    let result = stubs::missing.try_if_resolved(|| Ok::<_, &str>(()));
+    assert!(matches!(result, Err(weaklink::GroupCallError::Unresolved(_))));
+
+    // Test resolution from a specific handle
+    let handle = stubs::exporter_stub.handle().unwrap();
+    let bound = stubs::base.resolve_from(handle).unwrap();
+    let (name, address) = bound.symbols().next().unwrap();
+    assert_eq!(weaklink::loading::find_symbol(handle, name).unwrap(), address);
+    assert_eq!(bound.address(name), Some(address));
+    assert!(stubs::missing.resolve_from(handle).is_err());
+    let missing = std::ffi::CString::new("foo").unwrap();
+    let err = weaklink::loading::find_symbol(handle, &missing).err().unwrap();
+    let err = err.downcast_ref::<weaklink::loading::DlError>().unwrap();
+    assert_eq!(err.symbol.as_deref(), Some("foo"));
+    assert_eq!(err.path.as_ref().unwrap().file_name(), path.file_name());
+    assert!(!err.to_string().contains('"'));
+
+    // Test the standalone loading facade
+    let dylib = weaklink::loading::Dylib::open(&path).unwrap();
+    assert_eq!(dylib.handle().0, handle.0);
+    assert_eq!(dylib.path().unwrap().file_name(), path.file_name());
+    assert_eq!(
+        weaklink::loading::path_of(handle).unwrap().file_name(),
+        path.file_name()
+    );
+    assert_eq!(dylib.sym(name).unwrap(), address);
+    assert!(dylib.sym(&missing).is_err());
+    dylib.close().unwrap();
+    let err = weaklink::loading::Dylib::open(&path.with_file_name("missing.so")).err().unwrap();
+    let err = err.downcast_ref::<weaklink::loading::DlError>().unwrap();
+    assert_eq!(err.path.as_ref().unwrap().file_name().unwrap(), "missing.so");
+
+    // Test enumeration of loaded modules and their exports
+    let modules = weaklink::loading::loaded_modules();
+    let module = modules.iter().find(|module| module.path.file_name() == path.file_name()).unwrap();
+    assert_eq!(module.handle.0, handle.0);
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    {
+        // memcpy is an IFUNC in glibc, and has several versions
+        let libc = modules.iter().find(|module| module.path.to_string_lossy().contains("libc.so")).unwrap();
+        let exports = weaklink::loading::exports_of(libc.handle).unwrap();
+        let memcpy = exports.iter().filter(|export| export.name.to_bytes() == b"memcpy").collect::<Vec<_>>();
+        assert_eq!(memcpy.len(), 1);
+        assert_eq!(
+            Ok(memcpy[0].address),
+            weaklink::loading::find_symbol(libc.handle, &memcpy[0].name).map_err(|_| ())
+        );
+    }
+    let exports = weaklink::loading::exports_of(handle).unwrap();
+    let export = exports.iter().find(|export| export.name.to_bytes() == b"add_0").unwrap();
+    assert_eq!(Some(export.address), bound.address(&export.name));
+
+    // Test symbol index constants
+    let name = stubs::exporter_stub.symbol_name(stubs::sym_index::add_0).unwrap();
+    assert_eq!(name.to_str().unwrap(), "add_0");
+    let address = stubs::exporter_stub.resolve_by_index(stubs::sym_index::add_0).unwrap();
+    assert_eq!(bound.address(name), Some(address));
+    assert_eq!(
+        stubs::exporter_stub.symbol_address(stubs::sym_index::add_0),
+        Some(address)
+    );
+    assert_eq!(stubs::exporter_stub.symbol_address(stubs::sym_index::foo), None);
+    assert_eq!(stubs::exporter_stub.index_of("add_0"), Some(stubs::sym_index::add_0));
+    assert_eq!(stubs::exporter_stub.index_of("add_00"), None);
+
+    // Test introspection of the groups
+    let groups = stubs::exporter_stub.groups();
+    let names = groups.iter().map(|group| group.name()).collect::<Vec<_>>();
+    assert!(names.contains(&"base") && names.windows(2).all(|pair| pair[0] < pair[1]));
+    let extended = groups.iter().find(|group| group.name() == "extended").unwrap();
+    let symbols = extended.symbols().map(|name| name.to_str().unwrap()).collect::<Vec<_>>();
+    assert_eq!(symbols, ["add_1", "foo"]);
+    assert_eq!(extended.len(), 2);
+    assert!(stubs::needs_missing.is_empty());
+
+    // Test probing for symbols by name, whether they have a stub or not
+    assert_eq!(stubs::exporter_stub.try_resolve_by_name("add_0").unwrap(), address);
+    let vtable_name = std::ffi::CString::new("plugin_vtable").unwrap();
+    assert_eq!(
+        stubs::exporter_stub.try_resolve_by_name("plugin_vtable").unwrap(),
+        weaklink::loading::find_symbol(handle, &vtable_name).unwrap()
+    );
+    assert_eq!(stubs::exporter_stub.index_of("plugin_vtable"), None);
+    assert!(stubs::exporter_stub.has_symbol("plugin_create_object"));
+    assert!(!stubs::exporter_stub.has_symbol("plugin_shutdown"));
+    assert!(!stubs::exporter_stub.has_symbol("foo"));
+    let err = stubs::exporter_stub.try_resolve_by_name("plugin_shutdown").err().unwrap();
+    assert_eq!(err.code(), ErrorCode::SymbolNotFound);
+
+    // Test the capability manifest exported by the library
+    let manifest = weaklink::PluginManifest::from_module(handle).unwrap().unwrap();
+    let path = weaklink::loading::path_of(handle).unwrap();
+    assert_eq!(
+        weaklink::PluginManifest::from_file(&path).unwrap(),
+        Some(manifest.clone())
+    );
+    assert_eq!(manifest.version.as_deref(), Some("2.3"));
+    assert!(manifest.provides("signed") && !manifest.provides("missing"));
+    assert_eq!(
+        weaklink::PluginManifest::parse(&manifest.to_string()).unwrap(),
+        manifest
+    );
+    let err = weaklink::PluginManifest::parse("group base").err().unwrap();
+    assert_eq!(err.code(), ErrorCode::InvalidManifest);
+    assert_eq!(
+        weaklink::group_signature_hash(&[("a", "fn()"), ("b", "fn(u32)")]),
+        weaklink::group_signature_hash(&[("b", "fn( u32 )"), ("a", "fn()")])
+    );
+
+    // Hot group symbols come first, in a cache-line aligned table
+    assert_eq!(stubs::sym_index::foo, 0);
+    let table = stubs::exporter_stub.symbol_table_range();
+    assert_eq!(table.start % 64, 0);
+    let num_symbols = (0..).take_while(|idx| stubs::exporter_stub.symbol_name(*idx).is_some()).count();
+    assert_eq!(table.len(), num_symbols * std::mem::size_of::<usize>());
+    for sym_index in 0.. {
+        let Some(name) = stubs::exporter_stub.symbol_name(sym_index) else {
+            break;
+        };
+        // Names of compiled out symbols are empty, since names are emitted per group.
+        match name.to_str().unwrap() {
+            "" => assert_eq!(sym_index, stubs::sym_index::extra_api),
+            name => assert_eq!(stubs::exporter_stub.index_of(name), Some(sym_index)),
+        }
+    }
+
+    // Test group dependencies and optional members
+    let extended = stubs::extended.resolve().unwrap();
+    assert_eq!(importer::addition1(0), 10);
+    assert_eq!(stubs::exporter_stub.symbol_address(stubs::sym_index::foo), None);
+    drop(extended);
+    assert!(stubs::needs_missing.resolve().is_err());
+    assert!(stubs::exporter_stub.resolve_ungrouped().is_ok());
+
+    // Test capability reporting
+    let capabilities = stubs::exporter_stub.capabilities();
+    assert!(capabilities.contains("base") && !capabilities.contains("missing"));
+    let available = capabilities.available().collect::<Vec<_>>();
+    assert_eq!(available.contains(&"native"), cfg!(not(windows)));
+    assert_eq!(
+        available.into_iter().filter(|name| *name != "native").collect::<Vec<_>>(),
+        ["all", "base", "extended", "importer", "optional", "variants"]
+    );
+    let native = cfg!(not(windows));
+    let extras = match cfg!(feature = "extras") {
+        true => r#""extras":false,"#,
+        false => "",
+    };
+    assert_eq!(
+        capabilities.to_json(),
+        format!(
+            r#"{{"all":true,"base":true,"extended":true,{extras}"importer":true,"missing":false,"native":{native},"native_missing":false,"needs_missing":false,"optional":true,"variants":true}}"#
+        )
+    );
+
+    // Test set operations over groups
+    let base = weaklink::GroupSet::of(&stubs::base);
+    let extended = weaklink::GroupSet::from(&stubs::extended);
+    let missing = weaklink::GroupSet::of(&stubs::missing);
+    assert!(extended.contains(stubs::sym_index::add_1) && !extended.contains(stubs::sym_index::foo));
+    assert_eq!(extended.intersection(&base), base);
+    assert!(extended.difference(&base).is_empty());
+    // The importer group is derived from the importer's references, which don't include the unused data accessor.
+    let unreferenced = base.difference(&weaklink::GroupSet::of(&stubs::importer));
+    assert_eq!(unreferenced.sym_indices(), [stubs::sym_index::get_SOMEDATA]);
+
+    // Test groups defined at runtime
+    let runtime_group = stubs::exporter_stub.make_group("adders", &["add_1", "add_2", "add_1"]);
+    assert_eq!(runtime_group.name(), "adders");
+    assert_eq!(runtime_group.set().len(), 2);
+    let token = runtime_group.resolve().unwrap();
+    assert_eq!(unsafe { importer::add_2(1) }, 3);
+    drop(token);
+    let runtime_group = stubs::exporter_stub.make_group("unknown", &["add_1", "no_such_symbol"]);
+    assert_eq!(runtime_group.unknown_symbols(), ["no_such_symbol"]);
+    assert_eq!(runtime_group.resolve().err().unwrap().code(), ErrorCode::SymbolNotFound);
+    assert!(!stubs::exporter_stub.make_group("missing", &["foo"]).is_available());
+    assert!(base.intersection(&missing).is_empty());
+    let combined = extended.union(&missing);
+    assert_eq!(combined.len(), extended.len() + missing.len());
+    assert_eq!(combined.difference(&base), missing);
+    assert!(!combined.is_available());
+    assert_eq!(weaklink::GroupSet::of(&stubs::needs_missing), missing);
+    let available = combined.difference(&missing);
+    let token = available.resolve().unwrap();
+    assert_eq!(importer::addition1(0), 10);
+    assert!(token.set().symbol_names().any(|name| name.to_bytes() == b"add_1"));
+    drop(token);
+    assert!(weaklink::GroupSet::empty(&stubs::exporter_stub).resolve().is_ok());
+
+    // Test plugin discovery
+    let dir = std::env::temp_dir().join(format!("weaklink-discover-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let plugin = dir.join(path.file_name().unwrap());
+    fs::copy(&path, &plugin).unwrap();
+    fs::write(dir.join("junk").with_extension(std::env::consts::DLL_EXTENSION), "junk").unwrap();
+    let candidates = weaklink::discover(&dir, |exports| {
+        let names = exports.names().unwrap();
+        names.iter().any(|name| name.to_bytes() == b"add_0") && exports.contains("add_0")
+    })
+    .unwrap();
+    assert_eq!(candidates, [plugin]);
+    assert!(weaklink::discover(&dir, |exports| exports.contains("foo")).unwrap().is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+
+    // Test library instances and symbol table snapshots
+    let instance = stubs::exporter_stub.instance(handle);
+    assert_eq!(instance.address(name), Some(address));
+    let before = stubs::exporter_stub.snapshot();
+    assert_eq!(before.address(name), Some(address));
+    // The stubs of patched and natively weak-linked groups would bypass the instance's table.
+    let err = instance.with(|| unreachable!()).err().unwrap();
+    assert_eq!(err.code(), ErrorCode::Unsupported);
+    assert!(before.diff(&stubs::exporter_stub.snapshot()).is_empty());
+
+    // Test the symbol table layout against the manifest
+    let manifest = include_str!(concat!(env!("OUT_DIR"), "/stubs.manifest"));
+    stubs::exporter_stub.verify_layout(manifest).unwrap();
+    let (first, _) = manifest.split_once("\n1 ").unwrap();
+    assert!(stubs::exporter_stub.verify_layout(first).is_err());
+    let renamed = manifest.replace(" SOMEDATA\n", " OTHERDATA\n");
+    assert!(stubs::exporter_stub.verify_layout(&renamed).is_err());
+
+    // Test unused report
+    stubs::exporter_stub.report_unused_at_exit();
+    let mut report = Vec::new();
+    stubs::exporter_stub.write_unused_report(&mut report).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    // The `extras` group only exists if compiled in.
+    let never_resolved = match (cfg!(windows), cfg!(feature = "extras")) {
+        (true, true) => "extras, missing, native, native_missing, needs_missing",
+        (true, false) => "missing, native, native_missing, needs_missing",
+        (false, true) => "extras, missing, native_missing, needs_missing",
+        (false, false) => "missing, native_missing, needs_missing",
+    };
+    assert!(report.contains(&format!("groups never resolved: {never_resolved}")));
+    assert!(report.contains("foo"));
+
+    // Test record/replay
+    stubs::exporter_stub.stop_recording();
+    let recorded = fs::read_to_string(recording).unwrap();
+    assert!(recorded.starts_with("load "));
+    assert!(recorded.contains("resolve "));
+    stubs::exporter_stub.replay(recording).unwrap();
+    // Groups already bound to their own dylib, and groups no longer configured, are skipped.
+    let group_recording = Path::new(concat!(env!("OUT_DIR"), "/group_recording.txt"));
+    let group_loads = format!(
+        "load_group optional missing.so\nload_group renamed {}\n",
+        path.display()
+    );
+    fs::write(group_recording, group_loads).unwrap();
+    stubs::exporter_stub.replay(group_recording).unwrap();
+    fs::write(group_recording, "load_group optional\n").unwrap();
+    assert!(stubs::exporter_stub.replay(group_recording).is_err());
+
+    // Test sealing of the symbol table
+    let base = stubs::base.resolve().unwrap();
+    stubs::exporter_stub.seal().unwrap();
+    assert!(stubs::exporter_stub.is_sealed());
+    assert_eq!(importer::addition2(0), 35);
+    drop(base);
+    assert!(stubs::base.resolve().is_ok());
+    let err = stubs::exporter_stub.resolve_by_index(stubs::sym_index::foo).err().unwrap();
+    assert_eq!(err.code(), ErrorCode::Sealed);
+    let err = stubs::exporter_stub.resolve_by_index(u32::MAX).err().unwrap();
+    assert_eq!(err.code(), ErrorCode::InvalidArgument);
+
+    // Test customized error messages, which keep the default ones for logging
+    weaklink::set_message_formatter(Some(|err| match err.code() {
+        ErrorCode::InvalidArgument => Some(format!("Argument invalide : {err}")),
+        _ => None,
+    }));
+    let message = "Symbol index 4294967295 is out of range.";
+    assert_eq!(err.to_string(), format!("Argument invalide : {message}"));
+    assert_eq!(err.downcast_ref::<weaklink::CodedError>().unwrap().message, message);
+    weaklink::set_message_formatter(None);
+    assert_eq!(err.to_string(), message);
+    assert_eq!(ErrorCode::from_u32(ErrorCode::Sealed as u32), Some(ErrorCode::Sealed));
+    assert_eq!(ErrorCode::from_u32(0), None);
 
+    assert_eq!(MISMATCHED.lock().unwrap().len(), expected.len());
     println!("OK");
 }
 
@@ -33,3 +536,821 @@ fn main() {
 fn test_main() {
     main();
 }
+
+// Loads the exporter for the test of a single feature, whose library wraps it.
+#[cfg(test)]
+fn load_exporter() -> (std::path::PathBuf, weaklink::DylibHandle) {
+    let path = weaklink_build::locate::dylib("exporter").unwrap();
+    let handle = weaklink::loading::load_library(&path).unwrap();
+    (path, handle)
+}
+
+// Address of an export of a dylib, as looked up without stubs.
+#[cfg(test)]
+fn exported(handle: weaklink::DylibHandle, name: &str) -> Option<weaklink::Address> {
+    weaklink::loading::find_symbol(handle, &std::ffi::CString::new(name).unwrap()).ok()
+}
+
+// Compressed symbol names, which are expanded on use.
+#[test]
+fn test_compressed_names() {
+    compressed_stubs::compressed_stub.init_checked();
+    let (_, handle) = load_exporter();
+    assert!(!compressed_stubs::compressed_stub.has_symbol("add_0"));
+    compressed_stubs::compressed_stub.set_handle(handle);
+    let compressed = compressed_stubs::compressed.resolve().unwrap();
+    for i in 0..10 {
+        let name = format!("add_{i}");
+        let sym_index = compressed_stubs::compressed_stub.index_of(&name).unwrap();
+        let symbol_name = compressed_stubs::compressed_stub.symbol_name(sym_index).unwrap();
+        assert_eq!(symbol_name.to_str().unwrap(), name);
+        assert_eq!(
+            compressed_stubs::compressed_stub.symbol_address(sym_index),
+            exported(handle, &name)
+        );
+    }
+    assert_eq!(compressed_stubs::compressed_stub.symbol_name(10), None);
+    drop(compressed);
+
+    // Library instances switch the stubs to their own symbol table, outside of checked mode.
+    let instance = compressed_stubs::compressed_stub.instance(handle);
+    let sym_index = compressed_stubs::compressed_stub.index_of("add_0").unwrap();
+    let switched = instance.with(|| compressed_stubs::compressed_stub.symbol_address(sym_index));
+    match cfg!(feature = "checked") {
+        true => assert_eq!(switched.err().unwrap().code(), ErrorCode::Unsupported),
+        false => assert_eq!(switched.unwrap(), exported(handle, "add_0")),
+    }
+}
+
+// Hashed symbol names, which are set from the manifest at runtime.
+#[test]
+fn test_hashed_names() {
+    hashed_stubs::hashed_stub.init_checked();
+    let (_, handle) = load_exporter();
+    assert!(!include_str!(concat!(env!("OUT_DIR"), "/hashed_stubs.rs")).contains("\"add_"));
+    let manifest = include_str!(concat!(env!("OUT_DIR"), "/hashed_stubs.manifest"));
+    let sym_index = hashed_stubs::hashed_stub.index_of("add_3").unwrap();
+    assert_eq!(hashed_stubs::hashed_stub.index_of("add_33"), None);
+    assert!(hashed_stubs::hashed_stub.set_symbol_names(&manifest.replace("add_3", "add_33")).is_err());
+    assert!(stubs::exporter_stub.set_symbol_names(manifest).is_err());
+    hashed_stubs::hashed_stub.set_symbol_names(manifest).unwrap();
+    hashed_stubs::hashed_stub.set_handle(handle);
+    assert!(hashed_stubs::hashed_stub.set_symbol_names(manifest).is_err());
+    let hashed = hashed_stubs::hashed.resolve().unwrap();
+    let symbol_name = hashed_stubs::hashed_stub.symbol_name(sym_index).unwrap();
+    assert_eq!(symbol_name.to_str().unwrap(), "add_3");
+    assert_eq!(
+        hashed_stubs::hashed_stub.symbol_address(sym_index),
+        exported(handle, "add_3")
+    );
+    drop(hashed);
+}
+
+// Obfuscated symbol names, whose key is supplied at runtime.
+#[test]
+fn test_obfuscated_names() {
+    obfuscated_stubs::obfuscated_stub.init_checked();
+    let (_, handle) = load_exporter();
+    assert!(!include_str!(concat!(env!("OUT_DIR"), "/obfuscated_stubs.rs")).contains("\"add_"));
+    obfuscated_stubs::obfuscated_stub.set_handle(handle);
+    let err = obfuscated_stubs::obfuscated_stub.resolve_by_index(0).err().unwrap();
+    assert_eq!(err.code(), ErrorCode::NotConfigured);
+    assert_eq!(obfuscated_stubs::obfuscated_stub.index_of("add_3"), None);
+    obfuscated_stubs::obfuscated_stub.set_key_provider(|| 0x5eed_0bf5_ca7e_d000);
+    let sym_index = obfuscated_stubs::obfuscated_stub.index_of("add_3").unwrap();
+    assert_eq!(
+        obfuscated_stubs::obfuscated_stub.symbol_name(sym_index).unwrap().to_bytes(),
+        b""
+    );
+    let obfuscated = obfuscated_stubs::obfuscated.resolve().unwrap();
+    assert_eq!(
+        obfuscated_stubs::obfuscated_stub.symbol_address(sym_index),
+        exported(handle, "add_3")
+    );
+    drop(obfuscated);
+}
+
+// Resolution through a bootstrap export, which serves symbols that aren't in the dynamic symbol table.
+#[test]
+fn test_bootstrap_export() {
+    bootstrap_stubs::bootstrap_stub.init_checked();
+    let (_, handle) = load_exporter();
+    assert_eq!(exported(handle, "hidden_add_7"), None);
+    bootstrap_stubs::bootstrap_stub.set_handle(handle);
+    let bootstrapped = bootstrap_stubs::bootstrapped.resolve().unwrap();
+    let sym_index = bootstrap_stubs::bootstrap_stub.index_of("hidden_add_7").unwrap();
+    assert_eq!(
+        bootstrap_stubs::bootstrap_stub.symbol_address(sym_index),
+        exported(handle, "add_7")
+    );
+    drop(bootstrapped);
+}
+
+// Functions obtained from a struct of pointers.
+#[test]
+fn test_vtable_slots() {
+    extern "C" {
+        // Stubs of the functions in the struct returned by the exporter's `plugin_vtable`.
+        fn vtable_add_6(a: u32) -> u32;
+        fn vtable_add_8(a: u32) -> u32;
+    }
+
+    vtable_stubs::vtable_stub.init_checked();
+    let (_, handle) = load_exporter();
+    vtable_stubs::vtable_stub.set_handle(handle);
+    let vtable = vtable_stubs::vtable.resolve().unwrap();
+    assert_eq!(unsafe { vtable_add_6(1) }, 7);
+    assert_eq!(unsafe { vtable_add_8(1) }, 9);
+    drop(vtable);
+}
+
+// Methods of an interface created by a factory.
+#[test]
+fn test_interface_slots() {
+    extern "C" {
+        // Stubs of the methods of the object created by the exporter's `plugin_create_object`.
+        fn object_add(object: weaklink::Address, a: u32) -> u32;
+        fn object_mul(object: weaklink::Address, a: u32) -> u32;
+    }
+
+    // Number of objects created by `create_object`.
+    static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+    // Creates the exporter's object, as a callback calling `DllGetClassObject` would on Windows.
+    fn create_object(handle: weaklink::DylibHandle) -> Result<weaklink::Address, weaklink::Error> {
+        CREATED.fetch_add(1, Ordering::Relaxed);
+        let address = weaklink::loading::find_symbol(handle, &std::ffi::CString::new("plugin_create_object")?)?;
+        let factory: extern "C" fn() -> weaklink::Address = unsafe { std::mem::transmute(address) };
+        Ok(factory())
+    }
+
+    interface_stubs::interface_stub.init_checked();
+    let (_, handle) = load_exporter();
+    interface_stubs::interface_stub.set_handle(handle);
+    let err = interface_stubs::interface_stub.resolve_by_index(0).err().unwrap();
+    assert_eq!(err.code(), ErrorCode::NotConfigured);
+    interface_stubs::interface_stub.set_factory("object", create_object);
+    let object = interface_stubs::interface_stub.interface("object").unwrap();
+    let methods = interface_stubs::object_methods.resolve().unwrap();
+    assert_eq!(unsafe { object_add(object, 1) }, 101);
+    assert_eq!(unsafe { object_mul(object, 2) }, 200);
+    assert_eq!(CREATED.load(Ordering::Relaxed), 1);
+    drop(methods);
+}
+
+// The signature handshake, which fails groups whose signatures have drifted, and dylib names with placeholders, which
+// are expanded from the variables set at runtime.
+#[test]
+fn test_signature_handshake() {
+    signed_stubs::signed_stub.init_checked();
+    let (path, handle) = load_exporter();
+    let err = signed_stubs::signed_stub.load().err().unwrap();
+    assert_eq!(err.code(), ErrorCode::LibraryNotFound);
+    signed_stubs::signed_stub.set_vars(&[
+        ("plugin_dir", path.parent().unwrap().to_str().unwrap()),
+        ("plugin_file", path.file_name().unwrap().to_str().unwrap()),
+    ]);
+    assert_eq!(signed_stubs::signed_stub.load().unwrap().0, handle.0);
+    let signed = signed_stubs::signed.resolve().unwrap();
+    drop(signed);
+    let err = signed_stubs::drifted.resolve().err().unwrap();
+    assert_eq!(err.code(), ErrorCode::SignatureMismatch);
+
+    // Unloading the library resets its groups, so that they're resolved again once it's reloaded.
+    let signed = signed_stubs::signed.resolve().unwrap();
+    assert!(signed.check().is_ok());
+    if cfg!(feature = "checked") {
+        let err = signed_stubs::signed_stub.unload().err().unwrap();
+        assert_eq!(err.code(), ErrorCode::InUse);
+    } else {
+        // The token outlives the dylib it was resolved from, which it detects.
+        signed_stubs::signed_stub.unload().unwrap();
+        assert_eq!(signed.check().err().unwrap().code(), ErrorCode::StaleAddress);
+        signed_stubs::signed_stub.load_from(&path).unwrap();
+    }
+    drop(signed);
+    signed_stubs::signed_stub.unload().unwrap();
+    assert!(signed_stubs::signed_stub.handle().is_none());
+    let sym_index = signed_stubs::signed_stub.index_of("add_3").unwrap();
+    assert_eq!(signed_stubs::signed_stub.symbol_address(sym_index), None);
+    let err = signed_stubs::signed_stub.unload().err().unwrap();
+    assert_eq!(err.code(), ErrorCode::NotLoaded);
+    signed_stubs::signed_stub.load_from(&path).unwrap();
+    let signed = signed_stubs::signed.resolve().unwrap();
+    assert!(signed_stubs::signed_stub.symbol_address(sym_index).is_some());
+    drop(signed);
+    let err = signed_stubs::drifted.resolve().err().unwrap();
+    assert_eq!(err.code(), ErrorCode::SignatureMismatch);
+}
+
+// Eager binding, which resolves all symbols upon loading, and reports the missing ones.
+#[test]
+fn test_eager_binding() {
+    eager_stubs::eager_stub.init_checked();
+    let (path, _) = load_exporter();
+    let sym_index = eager_stubs::eager_stub.index_of("add_0").unwrap();
+    eager_stubs::eager_stub.load_from(&path).unwrap();
+    assert!(eager_stubs::eager_stub.recent_errors().iter().any(|failure| failure.symbol == "foo"));
+    let err = eager_stubs::absent.resolve().err().unwrap();
+    assert_eq!(err.code(), ErrorCode::GroupUnresolved);
+    assert_eq!(
+        eager_stubs::eager_stub.symbol_address(sym_index).is_some(),
+        !cfg!(feature = "checked")
+    );
+    let report = eager_stubs::eager_stub.resolve_all().unwrap();
+    let missing = report.missing.iter().map(|failure| failure.symbol.as_str()).collect::<Vec<_>>();
+    assert_eq!(missing, ["foo", "bar"]);
+    assert_eq!(report.unavailable_groups, ["absent", "needs_absent"]);
+    let err = report.check().err().unwrap();
+    assert_eq!(err.code(), ErrorCode::GroupUnresolved);
+    assert!(err.to_string().contains("missing symbols: foo, bar"), "{err}");
+    let eager = eager_stubs::eager.resolve().unwrap();
+    assert!(eager_stubs::eager_stub.symbol_address(sym_index).is_some());
+    drop(eager);
+}
+
+// Libraries loaded from the main program and the vDSO, rather than from a dylib.
+#[test]
+fn test_main_program_and_vdso() {
+    main_stubs::main_stub.init_checked();
+    vdso_stubs::vdso_stub.init_checked();
+    if cfg!(unix) {
+        let environment = main_stubs::environment.resolve().unwrap();
+        let sym_index = main_stubs::main_stub.index_of("getenv").unwrap();
+        assert!(main_stubs::main_stub.symbol_address(sym_index).is_some());
+        drop(environment);
+        let err = main_stubs::unavailable.resolve().err().unwrap();
+        assert_eq!(err.code(), ErrorCode::SymbolNotFound);
+    }
+    // The vDSO may be missing, e.g. under emulation.
+    let loaded = vdso_stubs::vdso_stub.load().is_ok();
+    assert!(loaded || !cfg!(all(target_os = "linux", target_arch = "x86_64")));
+    if loaded {
+        assert!(vdso_stubs::time.resolve().is_ok());
+        vdso_stubs::vdso_stub.unload().unwrap();
+        vdso_stubs::vdso_stub.load_from(Path::new(weaklink::loading::VDSO)).unwrap();
+    }
+}
+
+// Selection of dylib names by the C library of the process.
+#[test]
+fn test_libc_flavor() {
+    libc_stubs::libc_stub.init_checked();
+    let (path, handle) = load_exporter();
+    assert_eq!(weaklink::loading::libc_flavor().tag(), env!("WEAKLINK_LIBC_FLAVOR"));
+    let path = path.to_str().unwrap();
+    libc_stubs::libc_stub.set_vars(&[("other_path", path)]);
+    let err = libc_stubs::libc_stub.load().err().unwrap();
+    assert_eq!(err.code(), ErrorCode::LibraryNotFound);
+    libc_stubs::libc_stub.set_vars(&[("other_path", path), ("path", path)]);
+    assert_eq!(libc_stubs::libc_stub.load().unwrap().0, handle.0);
+    assert!(libc_stubs::libc.resolve().is_ok());
+}
+
+// Binding to a version of a symbol, which glibc's `dlvsym` distinguishes.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+#[test]
+fn test_symbol_versions() {
+    versioned_stubs::versioned_stub.init_checked();
+    let versioned = versioned_stubs::versioned.resolve().unwrap();
+    let sym_index = versioned_stubs::versioned_stub.index_of("gnu_get_libc_version").unwrap();
+    let main_program = weaklink::loading::main_program().unwrap();
+    assert_eq!(
+        versioned_stubs::versioned_stub.symbol_address(sym_index),
+        exported(main_program, "gnu_get_libc_version")
+    );
+    drop(versioned);
+    let err = versioned_stubs::future_version.resolve().err().unwrap();
+    assert_eq!(err.code(), ErrorCode::SymbolNotFound);
+    let failures = versioned_stubs::versioned_stub.recent_errors();
+    assert!(failures.iter().any(|failure| failure.message.contains("gnu_get_libc_release@GLIBC_999")));
+}
+
+// Unloading and closing the library while another thread loads it or resolves a group, which completes first.
+#[test]
+fn test_closing() {
+    // 1 while the resolution hook runs, and 2 once it's done.
+    static RESOLVING: AtomicUsize = AtomicUsize::new(0);
+
+    closing_stubs::closing_stub.init_checked();
+    let (path, _) = load_exporter();
+    closing_stubs::closing_stub.set_resolution_hook(|event| {
+        if let weaklink::HookEvent::Load { .. } | weaklink::HookEvent::Resolved { .. } = event {
+            RESOLVING.store(1, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            RESOLVING.store(2, Ordering::SeqCst);
+        }
+    });
+    let loading = {
+        let path = path.clone();
+        std::thread::spawn(move || closing_stubs::closing_stub.load_from(&path).map(drop).map_err(|err| err.code()))
+    };
+    while RESOLVING.load(Ordering::SeqCst) == 0 {
+        std::thread::yield_now();
+    }
+    closing_stubs::closing_stub.unload().unwrap();
+    assert_eq!(RESOLVING.load(Ordering::SeqCst), 2);
+    assert_eq!(loading.join().unwrap(), Ok(()));
+    assert!(closing_stubs::closing_stub.handle().is_none());
+
+    closing_stubs::closing_stub.load_from(&path).unwrap();
+    RESOLVING.store(0, Ordering::SeqCst);
+    let resolving = std::thread::spawn(|| closing_stubs::racing.resolve().map(drop).map_err(|err| err.code()));
+    while RESOLVING.load(Ordering::SeqCst) == 0 {
+        std::thread::yield_now();
+    }
+    closing_stubs::closing_stub.close(false).unwrap();
+    assert_eq!(RESOLVING.load(Ordering::SeqCst), 2);
+    assert_eq!(resolving.join().unwrap(), Ok(()));
+    assert!(closing_stubs::closing_stub.is_closed());
+    // In checked mode, the group's entries were reset once its token was dropped.
+    assert_eq!(closing_stubs::racing.resolve().is_ok(), !cfg!(feature = "checked"));
+    let err = closing_stubs::closing.resolve().err().unwrap();
+    assert_eq!(err.code(), ErrorCode::Closed);
+    closing_stubs::closing_stub.close(true).unwrap();
+    assert!(closing_stubs::closing_stub.handle().is_none());
+    let err = closing_stubs::closing_stub.load_from(&path).err().unwrap();
+    assert_eq!(err.code(), ErrorCode::Closed);
+}
+
+// Loading another copy of the library in a namespace of its own, where its symbols don't collide.
+#[test]
+fn test_isolated_namespace() {
+    isolated_stubs::isolated_stub.init_checked();
+    let (path, handle) = load_exporter();
+    isolated_stubs::isolated_stub.set_vars(&[("path", path.to_str().unwrap())]);
+    if cfg!(all(target_os = "linux", target_env = "gnu")) {
+        let isolated = isolated_stubs::isolated_stub.load_isolated().unwrap();
+        assert_ne!(isolated.0, handle.0);
+        assert_eq!(
+            weaklink::loading::namespace_of(handle).ok(),
+            Some(weaklink::loading::LM_ID_BASE)
+        );
+        let namespace = isolated_stubs::isolated_stub.namespace().unwrap();
+        assert_ne!(namespace, weaklink::loading::LM_ID_BASE);
+        let token = isolated_stubs::isolated.resolve().unwrap();
+        let sym_index = isolated_stubs::isolated_stub.index_of("add_0").unwrap();
+        let address = isolated_stubs::isolated_stub.symbol_address(sym_index);
+        assert_eq!(address, exported(isolated, "add_0"));
+        assert_ne!(address, exported(handle, "add_0"));
+        drop(token);
+    } else {
+        let err = isolated_stubs::isolated_stub.load_isolated().err().unwrap();
+        assert_eq!(err.code(), ErrorCode::Unsupported);
+    }
+}
+
+// In checked mode, unasserted access to data must crash, same as calling unasserted code.
+#[cfg(feature = "checked")]
+#[test]
+fn test_unasserted_data_access() {
+    use std::env;
+    use std::process::Command;
+
+    if env::var_os("WEAKLINK_UNASSERTED_ACCESS").is_some() {
+        let path = weaklink_build::locate::dylib("exporter").unwrap();
+        stubs::exporter_stub.init_checked();
+        stubs::exporter_stub.load_from(&path).unwrap();
+        drop(stubs::base.resolve().unwrap());
+        unsafe { importer::get_SOMEDATA() };
+        return;
+    }
+
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.args(["--exact", "test_unasserted_data_access"]);
+    let mut command = utils::add_runner(command);
+    command.env("WEAKLINK_UNASSERTED_ACCESS", "1");
+    let output = command.output().unwrap();
+    assert!(!output.status.success());
+    let output = [output.stdout, output.stderr].concat();
+    assert!(String::from_utf8_lossy(&output).contains("accessed without asserting"));
+}
+
+// Resolution through a broker must bind the stubs to its proxies, without loading the library.
+#[test]
+fn test_broker() {
+    use std::env;
+    use std::ffi::CStr;
+    use std::process::Command;
+
+    struct TestBroker;
+
+    extern "C" fn proxy_add(a: u32) -> u32 {
+        a + 100
+    }
+
+    impl weaklink::Broker for TestBroker {
+        fn has_symbol(&self, name: &CStr) -> bool {
+            let name = name.to_bytes();
+            name.starts_with(b"add_") || name == b"SOMEDATA" || name == b"foo"
+        }
+
+        fn proxy(&self, name: &CStr) -> Option<weaklink::Address> {
+            static DATA: i32 = 7;
+            match name.to_bytes() {
+                b"foo" => None,
+                b"SOMEDATA" => Some(&DATA as *const i32 as weaklink::Address),
+                _ => Some(proxy_add as *const () as weaklink::Address),
+            }
+        }
+    }
+
+    if env::var_os("WEAKLINK_BROKER").is_some() {
+        static BROKER: TestBroker = TestBroker;
+        stubs::exporter_stub.init_checked();
+        stubs::exporter_stub.set_auto_load(false);
+        stubs::exporter_stub.set_broker(&BROKER);
+        let base = stubs::base.resolve().unwrap();
+        assert_eq!(importer::addition1(0), 500);
+        assert_eq!(unsafe { *importer::get_SOMEDATA() }, 7);
+        drop(base);
+        assert!(stubs::exporter_stub.handle().is_none());
+        let err = stubs::missing.resolve().err().unwrap();
+        assert!(err.to_string().contains("has no proxy"));
+        assert_eq!(stubs::exporter_stub.last_error().unwrap().message, err.to_string());
+        return;
+    }
+
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.args(["--exact", "test_broker"]);
+    let mut command = utils::add_runner(command);
+    command.env("WEAKLINK_BROKER", "1");
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+// The generated initialization must load the library and resolve the required groups permanently, dependencies first.
+#[test]
+fn test_init_required() {
+    use std::process::Command;
+
+    if std::env::var_os("WEAKLINK_INIT_REQUIRED").is_some() {
+        stubs::exporter_stub.init_checked();
+        stubs::init_required().unwrap();
+        assert!(stubs::exporter_stub.handle().is_some());
+        assert!(stubs::base.resolve().unwrap().is_permanent());
+        assert!(stubs::extended.resolve().unwrap().is_permanent());
+        assert_eq!(importer::addition1(0), 10);
+        return;
+    }
+
+    let source = include_str!(concat!(env!("OUT_DIR"), "/stubs.rs"));
+    let base = source.find("    base.resolve_global()?;").unwrap();
+    assert!(base < source.find("    extended.resolve_global()?;").unwrap());
+
+    let mut command = Command::new(std::env::current_exe().unwrap());
+    command.args(["--exact", "test_init_required"]);
+    let mut command = utils::add_runner(command);
+    let path = weaklink_build::locate::dylib("exporter").unwrap();
+    command.env("WEAKLINK_INIT_REQUIRED", path);
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+// With panic-free initialization, failures that would otherwise panic must be reported to the callback instead.
+#[test]
+fn test_panic_free() {
+    use std::ffi::CStr;
+    use std::os::raw::c_char;
+    use std::process::Command;
+
+    static FAILURES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    extern "C" fn on_failure(library: *const c_char, code: u32, symbol: *const c_char) {
+        let library = unsafe { CStr::from_ptr(library) }.to_string_lossy();
+        let symbol = match symbol.is_null() {
+            true => "-".into(),
+            false => unsafe { CStr::from_ptr(symbol) }.to_string_lossy(),
+        };
+        FAILURES.lock().unwrap().push(format!("{library} {code} {symbol}"));
+    }
+
+    if std::env::var_os("WEAKLINK_PANIC_FREE").is_some() {
+        stubs::exporter_stub.init_panic_free(on_failure);
+        let err = stubs::base.resolve().err().unwrap();
+        assert_eq!(err.code(), ErrorCode::LibraryNotFound);
+        let not_found = ErrorCode::LibraryNotFound as u32;
+        assert_eq!(*FAILURES.lock().unwrap(), [format!("exporter_stub {not_found} -")]);
+
+        if cfg!(feature = "checked") {
+            let path = weaklink_build::locate::dylib("exporter").unwrap();
+            stubs::exporter_stub.load_from(&path).unwrap();
+            drop(stubs::base.resolve().unwrap());
+            assert!(unsafe { importer::get_SOMEDATA() }.is_null());
+            let unresolved = ErrorCode::GroupUnresolved as u32;
+            assert_eq!(
+                FAILURES.lock().unwrap()[1],
+                format!("exporter_stub {unresolved} SOMEDATA")
+            );
+        }
+        return;
+    }
+
+    let mut command = Command::new(std::env::current_exe().unwrap());
+    command.args(["--exact", "test_panic_free"]);
+    let mut command = utils::add_runner(command);
+    command.env("WEAKLINK_PANIC_FREE", "1");
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+// The management API, as called by a C client compiled against the generated header, in a subprocess of its own
+// since it loads the library.
+#[test]
+fn test_c_client() {
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_int};
+    use std::process::Command;
+
+    extern "C" {
+        // Defined in `c_client.c`.
+        fn c_client_run(path: *const c_char) -> c_int;
+    }
+
+    if let Some(path) = std::env::var_os("WEAKLINK_C_CLIENT") {
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { c_client_run(path.as_ptr()) }, 0);
+        return;
+    }
+
+    let mut command = Command::new(std::env::current_exe().unwrap());
+    command.args(["--exact", "test_c_client"]);
+    let mut command = utils::add_runner(command);
+    command.env("WEAKLINK_C_CLIENT", weaklink_build::locate::dylib("exporter").unwrap());
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+// Redirection of legacy code that opens the library itself to the stubs, via the dlopen shim.
+#[test]
+fn test_dlopen_shim() {
+    use std::ffi::CString;
+    use weaklink::dlopen_shim::{weaklink_dlclose, weaklink_dlerror, weaklink_dlopen, weaklink_dlsym};
+
+    shim_stubs::shim_stub.init_checked();
+    shim_stubs::register_dlopen_shim();
+    let path = weaklink_build::locate::dylib("exporter").unwrap();
+    let path_name = CString::new(path.to_str().unwrap()).unwrap();
+    let shim_handle = unsafe { weaklink_dlopen(path_name.as_ptr(), 1) };
+    let handle = shim_stubs::shim_stub.handle().unwrap();
+    assert_eq!(shim_handle as usize, handle.0);
+    // Registered names also match by file name.
+    let file_name = CString::new(path.file_name().unwrap().to_str().unwrap()).unwrap();
+    assert_eq!(unsafe { weaklink_dlopen(file_name.as_ptr(), 1) }, shim_handle);
+    let symbol = CString::new("add_0").unwrap();
+    let address = unsafe { weaklink_dlsym(shim_handle, symbol.as_ptr()) };
+    let sym_index = shim_stubs::shim_stub.index_of("add_0").unwrap();
+    assert_eq!(Some(address as usize), shim_stubs::shim_stub.symbol_address(sym_index));
+    assert_eq!(unsafe { weaklink_dlclose(shim_handle) }, 0);
+    assert_eq!(shim_stubs::shim_stub.handle().unwrap().0, handle.0);
+
+    let missing = CString::new("missing.so").unwrap();
+    assert!(unsafe { weaklink_dlopen(missing.as_ptr(), 1) }.is_null());
+    assert!(!weaklink_dlerror().is_null());
+    // Reading the error clears it, even if later calls succeed.
+    assert!(unsafe { weaklink_dlopen(missing.as_ptr(), 1) }.is_null());
+    let shim_handle = unsafe { weaklink_dlopen(file_name.as_ptr(), 1) };
+    assert!(!shim_handle.is_null());
+    assert_eq!(unsafe { weaklink_dlclose(shim_handle) }, 0);
+    assert!(!weaklink_dlerror().is_null());
+    assert!(weaklink_dlerror().is_null());
+
+    // As with `dlopen`, a null file name opens the main program.
+    let main_program = unsafe { weaklink_dlopen(std::ptr::null(), 1) };
+    assert!(!main_program.is_null());
+    if cfg!(unix) {
+        let getenv = CString::new("getenv").unwrap();
+        assert!(!unsafe { weaklink_dlsym(main_program, getenv.as_ptr()) }.is_null());
+    }
+    assert_eq!(unsafe { weaklink_dlclose(main_program) }, 0);
+    assert!(weaklink_dlerror().is_null());
+}
+
+// Generation through a cache, which serves the second generation without calling the generator.
+#[test]
+fn test_generation_cache() {
+    use weaklink_build::{Cache, CacheKey, Config};
+
+    let dylib_path = weaklink_build::locate::dylib("exporter").unwrap();
+    let out_dir = Path::new(env!("OUT_DIR"));
+    let cache_dir = out_dir.join("weaklink-cache");
+    let _ = fs::remove_dir_all(&cache_dir);
+    let cache = Cache::new(&cache_dir).unwrap();
+    let mut config = Config::new("cached_stub");
+    config.group("cached").add_data("cached_add_0", "add_0");
+    let key = CacheKey::new().file(&dylib_path).unwrap().text("cached_stub");
+    let generate = |text: &mut dyn std::io::Write| {
+        config.generate_source(text);
+        Ok(())
+    };
+    let source_path = out_dir.join("cached_stubs.rs");
+    assert!(!cache.generate(&key, &source_path, generate).unwrap());
+    let generated = fs::read(&source_path).unwrap();
+    let cached = cache.generate(&key, &source_path, |_| unreachable!()).unwrap();
+    assert!(cached && fs::read(&source_path).unwrap() == generated);
+    assert_ne!(key, CacheKey::new().file(&dylib_path).unwrap().text("other_stub"));
+}
+
+// Location of the artifacts built by Cargo.
+#[test]
+fn test_locate() {
+    use std::process::Command;
+
+    // Artifact dependencies are located from the variables set by Cargo, rather than searched for.
+    if let Some(path) = std::env::var_os("CARGO_CDYLIB_FILE_RENAMED_EXPORTER") {
+        assert_eq!(
+            weaklink_build::locate::dylib("renamed-exporter").unwrap(),
+            Path::new(&path)
+        );
+        return;
+    }
+
+    let path = weaklink_build::locate::dylib("exporter").unwrap();
+    assert!(path.starts_with(weaklink_build::locate::target_dir().unwrap()));
+    assert!(weaklink_build::locate::dylib("renamed-exporter").is_err());
+
+    // The variable is set for a subprocess, since other tests read the environment concurrently.
+    let mut command = Command::new(std::env::current_exe().unwrap());
+    command.args(["--exact", "test_locate"]);
+    let mut command = utils::add_runner(command);
+    command.env("CARGO_CDYLIB_FILE_RENAMED_EXPORTER", &path);
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+// All symbols the importer uses must have stubs, and those without are reported.
+#[test]
+fn test_check_imports() {
+    let unstubbed = include_str!(concat!(env!("OUT_DIR"), "/unstubbed_imports.txt"));
+    assert!(unstubbed.is_empty(), "{unstubbed}");
+
+    let importer = weaklink_build::locate::rlib("importer").unwrap();
+    let mut config = weaklink_build::Config::new("unchecked_stub");
+    config.group("base").add("add_0");
+    let unstubbed = config.check_imports(&[&importer]).unwrap();
+    // Names may be decorated, e.g. with a leading underscore on macOS.
+    let names = unstubbed.iter().map(|import| import.name.as_str()).collect::<Vec<_>>();
+    let reported = |symbol: &str| names.iter().any(|name| name.ends_with(symbol));
+    assert!(reported("add_1") && !reported("add_0"), "{names:?}");
+}
+
+// Stubbed symbols called by the functions of the importer.
+#[test]
+fn test_call_graph() {
+    use weaklink_build::{Config, SymbolStub};
+
+    let importer = weaklink_build::locate::rlib("importer").unwrap();
+    let mut config = Config::new("graph_stub");
+    let stubs = (0..10).map(|i| SymbolStub::new(&format!("add_{i}")));
+    config.add_symbol_group("base", stubs).unwrap();
+    let call_graph = config.call_graph(&[&importer]).unwrap();
+    assert_eq!(
+        call_graph.symbols_of("addition1"),
+        ["add_0", "add_1", "add_2", "add_3", "add_4"]
+    );
+}
+
+// Denied symbols are left out of the groups, and reported as skipped.
+#[test]
+fn test_denied_symbols() {
+    use weaklink_build::{Config, SymbolStub};
+
+    let mut config = Config::new("denied_stub");
+    config.deny_symbols(weaklink_build::RUNTIME_SYMBOLS.iter().copied());
+    config.deny_symbols(["add_*_internal"]);
+    let stubs = [SymbolStub::new("add_0"), SymbolStub::new("add_0_internal")];
+    config.add_symbol_group("base", stubs).unwrap();
+    assert_eq!(config.skipped_symbols(), ["add_0_internal"]);
+}
+
+// Conventional file names of the library, the last of which is the unversioned one that Cargo builds.
+#[test]
+fn test_dylib_candidates() {
+    let path = weaklink_build::locate::dylib("exporter").unwrap();
+    let candidates = weaklink_build::dylib_candidates(env!("TARGET"), "exporter", &["1"]);
+    assert_eq!(
+        candidates.last().map(String::as_str),
+        path.file_name().unwrap().to_str()
+    );
+}
+
+// Capability manifest embedded in the exporter.
+#[test]
+fn test_embedded_manifest() {
+    let dylib_path = weaklink_build::locate::dylib("exporter").unwrap();
+    let manifest = weaklink_build::exports::manifest(&dylib_path).unwrap().unwrap();
+    assert!(manifest.provides("base") && manifest.version.as_deref() == Some("2.3"));
+}
+
+// Evaluation of the groups against a directory of builds of the library, which also holds other files.
+#[test]
+fn test_compat_matrix() {
+    let history = Path::new(env!("OUT_DIR")).join("plugin_history");
+    fs::create_dir_all(&history).unwrap();
+    let dylib_path = weaklink_build::locate::dylib("exporter").unwrap();
+    fs::copy(&dylib_path, history.join(dylib_path.file_name().unwrap())).unwrap();
+    fs::write(history.join("README.txt"), "Builds of the exporter").unwrap();
+    let mut config = weaklink_build::Config::new("history_stub");
+    config.group("base").add("add_0");
+    config.group("extended").add("add_1").depends_on("base");
+    config.group("optional").add("add_9");
+    config.group("missing").add("foo");
+    config.group("needs_missing").depends_on("missing");
+    let matrix = config.compat_matrix(&history).unwrap();
+    assert_eq!(matrix.plugins.len(), 1);
+    let plugin = &matrix.plugins[0];
+    assert_eq!(plugin.version.as_deref(), Some("2.3"));
+    assert!(plugin.resolves("base") && plugin.resolves("extended") && plugin.resolves("optional"));
+    assert!(!plugin.resolves("missing") && !plugin.resolves("needs_missing"));
+    assert!(matrix.degraded_groups().is_empty());
+}
+
+// Stubs of all exports, including the data ones, with accessors named after a template.
+#[test]
+fn test_all_exports() {
+    let dylib_path = weaklink_build::locate::dylib("exporter").unwrap();
+    let mut config = weaklink_build::Config::new("auto_stub");
+    config.deny_symbols(weaklink_build::RUNTIME_SYMBOLS.iter().copied());
+    config.data_accessor_template = Some("get_{name}".to_string());
+    config.add_all_exports_from("all", &dylib_path).unwrap();
+    let mut manifest = Vec::new();
+    config.generate_manifest(&mut manifest);
+    let manifest = String::from_utf8(manifest).unwrap();
+    assert!(
+        manifest.contains(" add_0 add_0\n") && manifest.contains(" get_SOMEDATA SOMEDATA\n"),
+        "{manifest}"
+    );
+}
+
+// Binaries built for another target are rejected.
+#[test]
+fn test_foreign_binary_rejected() {
+    let dylib_path = weaklink_build::locate::dylib("exporter").unwrap();
+    let mut config = weaklink_build::Config::new("foreign_stub");
+    let target = env!("TARGET");
+    config.target = target.replacen(target.split('-').next().unwrap(), "riscv64gc", 1);
+    let err = config.check_binary(&dylib_path).unwrap_err().to_string();
+    assert!(err.contains("riscv64 (64-bit, little-endian)"), "{err}");
+    assert!(config.compat_of(&dylib_path).is_err());
+}
+
+// ARM64EC stubs would need thunks for x64 callers, which aren't generated.
+#[test]
+fn test_arm64ec_rejected() {
+    use weaklink_build::{Config, SymbolStub};
+
+    let mut config = Config::new("arm64ec_stub");
+    config.target = "arm64ec-pc-windows-msvc".to_string();
+    config.add_symbol_group("base", [SymbolStub::new("add_0")]).unwrap();
+    let generate = std::panic::AssertUnwindSafe(|| config.generate_source(&mut Vec::new()));
+    assert!(std::panic::catch_unwind(generate).is_err());
+}
+
+// Conversion of Windows paths into the extended-length form, which lifts the `MAX_PATH` limit.
+#[cfg(windows)]
+#[test]
+fn test_extended_length_paths() {
+    use weaklink::loading::windows::{to_extended_length, MAX_PATH};
+
+    let wide = |path: &str| path.encode_utf16().collect::<Vec<_>>();
+    // Short paths
+    assert_eq!(
+        to_extended_length(wide(r"C:\dir\plugin.dll")),
+        wide(r"C:\dir\plugin.dll")
+    );
+    assert_eq!(to_extended_length(wide(r"plugin.dll")), wide(r"plugin.dll"));
+    // UNC paths
+    assert_eq!(
+        to_extended_length(wide(r"\\server\share\plugin.dll")),
+        wide(r"\\?\UNC\server\share\plugin.dll")
+    );
+    assert_eq!(
+        to_extended_length(wide(r"\\?\UNC\server\share\a.dll")),
+        wide(r"\\?\UNC\server\share\a.dll")
+    );
+    assert_eq!(to_extended_length(wide(r"\\.\device")), wide(r"\\.\device"));
+    // Long paths
+    let long = format!(r"C:\{}\plugin.dll", "d".repeat(MAX_PATH));
+    assert_eq!(to_extended_length(wide(&long)), wide(&format!(r"\\?\{long}")));
+    let long_unc = format!(r"\\server\share\{}\plugin.dll", "d".repeat(MAX_PATH));
+    assert_eq!(
+        to_extended_length(wide(&long_unc)),
+        wide(&format!(r"\\?\UNC{}", &long_unc[1..]))
+    );
+    let long_dots = format!(r"C:\{}\..\plugin.dll", "d".repeat(MAX_PATH));
+    assert_eq!(to_extended_length(wide(&long_dots)), wide(&long_dots));
+    let long_relative = format!(r"{}\plugin.dll", "d".repeat(MAX_PATH));
+    assert_eq!(to_extended_length(wide(&long_relative)), wide(&long_relative));
+}
+
+// Descriptions of Win32 error codes.
+#[cfg(windows)]
+#[test]
+fn test_error_messages() {
+    use weaklink::loading::windows::{describe_error, error_message};
+
+    // ERROR_MOD_NOT_FOUND
+    assert!(!error_message(0x7E).unwrap().is_empty());
+    assert!(describe_error(0x7E).ends_with(" (0x0000007E)"));
+    assert_eq!(describe_error(0xDEAD_BEEF), "error 0xDEADBEEF");
+}
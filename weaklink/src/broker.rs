@@ -0,0 +1,51 @@
+//! Resolution through a broker, e.g. when the wrapped library is hosted by a helper process.
+
+use crate::{Address, Error, ErrorCode, Library};
+use std::ffi::CStr;
+use std::sync::PoisonError;
+
+/// Resolves symbols on behalf of a [`Library`], in place of loading its dynamic library.
+///
+/// This is intended for sandboxed architectures, where the wrapped library runs in a helper process, and calls are
+/// forwarded to it.  The broker reports which symbols the remote library provides, and supplies local proxy
+/// functions, which must have the same signatures as the symbols they stand for.  The same stub configuration thus
+/// serves both the in-process and the brokered mode.
+pub trait Broker: Send + Sync {
+    /// Returns true if the remote library provides the symbol.
+    fn has_symbol(&self, name: &CStr) -> bool;
+
+    /// Returns the address of a local function that forwards calls to the symbol, if the broker supports it.
+    ///
+    /// For data symbols, this is the address of a local copy of the data.
+    fn proxy(&self, name: &CStr) -> Option<Address>;
+}
+
+impl Library {
+    /// Sets the broker through which symbols are resolved, instead of loading the library's dynamic library.
+    ///
+    /// Applies to symbols resolved afterwards.  Groups bound to a dylib via
+    /// [`Group::resolve_loading_from`](crate::Group::resolve_loading_from) keep resolving from it, and natively
+    /// weak-linked symbols are unaffected.
+    pub fn set_broker(&self, broker: &'static dyn Broker) {
+        self.configure();
+        *self.broker.lock().unwrap_or_else(PoisonError::into_inner) = Some(broker);
+    }
+
+    // Returns the broker, if any.
+    pub(crate) fn broker(&self) -> Option<&'static dyn Broker> {
+        *self.broker.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Look up the proxy of a symbol provided by the broker.
+    pub(crate) fn resolve_brokered(&self, broker: &dyn Broker, sym_name: &CStr) -> Result<Address, Error> {
+        if !broker.has_symbol(sym_name) {
+            return Err(ErrorCode::SymbolNotFound.error(format!("Symbol {sym_name:?} is not provided by the broker.")));
+        }
+        match broker.proxy(sym_name) {
+            Some(address) => Ok(address),
+            None => Err(ErrorCode::SymbolNotFound.error(format!(
+                "Symbol {sym_name:?} is provided by the broker, but has no proxy."
+            ))),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Support for the C management API emitted by `weaklink_build` when `Config::c_api` is enabled.
+
+use crate::{Error, ErrorCode, ErrorExt};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::ptr;
+
+thread_local! {
+    // Message and code of the last error reported to C code on this thread.
+    static LAST_ERROR: RefCell<Option<(CString, ErrorCode)>> = const { RefCell::new(None) };
+}
+
+/// Records the error of `result`, if any, for [`last_error`] and [`last_error_code`], and returns whether `result`
+/// is `Ok`.
+pub fn report<T>(result: Result<T, Error>) -> bool {
+    let error = result.err().map(|err| {
+        let message = err.to_string().replace('\0', " ");
+        (CString::new(message).unwrap_or_default(), err.code())
+    });
+    let ok = error.is_none();
+    LAST_ERROR.with(|last| *last.borrow_mut() = error);
+    ok
+}
+
+/// Returns the message of the last error on this thread, or null if the last call succeeded.
+///
+/// The message remains valid until the next call of the C API on this thread.
+pub fn last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match &*last.borrow() {
+        Some((message, _)) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Returns the [`ErrorCode`] of the last error on this thread, or 0 if the last call succeeded.
+pub fn last_error_code() -> u32 {
+    LAST_ERROR.with(|last| match &*last.borrow() {
+        Some((_, code)) => *code as u32,
+        None => 0,
+    })
+}
+
+/// Converts a NUL-terminated path from C code.
+///
+/// # Safety
+/// `path` must point to a NUL-terminated string.
+pub unsafe fn path(path: *const c_char) -> Result<PathBuf, Error> {
+    if path.is_null() {
+        return Err(ErrorCode::InvalidArgument.error("Path is null"));
+    }
+    let path = CStr::from_ptr(path).to_str()?;
+    Ok(PathBuf::from(path))
+}
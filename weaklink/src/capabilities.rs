@@ -0,0 +1,56 @@
+//! Reporting of the groups available in the loaded library.
+
+use crate::Library;
+use std::fmt::Write;
+
+/// Availability of each group of a library. See [`Library::capabilities`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapabilitySet {
+    // Group name => availability, sorted by name.
+    groups: Vec<(&'static str, bool)>,
+}
+
+impl CapabilitySet {
+    /// Returns true if the specified group is available.
+    pub fn contains(&self, group_name: &str) -> bool {
+        self.groups.iter().any(|(name, available)| *name == group_name && *available)
+    }
+
+    /// Iterates over all groups and their availability, ordered by group name.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, bool)> + '_ {
+        self.groups.iter().copied()
+    }
+
+    /// Iterates over names of the available groups, in order.
+    pub fn available(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.iter().filter(|(_, available)| *available).map(|(name, _)| name)
+    }
+
+    /// Formats the set as a JSON object, mapping group names to their availability.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{");
+        for (i, (name, available)) in self.groups.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let _ = write!(json, "{separator}\"{}\":{available}", name.escape_default());
+        }
+        json.push('}');
+        json
+    }
+}
+
+impl Library {
+    /// Determines which groups of the library are available, by attempting to resolve each of them.
+    ///
+    /// Resolution results are cached by the groups, so this is cheap to call repeatedly.  The groups are not
+    /// asserted as resolved afterwards, and the library is not loaded implicitly: if it hasn't been loaded, only
+    /// groups bound via [`Group::resolve_loading_from`](crate::Group::resolve_loading_from) may be available.
+    pub fn capabilities(&self) -> CapabilitySet {
+        let mut groups = self
+            .groups
+            .iter()
+            .map(|group| (group.name(), group.is_loaded() && group.resolve().is_ok()))
+            .collect::<Vec<_>>();
+        groups.sort();
+        CapabilitySet { groups }
+    }
+}
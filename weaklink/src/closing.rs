@@ -0,0 +1,111 @@
+//! Shutdown of a library, which stops new resolutions while those in progress complete.
+
+use crate::{Error, ErrorCode, Library};
+use std::sync::{MutexGuard, PoisonError};
+
+// Number of resolutions and loads in progress, and whether the library has been closed, or is being unloaded.
+pub(crate) struct Activity {
+    in_flight: usize,
+    closed: bool,
+    unloading: bool,
+}
+
+impl Activity {
+    pub(crate) const fn new() -> Activity {
+        Activity {
+            in_flight: 0,
+            closed: false,
+            unloading: false,
+        }
+    }
+}
+
+// Marks a resolution or a load as in progress, until dropped.
+pub(crate) struct InFlight<'a>(&'a Library);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let mut activity = self.0.activity.lock().unwrap_or_else(PoisonError::into_inner);
+        activity.in_flight -= 1;
+        if activity.in_flight == 0 {
+            self.0.activity_done.notify_all();
+        }
+    }
+}
+
+// Lets resolutions start again once the library's dylib has been unloaded, or failed to.
+pub(crate) struct Quiesced<'a>(&'a Library);
+
+impl Drop for Quiesced<'_> {
+    fn drop(&mut self) {
+        self.0.activity.lock().unwrap_or_else(PoisonError::into_inner).unloading = false;
+    }
+}
+
+impl Library {
+    /// Shuts the library down: fails new resolutions, waits for those in progress to complete, and then unloads the
+    /// library if `unload` is true.
+    ///
+    /// This is meant for hosts that tear down while plugin threads may still race to resolve groups.  Once this is
+    /// called, resolving any symbol that isn't in the symbol table, as well as loading the library, fails with
+    /// [`ErrorCode::Closed`], which is reported like any other resolution failure, e.g. to the
+    /// [resolution hook](Library::set_resolution_hook).  In [checked mode](index.html#checked-mode), this includes
+    /// the symbols of groups whose tokens have been dropped.  Stubs of symbols that are resolved keep working, unless
+    /// the library is unloaded, which is subject to the conditions of [`Library::unload`].  The library can't be
+    /// reopened.  Must not be called from the library's callbacks, such as its resolution hook, which would wait
+    /// for their own resolution.
+    ///
+    /// ```ignore
+    /// stub::library.close(true)?;
+    /// // Threads racing with the shutdown find the groups unavailable.
+    /// assert!(stub::base.resolve().is_err());
+    /// ```
+    pub fn close(&self, unload: bool) -> Result<(), Error> {
+        let mut activity = self.activity.lock().unwrap_or_else(PoisonError::into_inner);
+        activity.closed = true;
+        drop(self.wait_for_resolutions(activity));
+        match unload && self.handle().is_some() {
+            true => self.unload(),
+            false => Ok(()),
+        }
+    }
+
+    /// Returns true if the library has been closed via [`Library::close`].
+    pub fn is_closed(&self) -> bool {
+        self.activity.lock().unwrap_or_else(PoisonError::into_inner).closed
+    }
+
+    // Marks a resolution or a load as in progress, unless the library has been closed.
+    pub(crate) fn begin_resolution(&self) -> Result<InFlight<'_>, Error> {
+        let mut activity = self.activity.lock().unwrap_or_else(PoisonError::into_inner);
+        if activity.closed {
+            return Err(ErrorCode::Closed.error(format!("{}: the library has been closed", self.name)));
+        }
+        if activity.unloading {
+            return Err(ErrorCode::NotLoaded.error(format!("{}: the library is being unloaded", self.name)));
+        }
+        activity.in_flight += 1;
+        Ok(InFlight(self))
+    }
+
+    // Fails new resolutions and loads, and waits for those in progress to complete, until the result is dropped.
+    pub(crate) fn quiesce(&self) -> Quiesced<'_> {
+        let mut activity = self.activity.lock().unwrap_or_else(PoisonError::into_inner);
+        activity.unloading = true;
+        drop(self.wait_for_resolutions(activity));
+        Quiesced(self)
+    }
+
+    // Returns true while resolutions fail because the library is closed or being unloaded.
+    pub(crate) fn is_quiesced(&self) -> bool {
+        let activity = self.activity.lock().unwrap_or_else(PoisonError::into_inner);
+        activity.closed || activity.unloading
+    }
+
+    fn wait_for_resolutions<'a>(&self, mut activity: MutexGuard<'a, Activity>) -> MutexGuard<'a, Activity> {
+        while activity.in_flight > 0 {
+            activity = self.activity_done.wait(activity).unwrap_or_else(PoisonError::into_inner);
+        }
+        activity
+    }
+}
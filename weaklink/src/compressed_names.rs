@@ -0,0 +1,94 @@
+//! Compact encoding of symbol names, generated at build time.
+//!
+//! Names are front-coded: they are sorted, and each one is stored as the length of the prefix it shares with the
+//! previous one, followed by the rest of it.  Mangled C++ names share long prefixes, so this typically takes a
+//! fraction of the space of separate literals.  The names are decoded on first use of the library.
+
+use std::ffi::CStr;
+
+/// Front-coded symbol names.
+#[doc(hidden)]
+pub struct CompressedNames {
+    // Shared prefix length and suffix length as LEB128 varints, followed by the suffix, for each name in sorted order.
+    blob: &'static [u8],
+    // Position in sorted order of each symbol's name.
+    order: &'static [u32],
+}
+
+impl CompressedNames {
+    pub const fn new(blob: &'static [u8], order: &'static [u32]) -> CompressedNames {
+        CompressedNames { blob, order }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    // Decodes the names of all symbols, in symbol index order.
+    pub(crate) fn decode(&self) -> &'static [&'static CStr] {
+        let mut buffer = Vec::new();
+        let mut ranges = Vec::new();
+        let mut name = Vec::new();
+        let mut pos = 0;
+        while pos < self.blob.len() {
+            let prefix_len = read_varint(self.blob, &mut pos);
+            let suffix_len = read_varint(self.blob, &mut pos);
+            name.truncate(prefix_len);
+            name.extend_from_slice(&self.blob[pos..pos + suffix_len]);
+            pos += suffix_len;
+            let start = buffer.len();
+            buffer.extend_from_slice(&name);
+            buffer.push(0);
+            ranges.push(start..buffer.len());
+        }
+        // Decoded once per library, which lives for the rest of the process.
+        let buffer: &'static [u8] = Box::leak(buffer.into_boxed_slice());
+        let names = self.order.iter().map(|position| {
+            let range = ranges[*position as usize].clone();
+            CStr::from_bytes_with_nul(&buffer[range]).expect("Malformed symbol name")
+        });
+        Box::leak(names.collect::<Vec<_>>().into_boxed_slice())
+    }
+}
+
+/// Encodes names for [`CompressedNames`], returning the blob and the order.
+#[doc(hidden)]
+pub fn encode(names: &[&str]) -> (Vec<u8>, Vec<u32>) {
+    let mut sorted = names.to_vec();
+    sorted.sort();
+    sorted.dedup();
+    let mut blob = Vec::new();
+    let mut previous: &[u8] = &[];
+    for name in &sorted {
+        let name = name.as_bytes();
+        let prefix_len = previous.iter().zip(name).take_while(|(a, b)| a == b).count();
+        write_varint(&mut blob, prefix_len);
+        write_varint(&mut blob, name.len() - prefix_len);
+        blob.extend_from_slice(&name[prefix_len..]);
+        previous = name;
+    }
+    let order = names.iter().map(|name| sorted.binary_search(name).unwrap() as u32);
+    (blob, order.collect())
+}
+
+fn write_varint(blob: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        blob.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    blob.push(value as u8);
+}
+
+fn read_varint(blob: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = blob[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
@@ -0,0 +1,142 @@
+//! Debugging aids for trimming stub configurations.
+
+use crate::{Error, ErrorCode, ErrorExt, Library};
+use std::io::{self, Write};
+use std::os::raw::c_int;
+use std::ptr;
+use std::sync::{Mutex, PoisonError};
+use std::time::SystemTime;
+
+// Libraries to report on at process exit.
+static EXIT_REPORTS: Mutex<Vec<&'static Library>> = Mutex::new(Vec::new());
+
+// Number of failures kept by `Library::recent_errors`.
+const RECENT_ERRORS: usize = 32;
+
+/// A failed resolution of a symbol.  See [`Library::recent_errors`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolutionFailure {
+    /// Index of the symbol.
+    pub sym_index: u32,
+    /// Name of the symbol, which is empty if the names are hashed and haven't been set, or are obfuscated.
+    pub symbol: String,
+    /// Code of the error.
+    pub code: ErrorCode,
+    /// Message of the error.
+    pub message: String,
+    /// When the resolution failed.
+    pub time: SystemTime,
+}
+
+extern "C" {
+    fn atexit(callback: extern "C" fn()) -> c_int;
+}
+
+impl Library {
+    /// Registers a process exit handler, which prints the groups and symbols of this library that were never
+    /// resolved during the run to stderr.
+    ///
+    /// Note that stubs jump straight through the symbol table, so individual calls are not observed: symbols
+    /// that were resolved as a part of some group, but never actually called, are not reported.
+    pub fn report_unused_at_exit(&'static self) {
+        let mut reports = EXIT_REPORTS.lock().unwrap();
+        if reports.is_empty() {
+            unsafe {
+                atexit(print_exit_reports);
+            }
+        }
+        if !reports.iter().any(|library| ptr::eq(*library, self)) {
+            reports.push(self);
+        }
+    }
+
+    /// Returns the most recent failures to resolve symbols of this library, oldest first.
+    ///
+    /// This allows diagnostics UIs to retrieve the details of failures that the host handled without reporting them,
+    /// e.g. of optional groups.  Only the last few failures are kept.
+    pub fn recent_errors(&self) -> Vec<ResolutionFailure> {
+        let recent_errors = self.recent_errors.lock().unwrap_or_else(PoisonError::into_inner);
+        recent_errors.iter().cloned().collect()
+    }
+
+    /// Returns the most recent failure to resolve a symbol of this library, if any.
+    pub fn last_error(&self) -> Option<ResolutionFailure> {
+        let recent_errors = self.recent_errors.lock().unwrap_or_else(PoisonError::into_inner);
+        recent_errors.back().cloned()
+    }
+
+    /// Forgets the failures returned by [`Library::recent_errors`].
+    pub fn clear_recent_errors(&self) {
+        self.recent_errors.lock().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+
+    // Describes a failure to resolve a symbol.
+    pub(crate) fn failure(&self, sym_index: u32, err: &Error) -> ResolutionFailure {
+        ResolutionFailure {
+            sym_index,
+            symbol: self.symbol_names()[sym_index as usize].to_string_lossy().into_owned(),
+            code: err.code(),
+            message: err.to_string(),
+            time: SystemTime::now(),
+        }
+    }
+
+    // Allocates the buffer of the failures returned by `recent_errors`, so that recording them doesn't allocate.
+    pub(crate) fn reserve_recent_errors(&self) {
+        let mut recent_errors = self.recent_errors.lock().unwrap_or_else(PoisonError::into_inner);
+        let len = recent_errors.len();
+        recent_errors.reserve(RECENT_ERRORS - len);
+    }
+
+    // Adds a failure to the ones returned by `recent_errors`, dropping the oldest one if needed.
+    pub(crate) fn record_failure(&self, sym_index: u32, err: &Error) {
+        let failure = self.failure(sym_index, err);
+        let mut recent_errors = self.recent_errors.lock().unwrap_or_else(PoisonError::into_inner);
+        if recent_errors.len() == RECENT_ERRORS {
+            recent_errors.pop_front();
+        }
+        recent_errors.push_back(failure);
+    }
+
+    /// Writes the names of groups and symbols of this library that have not been resolved so far.
+    ///
+    /// Writes nothing if everything has been resolved.
+    pub fn write_unused_report(&self, out: &mut dyn Write) -> io::Result<()> {
+        let groups = self.groups.iter().filter(|group| !group.was_resolved()).map(|group| group.name());
+        let groups = groups.collect::<Vec<_>>();
+        if !groups.is_empty() {
+            writeln!(
+                out,
+                "weaklink: {}: groups never resolved: {}",
+                self.name,
+                groups.join(", ")
+            )?;
+        }
+
+        // Symbols without a name belong to groups that are compiled out.
+        let symbols = (0..self.symbol_names().len() as u32)
+            .filter(|sym_index| !self.is_symbol_resolved(*sym_index))
+            .map(|sym_index| self.symbol_names()[sym_index as usize])
+            .filter(|sym_name| !sym_name.to_bytes().is_empty())
+            .map(|sym_name| sym_name.to_string_lossy())
+            .collect::<Vec<_>>();
+        if !symbols.is_empty() {
+            writeln!(
+                out,
+                "weaklink: {}: symbols never resolved: {}",
+                self.name,
+                symbols.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+extern "C" fn print_exit_reports() {
+    if let Ok(reports) = EXIT_REPORTS.lock() {
+        let mut stderr = io::stderr();
+        for library in reports.iter() {
+            let _ = library.write_unused_report(&mut stderr);
+        }
+    }
+}
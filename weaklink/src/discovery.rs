@@ -0,0 +1,68 @@
+//! Discovery of plugins in a directory.
+
+use crate::{loading, Address, DylibHandle, Error};
+use std::env::consts::DLL_EXTENSION;
+use std::ffi::CString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Exports of a dynamic library being examined by [`discover`].
+pub struct Exports<'a> {
+    path: &'a Path,
+    handle: DylibHandle,
+}
+
+impl<'a> Exports<'a> {
+    /// Returns the path of the dynamic library.
+    pub fn path(&self) -> &'a Path {
+        self.path
+    }
+
+    /// Returns the address of an exported symbol.
+    pub fn address(&self, name: &str) -> Option<Address> {
+        let name = CString::new(name).ok()?;
+        loading::find_symbol(self.handle, &name).ok()
+    }
+
+    /// Returns true if the dynamic library exports the specified symbol.
+    pub fn contains(&self, name: &str) -> bool {
+        self.address(name).is_some()
+    }
+
+    /// Returns the names of all symbols exported by the dynamic library.  See [`loading::exports_of`].
+    pub fn names(&self) -> Result<Vec<CString>, Error> {
+        let exports = loading::exports_of(self.handle)?;
+        Ok(exports.into_iter().map(|export| export.name).collect())
+    }
+}
+
+/// Scans a directory for dynamic libraries, and returns the paths of those accepted by `is_compatible`, sorted.
+///
+/// Each candidate is loaded for examination, and unloaded afterwards, so its initializers will run.  Files that
+/// can't be loaded are skipped.  The returned paths may be passed to [`Library::load_from`](crate::Library::load_from).
+pub fn discover(dir: &Path, mut is_compatible: impl FnMut(&Exports) -> bool) -> Result<Vec<PathBuf>, Error> {
+    let mut candidates = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(true, |ext| ext != DLL_EXTENSION) || !path.is_file() {
+            continue;
+        }
+        // Avoid making the candidate's symbols globally available on Unix.
+        #[cfg(unix)]
+        let handle =
+            loading::unix::load_library_with_flags(&path, loading::unix::RTLD_LAZY | loading::unix::RTLD_LOCAL);
+        #[cfg(windows)]
+        let handle = loading::load_library(&path);
+        let Ok(handle) = handle else {
+            continue;
+        };
+        let exports = Exports { path: &path, handle };
+        let compatible = is_compatible(&exports);
+        let _ = loading::close_library(handle);
+        if compatible {
+            candidates.push(path);
+        }
+    }
+    candidates.sort();
+    Ok(candidates)
+}
@@ -0,0 +1,165 @@
+//! Drop-in replacements of `dlopen`, `dlsym`, `dlclose` and `dlerror`, which redirect legacy host code that loads
+//! a plugin by itself through the [`Library`] wrapping it.  Enabled by the `dlopen-shim` feature.
+//!
+//! Libraries are registered along with the names under which the legacy code opens them, via the
+//! `register_dlopen_shim()` function generated for `Config::dlopen_names` in `weaklink_build`.  Opening one of
+//! these names then shares the library's handle, loading it via [`Library::load_from`] if needed, so that its
+//! manifest check, resolution hook and recording apply.  Looking up one of its stubbed symbols resolves it through
+//! the symbol table, as [`Library::resolve_by_index`] does, and closing the handle leaves the library loaded.
+//! Other names and handles are passed through to the platform loader.
+//!
+//! In C code, the legacy call sites may be redirected without rewriting them:
+//! ```c
+//! #define dlopen weaklink_dlopen
+//! #define dlsym weaklink_dlsym
+//! #define dlclose weaklink_dlclose
+//! #define dlerror weaklink_dlerror
+//! ```
+
+use crate::{loading, Address, DylibHandle, Error, ErrorCode, Library};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::path::Path;
+use std::ptr;
+use std::sync::{Mutex, PoisonError};
+
+// Registered libraries and the names legacy code opens them by.
+static REGISTRY: Mutex<Vec<(&'static Library, &'static [&'static str])>> = Mutex::new(Vec::new());
+
+thread_local! {
+    // Message of the last error of the shim on this thread, until `weaklink_dlerror` returns it, and the message it
+    // returned last, which must stay valid until its next call.
+    static DLERROR: RefCell<(Option<CString>, Option<CString>)> = const { RefCell::new((None, None)) };
+}
+
+/// Redirects opening of any of `names` through the shim to `library`.
+///
+/// Names match the argument of [`weaklink_dlopen`] either exactly, or by its file name.  Registering a library
+/// again replaces its names.
+pub fn register(library: &'static Library, names: &'static [&'static str]) {
+    let mut registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    registry.retain(|(registered, _)| !ptr::eq(*registered, library));
+    registry.push((library, names));
+}
+
+fn library_named(filename: &str) -> Option<&'static Library> {
+    let file_name = Path::new(filename).file_name().and_then(|name| name.to_str());
+    let registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    let matches =
+        |name: &&str| *name == filename || Path::new(name).file_name().and_then(|name| name.to_str()) == file_name;
+    registry.iter().find(|(_, names)| names.iter().any(matches)).map(|(library, _)| *library)
+}
+
+fn library_with_handle(handle: DylibHandle) -> Option<&'static Library> {
+    let registry = REGISTRY.lock().unwrap_or_else(PoisonError::into_inner);
+    let library = registry.iter().find(|(library, _)| library.handle().is_some_and(|own| own.0 == handle.0));
+    library.map(|(library, _)| *library)
+}
+
+fn open(filename: &CStr, flags: c_int) -> Result<DylibHandle, Error> {
+    let filename = filename.to_str()?;
+    let path = Path::new(filename);
+    match library_named(filename) {
+        Some(library) => match library.handle() {
+            Some(handle) => Ok(handle),
+            // Another thread may have loaded it in the meantime.
+            None => library.load_from(path).or_else(|err| library.handle().ok_or(err)),
+        },
+        #[cfg(unix)]
+        None => loading::unix::load_library_with_flags(path, flags),
+        #[cfg(not(unix))]
+        None => {
+            let _ = flags;
+            loading::load_library(path)
+        }
+    }
+}
+
+fn sym(handle: DylibHandle, symbol: &CStr) -> Result<Address, Error> {
+    if let Some(library) = library_with_handle(handle) {
+        if let Some(sym_index) = symbol.to_str().ok().and_then(|name| library.index_of(name)) {
+            return library.resolve_by_index(sym_index);
+        }
+    }
+    loading::find_symbol(handle, symbol)
+}
+
+// Records the error of `result`, if any, for `weaklink_dlerror`, and returns its value, or `failed` on error.
+// As with `dlerror`, successful calls keep the error of an earlier call.
+fn report<T>(result: Result<T, Error>, failed: T) -> T {
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            let message = CString::new(err.to_string().replace('\0', " ")).unwrap_or_default();
+            DLERROR.with(|dlerror| dlerror.borrow_mut().0 = Some(message));
+            failed
+        }
+    }
+}
+
+/// Replacement of `dlopen`: returns the handle of the registered library if `filename` is one of its names,
+/// loading it if needed.  Otherwise, opens `filename` with `flags` (ignored on Windows).
+///
+/// As with `dlopen`, a null `filename` returns a handle of the main program, via [`loading::main_program`], through
+/// which host code may look up its own symbols.  On Windows, this is the handle that `GetModuleHandle(NULL)` returns,
+/// which looks up the exports of the main executable only.
+///
+/// Returns null on failure, with the error available from [`weaklink_dlerror`].
+///
+/// # Safety
+/// `filename` must be null, or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn weaklink_dlopen(filename: *const c_char, flags: c_int) -> *mut c_void {
+    let result = match filename.is_null() {
+        true => loading::main_program(),
+        false => open(CStr::from_ptr(filename), flags),
+    };
+    report(result.map(|handle| handle.0 as *mut c_void), ptr::null_mut())
+}
+
+/// Replacement of `dlsym`: resolves stubbed symbols of a registered library through its symbol table, and looks
+/// up others directly.
+///
+/// Returns null on failure, with the error available from [`weaklink_dlerror`].
+///
+/// # Safety
+/// `handle` must have been returned by [`weaklink_dlopen`], and `symbol` must point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn weaklink_dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void {
+    let result = match symbol.is_null() {
+        true => Err(ErrorCode::InvalidArgument.error("Symbol name is null.")),
+        false => sym(DylibHandle(handle as usize), CStr::from_ptr(symbol)),
+    };
+    report(result.map(|address| address as *mut c_void), ptr::null_mut())
+}
+
+/// Replacement of `dlclose`: does nothing for handles of registered libraries, which stay loaded (see
+/// [`Library::unload`]), and closes others.
+///
+/// Returns 0 on success, with the error available from [`weaklink_dlerror`] otherwise.
+///
+/// # Safety
+/// `handle` must have been returned by [`weaklink_dlopen`].
+#[no_mangle]
+pub unsafe extern "C" fn weaklink_dlclose(handle: *mut c_void) -> c_int {
+    let handle = DylibHandle(handle as usize);
+    let result = match library_with_handle(handle) {
+        Some(_) => Ok(()),
+        None => loading::close_library(handle),
+    };
+    report(result.map(|()| 0), -1)
+}
+
+/// Replacement of `dlerror`: returns the message of the last error of the shim on this thread, or null if no error
+/// occurred since the last call of `weaklink_dlerror`, which clears it, as `dlerror` does.
+///
+/// The message remains valid until the next call of `weaklink_dlerror` on this thread.
+#[no_mangle]
+pub extern "C" fn weaklink_dlerror() -> *const c_char {
+    DLERROR.with(|dlerror| {
+        let (pending, returned) = &mut *dlerror.borrow_mut();
+        *returned = pending.take();
+        returned.as_ref().map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
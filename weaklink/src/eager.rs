@@ -0,0 +1,111 @@
+//! Resolution of all symbols at once, so that missing ones are found at startup rather than upon first use.
+
+use crate::{Error, ErrorCode, Group, Library, ResolutionFailure};
+use std::sync::atomic::Ordering;
+
+/// Outcome of [`Library::resolve_all`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResolutionReport {
+    /// Required symbols of groups that could not be resolved, in the order of their groups.
+    pub missing: Vec<ResolutionFailure>,
+    /// Names of the groups that can't be resolved: those with missing symbols, those rejected due to the version or
+    /// signatures of the library, and those that depend on any of these.
+    pub unavailable_groups: Vec<&'static str>,
+}
+
+impl ResolutionReport {
+    /// Whether all groups could be resolved.
+    pub fn is_complete(&self) -> bool {
+        self.unavailable_groups.is_empty()
+    }
+
+    /// Fails with [`ErrorCode::GroupUnresolved`], naming the unavailable groups and missing symbols, unless all
+    /// groups could be resolved.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_complete() {
+            return Ok(());
+        }
+        let symbols = self.missing.iter().map(|failure| failure.symbol.as_str()).collect::<Vec<_>>();
+        let message = format!(
+            "Groups {} could not be resolved, missing symbols: {}",
+            self.unavailable_groups.join(", "),
+            symbols.join(", ")
+        );
+        Err(ErrorCode::GroupUnresolved.error(message))
+    }
+}
+
+impl Library {
+    /// Resolves the symbols of all groups, loading the library first if needed, and reports the missing ones.
+    ///
+    /// This is meant for deployments that should fail fast at startup, e.g. via [`ResolutionReport::check`], rather
+    /// than find out about a missing symbol once its group is first used.  Unlike [`Group::resolve`], all symbols of
+    /// a group are looked up, so that the report lists all missing ones.  Groups that can't be resolved are marked
+    /// as failed, so that resolving them later fails right away.  Optional members of groups are resolved if
+    /// present, and never reported as missing.  Calling this again looks up the missing symbols again.
+    ///
+    /// Like [`Library::resolve_by_index`], this doesn't assert resolution of the groups: in
+    /// [checked mode](index.html#checked-mode), their symbol table entries are reset afterwards, while the addresses
+    /// stay cached, so that the groups must still be resolved before use.  See also
+    /// [`Library::set_eager_binding`].
+    ///
+    /// ```ignore
+    /// stub::library.resolve_all()?.check()?;
+    /// ```
+    pub fn resolve_all(&self) -> Result<ResolutionReport, Error> {
+        self.configure();
+        if self.handle().is_none() && self.broker().is_none() {
+            self.load_default()?;
+        }
+        Ok(self.resolve_groups())
+    }
+
+    /// Makes [`Library::load`] and [`Library::load_from`] resolve all symbols right after loading the library, as
+    /// [`Library::resolve_all`] does.
+    ///
+    /// Missing symbols don't fail the load, but the groups containing them fail to resolve right away.  Their
+    /// failures are reported to the [resolution hook](Library::set_resolution_hook), and by
+    /// [`Library::recent_errors`], and a report may be obtained by calling [`Library::resolve_all`] afterwards.
+    /// Handles set via [`Library::set_handle`] are not resolved.  The default is set via `Config::eager_binding` in
+    /// `weaklink_build`.
+    pub fn set_eager_binding(&self, enabled: bool) {
+        self.configure();
+        self.eager_binding.store(enabled, Ordering::Release);
+    }
+
+    // Resolves all symbols of the newly loaded library, if eager binding is enabled.
+    pub(crate) fn bind_eagerly(&self) {
+        if self.eager_binding.load(Ordering::Acquire) {
+            self.resolve_groups();
+        }
+    }
+
+    fn resolve_groups(&self) -> ResolutionReport {
+        let mut missing = Vec::new();
+        let mut failed = Vec::new();
+        for group in self.groups {
+            if !group.resolve_eagerly(&mut missing) {
+                failed.push(group.name());
+            }
+        }
+        self.reset_unasserted();
+
+        // Symbols of several groups are reported once.
+        let mut reported = Vec::new();
+        missing.retain(|failure: &ResolutionFailure| {
+            let first = !reported.contains(&failure.sym_index);
+            reported.push(failure.sym_index);
+            first
+        });
+        let unavailable_groups = self.groups.iter().filter(|group| !is_available(group, &failed));
+        ResolutionReport {
+            missing,
+            unavailable_groups: unavailable_groups.map(|group| group.name()).collect(),
+        }
+    }
+}
+
+// Whether neither the group, nor any group it depends on, has failed.
+fn is_available(group: &Group, failed: &[&str]) -> bool {
+    !failed.contains(&group.name()) && group.dependencies().iter().all(|dep| is_available(dep, failed))
+}
@@ -0,0 +1,143 @@
+//! Numeric codes of errors, which stay stable across versions, unlike the messages.
+
+use crate::loading::DlError;
+use crate::{messages, Error, VersionTooOld};
+use std::fmt;
+
+/// Stable numeric code of a weaklink error.  See [`ErrorExt::code`].
+///
+/// Codes are never reused or renumbered, so they may be mapped by C code or telemetry without matching messages.
+/// New codes may be added in minor versions.
+#[non_exhaustive]
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// An error without a specific code, e.g. one returned by a callback.
+    Other = 1,
+    /// The dylib could not be found.
+    LibraryNotFound = 2,
+    /// The library is not loaded, and can't be loaded implicitly.
+    NotLoaded = 3,
+    /// The library has already been loaded.
+    AlreadyLoaded = 4,
+    /// The dylib, or one of its dependencies, failed to load.
+    LoadFailed = 5,
+    /// A symbol is not present in the dylib.
+    SymbolNotFound = 6,
+    /// A group could not be resolved.
+    GroupUnresolved = 7,
+    /// A group requires a newer version of the library.  See [`VersionTooOld`].
+    VersionTooOld = 8,
+    /// The symbol table is sealed.
+    Sealed = 9,
+    /// The library has already been configured, or its settings have already been used.
+    AlreadyConfigured = 10,
+    /// A setting needed to resolve symbols, such as a key provider or a factory callback, is missing.
+    NotConfigured = 11,
+    /// A recording or a manifest is malformed, or doesn't match the library.
+    InvalidManifest = 12,
+    /// An argument is invalid, e.g. a symbol index is out of range.
+    InvalidArgument = 13,
+    /// The operation is not supported on this target.
+    Unsupported = 14,
+    /// A loaded module has an unexpected format.
+    InvalidModule = 15,
+    /// A call of the OS failed, e.g. changing the protection of memory.
+    SystemError = 16,
+    /// A cached address is no longer mapped.
+    StaleAddress = 17,
+    /// A struct of pointers, or an interface, returned by the dylib is unusable.
+    InterfaceUnavailable = 18,
+    /// The signatures of a group's symbols don't match those reported by the dylib.
+    SignatureMismatch = 19,
+    /// A dylib was rejected, before loading it because of its capability manifest, or by its post-load check.
+    PluginRejected = 20,
+    /// The library is in use, e.g. its groups are asserted, so it can't be unloaded.
+    InUse = 21,
+    /// The library has been closed via [`Library::close`](crate::Library::close).
+    Closed = 22,
+}
+
+impl ErrorCode {
+    /// Returns the code for a numeric value, if it is known.
+    pub fn from_u32(value: u32) -> Option<ErrorCode> {
+        use ErrorCode::*;
+        let codes = [
+            Other,
+            LibraryNotFound,
+            NotLoaded,
+            AlreadyLoaded,
+            LoadFailed,
+            SymbolNotFound,
+            GroupUnresolved,
+            VersionTooOld,
+            Sealed,
+            AlreadyConfigured,
+            NotConfigured,
+            InvalidManifest,
+            InvalidArgument,
+            Unsupported,
+            InvalidModule,
+            SystemError,
+            StaleAddress,
+            InterfaceUnavailable,
+            SignatureMismatch,
+            PluginRejected,
+            InUse,
+            Closed,
+        ];
+        codes.into_iter().find(|code| *code as u32 == value)
+    }
+
+    // Creates an error with this code.
+    pub(crate) fn error(self, message: impl Into<String>) -> Error {
+        CodedError {
+            code: self,
+            message: message.into(),
+        }
+        .into()
+    }
+}
+
+/// An error that carries an [`ErrorCode`].
+///
+/// It displays as customized by the [message formatter](crate::set_message_formatter), if any, while `message`
+/// keeps the default message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodedError {
+    /// Code of the error.
+    pub code: ErrorCode,
+    /// Message of the error.
+    pub message: String,
+}
+
+impl fmt::Display for CodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        messages::write_message(self, f, |f| f.write_str(&self.message))
+    }
+}
+
+impl std::error::Error for CodedError {}
+
+/// Provides the [`ErrorCode`] of an [`Error`].
+pub trait ErrorExt {
+    /// Returns the code of the error, or [`ErrorCode::Other`] if it didn't originate in weaklink.
+    fn code(&self) -> ErrorCode;
+}
+
+impl ErrorExt for dyn std::error::Error {
+    fn code(&self) -> ErrorCode {
+        if let Some(err) = self.downcast_ref::<CodedError>() {
+            err.code
+        } else if self.is::<VersionTooOld>() {
+            ErrorCode::VersionTooOld
+        } else if let Some(err) = self.downcast_ref::<DlError>() {
+            match err.symbol {
+                Some(_) => ErrorCode::SymbolNotFound,
+                None => ErrorCode::LoadFailed,
+            }
+        } else {
+            ErrorCode::Other
+        }
+    }
+}
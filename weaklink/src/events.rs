@@ -0,0 +1,59 @@
+//! Observation of the runtime's activity, e.g. for logging or telemetry.
+
+use crate::{Address, Error, Library};
+use std::ffi::CStr;
+use std::path::Path;
+use std::sync::PoisonError;
+
+/// Activity of a library, reported to its resolution hook.  See [`Library::set_resolution_hook`].
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum HookEvent<'a> {
+    /// An attempt to load the library from `path`, which failed with `error`, if any.
+    Load { path: &'a Path, error: Option<&'a Error> },
+    /// A symbol was resolved, and its entry in the symbol table was set to `address`.
+    Resolved {
+        sym_index: u32,
+        /// Name of the symbol, which is empty if the names are hashed and haven't been set, or are obfuscated.
+        symbol: &'a CStr,
+        address: Address,
+    },
+    /// A symbol could not be resolved.
+    Failed {
+        sym_index: u32,
+        /// Name of the symbol, which is empty if the names are hashed and haven't been set, or are obfuscated.
+        symbol: &'a CStr,
+        error: &'a Error,
+    },
+}
+
+/// Called with each event of a library.  See [`Library::set_resolution_hook`].
+pub type ResolutionHook = fn(&HookEvent);
+
+impl Library {
+    /// Sets the function that observes the library's activity: attempts to load it, and resolutions of its symbols,
+    /// whether successful or not.
+    ///
+    /// This lets applications log or collect telemetry about the behavior of plugins.  Addresses found in the
+    /// symbol table, e.g. of symbols resolved by an earlier resolution of another group, are not reported again.
+    /// The hook is called on the thread performing the resolution, and must not call into the library.
+    ///
+    /// ```ignore
+    /// stub::library.set_resolution_hook(|event| match event {
+    ///     HookEvent::Failed { symbol, error, .. } => log::warn!("{symbol:?}: {error}"),
+    ///     event => log::debug!("{event:?}"),
+    /// });
+    /// ```
+    pub fn set_resolution_hook(&self, hook: ResolutionHook) {
+        self.configure();
+        *self.resolution_hook.lock().unwrap_or_else(PoisonError::into_inner) = Some(hook);
+    }
+
+    // Reports an event to the resolution hook, if any.
+    pub(crate) fn notify(&self, event: &HookEvent) {
+        let hook = *self.resolution_hook.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(hook) = hook {
+            hook(event);
+        }
+    }
+}
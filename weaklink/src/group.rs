@@ -1,7 +1,9 @@
-use crate::{Error, Library};
+use crate::{loading, Address, DylibHandle, Error, ErrorCode, Library, ResolutionFailure};
 use std::{
-    mem,
-    sync::atomic::{AtomicU8, Ordering},
+    ffi::CStr,
+    fmt, mem,
+    path::Path,
+    sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
 };
 
 /// Represents a group of symbols defined at build time.
@@ -10,7 +12,20 @@ pub struct Group {
     name: &'static str,
     library: &'static Library,
     sym_indices: &'static [u32],
+    // Symbols resolved along with the group, if present.
+    optional_indices: &'static [u32],
+    // Names of the symbols in `sym_indices` followed by those in `optional_indices`, if emitted per group.
+    names: &'static [&'static CStr],
+    // Groups resolved along with this one.
+    dependencies: &'static [&'static Group],
+    // Minimum version of the library required by the group.
+    min_version: Option<&'static str>,
     status: AtomicU8,
+    permanent: AtomicBool,
+    // Whether the group has ever been resolved successfully (in checked mode `status` doesn't record this).
+    was_resolved: AtomicBool,
+    // Handle of the dylib loaded via `resolve_loading_from`, or 0 if the group uses the library's default one.
+    handle: AtomicUsize,
 }
 
 /// Not yet attempted to resolve
@@ -22,12 +37,27 @@ const GROUP_STATUS_FAILED: u8 = 2;
 
 impl Group {
     #[doc(hidden)]
-    pub const fn new(name: &'static str, library: &'static Library, sym_indices: &'static [u32]) -> Group {
+    pub const fn new(
+        name: &'static str,
+        library: &'static Library,
+        sym_indices: &'static [u32],
+        optional_indices: &'static [u32],
+        names: &'static [&'static CStr],
+        dependencies: &'static [&'static Group],
+        min_version: Option<&'static str>,
+    ) -> Group {
         Group {
             name,
             library,
             sym_indices,
+            optional_indices,
+            names,
+            dependencies,
+            min_version,
             status: AtomicU8::new(GROUP_STATUS_UNKNOWN),
+            permanent: AtomicBool::new(false),
+            was_resolved: AtomicBool::new(false),
+            handle: AtomicUsize::new(0),
         }
     }
 
@@ -35,33 +65,260 @@ impl Group {
     /// The result is cached, so repeated calls will not trigger re-resolution.
     ///
     /// On success, this function returns a resolution state token. In [checked mode](index.html#checked-mode),
-    /// the group’s resolution state is considered "resolved" only for the lifetime of the token. Once the token
-    /// is dropped, the group's state reverts to "unknown" from the perspective of the calling thread.
-    pub fn resolve(&self) -> Result<GroupResolved, Error> {
+    /// the group’s resolution state is considered "resolved" only for the lifetime of the token. Once the last
+    /// token is dropped, the group's state reverts to "unknown" for all threads.
+    ///
+    /// If the group requires a minimum library version, which the library doesn't satisfy, this fails with
+    /// [`VersionTooOld`](crate::VersionTooOld).  Groups the group depends on are resolved first, and the token
+    /// covers them as well.  Optional members of the group are resolved if present, but not required.
+    pub fn resolve(&self) -> Result<GroupResolved<'_>, Error> {
+        if let Some(required) = self.min_version {
+            self.library.check_version(self.name, required)?;
+        }
+        // Dependency tokens are released once this group's token asserts their resolution.
+        let _dependencies = self.dependencies.iter().map(|dep| dep.resolve()).collect::<Result<Vec<_>, _>>()?;
         let is_resolved = match self.status.load(Ordering::Acquire) {
             GROUP_STATUS_UNKNOWN => {
+                // Not being loaded yet is not a resolution failure, so don't cache it.  Natively weak-linked
+                // symbols, and those resolved through a broker, don't need the library to be loaded by us.
+                let handle = match self.handle() {
+                    Some(handle) => Some(handle),
+                    None if self.library.broker().is_some() => None,
+                    None if self.sym_indices.iter().all(|idx| self.library.is_native(*idx)) => None,
+                    None => Some(self.library.ensure_loaded()?),
+                };
+                if let Some(handle) = handle {
+                    if let Err(err) = self.library.check_group_signature(self.name, handle) {
+                        self.status.store(GROUP_STATUS_FAILED, Ordering::Release);
+                        return Err(err);
+                    }
+                }
                 for sym_index in self.sym_indices {
-                    if let Err(err) = self.library.resolve_symbol(*sym_index) {
+                    if let Err(err) = self.library.resolve_symbol_from(*sym_index, handle) {
                         // Cache failed status
                         self.status.store(GROUP_STATUS_FAILED, Ordering::Release);
                         return Err(err);
                     }
                 }
+                for sym_index in self.optional_indices {
+                    let _ = self.library.resolve_symbol_from(*sym_index, handle);
+                }
                 // In checked mode we can't cache the "resolved" state, as the symbol table entries
                 // will be reset to null upon dropping the token.
                 #[cfg(not(feature = "checked"))]
                 self.status.store(GROUP_STATUS_RESOLVED, Ordering::Release);
                 true
             }
-            GROUP_STATUS_RESOLVED => true,
-            GROUP_STATUS_FAILED | _ => false,
+            GROUP_STATUS_RESOLVED => {
+                // Spot-check the first symbol, in case the library has been unloaded since.
+                if let Some(sym_index) = self.sym_indices.first() {
+                    self.library.resolve_symbol_from(*sym_index, self.handle())?;
+                }
+                true
+            }
+            _ => false,
         };
         if is_resolved {
-            self.library.assert_resolved(self.sym_indices);
-            Ok(GroupResolved(self))
+            self.was_resolved.store(true, Ordering::Release);
+            self.assert_resolved();
+            Ok(GroupResolved(self, self.library.generation.load(Ordering::SeqCst)))
         } else {
-            Err(format!("Group {} could not be resolved", self.name).into())
+            Err(ErrorCode::GroupUnresolved.error(format!("Group {} could not be resolved", self.name)))
+        }
+    }
+
+    /// Resolves the group's symbols from the dynamic library at `path`, loading it the first time this is called.
+    ///
+    /// This is intended for optional groups implemented by a separate dynamic library, which should only be loaded
+    /// once the feature is used.  The group stays bound to that dylib: subsequent calls to [`Group::resolve`] resolve
+    /// from it as well, rather than loading the library's default dylib.  Symbols that have already been resolved,
+    /// e.g. via another group, keep their addresses.
+    ///
+    /// The dylib is loaded like the library's own: after pre-loading the library's dependencies from its directory,
+    /// and subject to the library's manifest and [post-load](crate::Library::set_post_load_check) checks.  Its load
+    /// is reported to the [resolution hook](crate::Library::set_resolution_hook), and recorded for
+    /// [replay](crate::Library::replay).  If threads race to bind the group, the dylib loaded by the first one is
+    /// used, and the others close theirs.
+    pub fn resolve_loading_from(&self, path: &Path) -> Result<GroupResolved<'_>, Error> {
+        if self.handle().is_none() {
+            let handle = self.library.load_group_dylib(path)?;
+            match self.handle.compare_exchange(0, handle.0, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => self.library.record_group_load(self.name, path),
+                Err(_) => {
+                    // Only drops this thread's reference, if both loaded the same dylib.
+                    let _ = loading::close_library(handle);
+                }
+            }
+        }
+        self.resolve()
+    }
+
+    // Returns the handle of the dylib the group has been bound to via `resolve_loading_from`.
+    pub(crate) fn handle(&self) -> Option<DylibHandle> {
+        match self.handle.load(Ordering::Acquire) {
+            0 => None,
+            raw_handle => Some(DylibHandle(raw_handle)),
+        }
+    }
+
+    /// Resolves the group's symbols in the specified library instance, rather than the one used by the stubs.
+    ///
+    /// This neither affects the symbol table, nor the group's cached resolution status. It is intended for
+    /// advanced users that load multiple copies of a library and dispatch calls to them explicitly.
+    pub fn resolve_from(&self, handle: DylibHandle) -> Result<BoundGroup<'_>, Error> {
+        let mut addresses = Vec::with_capacity(self.sym_indices.len());
+        for sym_index in self.sym_indices {
+            addresses.push(self.library.find_symbol_in(*sym_index, handle)?);
+        }
+        Ok(BoundGroup {
+            group: self,
+            handle,
+            addresses: addresses.into_boxed_slice(),
+        })
+    }
+
+    /// Resolves the group and makes the resolution permanent for the rest of the process lifetime.
+    ///
+    /// Equivalent to `group.resolve()?.mark_permanent()`.
+    pub fn resolve_global(&self) -> Result<(), Error> {
+        self.resolve()?.mark_permanent();
+        Ok(())
+    }
+
+    /// Calls `f` if the group's symbols could be resolved.
+    ///
+    /// The group is considered resolved for the duration of the call, as if a [`GroupResolved`] token was held.
+    pub fn if_resolved<R>(&self, f: impl FnOnce() -> R) -> Result<R, Error> {
+        let _token = self.resolve()?;
+        Ok(f())
+    }
+
+    /// Like [`Group::if_resolved`], but for fallible closures.
+    ///
+    /// The closure's own error is propagated via [`GroupCallError::Call`], so that it may be told apart from
+    /// a group resolution failure.
+    pub fn try_if_resolved<R, E>(&self, f: impl FnOnce() -> Result<R, E>) -> Result<R, GroupCallError<E>> {
+        let _token = self.resolve().map_err(GroupCallError::Unresolved)?;
+        f().map_err(GroupCallError::Call)
+    }
+
+    // Asserts resolution of the group's symbols, including those of its dependencies.
+    fn assert_resolved(&self) {
+        self.library.assert_resolved(self.sym_indices);
+        self.library.assert_resolved(self.optional_indices);
+        for dep in self.dependencies {
+            dep.assert_resolved();
+        }
+    }
+
+    fn deassert_resolved(&self) {
+        self.library.deassert_resolved(self.sym_indices);
+        self.library.deassert_resolved(self.optional_indices);
+        for dep in self.dependencies {
+            dep.deassert_resolved();
+        }
+    }
+
+    /// Returns the name of the group, as defined at build time.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the names of the group's symbols, as exported by the dynamic library: the required ones, followed by
+    /// the optional ones.  Symbols of the groups it depends on are not included.
+    ///
+    /// Names are empty if they are hashed and haven't been set, or are obfuscated.
+    pub fn symbols(&self) -> impl Iterator<Item = &'static CStr> {
+        let symbol_names = self.library.symbol_names();
+        let sym_indices = self.sym_indices.iter().chain(self.optional_indices);
+        sym_indices.map(move |sym_index| symbol_names[*sym_index as usize])
+    }
+
+    /// Returns the number of the group's symbols, including the optional ones.
+    pub fn len(&self) -> usize {
+        self.sym_indices.len() + self.optional_indices.len()
+    }
+
+    /// Returns true if the group has no symbols of its own, e.g. if it only depends on other groups.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn dependencies(&self) -> &'static [&'static Group] {
+        self.dependencies
+    }
+
+    pub(crate) fn library(&self) -> &'static Library {
+        self.library
+    }
+
+    // Appends the indices of the group's required symbols, including those of its dependencies.
+    pub(crate) fn collect_required(&self, sym_indices: &mut Vec<u32>) {
+        sym_indices.extend_from_slice(self.sym_indices);
+        for dep in self.dependencies {
+            dep.collect_required(sym_indices);
+        }
+    }
+
+    // Whether the dylib the group resolves from has been loaded.
+    pub(crate) fn is_loaded(&self) -> bool {
+        self.handle().is_some() || self.library.handle().is_some()
+    }
+
+    pub(crate) fn was_resolved(&self) -> bool {
+        self.was_resolved.load(Ordering::Acquire)
+    }
+
+    // Resolves the group's own symbols like `resolve`, but looks up all of them rather than stopping at the first
+    // missing one, whose failures are added to `missing`, and even if the group has failed before.  Returns whether
+    // the group could be resolved, regardless of its dependencies.  Doesn't assert the resolution.
+    pub(crate) fn resolve_eagerly(&self, missing: &mut Vec<ResolutionFailure>) -> bool {
+        if let Some(required) = self.min_version {
+            if self.library.check_version(self.name, required).is_err() {
+                return false;
+            }
+        }
+        let handle = match self.handle() {
+            Some(handle) => Some(handle),
+            None if self.library.broker().is_some() => None,
+            None => self.library.handle(),
+        };
+        if let Some(handle) = handle {
+            if self.library.check_group_signature(self.name, handle).is_err() {
+                self.status.store(GROUP_STATUS_FAILED, Ordering::Release);
+                return false;
+            }
+        }
+        let mut resolved = self.status.load(Ordering::Acquire) != GROUP_STATUS_FAILED;
+        for sym_index in self.sym_indices {
+            if let Err(err) = self.library.resolve_symbol_from(*sym_index, handle) {
+                missing.push(self.library.failure(*sym_index, &err));
+                resolved = false;
+            }
         }
+        for sym_index in self.optional_indices {
+            let _ = self.library.resolve_symbol_from(*sym_index, handle);
+        }
+        if !resolved {
+            self.status.store(GROUP_STATUS_FAILED, Ordering::Release);
+        }
+        #[cfg(not(feature = "checked"))]
+        if resolved {
+            self.status.store(GROUP_STATUS_RESOLVED, Ordering::Release);
+        }
+        resolved
+    }
+
+    // Forgets the resolution status, e.g. when the library is unloaded.
+    pub(crate) fn reset(&self) {
+        self.status.store(GROUP_STATUS_UNKNOWN, Ordering::Release);
+        self.permanent.store(false, Ordering::Release);
+    }
+
+    // Index and name of each symbol of the group, if the names are emitted per group.
+    pub(crate) fn symbol_names(&self) -> impl Iterator<Item = (u32, &'static CStr)> {
+        let sym_indices = self.sym_indices.iter().chain(self.optional_indices);
+        sym_indices.zip(self.names).map(|(sym_index, name)| (*sym_index, *name))
     }
 
     /// Marks the group as having failed symbol resolution.
@@ -73,19 +330,159 @@ impl Group {
 }
 
 /// Represents resolved state of a [Group]. See [Group::resolve()]
-pub struct GroupResolved<'a>(&'a Group);
+///
+/// Cloning the token is cheap: it re-asserts the group's resolved state, which is reference-counted.
+pub struct GroupResolved<'a>(&'a Group, usize);
 
 impl<'a> GroupResolved<'a> {
+    /// Returns the group this token belongs to.
+    pub fn group(&self) -> &'a Group {
+        self.0
+    }
+
     /// Make group resolution permanent.
     ///
     /// Intended for permanently resolving one or more non-optional API groups.
     pub fn mark_permanent(self) {
+        self.0.permanent.store(true, Ordering::Release);
         mem::forget(self);
     }
+
+    /// Checks that the group's symbols are still loaded, and fails with [`ErrorCode::StaleAddress`] otherwise.
+    ///
+    /// Calling the group's stubs would then jump into unmapped memory.  This is the case once the library has been
+    /// unloaded via [`Library::unload`] since the group was resolved, which is detected by comparing a counter of
+    /// unloads.  If [validity checks](Library::set_validity_checks) are enabled, this also verifies that the address
+    /// of the group's first symbol is still mapped, which catches dylibs unloaded by other code.
+    pub fn check(&self) -> Result<(), Error> {
+        let library = self.0.library;
+        if library.generation.load(Ordering::SeqCst) != self.1 {
+            let message = format!(
+                "{}: the library was unloaded since {} was resolved",
+                library.name, self.0.name
+            );
+            return Err(ErrorCode::StaleAddress.error(message));
+        }
+        match self.0.sym_indices.first() {
+            Some(&sym_index) => match library.symbol_table_entry(sym_index).load(Ordering::Acquire) {
+                0 => Ok(()),
+                address => library.check_mapped(sym_index, address).map(drop),
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Returns true if resolution of this token's group has been made permanent via any of its tokens.
+    pub fn is_permanent(&self) -> bool {
+        self.0.permanent.load(Ordering::Acquire)
+    }
+
+    /// Releases the resolved state assertion, while keeping the resolved symbol addresses cached.
+    ///
+    /// The returned [`GroupResolvedWeak`] may be upgraded back to a full token without re-resolving symbols.
+    pub fn downgrade(self) -> GroupResolvedWeak<'a> {
+        GroupResolvedWeak(self.0)
+    }
+}
+
+impl<'a> Clone for GroupResolved<'a> {
+    fn clone(&self) -> Self {
+        self.0.assert_resolved();
+        GroupResolved(self.0, self.1)
+    }
 }
 
 impl<'a> Drop for GroupResolved<'a> {
     fn drop(&mut self) {
-        self.0.library.deassert_resolved(self.0.sym_indices);
+        self.0.deassert_resolved();
+    }
+}
+
+// Tokens may be moved to, or shared with, other threads.
+#[allow(dead_code)]
+fn assert_tokens_send_sync() {
+    fn check<T: Send + Sync>() {}
+    check::<GroupResolved<'static>>();
+    check::<GroupResolvedWeak<'static>>();
+}
+
+/// A [Group] that has been resolved, but is not asserted as such. See [GroupResolved::downgrade()]
+#[derive(Clone, Copy)]
+pub struct GroupResolvedWeak<'a>(&'a Group);
+
+impl<'a> GroupResolvedWeak<'a> {
+    /// Returns the group this token belongs to.
+    pub fn group(&self) -> &'a Group {
+        self.0
+    }
+
+    /// Re-asserts the group's resolved state.
+    ///
+    /// The group is resolved again, as by [`Group::resolve`], so this fails if it can no longer be resolved, e.g.
+    /// because it has been marked as failed via [`Group::mark_failed`], the library has been unloaded or closed in the
+    /// meantime, or sealed while some of the group's symbols were unresolved, or one of them can't be found.
+    pub fn upgrade(&self) -> Result<GroupResolved<'a>, Error> {
+        self.0.resolve()
+    }
+}
+
+/// Symbols of a [Group] resolved in a specific library instance. See [Group::resolve_from()]
+pub struct BoundGroup<'a> {
+    group: &'a Group,
+    handle: DylibHandle,
+    // Parallel to `group.sym_indices`.
+    addresses: Box<[Address]>,
+}
+
+impl<'a> BoundGroup<'a> {
+    /// Returns the group this instance binding belongs to.
+    pub fn group(&self) -> &'a Group {
+        self.group
+    }
+
+    /// Returns the handle of the library instance the symbols were resolved from.
+    pub fn handle(&self) -> DylibHandle {
+        self.handle
+    }
+
+    /// Returns the address of the specified group member in this library instance.
+    pub fn address(&self, name: &CStr) -> Option<Address> {
+        self.symbols().find(|(sym_name, _)| *sym_name == name).map(|(_, address)| address)
+    }
+
+    /// Returns an iterator over the names and addresses of the group members.
+    pub fn symbols(&self) -> impl Iterator<Item = (&'static CStr, Address)> + '_ {
+        let symbol_names = self.group.library.symbol_names();
+        let sym_indices = self.group.sym_indices.iter();
+        sym_indices
+            .zip(self.addresses.iter())
+            .map(move |(sym_index, address)| (symbol_names[*sym_index as usize], *address))
+    }
+}
+
+/// Error returned by [`Group::try_if_resolved`].
+#[derive(Debug)]
+pub enum GroupCallError<E> {
+    /// The group could not be resolved, so the closure was not called.
+    Unresolved(Error),
+    /// The closure was called and returned an error.
+    Call(E),
+}
+
+impl<E: fmt::Display> fmt::Display for GroupCallError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupCallError::Unresolved(err) => err.fmt(f),
+            GroupCallError::Call(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for GroupCallError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GroupCallError::Unresolved(err) => Some(err.as_ref()),
+            GroupCallError::Call(err) => Some(err),
+        }
     }
 }
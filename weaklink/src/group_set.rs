@@ -0,0 +1,187 @@
+//! Sets of symbols combined from groups at runtime.
+
+use crate::{Error, Group, Library};
+use std::ffi::CStr;
+use std::fmt;
+use std::ptr;
+
+/// A set of symbols of a library, combined from its groups at runtime, which can be resolved as a unit.
+///
+/// This expresses capability predicates that span groups, such as "A and B, but not the legacy symbols of C",
+/// without defining a group for every combination at build time:
+/// ```ignore
+/// let set = GroupSet::of(&foo::a).union(&GroupSet::of(&foo::b)).difference(&GroupSet::of(&foo::legacy_c));
+/// if let Ok(_token) = set.resolve() {
+///     // All symbols of A and B, except those shared with C, may be called here.
+/// }
+/// ```
+///
+/// A set built from a group contains its required symbols and those of the groups it depends on.  Optional
+/// members, minimum versions and dylibs bound via [`Group::resolve_loading_from`] are not carried over: the set
+/// resolves from the library's default dylib, and is available iff all of its symbols are present.
+#[derive(Clone)]
+pub struct GroupSet {
+    library: &'static Library,
+    // Sorted and deduplicated.
+    sym_indices: Vec<u32>,
+}
+
+impl GroupSet {
+    /// Returns the set of the symbols required by a group.
+    pub fn of(group: &Group) -> GroupSet {
+        let mut sym_indices = Vec::new();
+        group.collect_required(&mut sym_indices);
+        sym_indices.sort_unstable();
+        sym_indices.dedup();
+        GroupSet {
+            library: group.library(),
+            sym_indices,
+        }
+    }
+
+    /// Returns an empty set of symbols of the library.
+    pub fn empty(library: &'static Library) -> GroupSet {
+        GroupSet {
+            library,
+            sym_indices: Vec::new(),
+        }
+    }
+
+    // Returns the set of the symbols with the specified indices, in any order.
+    pub(crate) fn from_indices(library: &'static Library, mut sym_indices: Vec<u32>) -> GroupSet {
+        sym_indices.sort_unstable();
+        sym_indices.dedup();
+        GroupSet { library, sym_indices }
+    }
+
+    /// Returns the symbols that are in either set.
+    ///
+    /// Panics if the sets belong to different libraries.
+    pub fn union(&self, other: &GroupSet) -> GroupSet {
+        self.check_library(other);
+        let mut sym_indices = [self.sym_indices.as_slice(), other.sym_indices.as_slice()].concat();
+        sym_indices.sort_unstable();
+        sym_indices.dedup();
+        self.with_indices(sym_indices)
+    }
+
+    /// Returns the symbols that are in both sets.
+    ///
+    /// Panics if the sets belong to different libraries.
+    pub fn intersection(&self, other: &GroupSet) -> GroupSet {
+        self.check_library(other);
+        self.with_indices(self.sym_indices.iter().copied().filter(|sym_index| other.contains(*sym_index)).collect())
+    }
+
+    /// Returns the symbols that are in this set, but not in `other`.
+    ///
+    /// Panics if the sets belong to different libraries.
+    pub fn difference(&self, other: &GroupSet) -> GroupSet {
+        self.check_library(other);
+        self.with_indices(self.sym_indices.iter().copied().filter(|sym_index| !other.contains(*sym_index)).collect())
+    }
+
+    /// Returns true if the set contains the symbol with the specified index.
+    pub fn contains(&self, sym_index: u32) -> bool {
+        self.sym_indices.binary_search(&sym_index).is_ok()
+    }
+
+    /// Returns the number of symbols in the set.
+    pub fn len(&self) -> usize {
+        self.sym_indices.len()
+    }
+
+    /// Returns true if the set contains no symbols.
+    pub fn is_empty(&self) -> bool {
+        self.sym_indices.is_empty()
+    }
+
+    /// Returns the indices of the symbols in the set, in ascending order.
+    pub fn sym_indices(&self) -> &[u32] {
+        &self.sym_indices
+    }
+
+    /// Iterates over the names of the symbols in the set, ordered by index.
+    ///
+    /// The names are empty if the library was generated with hashed or obfuscated names.
+    pub fn symbol_names(&self) -> impl Iterator<Item = &'static CStr> + '_ {
+        let names = self.library.symbol_names();
+        self.sym_indices.iter().map(move |sym_index| names[*sym_index as usize])
+    }
+
+    /// Resolves all symbols of the set, failing if any of them is missing.
+    ///
+    /// Like [`Group::resolve`], this returns a token, for the lifetime of which the symbols are considered resolved
+    /// in [checked mode](index.html#checked-mode).
+    pub fn resolve(&self) -> Result<GroupSetResolved<'_>, Error> {
+        for sym_index in &self.sym_indices {
+            self.library.resolve_symbol(*sym_index)?;
+        }
+        self.library.assert_resolved(&self.sym_indices);
+        Ok(GroupSetResolved(self))
+    }
+
+    /// Returns true if all symbols of the set can be resolved.
+    pub fn is_available(&self) -> bool {
+        self.resolve().is_ok()
+    }
+
+    pub(crate) fn library(&self) -> &'static Library {
+        self.library
+    }
+
+    fn with_indices(&self, sym_indices: Vec<u32>) -> GroupSet {
+        GroupSet {
+            library: self.library,
+            sym_indices,
+        }
+    }
+
+    fn check_library(&self, other: &GroupSet) {
+        assert!(
+            ptr::eq(self.library, other.library),
+            "Symbol sets of {} and {} can't be combined",
+            self.library.name,
+            other.library.name
+        );
+    }
+}
+
+impl From<&Group> for GroupSet {
+    fn from(group: &Group) -> GroupSet {
+        GroupSet::of(group)
+    }
+}
+
+impl PartialEq for GroupSet {
+    fn eq(&self, other: &GroupSet) -> bool {
+        ptr::eq(self.library, other.library) && self.sym_indices == other.sym_indices
+    }
+}
+
+impl Eq for GroupSet {}
+
+impl fmt::Debug for GroupSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GroupSet")
+            .field("library", &self.library.name)
+            .field("sym_indices", &self.sym_indices)
+            .finish()
+    }
+}
+
+/// Represents resolved state of a [`GroupSet`]. See [`GroupSet::resolve`].
+pub struct GroupSetResolved<'a>(&'a GroupSet);
+
+impl<'a> GroupSetResolved<'a> {
+    /// Returns the set this token belongs to.
+    pub fn set(&self) -> &'a GroupSet {
+        self.0
+    }
+}
+
+impl<'a> Drop for GroupSetResolved<'a> {
+    fn drop(&mut self) {
+        self.0.library.deassert_resolved(&self.0.sym_indices);
+    }
+}
@@ -0,0 +1,126 @@
+//! Multiple instances of a library.
+
+use crate::{loading, Address, DylibHandle, Error, ErrorCode, Library};
+use std::ffi::CStr;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::{MutexGuard, PoisonError};
+
+/// An instance of a library with its own snapshot of the symbol table.
+///
+/// Client code switches the stubs to an instance via [`LibraryInstance::with`], which affects all threads, so the
+/// instances are not isolated from one another.
+pub struct LibraryInstance<'a> {
+    library: &'a Library,
+    handle: DylibHandle,
+    table: Box<[Address]>,
+}
+
+impl Library {
+    /// Loads an additional instance of the library from the specified path.
+    ///
+    /// Note that most platforms return the already loaded instance when the same file is loaded again,
+    /// so distinct instances must be loaded from distinct files.
+    pub fn load_instance(&self, path: &Path) -> Result<LibraryInstance<'_>, Error> {
+        let handle = loading::load_library(path)?;
+        Ok(self.instance(handle))
+    }
+
+    /// Creates an instance from a library handle, resolving all symbols present in it.
+    ///
+    /// Symbols that could not be resolved are left null in the instance's symbol table.
+    pub fn instance(&self, handle: DylibHandle) -> LibraryInstance<'_> {
+        let table = self.symbol_names().iter().enumerate().map(|(sym_index, name)| {
+            match self.find_symbol_in(sym_index as u32, handle) {
+                Ok(address) => self.filter_address(name, address),
+                Err(_) => 0,
+            }
+        });
+        LibraryInstance {
+            library: self,
+            handle,
+            table: table.collect(),
+        }
+    }
+}
+
+impl<'a> LibraryInstance<'a> {
+    /// Returns the library this is an instance of.
+    pub fn library(&self) -> &'a Library {
+        self.library
+    }
+
+    /// Returns the handle of this instance.
+    pub fn handle(&self) -> DylibHandle {
+        self.handle
+    }
+
+    /// Returns the address of the specified symbol in this instance, if it has been resolved.
+    pub fn address(&self, name: &CStr) -> Option<Address> {
+        let sym_index = self.library.index_of(name.to_str().ok()?)?;
+        match self.table[sym_index as usize] {
+            0 => None,
+            address => Some(address),
+        }
+    }
+
+    /// Calls `f` with the stubs dispatching to this instance.
+    ///
+    /// The library's symbol table is replaced by this instance's table for the duration of the call, and restored
+    /// afterwards.  Since the symbol table is process-wide, this is not a way to isolate instances used concurrently:
+    /// stub calls made by other threads in the meantime are dispatched to this instance as well.  Calls to `with` on
+    /// instances of the same library are serialized, and must not be nested.
+    ///
+    /// Fails with [`ErrorCode::Unsupported`] if the library has patched or natively weak-linked groups, whose calls
+    /// bypass the symbol table, or in [checked mode](index.html#checked-mode), which resets and restores entries of
+    /// the symbol table as groups are de-asserted, and fails if the symbol table has been sealed.
+    pub fn with<R>(&self, f: impl FnOnce() -> R) -> Result<R, Error> {
+        let lock = self.library.instance_lock.lock().unwrap_or_else(PoisonError::into_inner);
+        self.check_switchable()?;
+        let saved = self.swap_table(&self.table);
+        let _restore = RestoreTable {
+            instance: self,
+            saved,
+            _lock: lock,
+        };
+        Ok(f())
+    }
+
+    // Checks that all stub calls of the library go through its symbol table, and that the table may be written.
+    fn check_switchable(&self) -> Result<(), Error> {
+        let library = self.library;
+        let reason = if cfg!(feature = "checked") {
+            "the symbol table is managed by checked mode"
+        } else if library.patch_sites.iter().any(|stub| *stub != 0) {
+            "the library has patched stubs"
+        } else if !library.native_symbols.is_empty() {
+            "the library has natively weak-linked symbols"
+        } else {
+            return library.check_unsealed();
+        };
+        Err(ErrorCode::Unsupported.error(format!("{}: can't switch instances: {reason}", library.name)))
+    }
+
+    // Write `table` into the library's symbol table, returning the previous contents.
+    fn swap_table(&self, table: &[Address]) -> Box<[Address]> {
+        let mut previous = Vec::with_capacity(table.len());
+        for (sym_index, address) in table.iter().enumerate() {
+            let entry = self.library.symbol_table_entry(sym_index as u32);
+            previous.push(entry.swap(*address, Ordering::AcqRel));
+        }
+        previous.into_boxed_slice()
+    }
+}
+
+// Restores the symbol table when leaving `LibraryInstance::with`, including by unwinding.
+struct RestoreTable<'a, 'b> {
+    instance: &'b LibraryInstance<'a>,
+    saved: Box<[Address]>,
+    _lock: MutexGuard<'b, ()>,
+}
+
+impl<'a, 'b> Drop for RestoreTable<'a, 'b> {
+    fn drop(&mut self) {
+        self.instance.swap_table(&self.saved);
+    }
+}
@@ -0,0 +1,43 @@
+//! Loading of the library in a link-map namespace of its own, so that its symbols don't collide with those of other
+//! copies of it.
+
+use crate::loading::{self, LibcFlavor, NamespaceId};
+use crate::{DylibHandle, Error, ErrorCode, Library};
+use std::path::Path;
+
+impl Library {
+    /// Loads the library with the default names, as [`Library::load`] does, but in a new link-map namespace, via
+    /// [`loading::load_library_in_namespace`].
+    ///
+    /// This lets the process use another version of the plugin at the same time, e.g. one loaded by other code, or
+    /// by another library, without either binding to the symbols of the other.  Its namespace is returned by
+    /// [`Library::namespace`], in which its dependencies may be pre-loaded.  Additional copies of the plugin may be
+    /// loaded the same way, and used via [`Library::instance`].
+    ///
+    /// Fails with [`ErrorCode::Unsupported`] unless the process runs on Linux with glibc, which provides `dlmopen`.
+    ///
+    /// ```ignore
+    /// stub::library.load_isolated()?;
+    /// let (handle, _) = loading::load_library_in_namespace(Path::new("v2/libplugin.so"), loading::LM_ID_NEWLM)?;
+    /// let v2 = stub::library.instance(handle);
+    /// ```
+    pub fn load_isolated(&self) -> Result<DylibHandle, Error> {
+        // Fail early, rather than report each default name as missing.
+        if loading::libc_flavor() != LibcFlavor::Glibc {
+            return Err(ErrorCode::Unsupported.error("Namespaces are only supported with glibc"));
+        }
+        let handle = self.load_default_with(load_in_new_namespace)?;
+        self.bind_eagerly();
+        Ok(handle)
+    }
+
+    /// Returns the link-map namespace that the library has been loaded in, or `None` if it isn't loaded, or
+    /// namespaces are not supported.  See [`Library::load_isolated`].
+    pub fn namespace(&self) -> Option<NamespaceId> {
+        loading::namespace_of(self.handle()?).ok()
+    }
+}
+
+fn load_in_new_namespace(path: &Path) -> Result<DylibHandle, Error> {
+    loading::load_library_in_namespace(path, loading::LM_ID_NEWLM).map(|(handle, _)| handle)
+}
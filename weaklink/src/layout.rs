@@ -0,0 +1,123 @@
+//! Uses of the manifest generated at build time: checks of the symbol table layout, and the names of libraries built
+//! with hashed names.
+
+use crate::{name_index, Error, ErrorCode, Library};
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::path::Path;
+
+impl Library {
+    /// Verifies that the symbol table matches a manifest generated by `weaklink_build::Config::generate_manifest`.
+    ///
+    /// This detects index drift, e.g. when the stubs were regenerated from a different configuration than the code
+    /// that relies on the manifest, or a stale manifest is deployed alongside them.  Intended as a debug check:
+    ///
+    /// ```ignore
+    /// debug_assert!(stub::library.verify_layout(include_str!(concat!(env!("OUT_DIR"), "/stubs.manifest"))).is_ok());
+    /// ```
+    pub fn verify_layout(&self, manifest: &str) -> Result<(), Error> {
+        let imp_names = self.manifest_names(manifest)?;
+        for (index, imp_name) in imp_names.iter().enumerate() {
+            match self.symbol_names().get(index) {
+                // Symbols of groups that are compiled out have no name, if names are emitted per group.
+                Some(sym_name) if sym_name.to_bytes().is_empty() => {}
+                Some(sym_name) if sym_name.to_bytes() == imp_name.as_bytes() => {}
+                Some(sym_name) => {
+                    return Err(ErrorCode::InvalidManifest.error(format!(
+                        "{}: entry {index} is {sym_name:?}, but the manifest expects {imp_name:?}",
+                        self.name
+                    )))
+                }
+                None => {
+                    return Err(ErrorCode::InvalidManifest.error(format!(
+                        "{}: the manifest has entry {index} beyond the symbol table",
+                        self.name
+                    )))
+                }
+            }
+        }
+        if imp_names.len() != self.symbol_names().len() {
+            return Err(ErrorCode::InvalidManifest.error(format!(
+                "{}: the symbol table has {} entries, but the manifest has {}",
+                self.name,
+                self.symbol_names().len(),
+                imp_names.len()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Sets the symbol names of a library built with hashed names (see `Config::hash_names` in `weaklink_build`),
+    /// from the manifest generated along with the stubs by `Config::generate_manifest`.
+    ///
+    /// The names are needed to resolve symbols, so this must be called before the library is used, e.g. from its
+    /// configuration hook (see [`Library::configure_once`]).  Fails if a name doesn't match its hash, e.g. if the
+    /// manifest is of a different build of the stubs, or if the names have already been set or used.
+    pub fn set_symbol_names(&self, manifest: &str) -> Result<(), Error> {
+        if self.name_hashes.is_empty() {
+            return Err(ErrorCode::InvalidArgument.error(format!("{} is not built with hashed names", self.name)));
+        }
+        let imp_names = self.manifest_names(manifest)?;
+        if imp_names.len() != self.name_hashes.len() {
+            return Err(ErrorCode::InvalidManifest.error(format!(
+                "{}: the symbol table has {} entries, but the manifest has {}",
+                self.name,
+                self.name_hashes.len(),
+                imp_names.len()
+            )));
+        }
+        for (index, (imp_name, hash)) in imp_names.iter().zip(self.name_hashes).enumerate() {
+            if name_index::hash(imp_name.as_bytes(), 0) != *hash {
+                return Err(ErrorCode::InvalidManifest.error(format!(
+                    "{}: entry {index} doesn't match the manifest's {imp_name:?}",
+                    self.name
+                )));
+            }
+        }
+        let mut names = Vec::with_capacity(imp_names.len());
+        for imp_name in imp_names {
+            // Names live for the rest of the process, like the library.
+            names.push(&*Box::leak(CString::new(imp_name)?.into_boxed_c_str()));
+        }
+        let names: &'static [&'static CStr] = Box::leak(names.into_boxed_slice());
+        match self.decoded_names.set(names) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(ErrorCode::AlreadyConfigured
+                .error(format!("{}: symbol names have already been set or used", self.name))),
+        }
+    }
+
+    /// Like [`Library::set_symbol_names`], reading the manifest from a file shipped along with the host.
+    pub fn load_symbol_names(&self, path: &Path) -> Result<(), Error> {
+        self.set_symbol_names(&fs::read_to_string(path)?)
+    }
+
+    // Returns the import names listed by a manifest, in table order.
+    fn manifest_names<'a>(&self, manifest: &'a str) -> Result<Vec<&'a str>, Error> {
+        let mut imp_names = Vec::new();
+        for line in manifest.lines() {
+            if let Some(header) = line.strip_prefix('#') {
+                match header.trim().strip_prefix("weaklink symbol table of ") {
+                    Some(name) if name != self.name => {
+                        return Err(
+                            ErrorCode::InvalidManifest.error(format!("Manifest is for {name}, not {}", self.name))
+                        );
+                    }
+                    _ => continue,
+                }
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.split(' ');
+            let (Some(index), Some(_), Some(imp_name)) = (fields.next(), fields.next(), fields.next()) else {
+                return Err(ErrorCode::InvalidManifest.error(format!("Malformed manifest line: {line}")));
+            };
+            if index.parse::<usize>().ok() != Some(imp_names.len()) {
+                return Err(ErrorCode::InvalidManifest.error(format!("Manifest index {index} is out of sequence")));
+            }
+            imp_names.push(imp_name);
+        }
+        Ok(imp_names)
+    }
+}
@@ -3,14 +3,29 @@
 //! Access to the API is through static variables exposed in the generated stubs crate:
 //! - A [`Library`] object, named according to the configuration in `weaklink_build::Config`.
 //! - A [`Group`] object for each symbol group defined via `weaklink_build::Config::add_symbol_group()`.
+//!   Symbols added without a group via `weaklink_build::Config::add_symbols()` form the group `all`.
 //!
 //! # Checked Mode
 //! Checked mode is a debugging tool that helps identify code paths using optional API groups
 //! without first verifying their successful resolution.
 //!
 //! When the stub crate is compiled with the `checked` feature enabled, the API stubs verify that at least one of
-//! the groups they belong to has been asserted as resolved by the current thread. If this condition is not met,
-//! the stub pointer reverts to null, causing a process abort if the stub is called during that time.
+//! the groups they belong to has been asserted as resolved. If this condition is not met, the stub pointer reverts
+//! to null, causing a process abort if the stub is called during that time. Data accessors are checked likewise,
+//! and panic if called while unasserted.
+//!
+//! Assertions are process-wide: a [`GroupResolved`] token is `Send` and `Sync`, and authorizes calls on any
+//! thread for as long as it is alive. When handing work to a thread pool, either move (or clone) a token into
+//! each job, or use [`Group::resolve_global()`] for groups that must stay available for the rest of the process
+//! lifetime.
+//!
+//! Bookkeeping of assertions is lock-free, and only relies on atomics, so checked mode is available on any target
+//! supported by the crate.
+//!
+//! The bookkeeping state is allocated on first use.  Hosts that must not allocate while resolving may allocate it at
+//! startup instead, via [`Library::init_checked()`].  Hosts using the library from threads that can't unwind, e.g.
+//! ones started by C code, should call [`Library::init_panic_free()`] instead, which also reports unasserted access
+//! to data to a callback, rather than panicking.
 //!
 //! See also:  [`Group::resolve()`]
 //!
@@ -50,85 +65,548 @@
 //! }
 //! ```
 
+#[doc(hidden)]
+pub mod c_api;
+mod broker;
+mod capabilities;
+mod closing;
+#[doc(hidden)]
+pub mod compressed_names;
+mod diagnostics;
+mod discovery;
+mod eager;
+#[cfg(feature = "dlopen-shim")]
+pub mod dlopen_shim;
+mod error_code;
+mod events;
 mod group;
+mod group_set;
+mod instance;
+mod isolation;
+mod layout;
 pub mod loading;
+mod messages;
+#[doc(hidden)]
+pub mod name_index;
+#[doc(hidden)]
+pub mod obfuscated_names;
+mod options;
+mod panic_free;
+mod patching;
+mod plugin_manifest;
+mod post_load;
+mod probing;
+mod recording;
+mod runtime_group;
+mod signatures;
+mod snapshot;
+mod unloading;
+mod variants;
+#[doc(hidden)]
+pub mod version;
+mod vtables;
 
 use std::{
-    cell::UnsafeCell,
-    ffi::CStr,
+    collections::VecDeque,
+    ffi::{CStr, CString},
+    fs::File,
     mem,
-    path::Path,
-    sync::atomic::{AtomicUsize, Ordering},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    sync::{Condvar, Mutex, Once, OnceLock, PoisonError},
 };
 
-pub use group::{Group, GroupResolved};
+pub use broker::Broker;
+pub use capabilities::CapabilitySet;
+#[doc(hidden)]
+pub use compressed_names::CompressedNames;
+pub use diagnostics::ResolutionFailure;
+pub use discovery::{discover, Exports};
+pub use eager::ResolutionReport;
+pub use error_code::{CodedError, ErrorCode, ErrorExt};
+pub use events::{HookEvent, ResolutionHook};
+pub use group::{BoundGroup, Group, GroupCallError, GroupResolved, GroupResolvedWeak};
+pub use group_set::{GroupSet, GroupSetResolved};
+pub use instance::LibraryInstance;
 pub use loading::{Address, DylibHandle};
+pub use messages::{set_message_formatter, MessageFormatter};
+#[doc(hidden)]
+pub use name_index::NameIndex;
+#[doc(hidden)]
+pub use obfuscated_names::ObfuscatedNames;
+pub use obfuscated_names::KeyProvider;
+pub use options::{ConfigureHook, Options};
+pub use panic_free::FailureCallback;
+pub use plugin_manifest::{
+    ManifestCheck, PluginManifest, ELF_MANIFEST_SECTION, MACHO_MANIFEST_SECTION, MANIFEST_EXPORT, PE_MANIFEST_SECTION,
+};
+pub use post_load::PostLoadCheck;
+pub use signatures::{
+    group_signature_hash, signature_hash, GroupSignature, SignatureMismatchHandler, SignatureQuery, SignatureTableFn,
+    SIGNATURE_TABLE_EXPORT,
+};
+pub use runtime_group::RuntimeGroup;
+pub use snapshot::{EntryChange, TableSnapshot};
+#[doc(hidden)]
+pub use variants::NameVariant;
+pub use version::VersionTooOld;
+pub use vtables::{FactoryCallback, VtableProvider};
 
 pub type Error = Box<dyn std::error::Error>;
 
+/// Version of the interface between this crate and the code generated by `weaklink_build`.
+///
+/// Must be incremented whenever the generated code would otherwise silently misbehave with a different runtime,
+/// e.g. when the fields of [`LibraryDescriptor`] or the stub calling convention change.
+#[doc(hidden)]
+pub const ABI_VERSION: u32 = 2;
+
+/// Called with the library name, the symbol name, and the version since which the symbol is deprecated.
+/// See [`Library::set_deprecation_handler`].
+pub type DeprecationHandler = fn(&str, &CStr, &str);
+
+/// Called with the symbol name and the address looked up in the dynamic library, and returns the address to store
+/// in the symbol table.  See [`Library::set_address_filter`].
+pub type AddressFilter = fn(&CStr, Address) -> Address;
+
 #[cfg(feature = "checked")]
-use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::sync::atomic::AtomicU32;
 
 /// Represents a weakly linked dynamic library.
 #[repr(C)]
 pub struct Library {
+    name: &'static str,
     handle: AtomicUsize,
     dylib_names: &'static [&'static str],
+    dependencies: &'static [&'static str],
+    // Empty if the names are emitted per group, compressed or hashed.  See `symbol_names()`.
     symbol_names: &'static [&'static CStr],
-    symbol_table: &'static [Address],
+    compressed_names: CompressedNames,
+    // Hashes of the names, if the names are supplied at runtime.  See `Library::set_symbol_names`.
+    name_hashes: &'static [u64],
+    // Empty unless the names are obfuscated.  See `Library::with_symbol_name`.
+    obfuscated_names: ObfuscatedNames,
+    // Entries are read by the stubs with plain pointer-sized loads, which are atomic on all supported architectures,
+    // and pair with the release stores below.
+    symbol_table: &'static [AtomicUsize],
+    groups: &'static [&'static Group],
+    name_index: NameIndex,
+    // Addresses of stubs that may be patched, or 0, per symbol.  Empty if there are none.
+    patch_sites: &'static [Address],
+    // Symbol index and address, or 0 if absent, of natively weak-linked symbols, sorted by index.
+    native_symbols: &'static [[Address; 2]],
+    // Symbol index => version since which the symbol is deprecated, sorted by index.
+    deprecations: &'static [(u32, &'static str)],
+    variants: &'static [NameVariant],
+    // Export through which symbols are looked up, instead of the dynamic symbol table, if any.
+    bootstrap_export: Option<&'static CStr>,
+    // Symbol index, provider export and index in its struct of symbols obtained from structs, sorted by symbol index.
+    vtable_slots: &'static [(u32, &'static CStr, u32)],
+    // Symbol index, factory and method index of interface methods, sorted by symbol index.
+    interface_slots: &'static [(u32, &'static str, u32)],
+    // Symbol index => hash of the expected signature, sorted by index.
+    signatures: &'static [(u32, u64)],
+    // Export that reports the signatures of the dylib's symbols, if any.
+    signature_query: Option<&'static CStr>,
+    // Group name => hash of the signatures of its symbols, sorted by name.
+    group_signatures: &'static [(&'static str, u64)],
+    // Symbol index => version of the symbol to bind to, sorted by index.
+    symbol_versions: &'static [(u32, &'static CStr)],
+    factories: Mutex<Vec<vtables::Factory>>,
+    // Raw handle of the dylib that a variant was last selected for, and the variant, if any.
+    selected_variant: Mutex<Option<(usize, Option<&'static NameVariant>)>>,
+    deprecation_handler: Mutex<Option<DeprecationHandler>>,
+    signature_mismatch_handler: Mutex<Option<SignatureMismatchHandler>>,
+    manifest_check: Mutex<Option<ManifestCheck>>,
+    post_load_check: Mutex<Option<PostLoadCheck>>,
+    address_filter: Mutex<Option<AddressFilter>>,
+    resolution_hook: Mutex<Option<ResolutionHook>>,
+    // Callback reporting failures instead of panicking, and the NUL-terminated library name passed to it.
+    failure_callback: Mutex<Option<(FailureCallback, CString)>>,
+    broker: Mutex<Option<&'static dyn Broker>>,
+    key_provider: Mutex<Option<KeyProvider>>,
+    plugin_version: Mutex<Option<String>>,
+    configure_hook: Mutex<Option<ConfigureHook>>,
+    // Completed once the configuration hook, if any, has been called.
+    configured: Once,
+    // Paths tried before `dylib_names`, as set by the configuration hook.
+    dylib_paths: Mutex<Vec<PathBuf>>,
+    // Values of the placeholders in `dylib_names`.
+    vars: Mutex<Vec<(String, String)>>,
+    auto_load: AtomicBool,
+    validity_checks: AtomicBool,
+    // Number of times the dylib has been unloaded via `unload`, which makes tokens obtained before stale.
+    generation: AtomicUsize,
+    eager_binding: AtomicBool,
+    sealed: AtomicBool,
+    // Destination of the resolution sequence recording, if enabled.
+    recorder: Mutex<Option<File>>,
+    // Most recent resolution failures, oldest first.
+    recent_errors: Mutex<VecDeque<ResolutionFailure>>,
+    // Serializes switching of the symbol table between library instances.
+    instance_lock: Mutex<()>,
+    // Resolutions and loads in progress, and whether the library has been closed.  See `Library::close`.
+    activity: Mutex<closing::Activity>,
+    // Signaled once no more resolutions are in progress.
+    activity_done: Condvar,
+    // Names of all symbols, decoded or assembled from the groups' name tables.
+    decoded_names: OnceLock<&'static [&'static CStr]>,
 
     // Must initialize this stuff lazily, so we can have a const constructor.
     #[cfg(feature = "checked")]
-    checked_state: OnceLock<Mutex<CheckedState>>,
+    checked_state: OnceLock<CheckedState>,
 }
 
+// Lock-free, so that it only requires atomics and one-time initialization from the platform.
 #[cfg(feature = "checked")]
 struct CheckedState {
-    asserted: Box<[u32]>,
+    asserted: Box<[AtomicU32]>,
+    // Resolved addresses, which survive reset of the symbol table entries upon de-assertion.
+    cached: Box<[AtomicUsize]>,
+}
+
+/// Build-time description of a library, which the code generated by `weaklink_build` constructs in const context, and
+/// passes to [`Library::new`].  Fields are added as the generated code needs them, along with a bump of
+/// [`ABI_VERSION`].
+#[doc(hidden)]
+pub struct LibraryDescriptor {
+    /// [`ABI_VERSION`] of the `weaklink_build` that generated the descriptor.
+    pub abi_version: u32,
+    pub name: &'static str,
+    pub dylib_names: &'static [&'static str],
+    pub dependencies: &'static [&'static str],
+    /// Empty if the names are emitted per group, compressed, hashed or obfuscated.
+    pub symbol_names: &'static [&'static CStr],
+    pub compressed_names: CompressedNames,
+    /// Hashes of the names, if the names are supplied at runtime.
+    pub name_hashes: &'static [u64],
+    pub obfuscated_names: ObfuscatedNames,
+    pub symbol_table: &'static [AtomicUsize],
+    pub groups: &'static [&'static Group],
+    pub name_index: NameIndex,
+    /// Addresses of stubs that may be patched, or 0, per symbol.  Empty if there are none.
+    pub patch_sites: &'static [Address],
+    /// Symbol index and address, or 0 if absent, of natively weak-linked symbols, sorted by index.
+    pub native_symbols: &'static [[Address; 2]],
+    /// Symbol index => version since which the symbol is deprecated, sorted by index.
+    pub deprecations: &'static [(u32, &'static str)],
+    pub variants: &'static [NameVariant],
+    pub configure_hook: Option<ConfigureHook>,
+    pub bootstrap_export: Option<&'static CStr>,
+    /// Symbol index, provider export and index in its struct, sorted by symbol index.
+    pub vtable_slots: &'static [(u32, &'static CStr, u32)],
+    /// Symbol index, factory and method index, sorted by symbol index.
+    pub interface_slots: &'static [(u32, &'static str, u32)],
+    /// Symbol index => hash of the expected signature, sorted by index.
+    pub signatures: &'static [(u32, u64)],
+    pub signature_query: Option<&'static CStr>,
+    /// Group name => hash of the signatures of its symbols, sorted by name.
+    pub group_signatures: &'static [(&'static str, u64)],
+    pub eager_binding: bool,
+    /// Symbol index => version of the symbol to bind to, sorted by index.
+    pub symbol_versions: &'static [(u32, &'static CStr)],
 }
 
 impl Library {
     #[doc(hidden)]
-    pub const fn new(
-        dylib_names: &'static [&'static str],
-        symbol_names: &'static [&'static CStr],
-        symbol_table: &'static [Address],
-    ) -> Library {
+    pub const fn new(descriptor: LibraryDescriptor) -> Library {
+        let LibraryDescriptor {
+            abi_version,
+            name,
+            dylib_names,
+            dependencies,
+            symbol_names,
+            compressed_names,
+            name_hashes,
+            obfuscated_names,
+            symbol_table,
+            groups,
+            name_index,
+            patch_sites,
+            native_symbols,
+            deprecations,
+            variants,
+            configure_hook,
+            bootstrap_export,
+            vtable_slots,
+            interface_slots,
+            signatures,
+            signature_query,
+            group_signatures,
+            eager_binding,
+            symbol_versions,
+        } = descriptor;
+        // Evaluated at compile time, since the generated library is a static.
+        assert!(
+            abi_version == ABI_VERSION,
+            "The stubs were generated by a version of weaklink_build that doesn't match the weaklink runtime"
+        );
         Library {
+            name,
             handle: AtomicUsize::new(0),
             dylib_names,
+            dependencies,
             symbol_names,
+            compressed_names,
+            name_hashes,
+            obfuscated_names,
             symbol_table,
+            groups,
+            name_index,
+            patch_sites,
+            native_symbols,
+            deprecations,
+            variants,
+            bootstrap_export,
+            vtable_slots,
+            interface_slots,
+            signatures,
+            signature_query,
+            group_signatures,
+            symbol_versions,
+            factories: Mutex::new(Vec::new()),
+            selected_variant: Mutex::new(None),
+            deprecation_handler: Mutex::new(None),
+            signature_mismatch_handler: Mutex::new(None),
+            manifest_check: Mutex::new(None),
+            post_load_check: Mutex::new(None),
+            address_filter: Mutex::new(None),
+            resolution_hook: Mutex::new(None),
+            failure_callback: Mutex::new(None),
+            broker: Mutex::new(None),
+            key_provider: Mutex::new(None),
+            plugin_version: Mutex::new(None),
+            configure_hook: Mutex::new(configure_hook),
+            configured: Once::new(),
+            dylib_paths: Mutex::new(Vec::new()),
+            vars: Mutex::new(Vec::new()),
+            auto_load: AtomicBool::new(true),
+            validity_checks: AtomicBool::new(false),
+            generation: AtomicUsize::new(0),
+            eager_binding: AtomicBool::new(eager_binding),
+            sealed: AtomicBool::new(false),
+            recorder: Mutex::new(None),
+            recent_errors: Mutex::new(VecDeque::new()),
+            instance_lock: Mutex::new(()),
+            activity: Mutex::new(closing::Activity::new()),
+            activity_done: Condvar::new(),
+            decoded_names: OnceLock::new(),
             #[cfg(feature = "checked")]
             checked_state: OnceLock::new(),
         }
     }
 
     /// Load library with default name (configured at build time).
+    ///
+    /// Paths set via [`Options::dylib_paths`] are tried first.  Placeholders in the default names are expanded from
+    /// the variables set via [`Library::set_vars`].  Names tagged for a C library other than that of the process
+    /// (see [`loading::libc_flavor`]) are skipped.  With [eager binding](Library::set_eager_binding), all symbols
+    /// are then resolved.
     pub fn load(&self) -> Result<DylibHandle, Error> {
+        let handle = self.load_default()?;
+        self.bind_eagerly();
+        Ok(handle)
+    }
+
+    // Loads the library with the default names, without binding eagerly.
+    fn load_default(&self) -> Result<DylibHandle, Error> {
+        self.load_default_with(loading::load_library)
+    }
+
+    // Like `load_default`, but loads dylibs via the specified function.
+    fn load_default_with(&self, load: fn(&Path) -> Result<DylibHandle, Error>) -> Result<DylibHandle, Error> {
+        self.configure();
+        let _in_flight = self.begin_resolution()?;
         let raw_handle = self.handle.load(Ordering::Acquire);
         if raw_handle != 0 {
-            return Err("Already loaded.".into());
+            return Err(ErrorCode::AlreadyLoaded.error("Already loaded."));
         } else {
-            for name in self.dylib_names {
-                if let Ok(handle) = loading::load_library(Path::new(name)) {
+            let dylib_paths = self.dylib_paths.lock().unwrap_or_else(PoisonError::into_inner).clone();
+            for path in dylib_paths {
+                if let Ok(handle) = self.load_checked(&path, load) {
                     self.handle.store(handle.0, Ordering::Release);
+                    self.record_load(&path);
                     return Ok(handle);
                 }
             }
+            for name in self.dylib_names {
+                // Names tagged for another C library are meant for other systems.
+                let Some(name) = loading::untag_dylib_name(name) else {
+                    continue;
+                };
+                // Names with placeholders that have no value can't be loaded.
+                let Some(name) = self.expand_vars(name) else {
+                    continue;
+                };
+                for path in Self::dylib_candidates(&name) {
+                    if let Ok(handle) = self.load_checked(&path, load) {
+                        self.handle.store(handle.0, Ordering::Release);
+                        self.record_load(&path);
+                        return Ok(handle);
+                    }
+                }
+            }
         }
-        Err("Library not found.".into())
+        Err(ErrorCode::LibraryNotFound.error("Library not found."))
+    }
+
+    // Returns the names of all symbols.  Compressed names (see `Config::compress_names` in `weaklink_build`) are
+    // decoded on first use.  Likewise, if the names are emitted per group (see `Config::group_name_tables`), they are
+    // assembled from the groups, and names of symbols whose groups are all compiled out are empty.  Hashed names are
+    // empty, unless set before first use, and obfuscated names are always empty.
+    pub(crate) fn symbol_names(&self) -> &'static [&'static CStr] {
+        if self.symbol_names.len() == self.symbol_table.len() {
+            return self.symbol_names;
+        }
+        self.decoded_names.get_or_init(|| {
+            if !self.compressed_names.is_empty() {
+                return self.compressed_names.decode();
+            }
+            let mut names = vec![<&CStr>::default(); self.symbol_table.len()];
+            for group in self.groups {
+                for (sym_index, name) in group.symbol_names() {
+                    names[sym_index as usize] = name;
+                }
+            }
+            Box::leak(names.into_boxed_slice())
+        })
+    }
+
+    /// Returns the name of the symbol at the specified index.
+    ///
+    /// Symbol indices are exposed as constants in the `sym_index` module of the generated stubs crate.  The name is
+    /// empty if the symbol's groups are all compiled out, and names are emitted per group (see
+    /// `Config::group_name_tables` in `weaklink_build`).
+    pub fn symbol_name(&self, sym_index: u32) -> Option<&'static CStr> {
+        self.symbol_names().get(sym_index as usize).copied()
+    }
+
+    /// Returns the current symbol table entry of a symbol, without attempting to resolve it.
+    ///
+    /// Returns `None` if the symbol is unresolved, or its index is out of range.  This is safe to call concurrently
+    /// with symbol resolution, e.g. from tests running under thread sanitizer.
+    pub fn symbol_address(&self, sym_index: u32) -> Option<Address> {
+        match self.symbol_table.get(sym_index as usize)?.load(Ordering::Acquire) {
+            0 => None,
+            address => Some(address),
+        }
+    }
+
+    /// Returns the address range occupied by the symbol table.
+    ///
+    /// May be used to lock the table in memory or to prefault it.  See `Config::hot_groups` in `weaklink_build`.
+    pub fn symbol_table_range(&self) -> Range<Address> {
+        let start = self.symbol_table.as_ptr() as Address;
+        start..start + mem::size_of_val(self.symbol_table)
+    }
+
+    /// Returns the index of a symbol, given its name as exported by the dynamic library.
+    ///
+    /// The lookup is a perfect hash generated at build time, so it takes constant time.  If the library is built
+    /// with hashed names, the name is compared by its hash, so this works even if the names haven't been set.
+    pub fn index_of(&self, name: &str) -> Option<u32> {
+        if !self.obfuscated_names.is_empty() {
+            let sym_index = self.name_index.candidate(name.as_bytes())?;
+            let matches = self.with_symbol_name(sym_index, |sym_name| Ok(sym_name.to_bytes() == name.as_bytes()));
+            return matches.unwrap_or(false).then_some(sym_index);
+        }
+        if self.name_hashes.is_empty() {
+            return self.name_index.lookup(self.symbol_names(), name.as_bytes());
+        }
+        let sym_index = self.name_index.candidate(name.as_bytes())?;
+        match self.name_hashes[sym_index as usize] == name_index::hash(name.as_bytes(), 0) {
+            true => Some(sym_index),
+            false => None,
+        }
+    }
+
+    /// Resolves a single symbol by index, regardless of the groups it belongs to.
+    ///
+    /// This does not assert the symbol as resolved in [checked mode](index.html#checked-mode).
+    pub fn resolve_by_index(&self, sym_index: u32) -> Result<Address, Error> {
+        if sym_index as usize >= self.symbol_names().len() {
+            return Err(ErrorCode::InvalidArgument.error(format!("Symbol index {sym_index} is out of range.")));
+        }
+        self.resolve_symbol(sym_index)
+    }
+
+    /// Resolves the symbols added without a group, i.e. via `Config::add_symbols` in `weaklink_build`.
+    ///
+    /// These form the implicit group "all", so this is equivalent to resolving that group.
+    pub fn resolve_ungrouped(&self) -> Result<GroupResolved<'_>, Error> {
+        match self.groups.iter().find(|group| group.name() == "all") {
+            Some(group) => group.resolve(),
+            None => Err(ErrorCode::InvalidArgument.error(format!("Library {} has no ungrouped symbols.", self.name))),
+        }
+    }
+
+    /// Returns the groups of the library, ordered by name, except for those compiled out.
+    pub fn groups(&self) -> &'static [&'static Group] {
+        self.groups
+    }
+
+    /// Sets the values of the placeholders in the default dylib names, replacing those set before.
+    ///
+    /// Names configured via `Config::dylib_names` in `weaklink_build` may contain placeholders, such as
+    /// `libplugin-{version}.so`, so that the deployment configuration can choose among versioned names at runtime:
+    /// ```ignore
+    /// stub::library.set_vars(&[("version", "3")]);
+    /// stub::library.load()?; // Loads libplugin-3.so
+    /// ```
+    /// Names with placeholders that have no value are skipped by [`Library::load`].
+    pub fn set_vars(&self, vars: &[(&str, &str)]) {
+        self.configure();
+        let vars = vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        *self.vars.lock().unwrap_or_else(PoisonError::into_inner) = vars;
+    }
+
+    // Replaces the `{name}` placeholders of a default dylib name with their values, if they all have one.
+    fn expand_vars(&self, name: &str) -> Option<String> {
+        let vars = self.vars.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut expanded = String::new();
+        let mut rest = name;
+        while let Some(start) = rest.find('{') {
+            let end = start + rest[start..].find('}')?;
+            let (_, value) = vars.iter().find(|(var, _)| *var == rest[start + 1..end])?;
+            expanded.push_str(&rest[..start]);
+            expanded.push_str(value);
+            rest = &rest[end + 1..];
+        }
+        expanded.push_str(rest);
+        Some(expanded)
+    }
+
+    // Expands a default dylib name into the list of paths to try loading.
+    fn dylib_candidates(name: &str) -> Vec<PathBuf> {
+        #[cfg(target_os = "macos")]
+        if name.starts_with('@') || name.starts_with(loading::macos::FRAMEWORK_PREFIX) {
+            if let Ok(context) = loading::macos::InstallNameContext::main_executable() {
+                return match name.strip_prefix(loading::macos::FRAMEWORK_PREFIX) {
+                    Some(framework) => loading::macos::framework_candidates(framework, &context),
+                    None => loading::macos::resolve_install_name(name, &context),
+                };
+            }
+        }
+        vec![PathBuf::from(name)]
     }
 
     /// Load library from the specified path.
+    ///
+    /// Paths [`loading::MAIN_PROGRAM`] and [`loading::VDSO`] stand for the main program and the vDSO, respectively.
+    /// With [eager binding](Library::set_eager_binding), all symbols are then resolved.
     pub fn load_from(&self, path: &Path) -> Result<DylibHandle, Error> {
+        self.configure();
+        let _in_flight = self.begin_resolution()?;
         let raw_handle = self.handle.load(Ordering::Acquire);
         if raw_handle != 0 {
-            Err("Already loaded.".into())
+            Err(ErrorCode::AlreadyLoaded.error("Already loaded."))
         } else {
-            match loading::load_library(path) {
+            match self.load_checked(path, loading::load_library) {
                 Ok(handle) => {
                     self.handle.store(handle.0, Ordering::Release);
+                    self.record_load(path);
+                    self.bind_eagerly();
                     Ok(handle)
                 }
                 Err(err) => Err(err),
@@ -136,13 +614,66 @@ impl Library {
         }
     }
 
+    // Loads a dylib via `load`, unless its manifest is rejected.  The main program and the vDSO have no manifest.
+    fn load_checked(&self, path: &Path, load: fn(&Path) -> Result<DylibHandle, Error>) -> Result<DylibHandle, Error> {
+        let result = match path.to_str() {
+            Some(loading::MAIN_PROGRAM) => loading::main_program(),
+            Some(loading::VDSO) => loading::vdso(),
+            _ => self.check_manifest(path).and_then(|()| load(path)),
+        };
+        let result = result.and_then(|handle| self.check_loaded(path, handle));
+        self.notify(&HookEvent::Load { path, error: result.as_ref().err() });
+        result
+    }
+
+    /// Loads the library from the specified path, after pre-loading its dependencies from the same directory.
+    ///
+    /// The dependencies are configured via `Config::dependencies` in `weaklink_build`.  This helps with plugins that
+    /// ship their dependencies next to them, which the platform loader would not otherwise look for there (e.g. on
+    /// Linux, without a suitable `RUNPATH`), or for which it would pick up different copies from the search path.
+    /// Pre-loaded dependencies are never unloaded.
+    pub fn load_with_dependencies(&self, path: &Path) -> Result<DylibHandle, Error> {
+        self.preload_dependencies(path)?;
+        self.load_from(path)
+    }
+
+    // Pre-loads the dependencies of the dylib at `path` from its directory.
+    fn preload_dependencies(&self, path: &Path) -> Result<(), Error> {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for dependency in self.dependencies {
+            if let Err(err) = loading::load_library(&dir.join(dependency)) {
+                return Err(ErrorCode::LoadFailed.error(format!("Could not load dependency {dependency:?}: {err}")));
+            }
+        }
+        Ok(())
+    }
+
+    // Loads the dylib of a group bound via `Group::resolve_loading_from`, after pre-loading the dependencies, and
+    // with the checks of the library's own dylib.  Unlike that, the handle is published by the group.
+    pub(crate) fn load_group_dylib(&self, path: &Path) -> Result<DylibHandle, Error> {
+        self.configure();
+        let _in_flight = self.begin_resolution()?;
+        self.preload_dependencies(path)?;
+        self.load_checked(path, loading::load_library)
+    }
+
     /// Sets the library handle directly.
     ///
     /// The handle may be obtained via [`loading::load_library`] or from platform-specific APIs.
     pub fn set_handle(&self, handle: DylibHandle) {
+        self.configure();
         self.handle.store(handle.0, Ordering::Release);
     }
 
+    /// Prevents the loaded library from ever being unloaded, e.g. by other code calling `dlclose` or `FreeLibrary`
+    /// on it, which would leave the symbol table pointing into unmapped memory.
+    pub fn pin(&self) -> Result<(), Error> {
+        match self.handle() {
+            Some(handle) => loading::pin_library(handle),
+            None => Err(ErrorCode::NotLoaded.error("Library is not loaded.")),
+        }
+    }
+
     /// Returns the library handle if it is loaded, or previously set via `set_handle`.
     pub fn handle(&self) -> Option<DylibHandle> {
         let raw_handle = self.handle.load(Ordering::Acquire);
@@ -153,33 +684,251 @@ impl Library {
         }
     }
 
-    // Make sure the library is loaded, or panic.
-    fn ensure_loaded(&self) -> DylibHandle {
+    /// Enables or disables implicit loading of the library (enabled by default).
+    ///
+    /// When enabled, the library is loaded using default names the first time any of its groups is resolved.
+    /// When disabled, group resolution fails unless the library has been loaded explicitly, or its handle
+    /// has been set via [`Library::set_handle`].
+    pub fn set_auto_load(&self, enabled: bool) {
+        self.configure();
+        self.auto_load.store(enabled, Ordering::Release);
+    }
+
+    /// Enables or disables validation of cached symbol addresses (disabled by default).
+    ///
+    /// When enabled, before reusing a previously resolved address, e.g. when a resolved group is resolved again, the
+    /// runtime verifies that it still belongs to a mapped module.  If the library has been unloaded behind our back,
+    /// the resolution fails with [`ErrorCode::StaleAddress`], rather than letting the caller go on to call the stubs.
+    /// Calls through the stubs themselves are not checked, so a token held while the dylib is unloaded should be
+    /// checked via [`GroupResolved::check`] before its stubs are called again.
+    pub fn set_validity_checks(&self, enabled: bool) {
+        self.configure();
+        self.validity_checks.store(enabled, Ordering::Release);
+    }
+
+    /// Makes the symbol table read-only, so that stubs can't be redirected by overwriting it.
+    ///
+    /// Symbols that haven't been resolved by this point can't be resolved afterwards: their resolution fails with
+    /// an error.  Sealing must not race with symbol resolution on other threads, and can't be undone.
+    ///
+    /// In [checked mode](index.html#checked-mode), the entries of groups are no longer reset once their last token
+    /// is dropped, since the table can't be written anymore.  Calls through stubs without a token then go
+    /// undetected, so sealing should be left to builds without checked mode.
+    pub fn seal(&self) -> Result<(), Error> {
+        let table = self.symbol_table_range();
+        let page_size = loading::page_size();
+        if table.start % page_size != 0 {
+            return Err(ErrorCode::Unsupported.error("Symbol table is not page-aligned."));
+        }
+        let len = (table.len() + page_size - 1) / page_size * page_size;
+        self.sealed.store(true, Ordering::Release);
+        let result = loading::protect_read_only(table.start, len);
+        if result.is_err() {
+            self.sealed.store(false, Ordering::Release);
+        }
+        result
+    }
+
+    /// Returns true if the symbol table has been sealed via [`Library::seal`].
+    pub fn is_sealed(&self) -> bool {
+        self.sealed.load(Ordering::Acquire)
+    }
+
+    // Fails if the symbol table may no longer be written to.
+    fn check_unsealed(&self) -> Result<(), Error> {
+        match self.is_sealed() {
+            true => Err(ErrorCode::Sealed.error("Symbol table is sealed.")),
+            false => Ok(()),
+        }
+    }
+
+    /// Sets the function called when a deprecated symbol is resolved for the first time, e.g. to collect telemetry.
+    ///
+    /// By default, a warning is printed to stderr.  Symbols are marked as deprecated via
+    /// `SymbolStub::deprecated_since` in `weaklink_build`.
+    pub fn set_deprecation_handler(&self, handler: DeprecationHandler) {
+        self.configure();
+        *self.deprecation_handler.lock().unwrap_or_else(PoisonError::into_inner) = Some(handler);
+    }
+
+    // Report resolution of a deprecated symbol.
+    fn check_deprecation(&self, sym_index: u32) {
+        if let Ok(position) = self.deprecations.binary_search_by_key(&sym_index, |(sym_index, _)| *sym_index) {
+            let since = self.deprecations[position].1;
+            let sym_name = self.symbol_names()[sym_index as usize];
+            let handler = *self.deprecation_handler.lock().unwrap_or_else(PoisonError::into_inner);
+            match handler {
+                Some(handler) => handler(self.name, sym_name, since),
+                None => eprintln!(
+                    "weaklink: {}: {sym_name:?} is deprecated since version {since}",
+                    self.name
+                ),
+            }
+        }
+    }
+
+    /// Sets the function that rewrites the addresses of symbols as they are looked up, before they are stored in
+    /// the symbol table.
+    ///
+    /// This lets the host route calls through thunks of its own, e.g. to set up thread-local state, strip pointer
+    /// authentication bits, or add instrumentation.  The filter applies to addresses resolved afterwards, including
+    /// those of [library instances](Library::instance), but not to natively weak-linked symbols, nor to the addresses
+    /// returned by [`BoundGroup`].
+    pub fn set_address_filter(&self, filter: AddressFilter) {
+        self.configure();
+        *self.address_filter.lock().unwrap_or_else(PoisonError::into_inner) = Some(filter);
+    }
+
+    // Apply the address filter, if any, to a looked up address.
+    pub(crate) fn filter_address(&self, sym_name: &CStr, address: Address) -> Address {
+        let filter = *self.address_filter.lock().unwrap_or_else(PoisonError::into_inner);
+        match filter {
+            Some(filter) => filter(sym_name, address),
+            None => address,
+        }
+    }
+
+    // Verify that a cached address is still mapped, if validity checks are enabled.
+    pub(crate) fn check_mapped(&self, sym_index: u32, address: Address) -> Result<Address, Error> {
+        if self.validity_checks.load(Ordering::Acquire) && !loading::is_address_mapped(address) {
+            let sym_name = self.symbol_names()[sym_index as usize];
+            let message = format!("Cached address of {sym_name:?} ({address:#x}) is no longer mapped.");
+            Err(ErrorCode::StaleAddress.error(message))
+        } else {
+            Ok(address)
+        }
+    }
+
+    // Make sure the library is loaded, or panic, unless failures are reported to a callback instead.
+    // Fails without attempting to load, if implicit loading has been disabled, or without panicking, if the library
+    // has been closed.
+    fn ensure_loaded(&self) -> Result<DylibHandle, Error> {
+        self.configure();
         match self.handle() {
-            Some(handle) => handle,
+            Some(handle) => Ok(handle),
+            None if !self.auto_load.load(Ordering::Acquire) => {
+                Err(ErrorCode::NotLoaded.error("Library is not loaded, and implicit loading is disabled."))
+            }
             None => match self.load() {
-                Ok(handle) => handle,
+                Ok(handle) => Ok(handle),
+                // Closing or unloading the library is not a failure to load it.
+                Err(err) if self.is_quiesced() => Err(err),
+                Err(err) if self.report_failure(err.code(), None) => Err(err),
                 Err(err) => panic!("{}", err),
             },
         }
     }
 
     // Resolve symbol address and update its entry in the symbol table.
+    // Entries that have already been resolved (e.g. by replaying a recording) are reused.
     fn resolve_symbol(&self, sym_index: u32) -> Result<Address, Error> {
-        unsafe {
-            let entry = self.symbol_table_entry(sym_index);
-            let handle = self.ensure_loaded();
-            let sym_name = self.symbol_names[sym_index as usize];
-            let address = loading::find_symbol(handle, sym_name)?;
-            entry.write(address);
-            Ok(address)
+        self.resolve_symbol_from(sym_index, None)
+    }
+
+    // Like `resolve_symbol`, but looks up unresolved symbols in the specified dylib, if any.
+    // Failures are kept for `recent_errors`.
+    fn resolve_symbol_from(&self, sym_index: u32, handle: Option<DylibHandle>) -> Result<Address, Error> {
+        let result = self.lookup_symbol(sym_index, handle);
+        if let Err(err) = &result {
+            self.record_failure(sym_index, err);
+            let symbol = self.symbol_names()[sym_index as usize];
+            self.notify(&HookEvent::Failed { sym_index, symbol, error: err });
+        }
+        result
+    }
+
+    fn lookup_symbol(&self, sym_index: u32, handle: Option<DylibHandle>) -> Result<Address, Error> {
+        self.configure();
+        let entry = self.symbol_table_entry(sym_index);
+        let address = entry.load(Ordering::Acquire);
+        if address != 0 {
+            return self.check_mapped(sym_index, address);
         }
+        let _in_flight = self.begin_resolution()?;
+        self.check_unsealed()?;
+        if let Some(address) = self.cached_address(sym_index) {
+            entry.store(self.check_mapped(sym_index, address)?, Ordering::Release);
+            return Ok(address);
+        }
+        let sym_name = self.symbol_names()[sym_index as usize];
+        // The library's own dylib, if the symbol is looked up in it, which must still be loaded once it's found.
+        let mut loaded = None;
+        let address = match self.native_address(sym_index) {
+            Some(0) => return Err(ErrorCode::SymbolNotFound.error(format!("Symbol {sym_name:?} is not present."))),
+            Some(address) => address,
+            None => {
+                let address = match (handle, self.broker()) {
+                    (None, Some(broker)) => {
+                        self.with_symbol_name(sym_index, |sym_name| self.resolve_brokered(broker, sym_name))?
+                    }
+                    (handle, _) => {
+                        let handle = match handle {
+                            Some(handle) => handle,
+                            None => *loaded.insert(self.ensure_loaded()?),
+                        };
+                        let address = self.find_symbol_in(sym_index, handle)?;
+                        self.check_signature(sym_index, handle);
+                        address
+                    }
+                };
+                self.filter_address(sym_name, address)
+            }
+        };
+        if let Some(loaded) = loaded {
+            if self.handle().map(|handle| handle.0) != Some(loaded.0) {
+                let message = format!("{}: the library was unloaded while resolving {sym_name:?}", self.name);
+                return Err(ErrorCode::NotLoaded.error(message));
+            }
+        }
+        entry.store(address, Ordering::Release);
+        self.check_deprecation(sym_index);
+        self.patch_stub(sym_index, address);
+        self.cache_address(sym_index, address);
+        self.record_resolve(sym_index);
+        self.notify(&HookEvent::Resolved { sym_index, symbol: sym_name, address });
+        Ok(address)
+    }
+
+    // Returns the address of a natively weak-linked symbol, as bound by the OS dynamic linker, which is 0 if the
+    // symbol is absent.  Returns `None` for symbols called through stubs.
+    fn native_address(&self, sym_index: u32) -> Option<Address> {
+        let pos = self.native_symbols.binary_search_by_key(&(sym_index as usize), |native| native[0]);
+        pos.ok().map(|pos| self.native_symbols[pos][1])
+    }
+
+    // Whether the symbol is weakly linked natively, rather than called through a stub.
+    pub(crate) fn is_native(&self, sym_index: u32) -> bool {
+        self.native_address(sym_index).is_some()
+    }
+
+    // Returns address of a data symbol. Called by the generated data accessors.
+    #[doc(hidden)]
+    pub fn data_address(&self, sym_index: u32) -> Address {
+        let address = self.symbol_table_entry(sym_index).load(Ordering::Acquire);
+        self.check_data_access(sym_index, address);
+        address
+    }
+
+    // Whether the symbol has been resolved at some point, even if its entry has been reset since.
+    fn is_symbol_resolved(&self, sym_index: u32) -> bool {
+        self.symbol_table_entry(sym_index).load(Ordering::Acquire) != 0 || self.cached_address(sym_index).is_some()
     }
 
     // Get a reference to the symbol pointer at the specified index.
-    unsafe fn symbol_table_entry(&self, sym_index: u32) -> *mut Address {
-        let ptr: &UnsafeCell<Address> = mem::transmute(&self.symbol_table[0]);
-        ptr.get().offset(sym_index as isize) as *mut Address
+    fn symbol_table_entry(&self, sym_index: u32) -> &AtomicUsize {
+        &self.symbol_table[sym_index as usize]
+    }
+}
+
+impl Library {
+    /// Allocates the bookkeeping state of [checked mode](index.html#checked-mode).
+    ///
+    /// The state is otherwise allocated when the library's groups are first resolved.  Calling this at startup
+    /// ensures that resolution never allocates.  Does nothing if checked mode is disabled, or if the state has already
+    /// been allocated.
+    pub fn init_checked(&self) {
+        #[cfg(feature = "checked")]
+        self.get_checked_state();
     }
 }
 
@@ -188,42 +937,117 @@ impl Library {
     fn assert_resolved(&self, _sym_indices: &[u32]) {}
 
     fn deassert_resolved(&self, _sym_indices: &[u32]) {}
+
+    fn cached_address(&self, _sym_index: u32) -> Option<Address> {
+        None
+    }
+
+    fn check_data_access(&self, _sym_index: u32, _address: Address) {}
+
+    fn cache_address(&self, _sym_index: u32, _address: Address) {}
+
+    fn is_asserted(&self) -> bool {
+        false
+    }
+
+    fn clear_cached_addresses(&self) {}
+
+    fn reset_unasserted(&self) {}
 }
 
 #[cfg(feature = "checked")]
 impl Library {
-    fn get_checked_state(&self) -> MutexGuard<CheckedState> {
-        let mutex = self.checked_state.get_or_init(|| {
-            Mutex::new(CheckedState {
-                asserted: Self::boxed_slice(self.symbol_table.len()),
-            })
-        });
-        mutex.lock().unwrap()
+    fn get_checked_state(&self) -> &CheckedState {
+        self.checked_state.get_or_init(|| CheckedState {
+            asserted: (0..self.symbol_table.len()).map(|_| AtomicU32::new(0)).collect(),
+            cached: (0..self.symbol_table.len()).map(|_| AtomicUsize::new(0)).collect(),
+        })
     }
 
+    // Sequentially consistent ordering of the assertion counts and the symbol table entries guarantees that
+    // a concurrent assertion and de-assertion never leave an asserted entry reset: either the de-asserting thread
+    // sees the new count and restores the entry, or the asserting thread sees the reset entry and restores it.
+
     fn assert_resolved(&self, sym_indices: &[u32]) {
-        let mut checked_state = self.get_checked_state();
+        let checked_state = self.get_checked_state();
         for sym_index in sym_indices {
-            checked_state.asserted[*sym_index as usize] += 1;
+            checked_state.asserted[*sym_index as usize].fetch_add(1, Ordering::SeqCst);
+            self.restore_entry(checked_state, *sym_index);
         }
     }
 
     fn deassert_resolved(&self, sym_indices: &[u32]) {
-        let mut checked_state = self.get_checked_state();
+        let checked_state = self.get_checked_state();
         for sym_index in sym_indices {
-            checked_state.asserted[*sym_index as usize] -= 1;
-            if checked_state.asserted[*sym_index as usize] == 0 {
+            let asserted = &checked_state.asserted[*sym_index as usize];
+            if asserted.fetch_sub(1, Ordering::SeqCst) == 1 && !self.is_sealed() {
                 // All threads have de-asserted, so noone should be using this entry.
-                unsafe {
-                    self.symbol_table_entry(*sym_index).write(0);
+                self.symbol_table_entry(*sym_index).store(0, Ordering::SeqCst);
+                if asserted.load(Ordering::SeqCst) != 0 {
+                    self.restore_entry(checked_state, *sym_index);
                 }
             }
         }
     }
 
-    fn boxed_slice<T: Copy + Default>(size: usize) -> Box<[T]> {
-        let mut v = Vec::<T>::with_capacity(size);
-        v.resize(size, Default::default());
-        v.into_boxed_slice()
+    // Restore a reset entry from the cached address.
+    fn restore_entry(&self, checked_state: &CheckedState, sym_index: u32) {
+        let entry = self.symbol_table_entry(sym_index);
+        if entry.load(Ordering::SeqCst) == 0 && !self.is_sealed() {
+            let _ = entry.compare_exchange(
+                0,
+                checked_state.cached[sym_index as usize].load(Ordering::Acquire),
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            );
+        }
+    }
+
+    fn cached_address(&self, sym_index: u32) -> Option<Address> {
+        match self.get_checked_state().cached[sym_index as usize].load(Ordering::Acquire) {
+            0 => None,
+            address => Some(address),
+        }
+    }
+
+    fn cache_address(&self, sym_index: u32, address: Address) {
+        self.get_checked_state().cached[sym_index as usize].store(address, Ordering::Release);
+    }
+
+    // Whether any symbol is asserted, i.e. may be in use.
+    fn is_asserted(&self) -> bool {
+        let checked_state = self.get_checked_state();
+        checked_state.asserted.iter().any(|count| count.load(Ordering::SeqCst) != 0)
+    }
+
+    fn clear_cached_addresses(&self) {
+        for address in self.get_checked_state().cached.iter() {
+            address.store(0, Ordering::Release);
+        }
+    }
+
+    // Resets the entries of the symbols that aren't asserted, as their de-assertion would, e.g. after resolving them
+    // without asserting.
+    fn reset_unasserted(&self) {
+        let checked_state = self.get_checked_state();
+        for (sym_index, asserted) in checked_state.asserted.iter().enumerate() {
+            if asserted.load(Ordering::SeqCst) == 0 && !self.is_sealed() {
+                self.symbol_table_entry(sym_index as u32).store(0, Ordering::SeqCst);
+                if asserted.load(Ordering::SeqCst) != 0 {
+                    self.restore_entry(checked_state, sym_index as u32);
+                }
+            }
+        }
+    }
+
+    // Data accessors must be subject to the same checks as the code stubs, which crash upon unasserted use.
+    fn check_data_access(&self, sym_index: u32, address: Address) {
+        if address == 0 {
+            let sym_name = self.symbol_names()[sym_index as usize];
+            if self.report_failure(ErrorCode::GroupUnresolved, Some(sym_name)) {
+                return;
+            }
+            panic!("Data symbol {sym_name:?} accessed without asserting resolution of a group that contains it.");
+        }
     }
 }
@@ -1,9 +1,40 @@
 //! Provides a platform-agnostic interface for loading dynamic libraries and finding symbols within them.
+//!
+//! The functions of this module may be used on their own, without any stubs.  [`Dylib`] wraps them into an owned
+//! handle, which is closed when dropped:
+//!
+//! ```no_run
+//! use std::ffi::CStr;
+//! use std::path::Path;
+//! use weaklink::loading::{Dylib, OpenFlags};
+//!
+//! let flags = OpenFlags { global: false, ..OpenFlags::default() };
+//! let dylib = Dylib::open_with(Path::new("libfoo.so"), flags)?;
+//! let address = dylib.sym(CStr::from_bytes_with_nul(b"foo_init\0")?)?;
+//! let foo_init: extern "C" fn() = unsafe { std::mem::transmute(address) };
+//! foo_init();
+//! println!("Loaded from {}", dylib.path()?.display());
+//! dylib.close()?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
 
-#[cfg(any(unix))]
-pub use unix::{find_symbol, load_library};
-#[cfg(any(windows))]
-pub use windows::{find_symbol, load_library};
+#[cfg(unix)]
+pub use unix::{
+    close_library, exports_of, find_symbol, is_address_mapped, load_library, loaded_modules, module_version, page_size,
+    path_of, pin_library, protect_read_only, set_code_writable,
+};
+#[cfg(windows)]
+pub use windows::{
+    close_library, exports_of, find_symbol, is_address_mapped, load_library, loaded_modules, module_version, page_size,
+    path_of, pin_library, protect_read_only, set_code_writable,
+};
+
+use crate::{messages, Error};
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::os::raw::{c_char, c_long, c_void};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 /// Represents a handle to a dynamic library.
 #[repr(transparent)]
@@ -13,16 +44,341 @@ pub struct DylibHandle(pub usize);
 /// Represents an address in memory.
 pub type Address = usize;
 
+/// A module loaded in the current process.  See [`loaded_modules`].
+#[derive(Clone)]
+pub struct LoadedModule {
+    /// File path of the module.
+    pub path: PathBuf,
+    /// Handle of the module, which remains valid for as long as the module stays loaded.
+    pub handle: DylibHandle,
+}
+
+/// A symbol exported by a loaded module.  See [`exports_of`].
+#[derive(Clone, Debug)]
+pub struct Export {
+    /// Symbol name, as accepted by [`find_symbol`].
+    pub name: CString,
+    /// Address of the symbol in the current process.
+    pub address: Address,
+}
+
+/// Error reported by the dynamic loader.
+#[derive(Debug)]
+pub struct DlError {
+    /// Message returned by `dlerror`, or the description of the error code returned by `GetLastError` on Windows.
+    pub message: String,
+    /// Name of the symbol being looked up, if any.
+    pub symbol: Option<String>,
+    /// Path of the library involved, if known.
+    pub path: Option<PathBuf>,
+    /// Error code returned by `GetLastError` on Windows.
+    pub os_error: Option<u32>,
+}
+
+impl fmt::Display for DlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        messages::write_message(self, f, |f| match (&self.symbol, &self.path) {
+            (Some(symbol), Some(path)) => {
+                write!(f, "Could not find {symbol} in {}: {}", path.display(), self.message)
+            }
+            (Some(symbol), None) => write!(f, "Could not find {symbol}: {}", self.message),
+            (None, Some(path)) => write!(f, "Could not load {}: {}", path.display(), self.message),
+            (None, None) => f.write_str(&self.message),
+        })
+    }
+}
+
+impl std::error::Error for DlError {}
+
+/// Portable options for [`open`] and [`Dylib::open_with`].
+#[derive(Clone, Copy, Debug)]
+pub struct OpenFlags {
+    /// Whether the library's symbols are available for resolving references of libraries loaded later.
+    /// Ignored on Windows, where imports are bound to the modules they name.
+    pub global: bool,
+    /// Whether references to functions are bound on first call, rather than at load time.  Ignored on Windows.
+    pub lazy: bool,
+}
+
+impl Default for OpenFlags {
+    /// The flags used by [`load_library`]: lazy binding and global visibility.
+    fn default() -> OpenFlags {
+        OpenFlags {
+            global: true,
+            lazy: true,
+        }
+    }
+}
+
+/// Loads a dynamic library with the specified options.  Fails with a [`DlError`].
+pub fn open(path: &Path, flags: OpenFlags) -> Result<DylibHandle, Error> {
+    #[cfg(unix)]
+    {
+        let binding = if flags.lazy { unix::RTLD_LAZY } else { unix::RTLD_NOW };
+        let visibility = if flags.global { unix::RTLD_GLOBAL } else { unix::RTLD_LOCAL };
+        unix::load_library_with_flags(path, binding | visibility)
+    }
+    #[cfg(windows)]
+    {
+        let _ = flags;
+        windows::load_library(path)
+    }
+}
+
+/// Name of the main program, when listed among the default dylib names of a library (see `Config::main_program` in
+/// `weaklink_build`), in which case [`main_program`] is loaded.
+pub const MAIN_PROGRAM: &str = "<main program>";
+
+/// Name of the vDSO, when listed among the default dylib names of a library (see `Config::vdso` in `weaklink_build`),
+/// in which case [`vdso`] is loaded.
+pub const VDSO: &str = "<vdso>";
+
+/// Returns a handle of the main program.
+///
+/// On Unix, symbols are looked up via this handle in the global scope: the main executable, the libraries it's
+/// linked against, and those loaded with global visibility since.  On Windows, they are looked up in the exports of
+/// the main executable only.
+pub fn main_program() -> Result<DylibHandle, Error> {
+    #[cfg(unix)]
+    {
+        unix::main_program()
+    }
+    #[cfg(windows)]
+    {
+        windows::main_program()
+    }
+}
+
+/// Returns a handle of the virtual dynamic shared object that the Linux kernel maps into each process, which exports
+/// fast implementations of some system calls, such as `__vdso_clock_gettime`.
+///
+/// Fails with [`ErrorCode::LibraryNotFound`](crate::ErrorCode::LibraryNotFound) if the vDSO is not mapped, and with
+/// [`ErrorCode::Unsupported`](crate::ErrorCode::Unsupported) on other operating systems.
+pub fn vdso() -> Result<DylibHandle, Error> {
+    #[cfg(target_os = "linux")]
+    {
+        unix::vdso()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err(crate::ErrorCode::Unsupported.error("The vDSO is only available on Linux"))
+    }
+}
+
+/// Identifier of a link-map namespace of the Linux dynamic linker (`Lmid_t`).  See [`load_library_in_namespace`].
+pub type NamespaceId = c_long;
+
+/// Namespace of the main program, and of the libraries loaded via [`load_library`].
+pub const LM_ID_BASE: NamespaceId = 0;
+
+/// Requests a new namespace from [`load_library_in_namespace`].
+pub const LM_ID_NEWLM: NamespaceId = -1;
+
+/// Loads a dynamic library in a link-map namespace via `dlmopen`, which isolates its symbols, and those of its
+/// dependencies, from the libraries in other namespaces, and returns its handle along with the namespace.
+///
+/// This lets a process load several versions of the same plugin at once, without their symbols colliding.  Passing
+/// [`LM_ID_NEWLM`] creates a namespace, whose identifier may then be passed to load further libraries in it, such as
+/// dependencies of the plugin.  Symbols are looked up via [`find_symbol`] as usual, which searches the namespace of
+/// the handle.  Each namespace loads its own copy of the dependencies, including the C library, and glibc only
+/// supports a few namespaces, e.g. 16.  The library is loaded with lazy binding and local visibility.
+///
+/// Fails with [`ErrorCode::Unsupported`](crate::ErrorCode::Unsupported) on other operating systems than Linux, and
+/// with C libraries that don't provide `dlmopen`, such as musl.
+pub fn load_library_in_namespace(path: &Path, namespace: NamespaceId) -> Result<(DylibHandle, NamespaceId), Error> {
+    #[cfg(target_os = "linux")]
+    {
+        unix::load_library_in_namespace(path, namespace)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (path, namespace);
+        Err(crate::ErrorCode::Unsupported.error("Namespaces are only supported on Linux"))
+    }
+}
+
+/// Returns the link-map namespace that a dynamic library has been loaded in.  See [`load_library_in_namespace`].
+///
+/// Fails with [`ErrorCode::Unsupported`](crate::ErrorCode::Unsupported) on other operating systems than Linux.
+pub fn namespace_of(handle: DylibHandle) -> Result<NamespaceId, Error> {
+    #[cfg(target_os = "linux")]
+    {
+        unix::namespace_of(handle)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = handle;
+        Err(crate::ErrorCode::Unsupported.error("Namespaces are only supported on Linux"))
+    }
+}
+
+/// C library of the process.  See [`libc_flavor`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LibcFlavor {
+    /// The GNU C library.
+    Glibc,
+    /// The musl C library.
+    Musl,
+    /// Another C library, e.g. that of macOS or Windows, or one that couldn't be identified.
+    Other,
+}
+
+impl LibcFlavor {
+    /// Returns the tag of default dylib names meant for this C library, such as `musl` in `musl:libfoo.so`.
+    ///
+    /// [`Library::load`](crate::Library::load) skips names tagged for another C library than that of the process,
+    /// and loads the others without their tag.  See `Config::add_dylib_names_for` in `weaklink_build`.
+    pub fn tag(self) -> &'static str {
+        match self {
+            LibcFlavor::Glibc => "glibc",
+            LibcFlavor::Musl => "musl",
+            LibcFlavor::Other => "other",
+        }
+    }
+}
+
+/// Returns the C library of the process, which is detected on first call.
+///
+/// This lets a single binary pick the builds of plugins that match the C library of the system it runs on.
+pub fn libc_flavor() -> LibcFlavor {
+    static FLAVOR: OnceLock<LibcFlavor> = OnceLock::new();
+    *FLAVOR.get_or_init(|| {
+        #[cfg(target_os = "linux")]
+        {
+            unix::detect_libc_flavor()
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            LibcFlavor::Other
+        }
+    })
+}
+
+// Returns a default dylib name without its C library tag, if any, or `None` if it is tagged for another C library.
+pub(crate) fn untag_dylib_name(name: &str) -> Option<&str> {
+    let flavors = [LibcFlavor::Glibc, LibcFlavor::Musl, LibcFlavor::Other];
+    for flavor in flavors {
+        if let Some(untagged) = name.strip_prefix(flavor.tag()).and_then(|rest| rest.strip_prefix(':')) {
+            return (flavor == libc_flavor()).then_some(untagged);
+        }
+    }
+    Some(name)
+}
+
+/// A function exported by a library in place of its dynamic symbol table, which returns the address of a symbol given
+/// its name, or null if the library doesn't provide it.  See [`find_symbol_via`].
+pub type BootstrapFn = unsafe extern "C" fn(name: *const c_char) -> *const c_void;
+
+/// Finds a symbol by calling the library's bootstrap export, rather than looking it up in the dynamic symbol table.
+///
+/// This is intended for libraries that strip their dynamic symbol table, and export a registration function such as
+/// `plugin_get_api(name)` instead.  Only the bootstrap export itself is looked up via [`find_symbol`].  Fails with a
+/// [`DlError`] if the bootstrap export is missing, or returns null.
+///
+/// # Safety
+/// The bootstrap export must be a [`BootstrapFn`].
+pub unsafe fn find_symbol_via(handle: DylibHandle, bootstrap: &CStr, name: &CStr) -> Result<Address, Error> {
+    let bootstrap_fn = std::mem::transmute::<Address, BootstrapFn>(find_symbol(handle, bootstrap)?);
+    match bootstrap_fn(name.as_ptr()) as Address {
+        0 => Err(DlError {
+            message: format!("{bootstrap:?} doesn't provide it"),
+            symbol: Some(name.to_string_lossy().into_owned()),
+            path: path_of(handle).ok(),
+            os_error: None,
+        }
+        .into()),
+        address => Ok(address),
+    }
+}
+
+/// Finds a symbol of the specified version, such as `foo` of version `LIBFOO_2.0`, which `readelf` lists as
+/// `foo@LIBFOO_2.0`.
+///
+/// On Linux with glibc, this uses `dlvsym`, so that the symbol binds to that version deterministically, rather than to
+/// the default version of its name, and fails with a [`DlError`] if the dylib doesn't export that version.  Elsewhere,
+/// where symbols aren't versioned, or `dlvsym` isn't available, e.g. with musl, `version` is ignored, and the symbol
+/// is looked up via [`find_symbol`].
+pub fn find_versioned_symbol(handle: DylibHandle, name: &CStr, version: &CStr) -> Result<Address, Error> {
+    #[cfg(target_os = "linux")]
+    if let Some(result) = unix::find_versioned_symbol(handle, name, version) {
+        return result;
+    }
+    let _ = version;
+    find_symbol(handle, name)
+}
+
+/// A dynamic library opened via [`Dylib::open`], which is closed when dropped.
+pub struct Dylib {
+    handle: DylibHandle,
+}
+
+impl Dylib {
+    /// Loads a dynamic library with the default [`OpenFlags`].
+    pub fn open(path: &Path) -> Result<Dylib, Error> {
+        Dylib::open_with(path, OpenFlags::default())
+    }
+
+    /// Loads a dynamic library with the specified options.
+    pub fn open_with(path: &Path, flags: OpenFlags) -> Result<Dylib, Error> {
+        Ok(Dylib {
+            handle: open(path, flags)?,
+        })
+    }
+
+    /// Finds a symbol in the library.  Fails with a [`DlError`].
+    pub fn sym(&self, name: &CStr) -> Result<Address, Error> {
+        find_symbol(self.handle, name)
+    }
+
+    /// Returns the file path the library has been loaded from.
+    pub fn path(&self) -> Result<PathBuf, Error> {
+        path_of(self.handle)
+    }
+
+    /// Returns the handle of the library, which remains valid for as long as `self` is alive.
+    pub fn handle(&self) -> DylibHandle {
+        self.handle
+    }
+
+    /// Returns the handle of the library, leaving it loaded, e.g. for passing it to
+    /// [`Library::set_handle`](crate::Library::set_handle).
+    pub fn into_handle(self) -> DylibHandle {
+        let handle = self.handle;
+        std::mem::forget(self);
+        handle
+    }
+
+    /// Closes the library, reporting failure, unlike dropping it.
+    pub fn close(self) -> Result<(), Error> {
+        close_library(self.into_handle())
+    }
+}
+
+impl Drop for Dylib {
+    fn drop(&mut self) {
+        let _ = close_library(self.handle);
+    }
+}
+
+// Reads a value from memory of a loaded module, which may not be aligned.
+unsafe fn read<T: Copy>(address: Address) -> T {
+    (address as *const T).read_unaligned()
+}
+
 /// Unix-spcific loading functions.
 #[cfg(any(unix, doc))]
 pub mod unix {
-    use super::{Address, DylibHandle};
-    use crate::Error;
+    pub use super::DlError;
+    use super::{Address, DylibHandle, Export, LoadedModule};
+    use crate::{Error, ErrorCode};
     use std::ffi::{CStr, CString};
     use std::os::raw::{c_char, c_int, c_void};
     #[cfg(unix)]
-    use std::os::unix::ffi::OsStrExt;
-    use std::path::Path;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Path, PathBuf};
+    #[cfg(target_os = "linux")]
+    use std::sync::OnceLock;
 
     pub const RTLD_LAZY: c_int = 0x0001;
     pub const RTLD_NOW: c_int = 0x0002;
@@ -37,23 +393,237 @@ pub mod unix {
     #[cfg(target_os = "macos")]
     pub const RTLD_GLOBAL: c_int = 0x0008;
 
+    #[cfg(target_os = "linux")]
+    pub const RTLD_NOLOAD: c_int = 0x0004;
+    #[cfg(target_os = "macos")]
+    pub const RTLD_NOLOAD: c_int = 0x0010;
+
+    #[cfg(target_os = "linux")]
+    pub const RTLD_NODELETE: c_int = 0x1000;
+    #[cfg(target_os = "macos")]
+    pub const RTLD_NODELETE: c_int = 0x0080;
+
+    #[repr(C)]
+    struct DlInfo {
+        dli_fname: *const c_char,
+        dli_fbase: *mut c_void,
+        dli_sname: *const c_char,
+        dli_saddr: *mut c_void,
+    }
+
     #[link(name = "dl")]
     extern "C" {
         fn dlopen(filename: *const c_char, flag: c_int) -> DylibHandle;
         fn dlsym(raw_handle: *const c_void, symbol: *const c_char) -> Address;
         fn dlerror() -> *const c_char;
+        fn dladdr(address: *const c_void, info: *mut DlInfo) -> c_int;
+        fn dlclose(raw_handle: *const c_void) -> c_int;
+        #[cfg(target_os = "linux")]
+        fn dlinfo(raw_handle: *const c_void, request: c_int, info: *mut c_void) -> c_int;
+    }
+
+    const PROT_READ: c_int = 0x1;
+    const PROT_WRITE: c_int = 0x2;
+    const PROT_EXEC: c_int = 0x4;
+
+    extern "C" {
+        fn getpagesize() -> c_int;
+        fn mprotect(address: *mut c_void, len: usize, prot: c_int) -> c_int;
+    }
+
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    struct DlPhdrInfo {
+        dlpi_addr: usize,
+        dlpi_name: *const c_char,
+        dlpi_phdr: *const c_void,
+        dlpi_phnum: u16,
+    }
+
+    #[cfg(target_os = "linux")]
+    extern "C" {
+        fn dl_iterate_phdr(
+            callback: unsafe extern "C" fn(info: *mut DlPhdrInfo, size: usize, data: *mut c_void) -> c_int,
+            data: *mut c_void,
+        ) -> c_int;
+    }
+
+    #[cfg(target_os = "macos")]
+    extern "C" {
+        fn _dyld_image_count() -> u32;
+        fn _dyld_get_image_name(image_index: u32) -> *const c_char;
+    }
+
+    // Captures the last dynamic linker error of this thread, which must be done right after the failed call,
+    // before anything else might call into the dynamic linker.
+    fn last_error() -> DlError {
+        let message = unsafe { dlerror() };
+        let message = match message.is_null() {
+            true => "Unknown error".to_string(),
+            false => unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned(),
+        };
+        DlError {
+            message,
+            symbol: None,
+            path: None,
+            os_error: None,
+        }
+    }
+
+    fn path_from_c(path: CString) -> PathBuf {
+        PathBuf::from(std::ffi::OsString::from_vec(path.into_bytes()))
     }
 
     /// Loads a dynamic library with the specified flags.
     pub fn load_library_with_flags(path: &Path, flags: c_int) -> Result<DylibHandle, Error> {
         let path_buf = CString::new(path.as_os_str().as_bytes()).unwrap();
-        unsafe {
-            let handle = dlopen(path_buf.as_ptr(), flags);
-            if handle.0 == 0 {
-                Err(format!("{:?}", CStr::from_ptr(dlerror())).into())
-            } else {
-                Ok(handle)
+        let handle = unsafe { dlopen(path_buf.as_ptr(), flags) };
+        if handle.0 == 0 {
+            let err = last_error();
+            Err(DlError {
+                path: Some(path.to_path_buf()),
+                ..err
+            }
+            .into())
+        } else {
+            Ok(handle)
+        }
+    }
+
+    /// Returns a handle of the main program, which looks up symbols in the global scope.
+    pub fn main_program() -> Result<DylibHandle, Error> {
+        let handle = unsafe { dlopen(std::ptr::null(), RTLD_LAZY) };
+        match handle.0 {
+            0 => Err(last_error().into()),
+            _ => Ok(handle),
+        }
+    }
+
+    /// Returns a handle of the vDSO, which is listed among the loaded modules under a name such as
+    /// `linux-vdso.so.1`, depending on the architecture.
+    #[cfg(target_os = "linux")]
+    pub fn vdso() -> Result<DylibHandle, Error> {
+        let is_vdso = |name: &&CString| {
+            let name = name.as_bytes();
+            name.starts_with(b"linux-vdso") || name.starts_with(b"linux-gate")
+        };
+        let names = module_names();
+        let Some(name) = names.iter().find(is_vdso) else {
+            return Err(ErrorCode::LibraryNotFound.error("The vDSO is not mapped"));
+        };
+        // Opened by name, as it's already loaded.
+        let path = Path::new(std::ffi::OsStr::from_bytes(name.as_bytes()));
+        load_library_with_flags(path, RTLD_LAZY | RTLD_NOLOAD)
+    }
+
+    #[cfg(target_os = "linux")]
+    type DlmopenFn =
+        unsafe extern "C" fn(namespace: super::NamespaceId, filename: *const c_char, flag: c_int) -> DylibHandle;
+
+    /// Loads a dynamic library in a link-map namespace via `dlmopen`, and returns its handle and namespace.
+    ///
+    /// `dlmopen` is looked up in the global scope rather than linked to, since musl lacks it.
+    #[cfg(target_os = "linux")]
+    pub fn load_library_in_namespace(
+        path: &Path,
+        namespace: super::NamespaceId,
+    ) -> Result<(DylibHandle, super::NamespaceId), Error> {
+        static DLMOPEN: OnceLock<Option<DlmopenFn>> = OnceLock::new();
+        let dlmopen = *DLMOPEN.get_or_init(|| {
+            let main_program = main_program().ok()?;
+            let address = find_symbol(main_program, CStr::from_bytes_with_nul(b"dlmopen\0").unwrap());
+            let _ = close_library(main_program);
+            // The signature is that of glibc's `dlmopen`.
+            Some(unsafe { std::mem::transmute::<Address, DlmopenFn>(address.ok()?) })
+        });
+        let Some(dlmopen) = dlmopen else {
+            return Err(ErrorCode::Unsupported.error("The C library doesn't provide dlmopen"));
+        };
+        let path_buf = CString::new(path.as_os_str().as_bytes()).unwrap();
+        // Libraries can't be loaded with global visibility in a new namespace.
+        let handle = unsafe { dlmopen(namespace, path_buf.as_ptr(), RTLD_LAZY | RTLD_LOCAL) };
+        if handle.0 == 0 {
+            let err = last_error();
+            return Err(DlError {
+                path: Some(path.to_path_buf()),
+                ..err
+            }
+            .into());
+        }
+        match namespace_of(handle) {
+            Ok(namespace) => Ok((handle, namespace)),
+            Err(err) => {
+                let _ = close_library(handle);
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns the link-map namespace that a dynamic library has been loaded in.
+    #[cfg(target_os = "linux")]
+    pub fn namespace_of(handle: DylibHandle) -> Result<super::NamespaceId, Error> {
+        const RTLD_DI_LMID: c_int = 1;
+
+        let mut namespace: super::NamespaceId = 0;
+        let info = &mut namespace as *mut super::NamespaceId as *mut c_void;
+        if unsafe { dlinfo(handle.0 as *const c_void, RTLD_DI_LMID, info) } != 0 {
+            return Err(last_error().into());
+        }
+        Ok(namespace)
+    }
+
+    #[cfg(target_os = "linux")]
+    type DlvsymFn =
+        unsafe extern "C" fn(raw_handle: *const c_void, symbol: *const c_char, version: *const c_char) -> Address;
+
+    /// Finds a symbol of the specified version via `dlvsym`, or returns `None` if the C library doesn't provide it.
+    ///
+    /// `dlvsym` is looked up in the global scope rather than linked to, since musl lacks it.
+    #[cfg(target_os = "linux")]
+    pub fn find_versioned_symbol(handle: DylibHandle, name: &CStr, version: &CStr) -> Option<Result<Address, Error>> {
+        static DLVSYM: OnceLock<Option<DlvsymFn>> = OnceLock::new();
+        let dlvsym = *DLVSYM.get_or_init(|| {
+            let main_program = main_program().ok()?;
+            let address = find_symbol(main_program, CStr::from_bytes_with_nul(b"dlvsym\0").unwrap());
+            let _ = close_library(main_program);
+            // The signature is that of glibc's `dlvsym`.
+            Some(unsafe { std::mem::transmute::<Address, DlvsymFn>(address.ok()?) })
+        });
+        let dlvsym = dlvsym?;
+        let ptr = unsafe {
+            // Clear any stale error, so that the one reported is the lookup's.
+            dlerror();
+            dlvsym(handle.0 as *const c_void, name.as_ptr(), version.as_ptr())
+        };
+        if ptr == 0 {
+            let err = last_error();
+            let path = library_path(handle).ok().map(path_from_c);
+            let symbol = format!("{}@{}", name.to_string_lossy(), version.to_string_lossy());
+            return Some(Err(DlError {
+                symbol: Some(symbol),
+                path,
+                ..err
             }
+            .into()));
+        }
+        Some(Ok(ptr))
+    }
+
+    // Tells glibc by its version query, which musl lacks, and musl by the name of its dynamic linker.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn detect_libc_flavor() -> super::LibcFlavor {
+        if let Ok(handle) = main_program() {
+            let version_query = CStr::from_bytes_with_nul(b"gnu_get_libc_version\0").unwrap();
+            let is_glibc = find_symbol(handle, version_query).is_ok();
+            let _ = close_library(handle);
+            if is_glibc {
+                return super::LibcFlavor::Glibc;
+            }
+        }
+        let is_musl = |name: &CString| name.as_bytes().windows(4).any(|part| part == b"musl");
+        match module_names().iter().any(is_musl) {
+            true => super::LibcFlavor::Musl,
+            false => super::LibcFlavor::Other,
         }
     }
 
@@ -63,14 +633,571 @@ pub mod unix {
     }
 
     /// Finds a symbol in a dynamic library.
+    ///
+    /// On failure, returns a [`DlError`] naming the symbol and the library.
     pub fn find_symbol(handle: DylibHandle, name: &CStr) -> Result<Address, Error> {
+        let ptr = unsafe {
+            // Clear any stale error, so that the one reported is the lookup's.
+            dlerror();
+            dlsym(handle.0 as *const c_void, name.as_ptr())
+        };
+        if ptr == 0 {
+            let err = last_error();
+            let path = library_path(handle).ok().map(path_from_c);
+            Err(DlError {
+                symbol: Some(name.to_string_lossy().into_owned()),
+                path,
+                ..err
+            }
+            .into())
+        } else {
+            Ok(ptr)
+        }
+    }
+
+    /// Releases a handle of a dynamic library, unloading it once no handles remain.
+    pub fn close_library(handle: DylibHandle) -> Result<(), Error> {
+        if unsafe { dlclose(handle.0 as *const c_void) } != 0 {
+            Err(last_error().into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the file path a dynamic library has been loaded from.
+    pub fn path_of(handle: DylibHandle) -> Result<PathBuf, Error> {
+        Ok(path_from_c(library_path(handle)?))
+    }
+
+    /// Returns the version of a dynamic library, as reported by its `plugin_version` export.
+    ///
+    /// By convention, `plugin_version` is a NUL-terminated character array, e.g.
+    /// `const char plugin_version[] = "2.1";` in C.
+    pub fn module_version(handle: DylibHandle) -> Option<String> {
+        let name = CStr::from_bytes_with_nul(b"plugin_version\0").unwrap();
+        let address = find_symbol(handle, name).ok()?;
+        let version = unsafe { CStr::from_ptr(address as *const c_char) };
+        Some(version.to_str().ok()?.to_string())
+    }
+
+    /// Returns the modules loaded in the current process, starting with the main executable.
+    ///
+    /// Modules that can't be opened by name are omitted.  Virtual modules, such as the vDSO on Linux, are listed with
+    /// their name rather than a file path.
+    pub fn loaded_modules() -> Vec<LoadedModule> {
+        let mut modules = Vec::new();
+        for name in module_names() {
+            let path = match name.as_bytes() {
+                [] => std::env::current_exe().unwrap_or_default(),
+                name => PathBuf::from(std::ffi::OsString::from_vec(name.to_vec())),
+            };
+            unsafe {
+                // The main executable is opened via a null file name.
+                let name_ptr = if name.as_bytes().is_empty() { std::ptr::null() } else { name.as_ptr() };
+                let handle = dlopen(name_ptr, RTLD_LAZY | RTLD_NOLOAD);
+                if handle.0 != 0 {
+                    // Handles stay the same for as long as the module is loaded.
+                    dlclose(handle.0 as *const c_void);
+                    modules.push(LoadedModule { path, handle });
+                }
+            }
+        }
+        modules
+    }
+
+    // Returns the file names of the loaded modules, as recorded by the dynamic linker.
+    #[cfg(target_os = "linux")]
+    fn module_names() -> Vec<CString> {
+        unsafe extern "C" fn callback(info: *mut DlPhdrInfo, _size: usize, data: *mut c_void) -> c_int {
+            let names = &mut *(data as *mut Vec<CString>);
+            let name = (*info).dlpi_name;
+            names.push(if name.is_null() { CString::default() } else { CStr::from_ptr(name).to_owned() });
+            0
+        }
+        let mut names = Vec::<CString>::new();
         unsafe {
-            let ptr = dlsym(handle.0 as *const c_void, name.as_ptr());
-            if ptr == 0 {
-                Err(format!("{:?}", CStr::from_ptr(dlerror())).into())
+            dl_iterate_phdr(callback, &mut names as *mut Vec<CString> as *mut c_void);
+        }
+        names
+    }
+
+    // Returns the file names of the loaded modules, as recorded by the dynamic linker.
+    #[cfg(target_os = "macos")]
+    fn module_names() -> Vec<CString> {
+        unsafe {
+            let names = (0.._dyld_image_count()).map(|image_index| _dyld_get_image_name(image_index));
+            names.filter(|name| !name.is_null()).map(|name| CStr::from_ptr(name).to_owned()).collect()
+        }
+    }
+
+    /// Returns the symbols exported by a loaded module, as listed in its dynamic symbol table.
+    ///
+    /// Only the default version of versioned symbols is listed.  Addresses of GNU indirect functions (IFUNCs) are
+    /// those returned by their resolvers, as with [`find_symbol`].  IFUNCs that can't be resolved are skipped.
+    #[cfg(target_os = "linux")]
+    pub fn exports_of(handle: DylibHandle) -> Result<Vec<Export>, Error> {
+        #[cfg(target_pointer_width = "64")]
+        #[derive(Clone, Copy)]
+        #[repr(C)]
+        struct Sym {
+            st_name: u32,
+            st_info: u8,
+            st_other: u8,
+            st_shndx: u16,
+            st_value: u64,
+            st_size: u64,
+        }
+        #[cfg(target_pointer_width = "32")]
+        #[derive(Clone, Copy)]
+        #[repr(C)]
+        struct Sym {
+            st_name: u32,
+            st_value: u32,
+            st_size: u32,
+            st_info: u8,
+            st_other: u8,
+            st_shndx: u16,
+        }
+        const DT_NULL: isize = 0;
+        const DT_HASH: isize = 4;
+        const DT_STRTAB: isize = 5;
+        const DT_SYMTAB: isize = 6;
+        const DT_GNU_HASH: isize = 0x6ffffef5;
+        const DT_VERSYM: isize = 0x6ffffff0;
+        const STT_SECTION: u8 = 3;
+        const STT_FILE: u8 = 4;
+        const STT_TLS: u8 = 6;
+        const STT_GNU_IFUNC: u8 = 10;
+        const VERSYM_HIDDEN: u16 = 0x8000;
+        const STV_HIDDEN: u8 = 2;
+        const STV_INTERNAL: u8 = 1;
+
+        let link_map = unsafe { &*link_map(handle)? };
+        let (mut hash, mut gnu_hash, mut strtab, mut symtab, mut versym) = (0, 0, 0, 0, 0);
+        unsafe {
+            let mut dyn_ptr = link_map.l_ld;
+            loop {
+                let (tag, value) = super::read::<(isize, usize)>(dyn_ptr as Address);
+                match tag {
+                    DT_NULL => break,
+                    DT_HASH => hash = value,
+                    DT_GNU_HASH => gnu_hash = value,
+                    DT_STRTAB => strtab = value,
+                    DT_SYMTAB => symtab = value,
+                    DT_VERSYM => versym = value,
+                    _ => {}
+                }
+                dyn_ptr = dyn_ptr.add(2 * std::mem::size_of::<usize>());
+            }
+        }
+        if strtab == 0 || symtab == 0 {
+            return Err(ErrorCode::InvalidModule.error("Module has no dynamic symbol table."));
+        }
+        // glibc relocates the dynamic section in place, other loaders don't.
+        let relocate = |ptr: usize| if ptr < link_map.l_addr { ptr + link_map.l_addr } else { ptr };
+        let (strtab, symtab) = (relocate(strtab), relocate(symtab));
+        let versym = if versym != 0 { relocate(versym) } else { 0 };
+
+        let num_symbols = unsafe {
+            if gnu_hash != 0 {
+                gnu_hash_num_symbols(relocate(gnu_hash))
+            } else if hash != 0 {
+                // nchain
+                super::read::<u32>(relocate(hash) + 4) as usize
             } else {
-                Ok(ptr)
+                return Err(ErrorCode::InvalidModule.error("Module has no symbol hash table."));
             }
+        };
+
+        let mut exports = Vec::new();
+        for sym_index in 0..num_symbols {
+            let sym = unsafe { super::read::<Sym>(symtab + sym_index * std::mem::size_of::<Sym>()) };
+            let sym_type = sym.st_info & 0xf;
+            let visibility = sym.st_other & 0x3;
+            if sym.st_shndx == 0
+                || sym.st_value == 0
+                || matches!(sym_type, STT_SECTION | STT_FILE | STT_TLS)
+                || matches!(visibility, STV_HIDDEN | STV_INTERNAL)
+            {
+                continue;
+            }
+            // Non-default versions are not found by name lookups.
+            if versym != 0 && unsafe { super::read::<u16>(versym + sym_index * 2) } & VERSYM_HIDDEN != 0 {
+                continue;
+            }
+            let name = unsafe { CStr::from_ptr((strtab + sym.st_name as usize) as *const c_char) };
+            if name.to_bytes().is_empty() {
+                continue;
+            }
+            // The value of an IFUNC is its resolver, which the dynamic linker calls to obtain the actual address.
+            let address = match sym_type {
+                STT_GNU_IFUNC => match find_symbol(handle, name) {
+                    Ok(address) => address,
+                    Err(_) => continue,
+                },
+                _ => link_map.l_addr + sym.st_value as usize,
+            };
+            exports.push(Export {
+                name: name.to_owned(),
+                address,
+            });
+        }
+        Ok(exports)
+    }
+
+    // Returns the number of entries of the dynamic symbol table, using its GNU hash table.
+    #[cfg(target_os = "linux")]
+    unsafe fn gnu_hash_num_symbols(gnu_hash: Address) -> usize {
+        let (num_buckets, sym_offset, bloom_size, _bloom_shift) = super::read::<(u32, u32, u32, u32)>(gnu_hash);
+        let buckets = gnu_hash + 16 + bloom_size as usize * std::mem::size_of::<usize>();
+        let chains = buckets + num_buckets as usize * 4;
+        let last_bucket = (0..num_buckets as usize).map(|i| super::read::<u32>(buckets + i * 4)).max();
+        let mut sym_index = match last_bucket {
+            Some(last_bucket) if last_bucket >= sym_offset => last_bucket,
+            _ => return sym_offset as usize,
+        };
+        // Walk the last chain to its end, which is marked by the low bit of the hash.
+        while super::read::<u32>(chains + (sym_index - sym_offset) as usize * 4) & 1 == 0 {
+            sym_index += 1;
+        }
+        sym_index as usize + 1
+    }
+
+    /// Returns the symbols exported by a loaded module, as listed in its symbol table.
+    #[cfg(target_os = "macos")]
+    pub fn exports_of(handle: DylibHandle) -> Result<Vec<Export>, Error> {
+        let image_index = image_index(handle)?;
+        unsafe { super::macos::image_exports(image_index) }
+    }
+
+    /// Returns true if the address belongs to a currently loaded dynamic library (or the main executable).
+    pub fn is_address_mapped(address: Address) -> bool {
+        unsafe {
+            let mut info = std::mem::zeroed::<DlInfo>();
+            dladdr(address as *const c_void, &mut info) != 0
+        }
+    }
+
+    /// Prevents a dynamic library from being unloaded, even after all of its handles have been closed.
+    ///
+    /// The library is re-opened with `RTLD_NODELETE`.
+    pub fn pin_library(handle: DylibHandle) -> Result<(), Error> {
+        let path = library_path(handle)?;
+        // The extra reference is never released, which doesn't matter, because the library can't be unloaded.
+        let pinned = unsafe { dlopen(path.as_ptr(), RTLD_LAZY | RTLD_NOLOAD | RTLD_NODELETE) };
+        if pinned.0 == 0 {
+            let err = last_error();
+            Err(DlError {
+                path: Some(path_from_c(path)),
+                ..err
+            }
+            .into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the size of a memory page.
+    pub fn page_size() -> usize {
+        unsafe { getpagesize() as usize }
+    }
+
+    /// Makes a page-aligned memory region read-only.
+    pub fn protect_read_only(address: Address, len: usize) -> Result<(), Error> {
+        unsafe {
+            if mprotect(address as *mut c_void, len, PROT_READ) != 0 {
+                Err(ErrorCode::SystemError
+                    .error(format!("Could not protect memory: {}", std::io::Error::last_os_error())))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    /// Makes a page-aligned region of code writable, or restores it to read-only.
+    ///
+    /// The code remains executable throughout, so that other threads may keep running it.
+    pub fn set_code_writable(address: Address, len: usize, writable: bool) -> Result<(), Error> {
+        let prot = if writable { PROT_READ | PROT_WRITE | PROT_EXEC } else { PROT_READ | PROT_EXEC };
+        unsafe {
+            if mprotect(address as *mut c_void, len, prot) != 0 {
+                Err(ErrorCode::SystemError
+                    .error(format!("Could not protect memory: {}", std::io::Error::last_os_error())))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    // Prefix of the `struct link_map` of the dynamic linker.
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    struct LinkMap {
+        l_addr: usize,
+        l_name: *const c_char,
+        l_ld: *const u8,
+    }
+
+    #[cfg(target_os = "linux")]
+    fn link_map(handle: DylibHandle) -> Result<*const LinkMap, Error> {
+        const RTLD_DI_LINKMAP: c_int = 2;
+
+        unsafe {
+            let mut link_map: *const LinkMap = std::ptr::null();
+            let info = &mut link_map as *mut *const LinkMap as *mut c_void;
+            if dlinfo(handle.0 as *const c_void, RTLD_DI_LINKMAP, info) != 0 {
+                return Err(last_error().into());
+            }
+            Ok(link_map)
+        }
+    }
+
+    // Returns the file path a library has been loaded from.
+    #[cfg(target_os = "linux")]
+    fn library_path(handle: DylibHandle) -> Result<CString, Error> {
+        unsafe { Ok(CStr::from_ptr((*link_map(handle)?).l_name).to_owned()) }
+    }
+
+    // Returns the file path a library has been loaded from.
+    #[cfg(target_os = "macos")]
+    fn library_path(handle: DylibHandle) -> Result<CString, Error> {
+        let image_index = image_index(handle)?;
+        unsafe { Ok(CStr::from_ptr(_dyld_get_image_name(image_index)).to_owned()) }
+    }
+
+    // Returns the dyld image index of a library.
+    #[cfg(target_os = "macos")]
+    fn image_index(handle: DylibHandle) -> Result<u32, Error> {
+        unsafe {
+            for image_index in 0.._dyld_image_count() {
+                let name = _dyld_get_image_name(image_index);
+                let image_handle = dlopen(name, RTLD_LAZY | RTLD_NOLOAD);
+                if image_handle.0 != 0 {
+                    dlclose(image_handle.0 as *const c_void);
+                    if image_handle.0 == handle.0 {
+                        return Ok(image_index);
+                    }
+                }
+            }
+        }
+        Err(ErrorCode::LibraryNotFound.error("Could not find the library among loaded images."))
+    }
+}
+
+/// MacOS-specific loading functions.
+#[cfg(any(target_os = "macos", doc))]
+pub mod macos {
+    use super::Export;
+    use crate::{Error, ErrorCode};
+    use std::ffi::CStr;
+    use std::os::raw::c_char;
+    use std::path::{Path, PathBuf};
+    use std::{env, mem};
+
+    #[repr(C)]
+    struct MachHeader64 {
+        magic: u32,
+        cputype: i32,
+        cpusubtype: i32,
+        filetype: u32,
+        ncmds: u32,
+        sizeofcmds: u32,
+        flags: u32,
+        reserved: u32,
+    }
+
+    #[repr(C)]
+    struct RpathCommand {
+        cmd: u32,
+        cmdsize: u32,
+        path_offset: u32,
+    }
+
+    #[repr(C)]
+    struct SegmentCommand64 {
+        cmd: u32,
+        cmdsize: u32,
+        segname: [u8; 16],
+        vmaddr: u64,
+        vmsize: u64,
+        fileoff: u64,
+        filesize: u64,
+    }
+
+    #[repr(C)]
+    struct SymtabCommand {
+        cmd: u32,
+        cmdsize: u32,
+        symoff: u32,
+        nsyms: u32,
+        stroff: u32,
+        strsize: u32,
+    }
+
+    #[derive(Clone, Copy)]
+    #[repr(C)]
+    struct Nlist64 {
+        n_strx: u32,
+        n_type: u8,
+        n_sect: u8,
+        n_desc: u16,
+        n_value: u64,
+    }
+
+    const LC_SYMTAB: u32 = 0x2;
+    const LC_SEGMENT_64: u32 = 0x19;
+    const LC_RPATH: u32 = 0x8000001c;
+    const N_STAB: u8 = 0xe0;
+    const N_TYPE: u8 = 0x0e;
+    const N_SECT: u8 = 0x0e;
+    const N_EXT: u8 = 0x01;
+
+    extern "C" {
+        fn _dyld_get_image_header(image_index: u32) -> *const MachHeader64;
+        fn _dyld_get_image_vmaddr_slide(image_index: u32) -> isize;
+    }
+
+    // Reads the external symbols defined by an image from its symbol table mapped in memory.
+    pub(super) unsafe fn image_exports(image_index: u32) -> Result<Vec<Export>, Error> {
+        let header = _dyld_get_image_header(image_index);
+        if header.is_null() {
+            return Err(ErrorCode::InvalidModule.error("Could not find the image header."));
+        }
+        let slide = _dyld_get_image_vmaddr_slide(image_index) as usize;
+        let mut linkedit = None;
+        let mut symtab = None;
+        let mut cmd_ptr = (header as *const u8).add(mem::size_of::<MachHeader64>());
+        for _ in 0..(*header).ncmds {
+            let cmd = &*(cmd_ptr as *const RpathCommand);
+            match cmd.cmd {
+                LC_SEGMENT_64 => {
+                    let segment = &*(cmd_ptr as *const SegmentCommand64);
+                    if segment.segname.starts_with(b"__LINKEDIT\0") {
+                        let base = slide.wrapping_add(segment.vmaddr as usize);
+                        linkedit = Some(base.wrapping_sub(segment.fileoff as usize));
+                    }
+                }
+                LC_SYMTAB => symtab = Some(&*(cmd_ptr as *const SymtabCommand)),
+                _ => {}
+            }
+            cmd_ptr = cmd_ptr.add(cmd.cmdsize as usize);
+        }
+        let (Some(linkedit), Some(symtab)) = (linkedit, symtab) else {
+            return Err(ErrorCode::InvalidModule.error("Image has no symbol table."));
+        };
+
+        let symbols = linkedit + symtab.symoff as usize;
+        let strings = linkedit + symtab.stroff as usize;
+        let mut exports = Vec::new();
+        for sym_index in 0..symtab.nsyms as usize {
+            let sym = super::read::<Nlist64>(symbols + sym_index * mem::size_of::<Nlist64>());
+            if sym.n_type & N_STAB != 0 || sym.n_type & N_TYPE != N_SECT || sym.n_type & N_EXT == 0 {
+                continue;
+            }
+            // Strip the underscore added to C names, to match the names accepted by `dlsym`.
+            let name = CStr::from_ptr((strings + sym.n_strx as usize) as *const c_char);
+            let name = match name.to_bytes_with_nul() {
+                [b'_', rest @ ..] => CStr::from_bytes_with_nul_unchecked(rest),
+                _ => name,
+            };
+            exports.push(Export {
+                name: name.to_owned(),
+                address: slide.wrapping_add(sym.n_value as usize),
+            });
+        }
+        Ok(exports)
+    }
+
+    /// Values of the dyld path tokens used for expansion of install names.
+    #[derive(Clone, Debug, Default)]
+    pub struct InstallNameContext {
+        /// Directory of the main executable (`@executable_path`).
+        pub executable_path: PathBuf,
+        /// Directory of the image performing the load (`@loader_path`).
+        pub loader_path: PathBuf,
+        /// Run path search list (`@rpath`), with tokens already expanded.
+        pub rpaths: Vec<PathBuf>,
+    }
+
+    impl InstallNameContext {
+        /// Creates the context of the main executable, using its `LC_RPATH` load commands.
+        pub fn main_executable() -> Result<InstallNameContext, Error> {
+            let executable = env::current_exe()?;
+            let executable_path = executable.parent().map(Path::to_path_buf).unwrap_or_default();
+            let mut context = InstallNameContext {
+                loader_path: executable_path.clone(),
+                executable_path,
+                rpaths: Vec::new(),
+            };
+            for rpath in unsafe { main_executable_rpaths()? } {
+                context.rpaths.extend(resolve_install_name(&rpath, &context));
+            }
+            Ok(context)
+        }
+    }
+
+    // Reads LC_RPATH entries of the main executable's Mach-O header mapped in memory.
+    unsafe fn main_executable_rpaths() -> Result<Vec<String>, Error> {
+        let header = _dyld_get_image_header(0);
+        if header.is_null() {
+            return Err(ErrorCode::InvalidModule.error("Could not find the main executable image."));
+        }
+        let mut rpaths = Vec::new();
+        let mut cmd_ptr = (header as *const u8).add(mem::size_of::<MachHeader64>());
+        for _ in 0..(*header).ncmds {
+            let cmd = &*(cmd_ptr as *const RpathCommand);
+            if cmd.cmd == LC_RPATH {
+                let path = CStr::from_ptr(cmd_ptr.add(cmd.path_offset as usize) as *const c_char);
+                rpaths.push(path.to_string_lossy().into_owned());
+            }
+            cmd_ptr = cmd_ptr.add(cmd.cmdsize as usize);
+        }
+        Ok(rpaths)
+    }
+
+    /// Prefix of `dylib_names` entries that refer to a framework by name, e.g. `framework:Foo`.
+    pub const FRAMEWORK_PREFIX: &str = "framework:";
+
+    /// Returns candidate paths of the binary of framework `name` (i.e. `<dir>/<name>.framework/<name>`),
+    /// following dyld's framework search order:
+    /// - `DYLD_FRAMEWORK_PATH`,
+    /// - the run path search list,
+    /// - `@executable_path/../Frameworks`,
+    /// - `DYLD_FALLBACK_FRAMEWORK_PATH`, or if not set, `~/Library/Frameworks`, `/Library/Frameworks` and
+    ///   `/System/Library/Frameworks`.
+    pub fn framework_candidates(name: &str, context: &InstallNameContext) -> Vec<PathBuf> {
+        let mut dirs = Vec::new();
+        if let Some(paths) = env::var_os("DYLD_FRAMEWORK_PATH") {
+            dirs.extend(env::split_paths(&paths));
+        }
+        dirs.extend(context.rpaths.iter().cloned());
+        dirs.push(context.executable_path.join("../Frameworks"));
+        match env::var_os("DYLD_FALLBACK_FRAMEWORK_PATH") {
+            Some(paths) => dirs.extend(env::split_paths(&paths)),
+            None => {
+                if let Some(home) = env::var_os("HOME") {
+                    dirs.push(Path::new(&home).join("Library/Frameworks"));
+                }
+                dirs.push(PathBuf::from("/Library/Frameworks"));
+                dirs.push(PathBuf::from("/System/Library/Frameworks"));
+            }
+        }
+        let relative = Path::new(&format!("{name}.framework")).join(name);
+        dirs.into_iter().map(|dir| dir.join(&relative)).collect()
+    }
+
+    /// Expands the `@rpath`, `@loader_path` and `@executable_path` tokens in an install name.
+    ///
+    /// Returns the list of candidate paths to try, in order.  Names without tokens are returned as is.
+    pub fn resolve_install_name(name: &str, context: &InstallNameContext) -> Vec<PathBuf> {
+        if let Some(rest) = name.strip_prefix("@rpath/") {
+            context.rpaths.iter().map(|rpath| rpath.join(rest)).collect()
+        } else if let Some(rest) = name.strip_prefix("@loader_path/") {
+            vec![context.loader_path.join(rest)]
+        } else if let Some(rest) = name.strip_prefix("@executable_path/") {
+            vec![context.executable_path.join(rest)]
+        } else {
+            vec![PathBuf::from(name)]
         }
     }
 }
@@ -78,13 +1205,13 @@ pub mod unix {
 /// Windows-specific loading functions.
 #[cfg(any(windows, doc))]
 pub mod windows {
-    use super::{Address, DylibHandle};
-    use crate::Error;
-    use std::ffi::CStr;
+    use super::{read, Address, DlError, DylibHandle, Export, LoadedModule};
+    use crate::{Error, ErrorCode};
+    use std::ffi::{CStr, OsString};
     use std::os::raw::{c_char, c_ushort, c_void};
     #[cfg(windows)]
-    use std::os::windows::ffi::OsStrExt;
-    use std::path::Path;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Path, PathBuf};
 
     pub const LOAD_WITH_ALTERED_SEARCH_PATH: u32 = 0x00000008;
     pub const LOAD_LIBRARY_SEARCH_APPLICATION_DIR: u32 = 0x00000200;
@@ -96,42 +1223,445 @@ pub mod windows {
     pub const LOAD_IGNORE_CODE_AUTHZ_LEVEL: u32 = 0x00000010;
     pub const LOAD_LIBRARY_SAFE_CURRENT_DIRS: u32 = 0x00002000;
 
+    pub const GET_MODULE_HANDLE_EX_FLAG_PIN: u32 = 0x00000001;
+    pub const GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT: u32 = 0x00000002;
+    pub const GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS: u32 = 0x00000004;
+
+    #[repr(C)]
+    struct VsFixedFileInfo {
+        signature: u32,
+        struc_version: u32,
+        file_version_ms: u32,
+        file_version_ls: u32,
+        product_version_ms: u32,
+        product_version_ls: u32,
+        file_flags_mask: u32,
+        file_flags: u32,
+        file_os: u32,
+        file_type: u32,
+        file_subtype: u32,
+        file_date_ms: u32,
+        file_date_ls: u32,
+    }
+
+    #[repr(C)]
+    struct SystemInfo {
+        processor_architecture: u16,
+        reserved: u16,
+        page_size: u32,
+        minimum_application_address: *mut c_void,
+        maximum_application_address: *mut c_void,
+        active_processor_mask: usize,
+        number_of_processors: u32,
+        processor_type: u32,
+        allocation_granularity: u32,
+        processor_level: u16,
+        processor_revision: u16,
+    }
+
     #[link(name = "kernel32")]
     extern "system" {
+        fn GetModuleFileNameW(module: DylibHandle, filename: *mut c_ushort, size: u32) -> u32;
         fn LoadLibraryExW(filename: *const c_ushort, hfile: DylibHandle, flags: u32) -> DylibHandle;
         fn GetProcAddress(raw_handle: *const c_void, symbol: *const c_char) -> Address;
+        fn GetModuleHandleExW(flags: u32, module_name: *const c_ushort, module: *mut DylibHandle) -> i32;
+        fn FreeLibrary(raw_handle: *const c_void) -> i32;
         fn GetLastError() -> u32;
+        fn FormatMessageW(
+            flags: u32,
+            source: *const c_void,
+            message_id: u32,
+            language_id: u32,
+            buffer: *mut c_ushort,
+            size: u32,
+            arguments: *mut c_void,
+        ) -> u32;
+        fn VirtualProtect(address: *mut c_void, size: usize, new_protect: u32, old_protect: *mut u32) -> i32;
+        fn GetCurrentProcess() -> *mut c_void;
+        fn GetSystemInfo(info: *mut SystemInfo);
+        fn K32EnumProcessModules(process: *mut c_void, modules: *mut DylibHandle, size: u32, needed: *mut u32) -> i32;
     }
 
+    #[link(name = "version")]
+    extern "system" {
+        fn GetFileVersionInfoSizeW(filename: *const c_ushort, handle: *mut u32) -> u32;
+        fn GetFileVersionInfoW(filename: *const c_ushort, handle: u32, len: u32, data: *mut c_void) -> i32;
+        fn VerQueryValueW(
+            block: *const c_void,
+            sub_block: *const c_ushort,
+            buffer: *mut *mut c_void,
+            len: *mut u32,
+        ) -> i32;
+    }
+
+    const FORMAT_MESSAGE_IGNORE_INSERTS: u32 = 0x00000200;
+    const FORMAT_MESSAGE_FROM_SYSTEM: u32 = 0x00001000;
+
+    const PAGE_READONLY: u32 = 0x02;
+    const PAGE_EXECUTE_READ: u32 = 0x20;
+    const PAGE_EXECUTE_READWRITE: u32 = 0x40;
+
+    /// Maximum path length supported by Win32 APIs, unless the path is in the extended-length form.
+    pub const MAX_PATH: usize = 260;
+
+    /// Returns a handle of the main executable, which looks up symbols in its exports.
+    pub fn main_program() -> Result<DylibHandle, Error> {
+        let mut handle = DylibHandle(0);
+        match unsafe { GetModuleHandleExW(0, std::ptr::null(), &mut handle) } {
+            0 => Err(last_error().into()),
+            _ => Ok(handle),
+        }
+    }
+
+    /// Loads a module with the specified `LoadLibraryExW` flags.  Fails with a [`DlError`].
     pub fn load_library_ex(path: &Path, flags: u32) -> Result<DylibHandle, Error> {
-        let mut path_buf = path
-            .as_os_str()
-            .encode_wide()
-            .map(|u| if u == '/' as u16 { '\\' as u16 } else { u }) // Normalize slashes
-            .collect::<Vec<_>>();
-        path_buf.push(0);
+        let path_buf = wide_path(path);
         unsafe {
             let handle = LoadLibraryExW(path_buf.as_ptr(), DylibHandle(0), flags);
             if handle.0 == 0 {
-                Err(format!("Could not load {:?} (err=0x{:08X})", path, GetLastError()).into())
+                Err(DlError {
+                    path: Some(path.to_path_buf()),
+                    ..last_error()
+                }
+                .into())
             } else {
                 Ok(handle)
             }
         }
     }
 
+    /// Converts a path into a NUL-terminated wide string for the `W` variants of Win32 APIs.
+    ///
+    /// Slashes are normalized to backslashes, and paths that are UNC paths or too long for the legacy APIs are
+    /// converted into the extended-length form (`\\?\`).
+    pub fn wide_path(path: &Path) -> Vec<u16> {
+        let path_buf = path
+            .as_os_str()
+            .encode_wide()
+            .map(|u| if u == '/' as u16 { '\\' as u16 } else { u }) // Normalize slashes
+            .collect::<Vec<_>>();
+        let mut path_buf = to_extended_length(path_buf);
+        path_buf.push(0);
+        path_buf
+    }
+
+    /// Converts a wide string returned by Win32 APIs into a path, up to the terminating NUL, if any.
+    pub fn path_from_wide(wide: &[u16]) -> PathBuf {
+        let len = wide.iter().position(|u| *u == 0).unwrap_or(wide.len());
+        PathBuf::from(OsString::from_wide(&wide[..len]))
+    }
+
+    // Captures the last error of this thread, which must be done right after the failed call.
+    fn last_error() -> DlError {
+        let code = unsafe { GetLastError() };
+        DlError {
+            message: describe_error(code),
+            symbol: None,
+            path: None,
+            os_error: Some(code),
+        }
+    }
+
+    /// Returns the system's description of a Win32 error code, in the user's language if available, or in English.
+    pub fn error_message(code: u32) -> Option<String> {
+        // Language neutral (which tries the thread's, user's and system's languages in turn), then US English
+        for language in [0, 0x0409] {
+            let mut buffer = [0u16; 512];
+            let flags = FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS;
+            let len = unsafe {
+                FormatMessageW(
+                    flags,
+                    std::ptr::null(),
+                    code,
+                    language,
+                    buffer.as_mut_ptr(),
+                    buffer.len() as u32,
+                    std::ptr::null_mut(),
+                )
+            };
+            if len != 0 {
+                let message = String::from_utf16_lossy(&buffer[..len as usize]);
+                return Some(message.trim_end().trim_end_matches('.').to_string());
+            }
+        }
+        None
+    }
+
+    // Formats an error code along with its description, e.g. "The specified module could not be found (0x0000007E)".
+    // Public for the tests of the examples.
+    #[doc(hidden)]
+    pub fn describe_error(code: u32) -> String {
+        match error_message(code) {
+            Some(message) => format!("{message} (0x{code:08X})"),
+            None => format!("error 0x{code:08X}"),
+        }
+    }
+
+    /// Loads a module, searching for its dependencies in its own directory first.
     pub fn load_library(path: &Path) -> Result<DylibHandle, Error> {
         load_library_ex(path, LOAD_WITH_ALTERED_SEARCH_PATH)
     }
 
+    // Converts a backslash-separated path into the extended-length form (`\\?\`), if it is a UNC path or is
+    // too long for the legacy APIs.  Relative paths and paths containing `.` or `..` components are returned as is,
+    // because the extended-length form disables their normalization.  Public for the tests of the examples.
+    #[doc(hidden)]
+    pub fn to_extended_length(path: Vec<u16>) -> Vec<u16> {
+        let sep = '\\' as u16;
+        let is_unc = path.starts_with(&[sep, sep]);
+        if !is_unc && path.len() < MAX_PATH {
+            return path;
+        }
+        let has_dots = path.split(|u| *u == sep).any(|c| c == ['.' as u16] || c == ['.' as u16, '.' as u16]);
+        if has_dots {
+            return path;
+        }
+        if is_unc {
+            // Leave \\?\ and \\.\ paths alone, convert \\server\share to \\?\UNC\server\share.
+            if path.len() >= 4 && path[3] == sep && (path[2] == '?' as u16 || path[2] == '.' as u16) {
+                return path;
+            }
+            let mut result = wide(r"\\?\UNC");
+            result.extend_from_slice(&path[1..]);
+            result
+        } else if path.len() >= 3 && path[1] == ':' as u16 && path[2] == sep {
+            let mut result = wide(r"\\?\");
+            result.extend_from_slice(&path);
+            result
+        } else {
+            path
+        }
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    /// Finds a symbol in a module.
+    ///
+    /// On failure, returns a [`DlError`] naming the symbol and the module.
     pub fn find_symbol(handle: DylibHandle, name: &CStr) -> Result<Address, Error> {
         unsafe {
             let ptr = GetProcAddress(handle.0 as *const c_void, name.as_ptr());
             if ptr == 0 {
-                Err(format!("Could not find {:?} (err=0x{:08X})", name, GetLastError()).into())
+                let err = last_error();
+                Err(DlError {
+                    symbol: Some(name.to_string_lossy().into_owned()),
+                    path: path_of(handle).ok(),
+                    ..err
+                }
+                .into())
             } else {
                 Ok(ptr)
             }
         }
     }
+
+    /// Releases a handle of a module, unloading it once no handles remain.
+    pub fn close_library(handle: DylibHandle) -> Result<(), Error> {
+        unsafe {
+            if FreeLibrary(handle.0 as *const c_void) == 0 {
+                Err(last_error().into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the file path a module has been loaded from.
+    pub fn path_of(handle: DylibHandle) -> Result<PathBuf, Error> {
+        match module_file_name(handle) {
+            Some(path) => Ok(path_from_wide(&path)),
+            None => Err(last_error().into()),
+        }
+    }
+
+    /// Returns the modules loaded in the current process, starting with the main executable.
+    pub fn loaded_modules() -> Vec<LoadedModule> {
+        let mut handles = Vec::<DylibHandle>::new();
+        unsafe {
+            loop {
+                let size = (handles.capacity() * std::mem::size_of::<DylibHandle>()) as u32;
+                let mut needed = 0;
+                if K32EnumProcessModules(GetCurrentProcess(), handles.as_mut_ptr(), size, &mut needed) == 0 {
+                    return Vec::new();
+                }
+                let count = needed as usize / std::mem::size_of::<DylibHandle>();
+                if needed <= size {
+                    handles.set_len(count);
+                    break;
+                }
+                handles.reserve(count);
+            }
+        }
+        let modules = handles.into_iter().filter_map(|handle| {
+            let path = path_from_wide(&module_file_name(handle)?);
+            Some(LoadedModule { path, handle })
+        });
+        modules.collect()
+    }
+
+    // Returns the NUL-terminated file name of a module.
+    fn module_file_name(handle: DylibHandle) -> Option<Vec<u16>> {
+        let mut path = vec![0; 32768];
+        let len = unsafe { GetModuleFileNameW(handle, path.as_mut_ptr(), path.len() as u32) as usize };
+        if len == 0 || len >= path.len() {
+            return None;
+        }
+        path.truncate(len);
+        path.push(0);
+        Some(path)
+    }
+
+    /// Returns the symbols exported by name from a loaded module, as listed in its export directory.
+    ///
+    /// Addresses of forwarded exports are those of their targets.
+    pub fn exports_of(handle: DylibHandle) -> Result<Vec<Export>, Error> {
+        const IMAGE_NT_SIGNATURE: u32 = 0x00004550;
+        const IMAGE_NT_OPTIONAL_HDR32_MAGIC: u16 = 0x10b;
+        const IMAGE_NT_OPTIONAL_HDR64_MAGIC: u16 = 0x20b;
+
+        // Flags of `LoadLibraryEx` may be set in the low bits of the handle.
+        let base = handle.0 & !3;
+        unsafe {
+            let nt_headers = base + read::<u32>(base + 0x3c) as usize;
+            if read::<u32>(nt_headers) != IMAGE_NT_SIGNATURE {
+                return Err(ErrorCode::InvalidModule.error("Module has an invalid PE header."));
+            }
+            let optional_header = nt_headers + 24;
+            let (num_directories, directories) = match read::<u16>(optional_header) {
+                IMAGE_NT_OPTIONAL_HDR32_MAGIC => (read::<u32>(optional_header + 92), optional_header + 96),
+                IMAGE_NT_OPTIONAL_HDR64_MAGIC => (read::<u32>(optional_header + 108), optional_header + 112),
+                _ => return Err(ErrorCode::InvalidModule.error("Module has an unknown optional header.")),
+            };
+            if num_directories == 0 {
+                return Ok(Vec::new());
+            }
+            let (export_rva, export_size) = read::<(u32, u32)>(directories);
+            if export_rva == 0 {
+                return Ok(Vec::new());
+            }
+            let export_dir = base + export_rva as usize;
+            let num_names = read::<u32>(export_dir + 24) as usize;
+            let functions = base + read::<u32>(export_dir + 28) as usize;
+            let names = base + read::<u32>(export_dir + 32) as usize;
+            let ordinals = base + read::<u32>(export_dir + 36) as usize;
+
+            let mut exports = Vec::with_capacity(num_names);
+            for i in 0..num_names {
+                let name = CStr::from_ptr((base + read::<u32>(names + i * 4) as usize) as *const c_char);
+                let ordinal = read::<u16>(ordinals + i * 2) as usize;
+                let rva = read::<u32>(functions + ordinal * 4);
+                let address = if (export_rva..export_rva + export_size).contains(&rva) {
+                    // Forwarded to another module, which the loader resolves for us.
+                    match find_symbol(handle, name) {
+                        Ok(address) => address,
+                        Err(_) => continue,
+                    }
+                } else {
+                    base + rva as usize
+                };
+                exports.push(Export {
+                    name: name.to_owned(),
+                    address,
+                });
+            }
+            Ok(exports)
+        }
+    }
+
+    /// Returns the file version of a module, as recorded in its `VERSIONINFO` resource.
+    pub fn module_version(handle: DylibHandle) -> Option<String> {
+        unsafe {
+            let path = module_file_name(handle)?;
+
+            let mut ignored = 0;
+            let size = GetFileVersionInfoSizeW(path.as_ptr(), &mut ignored);
+            if size == 0 {
+                return None;
+            }
+            let mut data = vec![0u8; size as usize];
+            if GetFileVersionInfoW(path.as_ptr(), 0, size, data.as_mut_ptr() as *mut c_void) == 0 {
+                return None;
+            }
+
+            let mut root = wide("\\");
+            root.push(0);
+            let mut info = std::ptr::null_mut();
+            let mut info_len = 0;
+            if VerQueryValueW(data.as_ptr() as *const c_void, root.as_ptr(), &mut info, &mut info_len) == 0
+                || (info_len as usize) < std::mem::size_of::<VsFixedFileInfo>()
+            {
+                return None;
+            }
+            let info = &*(info as *const VsFixedFileInfo);
+            Some(format!(
+                "{}.{}.{}.{}",
+                info.file_version_ms >> 16,
+                info.file_version_ms & 0xffff,
+                info.file_version_ls >> 16,
+                info.file_version_ls & 0xffff
+            ))
+        }
+    }
+
+    /// Returns true if the address belongs to a currently loaded module.
+    pub fn is_address_mapped(address: Address) -> bool {
+        unsafe {
+            let mut module = DylibHandle(0);
+            let flags = GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT | GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS;
+            GetModuleHandleExW(flags, address as *const c_ushort, &mut module) != 0
+        }
+    }
+
+    /// Prevents a module from being unloaded, even if `FreeLibrary` is called on it.
+    pub fn pin_library(handle: DylibHandle) -> Result<(), Error> {
+        unsafe {
+            let mut module = DylibHandle(0);
+            let flags = GET_MODULE_HANDLE_EX_FLAG_PIN | GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS;
+            if GetModuleHandleExW(flags, handle.0 as *const c_ushort, &mut module) == 0 {
+                Err(ErrorCode::SystemError.error(format!("Could not pin module: {}", describe_error(GetLastError()))))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the size of a memory page.
+    pub fn page_size() -> usize {
+        unsafe {
+            let mut info = std::mem::zeroed::<SystemInfo>();
+            GetSystemInfo(&mut info);
+            info.page_size as usize
+        }
+    }
+
+    /// Makes a page-aligned memory region read-only.
+    pub fn protect_read_only(address: Address, len: usize) -> Result<(), Error> {
+        unsafe {
+            let mut old_protect = 0;
+            if VirtualProtect(address as *mut c_void, len, PAGE_READONLY, &mut old_protect) == 0 {
+                let message = format!("Could not protect memory: {}", describe_error(GetLastError()));
+                Err(ErrorCode::SystemError.error(message))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    /// Makes a page-aligned region of code writable, or restores it to read-only.
+    ///
+    /// The code remains executable throughout, so that other threads may keep running it.
+    pub fn set_code_writable(address: Address, len: usize, writable: bool) -> Result<(), Error> {
+        let protect = if writable { PAGE_EXECUTE_READWRITE } else { PAGE_EXECUTE_READ };
+        unsafe {
+            let mut old_protect = 0;
+            if VirtualProtect(address as *mut c_void, len, protect, &mut old_protect) == 0 {
+                let message = format!("Could not protect memory: {}", describe_error(GetLastError()));
+                Err(ErrorCode::SystemError.error(message))
+            } else {
+                Ok(())
+            }
+        }
+    }
 }
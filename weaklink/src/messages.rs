@@ -0,0 +1,66 @@
+//! Customization of the error messages shown to end users.
+
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+
+/// Returns the message to display for an error, or `None` to display its default message.
+/// See [`set_message_formatter`].
+pub type MessageFormatter = fn(&(dyn Error + 'static)) -> Option<String>;
+
+static MESSAGE_FORMATTER: Mutex<Option<MessageFormatter>> = Mutex::new(None);
+
+thread_local! {
+    // Whether the formatter is running on this thread, in which case errors display their default messages.
+    static FORMATTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Sets the function that formats the messages of errors originating in weaklink, or removes it if `None`.
+///
+/// This allows the host to translate or brand the messages, which surface to end users via `Display`, e.g. in panics
+/// or through the C API.  The formatter may tell errors apart via [`ErrorExt::code`](crate::ErrorExt::code), and
+/// obtain their details by downcasting them to [`CodedError`](crate::CodedError),
+/// [`DlError`](crate::loading::DlError) or [`VersionTooOld`](crate::VersionTooOld), whose fields keep the default
+/// message and the details for logging.  Within the formatter, errors display their default messages.
+///
+/// ```
+/// use weaklink::{ErrorCode, ErrorExt};
+/// weaklink::set_message_formatter(Some(|err| match err.code() {
+///     ErrorCode::LibraryNotFound => Some(format!("Bibliothèque introuvable ({err})")),
+///     _ => None,
+/// }));
+/// # weaklink::set_message_formatter(None);
+/// ```
+pub fn set_message_formatter(formatter: Option<MessageFormatter>) {
+    *MESSAGE_FORMATTER.lock().unwrap_or_else(PoisonError::into_inner) = formatter;
+}
+
+// Writes the message of an error as customized by the formatter, or else its default message.
+pub(crate) fn write_message(
+    err: &(dyn Error + 'static),
+    f: &mut fmt::Formatter<'_>,
+    default: impl FnOnce(&mut fmt::Formatter<'_>) -> fmt::Result,
+) -> fmt::Result {
+    let formatter = *MESSAGE_FORMATTER.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(formatter) = formatter.filter(|_| !FORMATTING.with(Cell::get)) {
+        let message = {
+            FORMATTING.with(|formatting| formatting.set(true));
+            let _guard = FormattingGuard;
+            formatter(err)
+        };
+        if let Some(message) = message {
+            return f.write_str(&message);
+        }
+    }
+    default(f)
+}
+
+// Resets `FORMATTING`, even if the formatter panics.
+struct FormattingGuard;
+
+impl Drop for FormattingGuard {
+    fn drop(&mut self) {
+        FORMATTING.with(|formatting| formatting.set(false));
+    }
+}
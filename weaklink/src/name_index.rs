@@ -0,0 +1,54 @@
+//! Perfect hash of symbol names, generated at build time.
+//!
+//! Uses the "hash and displace" scheme: the first-level hash selects a bucket, and the bucket's displacement
+//! seeds the second-level hash, which selects a slot.  Displacements are chosen by `weaklink_build` such that
+//! every symbol name lands in a distinct slot, so a lookup is two hash computations and one name comparison.
+
+use std::ffi::CStr;
+
+/// Maps symbol names to symbol indices.
+#[doc(hidden)]
+pub struct NameIndex {
+    // Second-level hash seed for each bucket.
+    displacements: &'static [u32],
+    // Symbol index for each slot.
+    slots: &'static [u32],
+}
+
+impl NameIndex {
+    pub const fn new(displacements: &'static [u32], slots: &'static [u32]) -> NameIndex {
+        NameIndex { displacements, slots }
+    }
+
+    pub(crate) fn lookup(&self, symbol_names: &[&CStr], name: &[u8]) -> Option<u32> {
+        let sym_index = self.candidate(name)?;
+        match symbol_names[sym_index as usize].to_bytes() == name {
+            true => Some(sym_index),
+            false => None,
+        }
+    }
+
+    // Returns the only index the name may have, if it's a symbol name at all.
+    pub(crate) fn candidate(&self, name: &[u8]) -> Option<u32> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let bucket = hash(name, 0) % self.displacements.len() as u64;
+        let slot = hash(name, self.displacements[bucket as usize]) % self.slots.len() as u64;
+        Some(self.slots[slot as usize])
+    }
+}
+
+/// Seeded FNV-1a, followed by a finalizer to spread the seed's influence over all bits.
+#[doc(hidden)]
+pub fn hash(bytes: &[u8], seed: u32) -> u64 {
+    let mut h = 0xcbf29ce484222325 ^ seed as u64;
+    for &b in bytes {
+        h ^= b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h
+}
@@ -0,0 +1,111 @@
+//! Symbol names stored encrypted, and decrypted transiently when symbols are looked up.
+//!
+//! Each name is XORed with a keystream derived from a key and the symbol's index.  The key isn't part of the
+//! generated code: the host supplies it at runtime through a [`KeyProvider`].  This doesn't withstand a determined
+//! attacker, who may observe the lookups, but keeps the names out of reach of static analysis of the binary.
+
+use crate::{Error, ErrorCode, Library};
+use std::ffi::CStr;
+use std::iter;
+use std::ptr;
+use std::sync::atomic::{self, Ordering};
+use std::sync::PoisonError;
+
+/// Supplies the key that the symbol names of a library are obfuscated with.  See [`Library::set_key_provider`].
+pub type KeyProvider = fn() -> u64;
+
+/// Obfuscated symbol names.
+#[doc(hidden)]
+pub struct ObfuscatedNames {
+    // Start of each name in `blob`, followed by the end of the last one.
+    offsets: &'static [u32],
+    blob: &'static [u8],
+}
+
+impl ObfuscatedNames {
+    pub const fn new(offsets: &'static [u32], blob: &'static [u8]) -> ObfuscatedNames {
+        ObfuscatedNames { offsets, blob }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    // Decrypts the name of a symbol into a buffer, which is wiped after `f` returns.
+    fn with_name<T>(&self, sym_index: u32, key: u64, f: impl FnOnce(Option<&CStr>) -> T) -> T {
+        let range = self.offsets[sym_index as usize] as usize..self.offsets[sym_index as usize + 1] as usize;
+        let mut name = self.blob[range]
+            .iter()
+            .zip(keystream(key, sym_index))
+            .map(|(b, k)| b ^ k)
+            .collect::<Vec<_>>();
+        name.push(0);
+        // A wrong key may yield a NUL in the middle.
+        let result = f(CStr::from_bytes_with_nul(&name).ok());
+        for b in name.iter_mut() {
+            unsafe { ptr::write_volatile(b, 0) };
+        }
+        atomic::compiler_fence(Ordering::SeqCst);
+        result
+    }
+}
+
+/// Obfuscates names for [`ObfuscatedNames`], returning the offsets and the blob.
+#[doc(hidden)]
+pub fn obfuscate(names: &[&str], key: u64) -> (Vec<u32>, Vec<u8>) {
+    let mut offsets = vec![0];
+    let mut blob = Vec::new();
+    for (sym_index, name) in names.iter().enumerate() {
+        blob.extend(name.bytes().zip(keystream(key, sym_index as u32)).map(|(b, k)| b ^ k));
+        offsets.push(blob.len() as u32);
+    }
+    (offsets, blob)
+}
+
+// SplitMix64, seeded with the key and the symbol index.
+fn keystream(key: u64, sym_index: u32) -> impl Iterator<Item = u8> {
+    let mut state = key ^ (sym_index as u64).wrapping_mul(0x9e3779b97f4a7c15);
+    let words = iter::repeat_with(move || {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    });
+    words.flat_map(u64::to_le_bytes)
+}
+
+impl Library {
+    /// Sets the function that supplies the key that the symbol names are obfuscated with (see
+    /// `Config::obfuscation_key` in `weaklink_build`).
+    ///
+    /// The key is requested whenever a symbol is looked up, so it needn't be kept in memory in between.  Symbols of a
+    /// library with obfuscated names can't be resolved until a key provider is set, e.g. by the configuration hook
+    /// (see [`Options::key_provider`](crate::Options::key_provider)).
+    pub fn set_key_provider(&self, provider: KeyProvider) {
+        self.configure();
+        *self.key_provider.lock().unwrap_or_else(PoisonError::into_inner) = Some(provider);
+    }
+
+    // Calls `f` with the name of a symbol.  Obfuscated names are decrypted for the duration of the call, and errors
+    // are replaced, since they may quote the name.
+    pub(crate) fn with_symbol_name<T>(
+        &self,
+        sym_index: u32,
+        f: impl FnOnce(&CStr) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        if self.obfuscated_names.is_empty() {
+            return f(self.symbol_names()[sym_index as usize]);
+        }
+        let provider = *self.key_provider.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(provider) = provider else {
+            return Err(ErrorCode::NotConfigured.error(format!(
+                "{}: no key provider is set for the obfuscated symbol names",
+                self.name
+            )));
+        };
+        let result = self.obfuscated_names.with_name(sym_index, provider(), |name| f(name?).ok());
+        result
+            .ok_or_else(|| ErrorCode::SymbolNotFound.error(format!("{}: symbol {sym_index} was not found", self.name)))
+    }
+}
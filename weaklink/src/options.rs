@@ -0,0 +1,130 @@
+//! Runtime policies, established by a configuration hook on first use of a library.
+
+use crate::vtables::Factory;
+use crate::{
+    AddressFilter, Broker, DeprecationHandler, Error, ErrorCode, FactoryCallback, KeyProvider, Library, ManifestCheck,
+    PostLoadCheck, ResolutionHook, SignatureMismatchHandler,
+};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::PoisonError;
+
+/// Runtime policies of a library, which a configuration hook may adjust.  See [`Library::configure_once`].
+///
+/// The fields hold the library's current settings when the hook is called.
+#[non_exhaustive]
+pub struct Options {
+    /// Paths that [`Library::load`] tries, in order, before the names configured at build time.
+    pub dylib_paths: Vec<PathBuf>,
+    /// See [`Library::set_vars`].
+    pub vars: Vec<(String, String)>,
+    /// See [`Library::set_auto_load`].
+    pub auto_load: bool,
+    /// See [`Library::set_validity_checks`].
+    pub validity_checks: bool,
+    /// See [`Library::set_eager_binding`].
+    pub eager_binding: bool,
+    /// See [`Library::set_plugin_version`].
+    pub plugin_version: Option<String>,
+    /// See [`Library::set_deprecation_handler`].
+    pub deprecation_handler: Option<DeprecationHandler>,
+    /// See [`Library::set_signature_mismatch_handler`].
+    pub signature_mismatch_handler: Option<SignatureMismatchHandler>,
+    /// See [`Library::set_manifest_check`].
+    pub manifest_check: Option<ManifestCheck>,
+    /// See [`Library::set_post_load_check`].
+    pub post_load_check: Option<PostLoadCheck>,
+    /// See [`Library::set_address_filter`].
+    pub address_filter: Option<AddressFilter>,
+    /// See [`Library::set_resolution_hook`].
+    pub resolution_hook: Option<ResolutionHook>,
+    /// See [`Library::set_broker`].
+    pub broker: Option<&'static dyn Broker>,
+    /// See [`Library::set_key_provider`].
+    pub key_provider: Option<KeyProvider>,
+    /// See [`Library::set_factory`].
+    pub factories: Vec<(&'static str, FactoryCallback)>,
+}
+
+/// Establishes the runtime policies of a library.  See [`Library::configure_once`].
+pub type ConfigureHook = fn(&mut Options);
+
+impl Library {
+    /// Sets the hook that establishes the library's runtime policies, replacing the one configured at build time via
+    /// `Config::configure_hook` in `weaklink_build`, if any.
+    ///
+    /// The hook is called once, on first use of the library: before it is loaded, before any of its symbols is
+    /// resolved, and before any of its policies is set by other methods.  Policies are thus established
+    /// deterministically, even if a stub is called during static initialization, before the host's `main` had a
+    /// chance to set them up.  Settings made afterwards via the individual methods take precedence.
+    ///
+    /// The hook must not call into the library.  Fails if the library has already been used.
+    pub fn configure_once(&self, hook: ConfigureHook) -> Result<(), Error> {
+        let mut configure_hook = self.configure_hook.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.configured.is_completed() {
+            return Err(ErrorCode::AlreadyConfigured.error(format!("{} has already been configured", self.name)));
+        }
+        *configure_hook = Some(hook);
+        Ok(())
+    }
+
+    // Calls the configuration hook, if this is the first use of the library.
+    pub(crate) fn configure(&self) {
+        self.configured.call_once(|| {
+            let hook = *self.configure_hook.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(hook) = hook {
+                let mut options = self.options();
+                hook(&mut options);
+                self.apply_options(options);
+            }
+        });
+    }
+
+    // Returns the current settings.
+    fn options(&self) -> Options {
+        Options {
+            dylib_paths: self.dylib_paths.lock().unwrap_or_else(PoisonError::into_inner).clone(),
+            vars: self.vars.lock().unwrap_or_else(PoisonError::into_inner).clone(),
+            auto_load: self.auto_load.load(Ordering::Acquire),
+            validity_checks: self.validity_checks.load(Ordering::Acquire),
+            eager_binding: self.eager_binding.load(Ordering::Acquire),
+            plugin_version: self.plugin_version.lock().unwrap_or_else(PoisonError::into_inner).clone(),
+            deprecation_handler: *self.deprecation_handler.lock().unwrap_or_else(PoisonError::into_inner),
+            signature_mismatch_handler: *self.signature_mismatch_handler.lock().unwrap_or_else(PoisonError::into_inner),
+            manifest_check: *self.manifest_check.lock().unwrap_or_else(PoisonError::into_inner),
+            post_load_check: *self.post_load_check.lock().unwrap_or_else(PoisonError::into_inner),
+            address_filter: *self.address_filter.lock().unwrap_or_else(PoisonError::into_inner),
+            resolution_hook: *self.resolution_hook.lock().unwrap_or_else(PoisonError::into_inner),
+            broker: self.broker(),
+            key_provider: *self.key_provider.lock().unwrap_or_else(PoisonError::into_inner),
+            factories: self
+                .factories
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .map(|factory| (factory.name(), factory.callback()))
+                .collect(),
+        }
+    }
+
+    // Stores the settings directly, since the public setters wait for the configuration to complete.
+    fn apply_options(&self, options: Options) {
+        *self.dylib_paths.lock().unwrap_or_else(PoisonError::into_inner) = options.dylib_paths;
+        *self.vars.lock().unwrap_or_else(PoisonError::into_inner) = options.vars;
+        self.auto_load.store(options.auto_load, Ordering::Release);
+        self.validity_checks.store(options.validity_checks, Ordering::Release);
+        self.eager_binding.store(options.eager_binding, Ordering::Release);
+        *self.plugin_version.lock().unwrap_or_else(PoisonError::into_inner) = options.plugin_version;
+        *self.deprecation_handler.lock().unwrap_or_else(PoisonError::into_inner) = options.deprecation_handler;
+        *self.signature_mismatch_handler.lock().unwrap_or_else(PoisonError::into_inner) =
+            options.signature_mismatch_handler;
+        *self.manifest_check.lock().unwrap_or_else(PoisonError::into_inner) = options.manifest_check;
+        *self.post_load_check.lock().unwrap_or_else(PoisonError::into_inner) = options.post_load_check;
+        *self.address_filter.lock().unwrap_or_else(PoisonError::into_inner) = options.address_filter;
+        *self.resolution_hook.lock().unwrap_or_else(PoisonError::into_inner) = options.resolution_hook;
+        *self.broker.lock().unwrap_or_else(PoisonError::into_inner) = options.broker;
+        *self.key_provider.lock().unwrap_or_else(PoisonError::into_inner) = options.key_provider;
+        let factories = options.factories.into_iter().map(|(name, callback)| Factory::new(name, callback));
+        *self.factories.lock().unwrap_or_else(PoisonError::into_inner) = factories.collect();
+    }
+}
@@ -0,0 +1,49 @@
+//! Resolution without panics, for libraries used from threads that Rust didn't start, e.g. in callbacks from a C host,
+//! where a panic can't unwind and aborts the process.
+
+use crate::{ErrorCode, Library};
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::PoisonError;
+
+/// Called instead of panicking, with the NUL-terminated name of the library, the [`ErrorCode`] of the failure, and
+/// the name of the symbol concerned, or null.  See [`Library::init_panic_free`].
+pub type FailureCallback = extern "C" fn(library: *const c_char, code: u32, symbol: *const c_char);
+
+impl Library {
+    /// Prepares the library for use from contexts that must not panic, and sets the callback that failures which
+    /// would otherwise panic are reported to.
+    ///
+    /// Allocates up front what resolution would otherwise allocate on first use: the state of
+    /// [checked mode](index.html#checked-mode) (see [`Library::init_checked`]), the decoded symbol names, and the
+    /// buffer kept for [`Library::recent_errors`].  Then, failing to load the library implicitly (see
+    /// [`Library::set_auto_load`]) is reported to `callback`, and fails resolution with the error, rather than
+    /// panicking.  Likewise, in checked mode, unasserted access to data is reported, and the data accessor returns
+    /// null.
+    ///
+    /// The errors returned by resolution are still allocated, and failing to allocate aborts the process regardless.
+    /// Must be called at startup, before any of the library's groups are resolved.  The callback must not call into
+    /// the library.
+    pub fn init_panic_free(&self, callback: FailureCallback) {
+        self.configure();
+        self.init_checked();
+        let _ = self.symbol_names();
+        self.reserve_recent_errors();
+        let name = CString::new(self.name).unwrap_or_default();
+        *self.failure_callback.lock().unwrap_or_else(PoisonError::into_inner) = Some((callback, name));
+    }
+
+    // Reports a failure to the callback set via `init_panic_free`, returning false if there is none, in which case the
+    // caller should panic.
+    pub(crate) fn report_failure(&self, code: ErrorCode, symbol: Option<&CStr>) -> bool {
+        let failure_callback = self.failure_callback.lock().unwrap_or_else(PoisonError::into_inner);
+        match &*failure_callback {
+            Some((callback, name)) => {
+                callback(name.as_ptr(), code as u32, symbol.map_or(ptr::null(), CStr::as_ptr));
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Patching of stubs into direct jumps after the first resolution.
+//!
+//! A patch replaces the first instruction of a stub with a direct jump to the resolved address.  The new instruction
+//! is written with a single aligned store, so that threads concurrently running the stub observe either the old
+//! instruction, which still jumps via the symbol table, or the new one.
+//!
+//! On x86_64, targets out of range of a direct jump are reached via an indirect jump through a literal that follows
+//! the stub code, which keeps the stub independent of the symbol table.
+
+use crate::{loading, Address, Error, Library};
+use std::sync::{Mutex, PoisonError};
+
+// Serializes patching, since concurrent patches may share code pages.
+static PATCH_LOCK: Mutex<()> = Mutex::new(());
+
+impl Library {
+    // Patch the stub of a symbol to jump directly to its resolved address, if the stub has been opted in.
+    // Failures are ignored, since the stub keeps working via the symbol table.
+    pub(crate) fn patch_stub(&self, sym_index: u32, target: Address) {
+        if cfg!(feature = "checked") {
+            // Checked mode relies on the stubs going through the symbol table.
+            return;
+        }
+        match self.patch_sites.get(sym_index as usize) {
+            Some(&stub) if stub != 0 => {
+                let _lock = PATCH_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+                let _ = unsafe { patch_jump(stub, target) };
+            }
+            _ => {}
+        }
+    }
+}
+
+// Make the code pages around `address` writable for the duration of `write`.
+unsafe fn write_code(address: Address, len: usize, write: impl FnOnce()) -> Result<(), Error> {
+    let page_size = loading::page_size();
+    let start = address & !(page_size - 1);
+    let end = (address + len + page_size - 1) & !(page_size - 1);
+    loading::set_code_writable(start, end - start, true)?;
+    write();
+    loading::set_code_writable(start, end - start, false)
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn patch_jump(stub: Address, target: Address) -> Result<(), Error> {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    // Offset of the literal in patchable stubs (see `weaklink_build`).
+    const LITERAL_OFFSET: usize = 16;
+
+    // The new instruction is stored together with the following bytes of the stub as a single 8-byte word.
+    let site = &*(stub as *const AtomicU64);
+    let literal = &*((stub + LITERAL_OFFSET) as *const AtomicU64);
+    let mut bytes = site.load(Ordering::Relaxed).to_le_bytes();
+    let offset = target.wrapping_sub(stub + 5) as isize;
+    let use_literal = match i32::try_from(offset) {
+        Ok(offset) => {
+            // jmp rel32
+            bytes[0] = 0xE9;
+            bytes[1..5].copy_from_slice(&offset.to_le_bytes());
+            false
+        }
+        Err(_) => {
+            // jmp [rip + literal]
+            bytes[0] = 0xFF;
+            bytes[1] = 0x25;
+            bytes[2..6].copy_from_slice(&(LITERAL_OFFSET as i32 - 6).to_le_bytes());
+            true
+        }
+    };
+    write_code(stub, LITERAL_OFFSET + 8, || {
+        if use_literal {
+            literal.store(target as u64, Ordering::Release);
+        }
+        site.store(u64::from_le_bytes(bytes), Ordering::Release);
+    })
+}
+
+#[cfg(target_arch = "aarch64")]
+unsafe fn patch_jump(stub: Address, target: Address) -> Result<(), Error> {
+    use std::arch::asm;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    // `b imm26`, which has a range of +/-128MB.
+    let offset = (target as isize).wrapping_sub(stub as isize);
+    if !(-(1 << 27)..(1 << 27)).contains(&offset) || offset % 4 != 0 {
+        return Err(crate::ErrorCode::Unsupported.error("Jump target is out of range."));
+    }
+    let insn = 0x1400_0000 | ((offset >> 2) as u32 & 0x03FF_FFFF);
+    let site = &*(stub as *const AtomicU32);
+    write_code(stub, 4, || {
+        site.store(insn, Ordering::Release);
+        asm!("dc cvau, {0}", "dsb ish", "ic ivau, {0}", "dsb ish", "isb", in(reg) stub);
+    })
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+unsafe fn patch_jump(_stub: Address, _target: Address) -> Result<(), Error> {
+    Err(crate::ErrorCode::Unsupported.error("Stub patching is not supported on this architecture."))
+}
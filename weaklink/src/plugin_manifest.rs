@@ -0,0 +1,310 @@
+//! Capability manifests, which plugins embed to describe the API they provide.
+
+use crate::loading::{self, DylibHandle};
+use crate::{Error, ErrorCode, Library};
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::sync::PoisonError;
+
+/// Name of the export that holds the capability manifest of a plugin.
+pub const MANIFEST_EXPORT: &str = "weaklink_manifest";
+
+/// Section of ELF binaries that holds the capability manifest, so that it can be read without loading them.
+pub const ELF_MANIFEST_SECTION: &str = ".weaklink_manifest";
+
+/// Segment and section of Mach-O binaries that hold the capability manifest.
+pub const MACHO_MANIFEST_SECTION: (&str, &str) = ("__DATA", "__weaklink");
+
+/// Section of PE binaries that holds the capability manifest.
+pub const PE_MANIFEST_SECTION: &str = ".weaklnk";
+
+/// Decides whether a plugin may be loaded, given its capability manifest.  See [`Library::set_manifest_check`].
+pub type ManifestCheck = fn(&PluginManifest) -> Result<(), Error>;
+
+// First line of a manifest, which identifies the format.
+const MANIFEST_HEADER: &str = "weaklink-manifest 1";
+
+/// Capability manifest of a plugin: the version of its API, and the groups it provides.
+///
+/// Plugins embed the manifest as NUL-terminated text in their `weaklink_manifest` export, usually via
+/// `weaklink_export::export_manifest!`.  The text consists of a `weaklink-manifest 1` line, followed by a
+/// `version <version>` line and a `group <name>` line per group.  Lines with other keys are ignored, so that later
+/// versions of the format may add them.
+///
+/// The export is also placed in a dedicated section of the binary ([`ELF_MANIFEST_SECTION`],
+/// [`MACHO_MANIFEST_SECTION`] or [`PE_MANIFEST_SECTION`]), from which hosts read it before loading the plugin.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PluginManifest {
+    /// Version of the plugin's API, if stated.
+    pub version: Option<String>,
+    /// Names of the groups the plugin provides, in the order listed.
+    pub groups: Vec<String>,
+}
+
+impl PluginManifest {
+    /// Parses the text of a manifest.
+    ///
+    /// ```
+    /// let manifest = weaklink::PluginManifest::parse("weaklink-manifest 1\nversion 2.3\ngroup base\n").unwrap();
+    /// assert_eq!(manifest.version.as_deref(), Some("2.3"));
+    /// assert!(manifest.provides("base") && !manifest.provides("extras"));
+    /// ```
+    pub fn parse(text: &str) -> Result<PluginManifest, Error> {
+        let mut lines = text.lines();
+        if lines.next().map(str::trim) != Some(MANIFEST_HEADER) {
+            return Err(ErrorCode::InvalidManifest.error("Not a weaklink capability manifest"));
+        }
+        let mut manifest = PluginManifest::default();
+        for line in lines.map(str::trim).filter(|line| !line.is_empty()) {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "version" => manifest.version = Some(value.trim().to_string()),
+                "group" => manifest.groups.push(value.trim().to_string()),
+                _ => {}
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Reads the manifest exported by a loaded dynamic library, or returns `None` if it doesn't export one.
+    pub fn from_module(handle: DylibHandle) -> Result<Option<PluginManifest>, Error> {
+        let name = CString::new(MANIFEST_EXPORT).unwrap();
+        let Ok(address) = loading::find_symbol(handle, &name) else {
+            return Ok(None);
+        };
+        let text = unsafe { CStr::from_ptr(address as *const c_char) };
+        let text = text
+            .to_str()
+            .map_err(|_| ErrorCode::InvalidManifest.error("The manifest is not valid UTF-8"))?;
+        PluginManifest::parse(text).map(Some)
+    }
+
+    /// Reads the manifest embedded in a dynamic library file, or returns `None` if it has none.
+    ///
+    /// The file is only read, not loaded, so none of its code runs.
+    pub fn from_file(path: &Path) -> Result<Option<PluginManifest>, Error> {
+        let data = std::fs::read(path)?;
+        let Some(section) = manifest_section(&data) else {
+            return Ok(None);
+        };
+        let text = section.split(|b| *b == 0).next().unwrap_or_default();
+        let text = std::str::from_utf8(text)
+            .map_err(|_| ErrorCode::InvalidManifest.error("The manifest is not valid UTF-8"))?;
+        PluginManifest::parse(text).map(Some)
+    }
+
+    /// Returns true if the manifest lists the group.
+    pub fn provides(&self, group: &str) -> bool {
+        self.groups.iter().any(|name| name == group)
+    }
+}
+
+impl fmt::Display for PluginManifest {
+    /// Formats the text of the manifest, without the terminating NUL.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{MANIFEST_HEADER}")?;
+        if let Some(version) = &self.version {
+            writeln!(f, "version {version}")?;
+        }
+        for group in &self.groups {
+            writeln!(f, "group {group}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Library {
+    /// Sets the function that decides whether a dylib may be loaded, given the capability manifest embedded in its
+    /// file, e.g. to reject plugins that don't provide the groups the host requires.
+    ///
+    /// The manifest is read from the file before loading it, so that rejected plugins are never mapped, and their
+    /// initializers never run.  Loading them fails with [`ErrorCode::PluginRejected`].  Dylibs without a manifest,
+    /// and those loaded by name from the system search path, are not checked.
+    pub fn set_manifest_check(&self, check: ManifestCheck) {
+        self.configure();
+        *self.manifest_check.lock().unwrap_or_else(PoisonError::into_inner) = Some(check);
+    }
+
+    // Checks the manifest of a dylib file, if any, before it is loaded.
+    pub(crate) fn check_manifest(&self, path: &Path) -> Result<(), Error> {
+        let check = *self.manifest_check.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(check) = check else {
+            return Ok(());
+        };
+        // Files that can't be read are left for the loader to find, or fail on.
+        let manifest = match PluginManifest::from_file(path) {
+            Ok(Some(manifest)) => manifest,
+            Ok(None) => return Ok(()),
+            Err(err) if path.is_file() => return Err(err),
+            Err(_) => return Ok(()),
+        };
+        check(&manifest).map_err(|err| {
+            let message = format!("{}: {} was rejected: {err}", self.name, path.display());
+            ErrorCode::PluginRejected.error(message)
+        })
+    }
+}
+
+// Returns the contents of the manifest section of an ELF, Mach-O or PE binary, if any.
+fn manifest_section(data: &[u8]) -> Option<&[u8]> {
+    match data.get(..4)? {
+        [0x7f, b'E', b'L', b'F'] => elf_manifest_section(data),
+        [b'M', b'Z', ..] => pe_manifest_section(data),
+        _ => macho_manifest_section(data),
+    }
+}
+
+fn elf_manifest_section(data: &[u8]) -> Option<&[u8]> {
+    const SHT_NOBITS: u32 = 8;
+    let bytes = Bytes::new(data, *data.get(5)? == 2);
+    let is_64 = *data.get(4)? == 2;
+    let (offset, entry_size, count, names_index) = match is_64 {
+        true => (bytes.u64(0x28)?, bytes.u16(0x3a)?, bytes.u16(0x3c)?, bytes.u16(0x3e)?),
+        false => (
+            bytes.u32(0x20)? as u64,
+            bytes.u16(0x2e)?,
+            bytes.u16(0x30)?,
+            bytes.u16(0x32)?,
+        ),
+    };
+    // Name, type, file offset and size of a section.
+    let header = |index: u16| {
+        let at = usize::try_from(offset).ok()?.checked_add(index as usize * entry_size as usize)?;
+        match is_64 {
+            true => Some((
+                bytes.u32(at)?,
+                bytes.u32(at + 4)?,
+                bytes.u64(at + 0x18)?,
+                bytes.u64(at + 0x20)?,
+            )),
+            false => Some((
+                bytes.u32(at)?,
+                bytes.u32(at + 4)?,
+                bytes.u32(at + 0x10)? as u64,
+                bytes.u32(at + 0x14)? as u64,
+            )),
+        }
+    };
+    let (_, _, names_offset, _) = header(names_index)?;
+    for index in 0..count {
+        let (name, kind, offset, size) = header(index)?;
+        let name = bytes.c_str(usize::try_from(names_offset.checked_add(name as u64)?).ok()?)?;
+        if kind != SHT_NOBITS && name == ELF_MANIFEST_SECTION.as_bytes() {
+            return bytes.range(offset, size);
+        }
+    }
+    None
+}
+
+fn macho_manifest_section(data: &[u8]) -> Option<&[u8]> {
+    const LC_SEGMENT: u32 = 0x1;
+    const LC_SEGMENT_64: u32 = 0x19;
+    let bytes = Bytes::new(data, false);
+    let is_64 = match bytes.u32(0)? {
+        0xfeedface => false,
+        0xfeedfacf => true,
+        // A universal binary, whose slices are searched in turn.
+        0xbebafeca => {
+            let bytes = Bytes::new(data, true);
+            return (0..bytes.u32(4)? as usize).find_map(|index| {
+                let at = 8 + index * 20;
+                let slice = bytes.range(bytes.u32(at + 8)? as u64, bytes.u32(at + 12)? as u64)?;
+                macho_manifest_section(slice)
+            });
+        }
+        _ => return None,
+    };
+    let (segment, segment_size, section_size) = match is_64 {
+        true => (LC_SEGMENT_64, 72, 80),
+        false => (LC_SEGMENT, 56, 68),
+    };
+    let mut at = if is_64 { 32 } else { 28 };
+    for _ in 0..bytes.u32(16)? {
+        let (command, command_size) = (bytes.u32(at)?, bytes.u32(at + 4)? as usize);
+        if command == segment {
+            let count = bytes.u32(at + if is_64 { 64 } else { 48 })?;
+            for index in 0..count as usize {
+                let header = at + segment_size + index * section_size;
+                let names = (bytes.fixed_str(header + 16, 16)?, bytes.fixed_str(header, 16)?);
+                if names == (MACHO_MANIFEST_SECTION.0.as_bytes(), MACHO_MANIFEST_SECTION.1.as_bytes()) {
+                    let (size, offset) = match is_64 {
+                        true => (bytes.u64(header + 40)?, bytes.u32(header + 48)?),
+                        false => (bytes.u32(header + 36)? as u64, bytes.u32(header + 40)?),
+                    };
+                    return bytes.range(offset as u64, size);
+                }
+            }
+        }
+        at = at.checked_add(command_size)?;
+    }
+    None
+}
+
+fn pe_manifest_section(data: &[u8]) -> Option<&[u8]> {
+    let bytes = Bytes::new(data, false);
+    let header = bytes.u32(0x3c)? as usize;
+    if data.get(header..header.checked_add(4)?)? != b"PE\0\0" {
+        return None;
+    }
+    let count = bytes.u16(header + 6)?;
+    let mut at = header + 24 + bytes.u16(header + 20)? as usize;
+    for _ in 0..count {
+        if bytes.fixed_str(at, 8)? == PE_MANIFEST_SECTION.as_bytes() {
+            let size = bytes.u32(at + 8)?.min(bytes.u32(at + 16)?);
+            return bytes.range(bytes.u32(at + 20)? as u64, size as u64);
+        }
+        at += 40;
+    }
+    None
+}
+
+// Bounds-checked reads of the headers of a binary.
+struct Bytes<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Bytes<'a> {
+    fn new(data: &'a [u8], big_endian: bool) -> Bytes<'a> {
+        Bytes { data, big_endian }
+    }
+
+    fn array<const N: usize>(&self, at: usize) -> Option<[u8; N]> {
+        let mut bytes: [u8; N] = self.data.get(at..at.checked_add(N)?)?.try_into().ok()?;
+        if self.big_endian {
+            bytes.reverse();
+        }
+        Some(bytes)
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        self.array(at).map(u16::from_le_bytes)
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        self.array(at).map(u32::from_le_bytes)
+    }
+
+    fn u64(&self, at: usize) -> Option<u64> {
+        self.array(at).map(u64::from_le_bytes)
+    }
+
+    fn range(&self, offset: u64, size: u64) -> Option<&'a [u8]> {
+        let start = usize::try_from(offset).ok()?;
+        self.data.get(start..start.checked_add(usize::try_from(size).ok()?)?)
+    }
+
+    // A NUL-terminated string.
+    fn c_str(&self, at: usize) -> Option<&'a [u8]> {
+        let rest = self.data.get(at..)?;
+        rest.split(|b| *b == 0).next()
+    }
+
+    // A name in a fixed-size field, padded with NULs.
+    fn fixed_str(&self, at: usize, size: usize) -> Option<&'a [u8]> {
+        let field = self.data.get(at..at.checked_add(size)?)?;
+        field.split(|b| *b == 0).next()
+    }
+}
@@ -0,0 +1,50 @@
+//! Validation of dylibs right after loading them, e.g. to catch plugins whose initializers failed.
+
+use crate::{loading, DylibHandle, Error, ErrorCode, Library};
+use std::path::Path;
+use std::sync::PoisonError;
+
+/// Decides whether a newly loaded dylib may be used, given its handle.  See [`Library::set_post_load_check`].
+pub type PostLoadCheck = fn(DylibHandle) -> Result<(), Error>;
+
+impl Library {
+    /// Sets the function that validates a dylib once it is loaded, before its handle is published to the library.
+    ///
+    /// This catches plugins whose initializers ran but failed, e.g. a constructor that panicked, or a `DllMain`
+    /// whose failure the loader ignored, and which would otherwise be used in a broken state.  The check may look up
+    /// and call an export of the plugin that reports its status, via [`loading::find_symbol`].  If it fails, the
+    /// dylib is unloaded, and loading it fails with [`ErrorCode::PluginRejected`], so that [`Library::load`] goes on
+    /// to the next candidate.  Handles set via [`Library::set_handle`] are not checked.
+    ///
+    /// ```ignore
+    /// stub::library.set_post_load_check(|handle| {
+    ///     let status = loading::find_symbol(handle, &std::ffi::CString::new("plugin_status")?)?;
+    ///     let status: extern "C" fn() -> i32 = unsafe { std::mem::transmute(status) };
+    ///     match status() {
+    ///         0 => Ok(()),
+    ///         code => Err(format!("plugin failed to initialize: {code}").into()),
+    ///     }
+    /// });
+    /// ```
+    pub fn set_post_load_check(&self, check: PostLoadCheck) {
+        self.configure();
+        *self.post_load_check.lock().unwrap_or_else(PoisonError::into_inner) = Some(check);
+    }
+
+    // Checks a dylib that was just loaded from `path`, unloading it if it is rejected.
+    pub(crate) fn check_loaded(&self, path: &Path, handle: DylibHandle) -> Result<DylibHandle, Error> {
+        let check = *self.post_load_check.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(check) = check else {
+            return Ok(handle);
+        };
+        match check(handle) {
+            Ok(()) => Ok(handle),
+            Err(err) => {
+                // The dylib may be unusable anyway, so failing to unload it doesn't change the outcome.
+                let _ = loading::close_library(handle);
+                let message = format!("{}: {} failed its post-load check: {err}", self.name, path.display());
+                Err(ErrorCode::PluginRejected.error(message))
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Lookup of symbols by name at runtime, including symbols that have no stub.
+
+use crate::{Address, DylibHandle, Error, ErrorCode, Library};
+use std::ffi::CString;
+use std::sync::atomic::Ordering;
+
+impl Library {
+    /// Returns whether the library provides a symbol, given its name as exported by the dynamic library.
+    ///
+    /// The symbol is resolved as by [`Library::try_resolve_by_name`], so any failure to do so, such as the library
+    /// failing to load, is reported as the symbol being absent.
+    pub fn has_symbol(&self, name: &str) -> bool {
+        self.try_resolve_by_name(name).is_ok()
+    }
+
+    /// Resolves a symbol given its name as exported by the dynamic library, whether it has a stub or not.
+    ///
+    /// This lets hosts probe for optional entry points of plugins without defining a group for each of them at build
+    /// time.  Symbols that have a stub are resolved as by [`Library::resolve_by_index`], which sets their symbol
+    /// table entry.  Others are looked up in the dylib, via its bootstrap export if any, or through the broker, and
+    /// their addresses are not kept.  The library is loaded if needed, unless implicit loading is disabled, in
+    /// which case this fails with [`ErrorCode::NotLoaded`].  Unlike the resolution of groups, failing to load the
+    /// library never panics.
+    ///
+    /// ```ignore
+    /// if let Ok(address) = stub::library.try_resolve_by_name("plugin_shutdown") {
+    ///     let shutdown: extern "C" fn() = unsafe { std::mem::transmute(address) };
+    ///     shutdown();
+    /// }
+    /// ```
+    pub fn try_resolve_by_name(&self, name: &str) -> Result<Address, Error> {
+        self.configure();
+        if let Some(sym_index) = self.index_of(name) {
+            if self.broker().is_none() && !self.is_native(sym_index) {
+                self.load_implicitly()?;
+            }
+            return self.resolve_by_index(sym_index);
+        }
+        let sym_name = CString::new(name)?;
+        let _in_flight = self.begin_resolution()?;
+        let address = match self.broker() {
+            Some(broker) => self.resolve_brokered(broker, &sym_name)?,
+            None => self.find_export(self.load_implicitly()?, &sym_name)?,
+        };
+        Ok(self.filter_address(&sym_name, address))
+    }
+
+    // Returns the handle of the library, loading it unless implicit loading is disabled.
+    fn load_implicitly(&self) -> Result<DylibHandle, Error> {
+        match self.handle() {
+            Some(handle) => Ok(handle),
+            None if !self.auto_load.load(Ordering::Acquire) => {
+                Err(ErrorCode::NotLoaded.error("Library is not loaded, and implicit loading is disabled."))
+            }
+            // Another thread may have loaded it in the meantime.
+            None => self.load().or_else(|err| self.handle().ok_or(err)),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Recording and replaying of the resolution sequence.
+//!
+//! A recording is a plain text file with one event per line:
+//! ```text
+//! load /path/to/libplugin.so
+//! resolve plugin_init
+//! resolve plugin_process
+//! load_group extras /path/to/libplugin-extras.so
+//! ```
+//! Replaying it at startup performs the same library loads and symbol resolutions in the same order,
+//! so that they don't happen lazily later, e.g. on a latency-critical path.
+
+use crate::{Error, ErrorCode, Library};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::PoisonError;
+
+const LOAD_EVENT: &str = "load";
+const RESOLVE_EVENT: &str = "resolve";
+const GROUP_LOAD_EVENT: &str = "load_group";
+
+impl Library {
+    /// Starts recording library loads and symbol resolutions into the specified file.
+    ///
+    /// The file is truncated if it already exists.  Each event is written out as soon as it happens.
+    pub fn record_to(&self, path: &Path) -> Result<(), Error> {
+        let file = File::create(path)?;
+        *self.recorder.lock().unwrap_or_else(PoisonError::into_inner) = Some(file);
+        Ok(())
+    }
+
+    /// Stops recording started via [`Library::record_to`].
+    pub fn stop_recording(&self) {
+        *self.recorder.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// Replays a recording created via [`Library::record_to`].
+    ///
+    /// Library loads are skipped if the library is already loaded, and likewise, dylibs of groups bound via
+    /// [`Group::resolve_loading_from`](crate::Group::resolve_loading_from) are bound and resolved unless the group is
+    /// bound already.  Symbols and groups that are no longer present in this library's stub configuration are
+    /// ignored.
+    pub fn replay(&self, path: &Path) -> Result<(), Error> {
+        let text = fs::read_to_string(path)?;
+        for line in text.lines() {
+            match line.split_once(' ') {
+                Some((LOAD_EVENT, dylib_path)) => {
+                    if self.handle().is_none() {
+                        self.load_from(Path::new(dylib_path))?;
+                    }
+                }
+                Some((RESOLVE_EVENT, sym_name)) => {
+                    if let Some(sym_index) = self.index_of(sym_name) {
+                        self.resolve_symbol(sym_index)?;
+                    }
+                }
+                Some((GROUP_LOAD_EVENT, group_load)) if group_load.contains(' ') => {
+                    let (group_name, dylib_path) = group_load.split_once(' ').unwrap_or_default();
+                    let group = self.groups.iter().find(|group| group.name() == group_name);
+                    if let Some(group) = group.filter(|group| group.handle().is_none()) {
+                        group.resolve_loading_from(Path::new(dylib_path))?;
+                    }
+                }
+                _ => return Err(ErrorCode::InvalidManifest.error(format!("Invalid recording entry: {line:?}"))),
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn record_load(&self, path: &Path) {
+        self.record(LOAD_EVENT, &path.to_string_lossy());
+    }
+
+    pub(crate) fn record_group_load(&self, group_name: &str, path: &Path) {
+        self.record(GROUP_LOAD_EVENT, &format!("{group_name} {}", path.to_string_lossy()));
+    }
+
+    pub(crate) fn record_resolve(&self, sym_index: u32) {
+        self.record(RESOLVE_EVENT, &self.symbol_names()[sym_index as usize].to_string_lossy());
+    }
+
+    fn record(&self, event: &str, arg: &str) {
+        if let Some(file) = self.recorder.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+            // Recording is a diagnostic aid, so failing to write is not fatal.
+            let _ = writeln!(file, "{event} {arg}");
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Groups of symbols defined at runtime, e.g. from configuration files.
+
+use crate::{Error, ErrorCode, GroupSet, GroupSetResolved, Library};
+
+/// A group of symbols defined at runtime by name.  See [`Library::make_group`].
+///
+/// Like groups defined at build time, it resolves iff all of its symbols are present, and returns a token, for the
+/// lifetime of which the symbols are considered resolved in [checked mode](index.html#checked-mode).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuntimeGroup {
+    name: String,
+    set: GroupSet,
+    // Names that aren't in the symbol table, which keep the group from resolving.
+    unknown: Vec<String>,
+}
+
+impl RuntimeGroup {
+    /// Returns the name of the group.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the symbols of the group that are in the library's symbol table.
+    pub fn set(&self) -> &GroupSet {
+        &self.set
+    }
+
+    /// Returns the names of the group that are not in the library's symbol table, i.e. that have no stubs.
+    pub fn unknown_symbols(&self) -> &[String] {
+        &self.unknown
+    }
+
+    /// Resolves all symbols of the group, failing if any of them is missing, or has no stub.
+    pub fn resolve(&self) -> Result<GroupSetResolved<'_>, Error> {
+        if let Some(name) = self.unknown.first() {
+            return Err(ErrorCode::SymbolNotFound.error(format!(
+                "{}: symbol {name:?} of group {} has no stub",
+                self.set.library().name,
+                self.name
+            )));
+        }
+        self.set.resolve()
+    }
+
+    /// Returns true if all symbols of the group can be resolved.
+    pub fn is_available(&self) -> bool {
+        self.resolve().is_ok()
+    }
+}
+
+impl Library {
+    /// Creates a group of the symbols with the specified names, as exported by the dynamic library.
+    ///
+    /// This suits hosts whose optional features are data-driven, e.g. listed in a configuration file, rather than
+    /// fixed at build time.  The symbols are looked up via [`Library::index_of`], so they must have stubs; a group
+    /// with names that don't never resolves.
+    ///
+    /// ```ignore
+    /// let group = stub::library.make_group("video", &["foo_decode", "foo_scale"]);
+    /// if let Ok(_token) = group.resolve() {
+    ///     // The stubs of foo_decode and foo_scale may be called here.
+    /// }
+    /// ```
+    pub fn make_group(&'static self, name: &str, symbols: &[&str]) -> RuntimeGroup {
+        let mut sym_indices = Vec::new();
+        let mut unknown = Vec::new();
+        for symbol in symbols {
+            match self.index_of(symbol) {
+                Some(sym_index) => sym_indices.push(sym_index),
+                None => unknown.push(symbol.to_string()),
+            }
+        }
+        RuntimeGroup {
+            name: name.to_string(),
+            set: GroupSet::from_indices(self, sym_indices),
+            unknown,
+        }
+    }
+}
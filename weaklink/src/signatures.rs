@@ -0,0 +1,185 @@
+//! Detection of drift between the signatures that the host expects and those that the dylib implements.
+
+use crate::{Address, DylibHandle, Error, ErrorCode, Library};
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::sync::PoisonError;
+
+/// Name of the export through which plugins report the signature hashes of their groups.
+/// See [`export_signatures!`](crate::export_signatures).
+pub const SIGNATURE_TABLE_EXPORT: &str = "weaklink_sig_hashes";
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// An export of the dylib that returns the hash of the signature of a symbol, or 0 if it doesn't know the symbol.
+/// See `Config::signature_query` in `weaklink_build`.
+pub type SignatureQuery = unsafe extern "C" fn(name: *const c_char) -> u64;
+
+/// Handles a mismatch of signatures.  Parameters are: library name, symbol name, the hash of the expected signature
+/// and the hash reported by the dylib.
+pub type SignatureMismatchHandler = fn(&str, &CStr, u64, u64);
+
+/// An entry of the table returned by the `weaklink_sig_hashes` export of a plugin, which ends with an entry whose
+/// `group` is null.  See [`export_signatures!`](crate::export_signatures).
+#[repr(C)]
+pub struct GroupSignature {
+    /// NUL-terminated name of the group.
+    pub group: *const c_char,
+    /// Hash of the signatures of the group's symbols.  See [`group_signature_hash`].
+    pub hash: u64,
+}
+
+// Tables are immutable statics.
+unsafe impl Sync for GroupSignature {}
+
+/// The `weaklink_sig_hashes` export of a plugin, which returns its table of group signature hashes.
+pub type SignatureTableFn = unsafe extern "C" fn() -> *const GroupSignature;
+
+/// Hashes a signature, as expected from a [`SignatureQuery`].
+///
+/// The hash is the 64-bit FNV-1a hash of the signature with whitespace removed, so that plugins written in any
+/// language may easily compute it.
+///
+/// ```
+/// assert_eq!(weaklink::signature_hash("fn(u32) -> u32"), weaklink::signature_hash("fn(u32)->u32"));
+/// ```
+pub const fn signature_hash(signature: &str) -> u64 {
+    fnv_hash(FNV_OFFSET_BASIS, signature.as_bytes())
+}
+
+/// Hashes the signatures of the symbols of a group, given as pairs of import name and signature, in any order.
+///
+/// This is the sum of the FNV-1a hashes of `<name>:<signature>`, with whitespace removed.
+pub const fn group_signature_hash(signatures: &[(&str, &str)]) -> u64 {
+    let mut hash = 0u64;
+    let mut i = 0;
+    while i < signatures.len() {
+        let (name, signature) = signatures[i];
+        let symbol_hash = fnv_hash(fnv_hash(FNV_OFFSET_BASIS, name.as_bytes()), b":");
+        hash = hash.wrapping_add(fnv_hash(symbol_hash, signature.as_bytes()));
+        i += 1;
+    }
+    hash
+}
+
+// Continues an FNV-1a hash with the bytes, skipping whitespace.
+const fn fnv_hash(mut hash: u64, bytes: &[u8]) -> u64 {
+    let mut i = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_whitespace() {
+            hash = (hash ^ bytes[i] as u64).wrapping_mul(FNV_PRIME);
+        }
+        i += 1;
+    }
+    hash
+}
+
+/// Exports the signature hashes of groups from a plugin, as the `weaklink_sig_hashes` table that stubs generated with
+/// `Config::signature_handshake` in `weaklink_build` check groups against.
+///
+/// Signatures are listed per group and symbol, using the import names and signatures declared via
+/// `SymbolStub::signature`.  Groups that the plugin doesn't list, and symbols without a declared signature, are not
+/// checked.
+///
+/// ```
+/// weaklink::export_signatures! {
+///     "base" => { "foo_init" => "fn() -> i32", "foo_exit" => "fn()" },
+///     "extras" => { "foo_frobnicate" => "fn(u32) -> u32" },
+/// }
+/// ```
+#[macro_export]
+macro_rules! export_signatures {
+    ($($group:literal => { $($name:literal => $signature:literal),* $(,)? }),* $(,)?) => {
+        #[no_mangle]
+        pub extern "C" fn weaklink_sig_hashes() -> *const $crate::GroupSignature {
+            static TABLE: &[$crate::GroupSignature] = &[
+                $($crate::GroupSignature {
+                    group: concat!($group, "\0").as_ptr() as *const ::std::os::raw::c_char,
+                    hash: $crate::group_signature_hash(&[$(($name, $signature)),*]),
+                },)*
+                $crate::GroupSignature { group: ::std::ptr::null(), hash: 0 },
+            ];
+            TABLE.as_ptr()
+        }
+    };
+}
+
+impl Library {
+    /// Sets the function called when the dylib reports a signature for a symbol that differs from the one expected
+    /// by the host.  By default, a warning is printed to stderr.
+    ///
+    /// Signatures are only checked in debug builds, for symbols annotated via `SymbolStub::signature` in
+    /// `weaklink_build`, when they are first resolved.  The dylib reports them through the export configured by
+    /// `Config::signature_query`.
+    pub fn set_signature_mismatch_handler(&self, handler: SignatureMismatchHandler) {
+        self.configure();
+        *self.signature_mismatch_handler.lock().unwrap_or_else(PoisonError::into_inner) = Some(handler);
+    }
+
+    // Compares the signature hash of a group, if any, with the one reported by the dylib's table, if it has one.
+    pub(crate) fn check_group_signature(&self, group: &str, handle: DylibHandle) -> Result<(), Error> {
+        let Ok(position) = self.group_signatures.binary_search_by_key(&group, |(group, _)| *group) else {
+            return Ok(());
+        };
+        // Plugins that don't take part in the handshake aren't checked.
+        let export = CString::new(SIGNATURE_TABLE_EXPORT).unwrap();
+        let Ok(address) = self.find_export(handle, &export) else {
+            return Ok(());
+        };
+        let table_fn = unsafe { std::mem::transmute::<Address, SignatureTableFn>(address) };
+        let mut entry = unsafe { table_fn() };
+        while !entry.is_null() && !unsafe { (*entry).group }.is_null() {
+            let (name, found) = unsafe { (CStr::from_ptr((*entry).group), (*entry).hash) };
+            if name.to_bytes() == group.as_bytes() {
+                let expected = self.group_signatures[position].1;
+                if found != expected {
+                    return Err(ErrorCode::SignatureMismatch.error(format!(
+                        "{}: signatures of group {group} don't match the dylib's \
+                         (expected hash {expected:#x}, found {found:#x})",
+                        self.name
+                    )));
+                }
+                break;
+            }
+            entry = unsafe { entry.add(1) };
+        }
+        Ok(())
+    }
+
+    // Compares the expected signature of a symbol, if any, with the one reported by the dylib, in debug builds.
+    pub(crate) fn check_signature(&self, sym_index: u32, handle: DylibHandle) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        let (Some(query), Ok(position)) = (
+            self.signature_query,
+            self.signatures.binary_search_by_key(&sym_index, |(sym_index, _)| *sym_index),
+        ) else {
+            return;
+        };
+        // Signature checks are advisory, so dylibs that can't be queried are skipped.
+        let Ok(address) = self.find_export(handle, query) else {
+            return;
+        };
+        // The signature is promised by `Config::signature_query`.
+        let query_fn = unsafe { std::mem::transmute::<Address, SignatureQuery>(address) };
+        let found = self.with_symbol_name(sym_index, |name| Ok(unsafe { query_fn(name.as_ptr()) }));
+        let expected = self.signatures[position].1;
+        match found {
+            Ok(found) if found != 0 && found != expected => {
+                let sym_name = self.symbol_names()[sym_index as usize];
+                let handler = *self.signature_mismatch_handler.lock().unwrap_or_else(PoisonError::into_inner);
+                match handler {
+                    Some(handler) => handler(self.name, sym_name, expected, found),
+                    None => eprintln!(
+                        "weaklink: {}: the signature of {sym_name:?} doesn't match the dylib's \
+                         (expected hash {expected:#x}, found {found:#x})",
+                        self.name
+                    ),
+                }
+            }
+            _ => {}
+        }
+    }
+}
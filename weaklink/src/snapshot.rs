@@ -0,0 +1,104 @@
+//! Snapshots of the symbol table, for diagnosing rebinding of symbols.
+
+use crate::{Address, Library};
+use std::ffi::CStr;
+use std::fmt;
+use std::ptr;
+use std::sync::atomic::Ordering;
+
+/// The entries of a library's symbol table at some point in time.  See [`Library::snapshot`].
+#[derive(Clone, Debug)]
+pub struct TableSnapshot {
+    library: &'static str,
+    symbol_names: &'static [&'static CStr],
+    entries: Box<[Address]>,
+}
+
+/// An entry of the symbol table that differs between two snapshots.  See [`TableSnapshot::diff`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EntryChange {
+    pub symbol: &'static CStr,
+    /// Address in the earlier snapshot, or 0 if unresolved.
+    pub old: Address,
+    /// Address in the later snapshot, or 0 if unresolved.
+    pub new: Address,
+}
+
+impl Library {
+    /// Takes a snapshot of the symbol table, e.g. to find out which symbols are rebound by some operation.
+    ///
+    /// ```ignore
+    /// let before = stub::library.snapshot();
+    /// load_overrides();
+    /// for change in before.diff(&stub::library.snapshot()) {
+    ///     eprintln!("{change}");
+    /// }
+    /// ```
+    pub fn snapshot(&self) -> TableSnapshot {
+        TableSnapshot {
+            library: self.name,
+            symbol_names: self.symbol_names(),
+            entries: self.symbol_table.iter().map(|entry| entry.load(Ordering::Acquire)).collect(),
+        }
+    }
+}
+
+impl TableSnapshot {
+    /// Returns the address of a symbol in the snapshot, if it was resolved.
+    pub fn address(&self, name: &CStr) -> Option<Address> {
+        let sym_index = self.symbol_names.iter().position(|sym_name| *sym_name == name)?;
+        match self.entries[sym_index] {
+            0 => None,
+            address => Some(address),
+        }
+    }
+
+    /// Returns the entries that differ in the `later` snapshot, in symbol table order.
+    ///
+    /// Panics if the snapshots were taken of different libraries.
+    pub fn diff(&self, later: &TableSnapshot) -> Vec<EntryChange> {
+        assert!(
+            ptr::eq(self.symbol_names, later.symbol_names),
+            "Snapshots of {} and {} can't be compared",
+            self.library,
+            later.library
+        );
+        let entries = self.symbol_names.iter().zip(self.entries.iter().zip(later.entries.iter()));
+        entries
+            .filter(|(_, (old, new))| old != new)
+            .map(|(symbol, (old, new))| EntryChange {
+                symbol,
+                old: *old,
+                new: *new,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for TableSnapshot {
+    /// Lists the entries, one per line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (symbol, address) in self.symbol_names.iter().zip(self.entries.iter()) {
+            writeln!(f, "{}: {symbol:?} = {}", self.library, AddressFmt(*address))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for EntryChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {} -> {}", self.symbol, AddressFmt(self.old), AddressFmt(self.new))
+    }
+}
+
+// Formats an entry, which is 0 if unresolved.
+struct AddressFmt(Address);
+
+impl fmt::Display for AddressFmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            0 => write!(f, "unresolved"),
+            address => write!(f, "{address:#x}"),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Unloading of the library's dylib, so that it may be reloaded, or replaced by another version.
+
+use crate::{loading, Error, ErrorCode, Library};
+use std::sync::atomic::Ordering;
+use std::sync::PoisonError;
+
+impl Library {
+    /// Closes the dylib that the library is loaded from, and resets the symbol table and the resolution status of
+    /// all groups, so that the library may be loaded again later, e.g. from a newer version of the plugin.
+    ///
+    /// Resolutions and loads in progress are waited for, and those started in the meantime fail with
+    /// [`ErrorCode::NotLoaded`], so that no address in the unmapped dylib is stored in the symbol table.  Must not be
+    /// called from the library's callbacks, such as its resolution hook, which would wait for their own resolution.
+    /// Stubs of unresolved symbols crash rather than jump into the unmapped dylib, so no stub may be called until
+    /// its group is resolved again.  In [checked mode](index.html#checked-mode), this fails with
+    /// [`ErrorCode::InUse`] while any group is asserted, i.e. while tokens are alive, or if a group has been resolved
+    /// permanently.  Otherwise, the caller must ensure that no calls into the library are in progress, e.g. by
+    /// joining the threads using it, and that no tokens are used afterwards, which
+    /// [`GroupResolved::check`](crate::GroupResolved::check) detects.
+    ///
+    /// This fails with [`ErrorCode::Sealed`] if the symbol table has been sealed, and with
+    /// [`ErrorCode::Unsupported`] if any stub has been patched into a direct jump (see `Config::patched_groups` in
+    /// `weaklink_build`), since patches can't be undone.  Dylibs bound to groups via
+    /// [`Group::resolve_loading_from`](crate::Group::resolve_loading_from) stay loaded.  Handles set via
+    /// [`set_handle`](Library::set_handle) are closed as well, so the library must own a reference to them.
+    ///
+    /// ```ignore
+    /// stub::library.unload()?;
+    /// stub::library.load_from(Path::new("plugins/libplugin-2.so"))?;
+    /// let _token = stub::base.resolve()?;
+    /// ```
+    pub fn unload(&self) -> Result<(), Error> {
+        // Keeps instances from swapping their tables in, which would be restored over the reset table.
+        let _lock = self.instance_lock.lock().unwrap_or_else(PoisonError::into_inner);
+        let _quiesced = self.quiesce();
+        let Some(handle) = self.handle() else {
+            return Err(ErrorCode::NotLoaded.error("Library is not loaded."));
+        };
+        self.check_unsealed()?;
+        if self.is_asserted() {
+            return Err(ErrorCode::InUse.error(format!("{}: groups of the library are asserted", self.name)));
+        }
+        let patched = self.patch_sites.iter().enumerate().any(|(sym_index, stub)| {
+            *stub != 0 && !cfg!(feature = "checked") && self.is_symbol_resolved(sym_index as u32)
+        });
+        if patched {
+            return Err(ErrorCode::Unsupported.error(format!("{}: stubs have been patched", self.name)));
+        }
+
+        // New resolutions must not find the old handle once the table is reset.
+        self.handle.store(0, Ordering::SeqCst);
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        for group in self.groups {
+            group.reset();
+        }
+        for entry in self.symbol_table {
+            entry.store(0, Ordering::SeqCst);
+        }
+        self.clear_cached_addresses();
+        *self.selected_variant.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        self.forget_interfaces();
+        loading::close_library(handle)
+    }
+}
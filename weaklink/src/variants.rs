@@ -0,0 +1,84 @@
+//! Alternative symbol names for ABI variants of the wrapped library.
+
+use crate::{loading, Address, DylibHandle, Error, Library};
+use std::ffi::CStr;
+use std::sync::PoisonError;
+
+/// Alternative names of symbols, which apply to dylibs that export the variant's marker symbol.
+#[doc(hidden)]
+pub struct NameVariant {
+    name: &'static str,
+    marker: &'static CStr,
+    // Symbol index and alternative name, sorted by index.
+    names: &'static [(u32, &'static CStr)],
+}
+
+impl NameVariant {
+    pub const fn new(name: &'static str, marker: &'static CStr, names: &'static [(u32, &'static CStr)]) -> NameVariant {
+        NameVariant { name, marker, names }
+    }
+}
+
+impl Library {
+    /// Returns the name of the variant whose symbol names are used with the loaded dylib, or `None` if the library
+    /// isn't loaded, or the default names are used.
+    ///
+    /// Variants are configured at build time via `weaklink_build::Config::add_name_variant`.  The first variant
+    /// whose marker symbol is exported by the dylib is selected.
+    pub fn name_variant(&self) -> Option<&'static str> {
+        Some(self.variant_for(self.handle()?)?.name)
+    }
+
+    // Returns the name variant matching a dylib, if any.
+    fn variant_for(&self, handle: DylibHandle) -> Option<&'static NameVariant> {
+        if self.variants.is_empty() {
+            return None;
+        }
+        let mut selected = self.selected_variant.lock().unwrap_or_else(PoisonError::into_inner);
+        match *selected {
+            Some((raw_handle, variant)) if raw_handle == handle.0 => variant,
+            _ => {
+                let variant = self.variants.iter().find(|variant| self.find_export(handle, variant.marker).is_ok());
+                *selected = Some((handle.0, variant));
+                variant
+            }
+        }
+    }
+
+    // Looks up a symbol in the specified dylib, by the name of the variant that the dylib matches, if any, or else by
+    // its name and version, if any.  Symbols obtained from structs or interfaces are looked up there instead.
+    pub(crate) fn find_symbol_in(&self, sym_index: u32, handle: DylibHandle) -> Result<Address, Error> {
+        if let Some((provider, index)) = self.vtable_slot(sym_index) {
+            return self.find_in_vtable(handle, provider, index);
+        }
+        if let Some((factory, index)) = self.interface_slot(sym_index) {
+            return self.find_in_interface(handle, factory, index);
+        }
+        if let Some(variant) = self.variant_for(handle) {
+            if let Ok(position) = variant.names.binary_search_by_key(&sym_index, |(sym_index, _)| *sym_index) {
+                return self.find_export(handle, variant.names[position].1);
+            }
+        }
+        self.with_symbol_name(sym_index, |sym_name| match self.symbol_version(sym_index) {
+            // Bootstrap exports look up names by themselves, which have no versions.
+            Some(version) if self.bootstrap_export.is_none() => {
+                loading::find_versioned_symbol(handle, sym_name, version)
+            }
+            _ => self.find_export(handle, sym_name),
+        })
+    }
+
+    fn symbol_version(&self, sym_index: u32) -> Option<&'static CStr> {
+        let position = self.symbol_versions.binary_search_by_key(&sym_index, |(sym_index, _)| *sym_index).ok()?;
+        Some(self.symbol_versions[position].1)
+    }
+
+    // Looks up a name in the specified dylib, through its bootstrap export, if any.
+    pub(crate) fn find_export(&self, handle: DylibHandle, name: &CStr) -> Result<Address, Error> {
+        match self.bootstrap_export {
+            // The signature is promised by `Config::bootstrap_export`.
+            Some(bootstrap) => unsafe { loading::find_symbol_via(handle, bootstrap, name) },
+            None => loading::find_symbol(handle, name),
+        }
+    }
+}
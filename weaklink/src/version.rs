@@ -0,0 +1,88 @@
+//! Version requirements of groups.
+
+use crate::{loading, messages, Error, Library};
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::PoisonError;
+
+/// Error returned when a group requires a newer version of the library than the one loaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VersionTooOld {
+    /// Name of the group.
+    pub group: &'static str,
+    /// Minimum version required by the group.
+    pub required: &'static str,
+    /// Version of the library, or `None` if it is unknown.
+    pub found: Option<String>,
+}
+
+impl fmt::Display for VersionTooOld {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        messages::write_message(self, f, |f| match &self.found {
+            Some(found) => write!(
+                f,
+                "Group {} requires version {}, found {}",
+                self.group, self.required, found
+            ),
+            None => write!(
+                f,
+                "Group {} requires version {}, but the version is unknown",
+                self.group, self.required
+            ),
+        })
+    }
+}
+
+impl std::error::Error for VersionTooOld {}
+
+impl Library {
+    /// Sets the version of the loaded library, against which minimum group versions are checked.
+    ///
+    /// This overrides the version detected by [`Library::plugin_version`].  Minimum versions are configured via
+    /// `Config::set_min_version` in `weaklink_build`.
+    pub fn set_plugin_version(&self, version: &str) {
+        self.configure();
+        *self.plugin_version.lock().unwrap_or_else(PoisonError::into_inner) = Some(version.to_string());
+    }
+
+    /// Returns the version of the loaded library, if known.
+    ///
+    /// Unless set via [`Library::set_plugin_version`], the version is detected from the loaded library: on Windows
+    /// from its `VERSIONINFO` resource, elsewhere from its `plugin_version` export (see
+    /// [`loading::module_version`]).  Groups that require a minimum version fail to resolve while the version is
+    /// unknown.
+    pub fn plugin_version(&self) -> Option<String> {
+        let mut version = self.plugin_version.lock().unwrap_or_else(PoisonError::into_inner);
+        if version.is_none() {
+            *version = self.handle().and_then(loading::module_version);
+        }
+        version.clone()
+    }
+
+    pub(crate) fn check_version(&self, group: &'static str, required: &'static str) -> Result<(), Error> {
+        let found = self.plugin_version();
+        match &found {
+            Some(found) if compare_versions(found, required) != Ordering::Less => Ok(()),
+            _ => Err(VersionTooOld { group, required, found }.into()),
+        }
+    }
+}
+
+/// Compares dot-separated versions component-wise, numerically where possible.  Missing components count as 0.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let mut a = a.split('.');
+    let mut b = b.split('.');
+    loop {
+        let (x, y) = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (x, y) => (x.unwrap_or("0"), y.unwrap_or("0")),
+        };
+        let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Symbols obtained from structs of pointers returned by the wrapped library, or from the method tables of COM-style
+//! interfaces, rather than by name.
+
+use crate::{Address, DylibHandle, Error, ErrorCode, Library};
+use std::ffi::{c_void, CStr};
+use std::sync::PoisonError;
+
+/// An export that returns a struct of pointer-sized members, e.g. a plugin's table of functions.
+/// See `SymbolStub::vtable_slot` in `weaklink_build`.
+pub type VtableProvider = unsafe extern "C" fn() -> *const *const c_void;
+
+/// Creates an interface of a loaded dylib, e.g. by calling its `DllGetClassObject` export, and returns a pointer to it.
+/// See [`Library::set_factory`].
+pub type FactoryCallback = fn(DylibHandle) -> Result<Address, Error>;
+
+// A factory callback, and the interface it created, if any.
+pub(crate) struct Factory {
+    name: &'static str,
+    callback: FactoryCallback,
+    // Raw handle of the dylib, and the interface created for it.
+    interface: Option<(usize, Address)>,
+}
+
+impl Factory {
+    pub(crate) fn new(name: &'static str, callback: FactoryCallback) -> Factory {
+        Factory {
+            name,
+            callback,
+            interface: None,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub(crate) fn callback(&self) -> FactoryCallback {
+        self.callback
+    }
+}
+
+impl Library {
+    // Returns the provider export and the index in its struct of a symbol, if it's obtained from a struct.
+    pub(crate) fn vtable_slot(&self, sym_index: u32) -> Option<(&'static CStr, u32)> {
+        let position = self.vtable_slots.binary_search_by_key(&sym_index, |(sym_index, _, _)| *sym_index).ok()?;
+        let (_, provider, index) = self.vtable_slots[position];
+        Some((provider, index))
+    }
+
+    // Looks up a symbol in the struct returned by the provider export of the specified dylib.
+    pub(crate) fn find_in_vtable(
+        &self,
+        handle: DylibHandle,
+        provider: &'static CStr,
+        index: u32,
+    ) -> Result<Address, Error> {
+        // The signature is promised by `SymbolStub::vtable_slot`.
+        let provider_fn =
+            unsafe { std::mem::transmute::<Address, VtableProvider>(self.find_export(handle, provider)?) };
+        let vtable = unsafe { provider_fn() };
+        if vtable.is_null() {
+            return Err(ErrorCode::InterfaceUnavailable.error(format!("{}: {provider:?} returned null", self.name)));
+        }
+        match unsafe { *vtable.add(index as usize) } as Address {
+            0 => Err(ErrorCode::InterfaceUnavailable.error(format!(
+                "{}: entry {index} of the struct returned by {provider:?} is null",
+                self.name
+            ))),
+            address => Ok(address),
+        }
+    }
+
+    /// Sets the callback that creates the interface named `factory`, whose methods are bound to the stubs declared
+    /// via `SymbolStub::interface_slot` in `weaklink_build`.
+    ///
+    /// This supports plugins that export only a factory, such as `DllGetClassObject`, rather than their functions.
+    /// The callback is invoked once for the loaded dylib, when the first of the interface's methods is resolved, and
+    /// must not call into the library.  Replaces the callback previously set for `factory`, if any.
+    pub fn set_factory(&self, factory: &'static str, callback: FactoryCallback) {
+        self.configure();
+        let mut factories = self.factories.lock().unwrap_or_else(PoisonError::into_inner);
+        factories.retain(|other| other.name != factory);
+        factories.push(Factory::new(factory, callback));
+    }
+
+    /// Returns the interface created by the callback set for `factory`, for the dylib that the library is loaded
+    /// from, creating it if needed.
+    ///
+    /// Methods of COM interfaces take the interface pointer as their first argument, so callers of their stubs need
+    /// to pass it.
+    pub fn interface(&self, factory: &str) -> Result<Address, Error> {
+        let handle = self.ensure_loaded()?;
+        self.interface_for(handle, factory)
+    }
+
+    // Returns the interface created by a factory for the specified dylib, calling the factory if needed.
+    fn interface_for(&self, handle: DylibHandle, factory: &str) -> Result<Address, Error> {
+        let callback = {
+            let factories = self.factories.lock().unwrap_or_else(PoisonError::into_inner);
+            let Some(entry) = factories.iter().find(|entry| entry.name == factory) else {
+                return Err(ErrorCode::NotConfigured
+                    .error(format!("{}: no callback is set for factory {factory:?}", self.name)));
+            };
+            match entry.interface {
+                Some((raw_handle, interface)) if raw_handle == handle.0 => return Ok(interface),
+                _ => entry.callback,
+            }
+        };
+        // The lock isn't held during the call, in case the callback takes long.
+        let interface = match callback(handle)? {
+            0 => {
+                return Err(
+                    ErrorCode::InterfaceUnavailable.error(format!("{}: factory {factory:?} returned null", self.name))
+                )
+            }
+            interface => interface,
+        };
+        let mut factories = self.factories.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(entry) = factories.iter_mut().find(|entry| entry.name == factory) {
+            entry.interface = Some((handle.0, interface));
+        }
+        Ok(interface)
+    }
+
+    // Forgets the interfaces created by the factories, e.g. when the library is unloaded.
+    pub(crate) fn forget_interfaces(&self) {
+        let mut factories = self.factories.lock().unwrap_or_else(PoisonError::into_inner);
+        for entry in factories.iter_mut() {
+            entry.interface = None;
+        }
+    }
+
+    // Returns the factory and the method index of a symbol, if it's a method of an interface.
+    pub(crate) fn interface_slot(&self, sym_index: u32) -> Option<(&'static str, u32)> {
+        let position = self
+            .interface_slots
+            .binary_search_by_key(&sym_index, |(sym_index, _, _)| *sym_index)
+            .ok()?;
+        let (_, factory, index) = self.interface_slots[position];
+        Some((factory, index))
+    }
+
+    // Looks up a method in the method table of the interface created by a factory for the specified dylib.
+    pub(crate) fn find_in_interface(&self, handle: DylibHandle, factory: &str, index: u32) -> Result<Address, Error> {
+        let interface = self.interface_for(handle, factory)?;
+        // The first member of a COM object points to its method table.
+        let methods = unsafe { *(interface as *const *const Address) };
+        if methods.is_null() {
+            return Err(ErrorCode::InterfaceUnavailable.error(format!(
+                "{}: the interface created by factory {factory:?} has no methods",
+                self.name
+            )));
+        }
+        match unsafe { *methods.add(index as usize) } {
+            0 => Err(ErrorCode::InterfaceUnavailable.error(format!(
+                "{}: method {index} of the interface created by factory {factory:?} is null",
+                self.name
+            ))),
+            address => Ok(address),
+        }
+    }
+}
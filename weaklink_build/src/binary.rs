@@ -0,0 +1,70 @@
+//! Binaries parsed once for multiple queries.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use goblin::Object;
+
+use crate::exports::{dylib_exports_from, Export};
+use crate::imports::{archive_import_sites_from, archive_imports_from, Import, ImportSite};
+use crate::Error;
+
+/// A binary file parsed by [Goblin](https://crates.io/crates/goblin), which may be queried repeatedly without
+/// re-parsing.
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use weaklink_build::ParsedBinary;
+/// let data = std::fs::read("libfoo.so")?;
+/// let binary = ParsedBinary::parse(&data)?;
+/// let exports = binary.exports()?;
+/// if let goblin::Object::Elf(elf) = binary.object() {
+///     println!("{} sections", elf.section_headers.len());
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct ParsedBinary<'a> {
+    data: &'a [u8],
+    object: Object<'a>,
+}
+
+impl<'a> ParsedBinary<'a> {
+    /// Parses the contents of a binary file.
+    pub fn parse(data: &'a [u8]) -> Result<ParsedBinary<'a>, Error> {
+        let object = Object::parse(data)?;
+        Ok(ParsedBinary { data, object })
+    }
+
+    /// Reads a binary file, for parsing by [`ParsedBinary::parse`].
+    pub fn read(path: &Path) -> Result<Vec<u8>, Error> {
+        Ok(fs::read(path)?)
+    }
+
+    /// Returns the data the binary was parsed from.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns the parsed object.
+    pub fn object(&self) -> &Object<'a> {
+        &self.object
+    }
+
+    /// Returns the list of exported symbols, if the binary is a dynamic library.  See [`dylib_exports_from`].
+    pub fn exports(&self) -> Result<Vec<Export>, Error> {
+        dylib_exports_from(&self.object)
+    }
+
+    /// Returns the list of imported symbols, if the binary is a static library or an object file.
+    /// See [`archive_imports_from`].
+    pub fn imports(&self) -> Result<Vec<Import>, Error> {
+        archive_imports_from(&self.object, self.data)
+    }
+
+    /// Returns the sites referencing each imported symbol, if the binary is a static library or an object file.
+    /// See [`archive_import_sites_from`].
+    pub fn import_sites(&self) -> Result<HashMap<String, Vec<ImportSite>>, Error> {
+        archive_import_sites_from(&self.object, self.data)
+    }
+}
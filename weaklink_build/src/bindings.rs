@@ -0,0 +1,78 @@
+//! Stubs of all exports of a library, and Rust declarations of the generated data accessors.
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::exports::{dylib_exports, ExportKind};
+use crate::util::{iter_fmt, rust_ident};
+use crate::{Config, Error, SymbolStub};
+
+impl Config {
+    /// Adds all functions exported by a dynamic library to a group, creating the group if it doesn't exist yet.
+    ///
+    /// Data exports are added as well if [`Config::data_accessor_template`] is set, with accessors named after
+    /// it; otherwise they are left out.  Thread-local variables, and exports of unknown kind, are always left out,
+    /// as are [denied](Config::deny_symbols) symbols.
+    ///
+    /// ```no_run
+    /// # use std::path::Path;
+    /// let mut config = weaklink_build::Config::new("foo_stub");
+    /// config.data_accessor_template = Some("get_{name}".to_string());
+    /// config.add_all_exports_from("all", Path::new("libfoo.so"))?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn add_all_exports_from(&mut self, group_name: &str, path: &Path) -> Result<(), Error> {
+        self.check_binary(path)?;
+        let mut exports = dylib_exports(path)?;
+        exports.sort_by(|a, b| a.name.cmp(&b.name));
+        for export in exports {
+            let stub = match (export.kind, &self.data_accessor_template) {
+                (ExportKind::Code | ExportKind::Indirect, _) => SymbolStub::new(&export.name),
+                (ExportKind::Data, Some(template)) => {
+                    SymbolStub::new_data(&template.replace("{name}", &export.name), &export.name)
+                }
+                _ => continue,
+            };
+            self.add_symbol(group_name, stub)?;
+        }
+        Ok(())
+    }
+
+    /// Generate Rust declarations of the data accessors, for the crate that calls them.
+    ///
+    /// Each accessor returns the address of a data symbol of the wrapped library.  As with
+    /// [`Config::generate_c_header`], code symbols are not declared, since their signatures are not known.
+    ///
+    /// ```
+    /// # use weaklink_build::{Config, SymbolStub};
+    /// let mut config = Config::new("foo_stub");
+    /// config.add_symbol_group("base", [SymbolStub::new_data("get_FOO_TABLE", "FOO_TABLE")])?;
+    /// let mut text = Vec::new();
+    /// config.generate_bindings(&mut text);
+    /// assert!(String::from_utf8(text)?.contains("pub fn get_FOO_TABLE() -> *mut core::ffi::c_void;"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn generate_bindings(&self, text: &mut dyn Write) {
+        let mut accessors = self.stubs.iter().filter(|stub| stub.is_data).collect::<Vec<_>>();
+        accessors.sort_by(|a, b| a.export_name.cmp(&b.export_name));
+
+        write_lines!(text,
+            "// Generated by weaklink_build: data accessors of the `{name}` stubs."
+            "#[allow(dead_code, non_snake_case)]"
+            "extern \"C\" {{"
+            "{accessors}}}",
+            name = self.name,
+            accessors = iter_fmt(&accessors, |f, stub| {
+                writeln!(f, "    /// Returns the address of `{}` of the wrapped library.", stub.import_name)?;
+                match rust_ident(&stub.export_name) {
+                    Some(ident) => writeln!(f, "    pub fn {ident}() -> *mut core::ffi::c_void;"),
+                    None => {
+                        let ident = stub.export_name.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+                        writeln!(f, "    #[link_name = \"{}\"]", stub.export_name)?;
+                        writeln!(f, "    pub fn {ident}() -> *mut core::ffi::c_void;")
+                    }
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Cache of generated files, which lets build scripts skip scanning binaries and generating code for unchanged inputs.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+
+// Parameters of the 128-bit FNV-1a hash.
+const FNV_OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// Hash of the inputs of a generated file, under which it's stored in a [`Cache`].
+///
+/// The key should cover everything that the generated file depends on: the binaries that are scanned, via
+/// [`CacheKey::file`], and the configuration, e.g. the text of the build script, via [`CacheKey::text`].  The
+/// version of `weaklink_build` and the target are always included.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey(u128);
+
+impl CacheKey {
+    /// Creates a key for the current version of `weaklink_build` and target.
+    pub fn new() -> CacheKey {
+        let key = CacheKey(FNV_OFFSET).text(env!("CARGO_PKG_VERSION"));
+        key.text(&crate::util::current_target())
+    }
+
+    /// Adds the contents of a file to the key.
+    pub fn file(self, path: &Path) -> Result<CacheKey, Error> {
+        let mut file = File::open(path)?;
+        let mut key = self.bytes(&[0xff]);
+        let mut buffer = vec![0; 1 << 16];
+        loop {
+            let len = file.read(&mut buffer)?;
+            if len == 0 {
+                return Ok(key);
+            }
+            key = key.bytes(&buffer[..len]);
+        }
+    }
+
+    /// Adds a string to the key, e.g. the text of the build script, or a description of the configuration.
+    pub fn text(self, text: &str) -> CacheKey {
+        // The length keeps consecutive strings from being ambiguous, e.g. "ab" + "c" and "a" + "bc".
+        self.bytes(&(text.len() as u64).to_le_bytes()).bytes(text.as_bytes())
+    }
+
+    fn bytes(self, bytes: &[u8]) -> CacheKey {
+        let hash = bytes.iter().fold(self.0, |hash, byte| (hash ^ *byte as u128).wrapping_mul(FNV_PRIME));
+        CacheKey(hash)
+    }
+}
+
+impl Default for CacheKey {
+    fn default() -> Self {
+        CacheKey::new()
+    }
+}
+
+/// Content-addressed store of generated files.
+///
+/// Scanning the binaries of a large library and generating its stubs may take a while, and doing it in every clean
+/// build adds up.  The cache stores generated files under the [key](CacheKey) of their inputs, so that a build
+/// script whose inputs are unchanged copies the file from the cache instead:
+///
+/// ```no_run
+/// # use std::path::{Path, PathBuf};
+/// # use weaklink_build::{exports, Cache, CacheKey, Config, SymbolStub};
+/// let plugin = weaklink_build::locate::dylib("plugin")?;
+/// // Build scripts run in the directory of the package.
+/// let key = CacheKey::new().file(&plugin)?.file(Path::new("build.rs"))?;
+/// let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+/// Cache::from_env()?.generate(&key, &out_dir.join("stubs.rs"), |text| {
+///     // Only executed if the stubs are not in the cache.
+///     let exports = exports::dylib_exports(&plugin)?;
+///     let mut config = Config::new("plugin_stub");
+///     config.add_symbol_group("base", exports.iter().map(|export| SymbolStub::new(&export.name)))?;
+///     config.generate_source(text);
+///     Ok(())
+/// })?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Creates a cache stored in the specified directory, creating it if needed.
+    pub fn new(dir: &Path) -> Result<Cache, Error> {
+        fs::create_dir_all(dir)?;
+        Ok(Cache { dir: dir.to_path_buf() })
+    }
+
+    /// Creates a cache stored in the directory set by the `WEAKLINK_CACHE_DIR` environment variable, which may be
+    /// shared by builds in different target directories and survives `cargo clean`.  If it's not set, the cache is
+    /// stored in `OUT_DIR`.
+    pub fn from_env() -> Result<Cache, Error> {
+        println!("cargo:rerun-if-env-changed=WEAKLINK_CACHE_DIR");
+        if let Some(dir) = env::var_os("WEAKLINK_CACHE_DIR") {
+            return Cache::new(Path::new(&dir));
+        }
+        let out_dir = env::var_os("OUT_DIR").ok_or("neither WEAKLINK_CACHE_DIR nor OUT_DIR is set")?;
+        Cache::new(&Path::new(&out_dir).join("weaklink-cache"))
+    }
+
+    /// Returns the directory of the cache.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes the file generated for `key` to `dest`, calling `generate` to generate it if it's not in the cache.
+    ///
+    /// Returns true if the file was found in the cache.
+    pub fn generate(
+        &self,
+        key: &CacheKey,
+        dest: &Path,
+        generate: impl FnOnce(&mut dyn Write) -> Result<(), Error>,
+    ) -> Result<bool, Error> {
+        let path = self.dir.join(format!("{:032x}", key.0));
+        if path.is_file() {
+            fs::copy(&path, dest)?;
+            return Ok(true);
+        }
+        let mut text = Vec::new();
+        generate(&mut text)?;
+        fs::write(dest, &text)?;
+        // Concurrent builds may store the same entry; the rename makes that of either one visible in whole.
+        let temp_path = self.dir.join(format!("{:032x}.{}.tmp", key.0, std::process::id()));
+        fs::write(&temp_path, &text)?;
+        fs::rename(&temp_path, &path)?;
+        Ok(false)
+    }
+}
@@ -0,0 +1,107 @@
+//! Mapping of client functions to the stubbed symbols they reference.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::Path;
+
+use crate::imports::{archive_import_sites, Import};
+use crate::{Config, Error};
+
+/// A function of client code, and the stubbed symbols it references.  See [`Config::call_graph`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Caller {
+    /// Name of the function, as it appears in the symbol table (i.e. mangled), or the name of the section holding
+    /// the references if the function is not known.
+    pub function: String,
+    /// Name of the archive member defining the function, or `None` if the scanned file is an object file.
+    pub object: Option<String>,
+    /// Export names of the stubbed symbols the function references, sorted.
+    pub symbols: Vec<String>,
+}
+
+/// Which functions of client code reference which stubbed symbols.  See [`Config::call_graph`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallGraph {
+    /// Name of the library, i.e. [`Config::name`].
+    pub library: String,
+    /// Functions referencing stubbed symbols, sorted by function and object name.
+    pub callers: Vec<Caller>,
+}
+
+impl CallGraph {
+    /// Returns the stubbed symbols referenced by the functions whose names contain `function`, e.g. its unmangled
+    /// path, sorted and deduplicated.
+    pub fn symbols_of(&self, function: &str) -> Vec<&str> {
+        let callers = self.callers.iter().filter(|caller| caller.function.contains(function));
+        let symbols = callers.flat_map(|caller| caller.symbols.iter().map(String::as_str));
+        symbols.collect::<BTreeSet<_>>().into_iter().collect()
+    }
+
+    /// Formats the call graph as a JSON object, with the library name and an array of callers.
+    pub fn to_json(&self) -> String {
+        let mut json = format!("{{\"library\":\"{}\",\"callers\":[", self.library.escape_default());
+        for (i, caller) in self.callers.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let object = match &caller.object {
+                Some(object) => format!("\"{}\"", object.escape_default()),
+                None => "null".to_string(),
+            };
+            let _ = write!(
+                json,
+                "{separator}{{\"function\":\"{}\",\"object\":{object},\"symbols\":[",
+                caller.function.escape_default()
+            );
+            for (i, symbol) in caller.symbols.iter().enumerate() {
+                let separator = if i == 0 { "" } else { "," };
+                let _ = write!(json, "{separator}\"{}\"", symbol.escape_default());
+            }
+            json.push_str("]}");
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+impl Config {
+    /// Scans static libraries of client code for the functions that reference stubbed symbols, from the
+    /// relocations of their object files.
+    ///
+    /// This tells which symbols of the wrapped library each feature of the program requires, e.g. to document it,
+    /// or to choose the members of groups.  References from code that the linker later discards are included.
+    pub fn call_graph(&self, archives: &[&Path]) -> Result<CallGraph, Error> {
+        let stubbed = self.stubs.iter().map(|stub| stub.export_name.as_str()).collect::<HashSet<_>>();
+        let mut callers = BTreeMap::<(String, Option<String>), BTreeSet<String>>::new();
+        for path in archives {
+            self.check_binary(path)?;
+            for (name, sites) in archive_import_sites(path)? {
+                let import = Import { name };
+                let symbol = import.undecorated_name(&self.target);
+                if !stubbed.contains(symbol) {
+                    continue;
+                }
+                for site in sites {
+                    let function = site.function.unwrap_or(site.section);
+                    callers.entry((function, site.object)).or_default().insert(symbol.to_string());
+                }
+            }
+        }
+        let callers = callers.into_iter().map(|((function, object), symbols)| Caller {
+            function,
+            object,
+            symbols: symbols.into_iter().collect(),
+        });
+        Ok(CallGraph {
+            library: self.name.clone(),
+            callers: callers.collect(),
+        })
+    }
+
+    /// Writes the [call graph](Config::call_graph) of client code into the stubbed symbols as JSON, for
+    /// documentation and other tools to consume.
+    pub fn generate_call_graph(&self, archives: &[&Path], text: &mut dyn Write) -> Result<(), Error> {
+        let call_graph = self.call_graph(archives)?;
+        writeln!(text, "{}", call_graph.to_json())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+//! File names under which dynamic libraries are installed on each platform.
+
+use crate::stub_gen::TargetOs;
+use crate::Config;
+use weaklink::loading::LibcFlavor;
+
+/// Returns the file names under which a dynamic library may be found on the target, most specific first.
+///
+/// `name` is the base name of the library, without any platform prefix or suffix, and `versions` are its ABI
+/// versions, as they appear in file names (e.g. the SONAME suffix), most preferred first.  Versioned names come
+/// before the unversioned one, which is typically only installed by development packages:
+///
+/// | Target  | Names                                                           |
+/// |---------|-----------------------------------------------------------------|
+/// | Linux   | `libfoo.so.3`, `libfoo.so`                                      |
+/// | macOS   | `libfoo.3.dylib`, `libfoo.dylib`                                |
+/// | Windows | `foo-3.dll`, `foo.dll` (and `libfoo-3.dll`, `libfoo.dll` first for the GNU toolchain) |
+///
+/// Returns an empty vector if the target's operating system is not supported.
+///
+/// ```
+/// let names = weaklink_build::dylib_candidates("x86_64-unknown-linux-gnu", "foo", &["3.2", "3"]);
+/// assert_eq!(names, ["libfoo.so.3.2", "libfoo.so.3", "libfoo.so"]);
+/// let names = weaklink_build::dylib_candidates("aarch64-apple-darwin", "foo", &["3"]);
+/// assert_eq!(names, ["libfoo.3.dylib", "libfoo.dylib"]);
+/// let names = weaklink_build::dylib_candidates("x86_64-pc-windows-msvc", "foo", &["3"]);
+/// assert_eq!(names, ["foo-3.dll", "foo.dll"]);
+/// ```
+pub fn dylib_candidates(target: &str, name: &str, versions: &[&str]) -> Vec<String> {
+    let versions = versions.iter().map(Some).chain([None]);
+    match TargetOs::from_target(target) {
+        Some(TargetOs::Linux) => versions
+            .map(|version| match version {
+                Some(version) => format!("lib{name}.so.{version}"),
+                None => format!("lib{name}.so"),
+            })
+            .collect(),
+        Some(TargetOs::MacOS) => versions
+            .map(|version| match version {
+                Some(version) => format!("lib{name}.{version}.dylib"),
+                None => format!("lib{name}.dylib"),
+            })
+            .collect(),
+        Some(TargetOs::Windows) => {
+            // MinGW names libraries with the `lib` prefix, but may also load those built with MSVC.
+            let prefixes: &[&str] = if target.ends_with("-gnu") { &["lib", ""] } else { &[""] };
+            versions
+                .flat_map(|version| {
+                    prefixes.iter().map(move |prefix| match version {
+                        Some(version) => format!("{prefix}{name}-{version}.dll"),
+                        None => format!("{prefix}{name}.dll"),
+                    })
+                })
+                .collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+impl Config {
+    /// Appends the [candidate file names](dylib_candidates) of a library on the target to
+    /// [`dylib_names`](Config::dylib_names).
+    ///
+    /// ```
+    /// let mut config = weaklink_build::Config::new("foo_stub");
+    /// config.target = "x86_64-unknown-linux-gnu".into();
+    /// config.add_dylib_candidates("foo", &["3"]);
+    /// assert_eq!(config.dylib_names, ["libfoo.so.3", "libfoo.so"]);
+    /// ```
+    pub fn add_dylib_candidates(&mut self, name: &str, versions: &[&str]) {
+        let names = dylib_candidates(&self.target, name, versions);
+        self.dylib_names.extend(names);
+    }
+
+    /// Appends names to [`dylib_names`](Config::dylib_names), which are only tried on systems whose C library is
+    /// `flavor`, e.g. for plugins built separately for glibc and musl, under different names.
+    ///
+    /// Names are tagged with the [flavor's tag](LibcFlavor::tag), and the runtime selects them according to
+    /// [`weaklink::loading::libc_flavor`] as it loads the library.  Untagged names are tried on all systems.
+    ///
+    /// ```
+    /// use weaklink_build::LibcFlavor;
+    /// let mut config = weaklink_build::Config::new("foo_stub");
+    /// config.add_dylib_names_for(LibcFlavor::Glibc, ["libfoo.so.3"]);
+    /// config.add_dylib_names_for(LibcFlavor::Musl, ["libfoo-musl.so.3"]);
+    /// assert_eq!(config.dylib_names, ["glibc:libfoo.so.3", "musl:libfoo-musl.so.3"]);
+    /// ```
+    pub fn add_dylib_names_for<'a>(&mut self, flavor: LibcFlavor, names: impl IntoIterator<Item = &'a str>) {
+        let names = names.into_iter().map(|name| format!("{}:{name}", flavor.tag()));
+        self.dylib_names.extend(names);
+    }
+}
@@ -0,0 +1,212 @@
+//! Compatibility of the configuration with historical builds of the wrapped library.
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::exports::{dylib_exports, manifest};
+use crate::{Config, Error};
+
+/// Which groups of the configuration resolve against a build of the wrapped library.  See [`Config::compat_matrix`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PluginCompat {
+    /// Path of the dynamic library.
+    pub path: PathBuf,
+    /// Version of the library, as stated by its [capability manifest](weaklink::PluginManifest), if any.
+    pub version: Option<String>,
+    /// Whether each group resolves, sorted by group name.
+    pub groups: Vec<(String, bool)>,
+}
+
+impl PluginCompat {
+    /// Returns true if the specified group resolves.
+    pub fn resolves(&self, group_name: &str) -> bool {
+        self.groups.iter().any(|(name, resolves)| name == group_name && *resolves)
+    }
+
+    // Label of the build in reports: its version, or else its file name.
+    fn label(&self) -> String {
+        match &self.version {
+            Some(version) => version.clone(),
+            None => self.path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        }
+    }
+}
+
+/// Which groups resolve against each of several builds of the wrapped library.  See [`Config::compat_matrix`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompatMatrix {
+    /// Builds of the library, ordered by version.  Those without a known version come first, ordered by path.
+    pub plugins: Vec<PluginCompat>,
+}
+
+impl CompatMatrix {
+    /// Returns the groups that resolve against some builds, but not against others, sorted by name.
+    pub fn degraded_groups(&self) -> Vec<&str> {
+        let Some(first) = self.plugins.first() else {
+            return Vec::new();
+        };
+        let groups = first.groups.iter().map(|(name, _)| name.as_str());
+        groups
+            .filter(|name| {
+                let resolves = self.plugins.iter().map(|plugin| plugin.resolves(name)).collect::<HashSet<_>>();
+                resolves.len() > 1
+            })
+            .collect()
+    }
+
+    /// Formats the matrix as a JSON array, with an object per build of the library.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("[");
+        for (i, plugin) in self.plugins.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let version = match &plugin.version {
+                Some(version) => format!("\"{}\"", version.escape_default()),
+                None => "null".to_string(),
+            };
+            json.push_str(&format!(
+                "{separator}{{\"path\":\"{}\",\"version\":{version},\"groups\":{{",
+                plugin.path.display().to_string().escape_default()
+            ));
+            for (i, (name, resolves)) in plugin.groups.iter().enumerate() {
+                let separator = if i == 0 { "" } else { "," };
+                json.push_str(&format!("{separator}\"{}\":{resolves}", name.escape_default()));
+            }
+            json.push_str("}}");
+        }
+        json.push(']');
+        json
+    }
+}
+
+impl fmt::Display for CompatMatrix {
+    /// Formats the matrix as a table, with a row per group and a column per build of the library.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<24}", "group")?;
+        for plugin in &self.plugins {
+            write!(f, " {:>12}", plugin.label())?;
+        }
+        writeln!(f)?;
+        let Some(first) = self.plugins.first() else {
+            return Ok(());
+        };
+        for (name, _) in &first.groups {
+            write!(f, "{name:<24}")?;
+            for plugin in &self.plugins {
+                write!(f, " {:>12}", if plugin.resolves(name) { "yes" } else { "no" })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Evaluates the groups against each build of the wrapped library in a directory, e.g. the builds of all
+    /// supported versions, to tell which features of the program degrade with each of them.
+    ///
+    /// Files of the directory that aren't dynamic libraries, or that are built for another target, are skipped.
+    /// See [`Config::compat_of`] for how the groups are evaluated.
+    ///
+    /// ```no_run
+    /// # let config = weaklink_build::Config::new("foo");
+    /// let matrix = config.compat_matrix(std::path::Path::new("plugins/history"))?;
+    /// println!("{matrix}");
+    /// assert!(matrix.plugins.iter().all(|plugin| plugin.resolves("base")));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn compat_matrix(&self, dir: &Path) -> Result<CompatMatrix, Error> {
+        let mut plugins = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_file() {
+                if let Ok(plugin) = self.compat_of(&path) {
+                    plugins.push(plugin);
+                }
+            }
+        }
+        plugins.sort_by(|a, b| match (&a.version, &b.version) {
+            (Some(x), Some(y)) => weaklink::version::compare_versions(x, y).then_with(|| a.path.cmp(&b.path)),
+            (x, y) => x.is_some().cmp(&y.is_some()).then_with(|| a.path.cmp(&b.path)),
+        });
+        Ok(CompatMatrix { plugins })
+    }
+
+    /// Evaluates which groups resolve against a build of the wrapped library, from its exports, without loading it.
+    ///
+    /// A group resolves if all of its required symbols are exported under their import names or those of a name
+    /// variant, its dependencies resolve, and the version from the library's capability manifest satisfies its
+    /// minimum version, if any.  Symbols served by a [bootstrap export](Config::bootstrap_export) or by a struct of
+    /// pointers are deemed present if that export is, and methods of interfaces are always deemed present.
+    ///
+    /// Fails if the library is not built for [`Config::target`].
+    pub fn compat_of(&self, path: &Path) -> Result<PluginCompat, Error> {
+        self.check_binary(path)?;
+        let apple = self.target.contains("-apple-");
+        let exports = dylib_exports(path)?;
+        // Mach-O exports carry the C symbol prefix, which lookups at runtime add implicitly.
+        let exported = exports.iter().map(|export| match apple {
+            true => export.name.strip_prefix('_').unwrap_or(&export.name),
+            false => export.name.as_str(),
+        });
+        let exported = exported.collect::<HashSet<_>>();
+        let version = manifest(path).ok().flatten().and_then(|manifest| manifest.version);
+
+        let stubs = self.adjust_names(&self.stubs);
+        let bootstrapped = self.bootstrap_export.as_ref().is_some_and(|name| exported.contains(name.as_str()));
+        let present = stubs
+            .iter()
+            .map(|stub| match (&stub.vtable_slot, &stub.interface_slot) {
+                (Some((provider, _)), _) => exported.contains(provider.as_str()),
+                (_, Some(_)) => true,
+                _ => {
+                    bootstrapped
+                        || exported.contains(stub.import_name.as_str())
+                        || stub.variant_names.iter().any(|(_, name)| exported.contains(name.as_str()))
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut resolves = HashMap::new();
+        let mut grp_names = self.groups.keys().collect::<Vec<_>>();
+        grp_names.sort();
+        let groups = grp_names.into_iter().map(|grp_name| {
+            let result = self.group_resolves(grp_name, &present, version.as_deref(), &mut resolves);
+            (grp_name.clone(), result)
+        });
+        Ok(PluginCompat {
+            path: path.to_path_buf(),
+            groups: groups.collect(),
+            version,
+        })
+    }
+
+    // Evaluates a group and its dependencies, memoizing the results.  Groups in a dependency cycle don't resolve.
+    fn group_resolves<'a>(
+        &'a self,
+        grp_name: &'a str,
+        present: &[bool],
+        version: Option<&str>,
+        resolves: &mut HashMap<&'a str, bool>,
+    ) -> bool {
+        if let Some(result) = resolves.get(grp_name) {
+            return *result;
+        }
+        resolves.insert(grp_name, false);
+        let members = self.groups.get(grp_name).map(Vec::as_slice).unwrap_or_default();
+        let version_ok = match (self.min_versions.get(grp_name), version) {
+            (None, _) => true,
+            (Some(required), Some(version)) => weaklink::version::compare_versions(version, required) != Ordering::Less,
+            (Some(_), None) => false,
+        };
+        let dependencies = self.group_dependencies.get(grp_name).map(Vec::as_slice).unwrap_or_default();
+        let result = version_ok
+            && members.iter().all(|idx| present[*idx])
+            && dependencies
+                .iter()
+                .all(|dep_name| self.group_resolves(dep_name, present, version, resolves));
+        resolves.insert(grp_name, result);
+        result
+    }
+}
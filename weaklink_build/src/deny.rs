@@ -0,0 +1,94 @@
+//! Symbols that are never stubbed, even if client code imports them from the library.
+
+use crate::Config;
+
+/// Patterns of symbols of language runtimes and of the toolchain, which libraries export as a side effect of being
+/// linked, rather than as part of their API.
+///
+/// Stubbing these breaks the runtime of the program: e.g. calls of `malloc` or of the Rust allocator would fail until
+/// a group is resolved, and the C++ ABI functions of the program would be redirected into those of the library.
+pub const RUNTIME_SYMBOLS: &[&str] = &[
+    "_init",
+    "_fini",
+    "__gmon_start__",
+    "_ITM_*",
+    "__cxa_*",
+    "__gxx_personality_*",
+    "_Unwind_*",
+    "__stack_chk_*",
+    "malloc",
+    "calloc",
+    "realloc",
+    "free",
+    "posix_memalign",
+    "aligned_alloc",
+    "__rust_*",
+    "__rdl_*",
+    "__rg_*",
+    "rust_eh_personality",
+    "DllMain",
+];
+
+impl Config {
+    /// Never stubs symbols whose name in the library matches one of `patterns`, in which `*` matches any sequence of
+    /// characters, e.g. [`RUNTIME_SYMBOLS`].
+    ///
+    /// Groups built from the intersection of the library's exports with the imports of client code may otherwise
+    /// pick up symbols that both merely happen to share, such as those of the C or C++ runtime.  Denied symbols are
+    /// silently left out of groups as they are added, so this should be called before adding any.  The names of
+    /// those left out are returned by [`Config::skipped_symbols`], and [`Config::generate_source`] reports them as a
+    /// cargo warning.
+    ///
+    /// ```
+    /// # use weaklink_build::{Config, SymbolStub, RUNTIME_SYMBOLS};
+    /// let mut config = Config::new("foo");
+    /// config.deny_symbols(RUNTIME_SYMBOLS.iter().copied());
+    /// config.deny_symbols(["foo_internal_*"]);
+    /// let common = ["foo_init", "__cxa_finalize", "foo_internal_trace", "_init"];
+    /// config.add_symbol_group("base", common.iter().map(|name| SymbolStub::new(name)))?;
+    /// assert_eq!(config.skipped_symbols(), ["__cxa_finalize", "foo_internal_trace", "_init"]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deny_symbols<'a>(&mut self, patterns: impl IntoIterator<Item = &'a str>) {
+        self.denied_patterns.extend(patterns.into_iter().map(str::to_string));
+    }
+
+    /// Returns the names of the symbols that were left out of groups because they are
+    /// [denied](Config::deny_symbols), in the order they were first added.
+    pub fn skipped_symbols(&self) -> &[String] {
+        &self.skipped_symbols
+    }
+
+    // Returns true if the symbol must not be stubbed, recording it as skipped.
+    pub(crate) fn is_denied(&mut self, import_name: &str) -> bool {
+        if !self.denied_patterns.iter().any(|pattern| matches_pattern(pattern, import_name)) {
+            return false;
+        }
+        if !self.skipped_symbols.iter().any(|name| name == import_name) {
+            self.skipped_symbols.push(import_name.to_string());
+        }
+        true
+    }
+}
+
+// Matches `name` against a pattern in which `*` matches any sequence of characters.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            // The last part is anchored at the end of the name.
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    // There's no wildcard.
+    rest.is_empty()
+}
@@ -6,6 +6,11 @@ use std::path::Path;
 use goblin::*;
 
 use crate::{Error, SymbolStub};
+use weaklink::PluginManifest;
+
+// Machine types of ARM64EC object files and of ARM64X images.  ARM64EC images are marked as x86_64 instead.
+const COFF_MACHINE_ARM64EC: u16 = 0xa641;
+const COFF_MACHINE_ARM64X: u16 = 0xa64e;
 
 #[derive(Clone, Debug)]
 pub struct Export {
@@ -18,24 +23,67 @@ pub struct Export {
     ///
     /// On MacOS this will contain a combination of segment and section names, e.g. "__TEXT.__text".
     pub section: Option<String>,
+
+    /// Kind of the exported symbol.
+    pub kind: ExportKind,
+}
+
+/// Kind of an exported symbol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportKind {
+    /// A function.
+    Code,
+    /// A variable.
+    Data,
+    /// A function whose address is computed at load time by a resolver function, i.e. a GNU IFUNC on ELF or
+    /// a resolver stub on MacOS.
+    ///
+    /// The symbol's value in the binary is that of the resolver, so it must only be resolved via the dynamic
+    /// linker (which calls the resolver), never by reading the export table directly.
+    Indirect,
+    /// A thread-local variable, which has no process-wide address.
+    ThreadLocal,
+    /// Kind could not be determined, e.g. for untyped ELF symbols or forwarded exports.
+    Unknown,
 }
 
 /// Returns the list of symbols exported from a dynamic library.
 pub fn dylib_exports(path: &Path) -> Result<Vec<Export>, Error> {
+    let _timing = crate::util::Timing::start(|| format!("dylib_exports({})", path.display()));
     let mut fd = File::open(path)?;
     let mut buffer = Vec::new();
     fd.read_to_end(&mut buffer)?;
     let object = Object::parse(&buffer)?;
+    dylib_exports_from(&object)
+}
+
+/// Returns the list of symbols exported from a parsed dynamic library.
+///
+/// See also [`ParsedBinary::exports`](crate::ParsedBinary::exports).
+pub fn dylib_exports_from(object: &Object) -> Result<Vec<Export>, Error> {
     match object {
         Object::Elf(elf) => {
             let mut result = Vec::new();
-            for sym in elf.dynsyms.iter().filter(|sym| !sym.is_import()) {
+            for (sym_idx, sym) in elf.dynsyms.iter().enumerate().filter(|(_, sym)| !sym.is_import()) {
+                // Skip non-default versions of versioned symbols, which are not found by name lookups.
+                let versym = elf.versym.as_ref().and_then(|versym| versym.get_at(sym_idx));
+                if versym.is_some_and(|versym| versym.is_hidden()) {
+                    continue;
+                }
                 if let Some(name) = elf.dynstrtab.get_at(sym.st_name) {
                     if !name.is_empty() {
                         let sec_name = elf.shdr_strtab.get_at(elf.section_headers[sym.st_shndx].sh_name);
+                        let kind = match sym.st_type() {
+                            elf::sym::STT_FUNC => ExportKind::Code,
+                            elf::sym::STT_OBJECT | elf::sym::STT_COMMON => ExportKind::Data,
+                            elf::sym::STT_GNU_IFUNC => ExportKind::Indirect,
+                            elf::sym::STT_TLS => ExportKind::ThreadLocal,
+                            _ => ExportKind::Unknown,
+                        };
                         result.push(Export {
                             name: name.into(),
                             section: sec_name.map(|s| s.into()),
+                            kind,
                         });
                     }
                 }
@@ -45,21 +93,36 @@ pub fn dylib_exports(path: &Path) -> Result<Vec<Export>, Error> {
         Object::Mach(mach) => {
             fn macho_exports(macho: &mach::MachO) -> Result<Vec<Export>, Error> {
                 let mut ranges = SectionRanges::new();
-                for sec in macho.segments.sections().flatten() {
-                    if let Ok((sec, _)) = sec {
-                        let seg_name = sec.segname()?;
-                        let sec_name = sec.name()?;
-                        ranges.insert(sec.offset as u64, sec.size, format!("{}.{}", seg_name, sec_name));
-                    }
+                for (sec, _) in macho.segments.sections().flatten().flatten() {
+                    let seg_name = sec.segname()?;
+                    let sec_name = sec.name()?;
+                    ranges.insert(sec.offset as u64, sec.size, format!("{}.{}", seg_name, sec_name));
                 }
 
                 match macho.exports() {
                     Ok(exports) => {
                         let mut result = Vec::new();
                         for export in exports {
+                            let section = ranges.lookup(export.offset);
+                            let kind = match export.info {
+                                mach::exports::ExportInfo::Regular { flags, .. } => {
+                                    match flags & mach::exports::EXPORT_SYMBOL_FLAGS_KIND_MASK {
+                                        mach::exports::EXPORT_SYMBOL_FLAGS_KIND_THREAD_LOCAL => ExportKind::ThreadLocal,
+                                        mach::exports::EXPORT_SYMBOL_FLAGS_KIND_REGULAR => match section {
+                                            Some(section) if section.starts_with("__TEXT.") => ExportKind::Code,
+                                            Some(_) => ExportKind::Data,
+                                            None => ExportKind::Unknown,
+                                        },
+                                        _ => ExportKind::Unknown,
+                                    }
+                                }
+                                mach::exports::ExportInfo::Stub { .. } => ExportKind::Indirect,
+                                mach::exports::ExportInfo::Reexport { .. } => ExportKind::Unknown,
+                            };
                             result.push(Export {
                                 name: export.name,
-                                section: ranges.lookup(export.offset).map(|name| name.into()),
+                                section: section.map(|name| name.into()),
+                                kind,
                             });
                         }
                         Ok(result)
@@ -69,11 +132,11 @@ pub fn dylib_exports(path: &Path) -> Result<Vec<Export>, Error> {
             }
 
             match mach {
-                mach::Mach::Binary(macho) => macho_exports(&macho),
+                mach::Mach::Binary(macho) => macho_exports(macho),
                 mach::Mach::Fat(multi) => match multi.get(0) {
                     Ok(mach::SingleArch::MachO(macho)) => macho_exports(&macho),
                     Ok(mach::SingleArch::Archive(_)) => {
-                        Err(format!("The first object in a multiarch binary is not MachO").into())
+                        Err("The first object in a multiarch binary is not MachO".to_string().into())
                     }
                     Err(err) => Err(err.to_string().into()),
                 },
@@ -81,16 +144,40 @@ pub fn dylib_exports(path: &Path) -> Result<Vec<Export>, Error> {
         }
         Object::PE(pe) => {
             let mut ranges = SectionRanges::new();
-            for sec in pe.sections {
-                ranges.insert(sec.virtual_address as u64, sec.virtual_size as u64, sec.name()?.into());
+            let mut code_sections = Vec::new();
+            for sec in &pe.sections {
+                let name = sec.name()?;
+                ranges.insert(sec.virtual_address as u64, sec.virtual_size as u64, name.into());
+                let code_flags = pe::section_table::IMAGE_SCN_CNT_CODE | pe::section_table::IMAGE_SCN_MEM_EXECUTE;
+                if sec.characteristics & code_flags != 0 {
+                    code_sections.push(name);
+                }
             }
 
             let mut result = Vec::new();
+            let arm64ec = is_arm64ec(object);
             for export in &pe.exports {
                 if let Some(name) = export.name {
+                    // ARM64EC images export native code under mangled names, next to their x64-compatible entry
+                    // points, which are found by the unmangled names.
+                    let name = match arm64ec {
+                        true => arm64ec_unmangled(name),
+                        false => name.into(),
+                    };
+                    if arm64ec && result.iter().any(|export: &Export| export.name == name) {
+                        continue;
+                    }
+                    let section = ranges.lookup(export.rva as u64);
+                    let kind = match section {
+                        _ if export.reexport.is_some() => ExportKind::Unknown,
+                        Some(section) if code_sections.contains(&section) => ExportKind::Code,
+                        Some(_) => ExportKind::Data,
+                        None => ExportKind::Unknown,
+                    };
                     result.push(Export {
-                        name: name.into(),
-                        section: ranges.lookup(export.rva as u64).map(|name| name.into()),
+                        name,
+                        section: section.map(|name| name.into()),
+                        kind,
                     })
                 }
             }
@@ -100,6 +187,90 @@ pub fn dylib_exports(path: &Path) -> Result<Vec<Export>, Error> {
     }
 }
 
+/// Returns true if a Windows binary contains ARM64EC code, i.e. if it's an ARM64EC object file, an ARM64X image, or
+/// an ARM64EC image, which is marked as x86_64 but exports native code under mangled names.
+///
+/// Names of ARM64EC functions are mangled with a `#` prefix (or a `$$h` tag in C++ names) in such binaries, and are
+/// reported unmangled by [`dylib_exports`].
+pub fn is_arm64ec(object: &Object) -> bool {
+    match object {
+        Object::COFF(coff) => matches!(coff.header.machine, COFF_MACHINE_ARM64EC | COFF_MACHINE_ARM64X),
+        Object::PE(pe) => match pe.header.coff_header.machine {
+            COFF_MACHINE_ARM64EC | COFF_MACHINE_ARM64X => true,
+            pe::header::COFF_MACHINE_X86_64 => {
+                let mut names = pe.exports.iter().filter_map(|export| export.name);
+                names.any(|name| arm64ec_unmangled(name) != name)
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+// Returns the name of an ARM64EC function without its mangling, e.g. `foo` for `#foo`.
+pub(crate) fn arm64ec_unmangled(name: &str) -> String {
+    if let Some(name) = name.strip_prefix('#') {
+        name.into()
+    } else if name.starts_with('?') {
+        name.replacen("$$h", "", 1)
+    } else {
+        name.into()
+    }
+}
+
+/// Returns the capability manifest embedded in a dynamic library, or `None` if it has none.
+///
+/// See [`PluginManifest`](weaklink::PluginManifest) and `weaklink_export::export_manifest!`.
+pub fn manifest(path: &Path) -> Result<Option<PluginManifest>, Error> {
+    let mut fd = File::open(path)?;
+    let mut buffer = Vec::new();
+    fd.read_to_end(&mut buffer)?;
+    let object = Object::parse(&buffer)?;
+    let section = match &object {
+        Object::Elf(elf) => elf.section_headers.iter().find_map(|sec| {
+            let name = elf.shdr_strtab.get_at(sec.sh_name)?;
+            match name == weaklink::ELF_MANIFEST_SECTION && sec.sh_type != elf::section_header::SHT_NOBITS {
+                true => buffer.get(sec.file_range()?),
+                false => None,
+            }
+        }),
+        Object::Mach(mach) => {
+            fn macho_section<'a>(macho: &mach::MachO<'a>) -> Result<Option<&'a [u8]>, Error> {
+                let (seg_name, sec_name) = weaklink::MACHO_MANIFEST_SECTION;
+                for (sec, data) in macho.segments.sections().flatten().flatten() {
+                    if sec.segname()? == seg_name && sec.name()? == sec_name {
+                        return Ok(Some(data));
+                    }
+                }
+                Ok(None)
+            }
+
+            match mach {
+                mach::Mach::Binary(macho) => macho_section(macho)?,
+                mach::Mach::Fat(multi) => match multi.get(0)? {
+                    mach::SingleArch::MachO(macho) => macho_section(&macho)?,
+                    mach::SingleArch::Archive(_) => {
+                        return Err("The first object in a multiarch binary is not MachO".into())
+                    }
+                },
+            }
+        }
+        Object::PE(pe) => pe.sections.iter().find_map(|sec| match sec.name() {
+            Ok(name) if name == weaklink::PE_MANIFEST_SECTION => {
+                let start = sec.pointer_to_raw_data as usize;
+                buffer.get(start..start + sec.virtual_size.min(sec.size_of_raw_data) as usize)
+            }
+            _ => None,
+        }),
+        _ => return Err(format!("Unsupported object type: {object:?}").into()),
+    };
+    let Some(section) = section else {
+        return Ok(None);
+    };
+    let text = section.split(|b| *b == 0).next().unwrap_or_default();
+    PluginManifest::parse(std::str::from_utf8(text)?).map(Some)
+}
+
 struct SectionRanges {
     ranges: Vec<(Range<u64>, String)>,
 }
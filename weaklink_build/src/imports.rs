@@ -5,6 +5,7 @@ use std::path::Path;
 
 use goblin::*;
 
+use crate::util::Timing;
 use crate::{Error, SymbolStub};
 
 #[derive(Clone, Debug)]
@@ -12,26 +13,218 @@ pub struct Import {
     pub name: String,
 }
 
+/// Origin of an imported symbol, as guessed by [`Import::category`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportCategory {
+    /// A symbol of the C runtime or of the OS, e.g. `memcpy` or `pthread_create`.
+    System,
+    /// A symbol of the Rust runtime, of the unwinder, or a compiler builtin, e.g. `__rust_alloc` or `__udivti3`.
+    Runtime,
+    /// Any other symbol, which may be provided by a plugin.
+    Other,
+}
+
+impl Import {
+    /// Classifies the symbol for the current cargo build target.  See [`Import::category_for`].
+    pub fn category(&self) -> ImportCategory {
+        self.category_for(&crate::util::current_target())
+    }
+
+    /// Classifies the symbol for the specified target, based on lists of well-known system and runtime symbols.
+    ///
+    /// The lists are not exhaustive, so symbols of less common system libraries are classified as
+    /// [`ImportCategory::Other`].  [`plugin_candidates`] can additionally check the exports of such libraries.
+    pub fn category_for(&self, target: &str) -> ImportCategory {
+        let name = self.undecorated_name(target);
+        if RUNTIME_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) || is_compiler_builtin(name) {
+            ImportCategory::Runtime
+        } else if SYSTEM_SYMBOLS.contains(&name) || SYSTEM_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) {
+            ImportCategory::System
+        } else {
+            ImportCategory::Other
+        }
+    }
+
+    /// Returns the symbol name as it appears in the source code, i.e. without the import and C symbol prefixes of
+    /// the specified target, if any.
+    ///
+    /// ```
+    /// let import = weaklink_build::imports::Import { name: "__imp_aux_foo".into() };
+    /// assert_eq!(import.undecorated_name("arm64ec-pc-windows-msvc"), "foo");
+    /// let import = weaklink_build::imports::Import { name: "#foo".into() };
+    /// assert_eq!(import.undecorated_name("arm64ec-pc-windows-msvc"), "foo");
+    /// ```
+    pub fn undecorated_name(&self, target: &str) -> &str {
+        let mut name = self.name.as_str();
+        if target.starts_with("arm64ec-") {
+            // ARM64EC code imports functions through auxiliary entries as well, and calls native code by the `#`
+            // mangled names.
+            name = name.strip_prefix("__imp_aux_").unwrap_or(name);
+            name = name.strip_prefix('#').unwrap_or(name);
+        }
+        if target.contains("windows") {
+            name = name.strip_prefix("__imp_").unwrap_or(name);
+        }
+        if target.contains("apple") || target.starts_with("i686-pc-windows") {
+            name = name.strip_prefix('_').unwrap_or(name);
+        }
+        name
+    }
+}
+
+#[rustfmt::skip]
+const RUNTIME_PREFIXES: &[&str] = &[
+    "__rust_", "__rdl_", "__rg_", "rust_", "_ZN4core", "_ZN3std", "_ZN5alloc", "_ZN17compiler_builtins", "_Unwind_",
+    "__gcc_personality", "__gxx_personality", "__CxxFrameHandler", "__chkstk", "__security_", "__stack_chk_",
+];
+
+#[rustfmt::skip]
+const SYSTEM_PREFIXES: &[&str] = &[
+    "pthread_", "__pthread_", "__libc_", "__cxa_", "__errno", "__tls_get_addr", "__xpg_", "__isoc99_", "__fprintf_chk",
+    "__sprintf_chk", "__memcpy_chk", "__memset_chk", "__strcpy_chk", "sigaction", "sigaltstack", "posix_", "_tlv_",
+    "dyld_", "_dyld_", "dl_iterate_phdr", "mach_", "_NSGetEnviron", "_NSGetExecutablePath",
+];
+
+#[rustfmt::skip]
+const SYSTEM_SYMBOLS: &[&str] = &[
+    // Memory and strings
+    "memcpy", "memmove", "memset", "memcmp", "bcmp", "memchr", "memrchr", "strlen", "strnlen", "strcmp", "strncmp",
+    "strcpy", "strncpy", "strchr", "strrchr", "strstr", "strdup", "strerror", "strerror_r", "wcslen",
+    // Allocation
+    "malloc", "calloc", "realloc", "free", "aligned_alloc", "memalign", "valloc", "mmap", "munmap", "mprotect",
+    "madvise",
+    // Process and environment
+    "abort", "exit", "_exit", "atexit", "getenv", "setenv", "unsetenv", "getpid", "getppid", "getuid", "geteuid",
+    "sysconf", "raise", "signal", "kill", "fork", "execve", "execvp", "waitpid", "environ", "getcwd", "chdir",
+    // Files and I/O
+    "open", "open64", "openat", "close", "read", "write", "pread", "pread64", "pwrite", "pwrite64", "readv", "writev",
+    "lseek", "lseek64", "fstat", "fstat64", "stat", "stat64", "lstat", "lstat64", "statx", "fcntl", "ioctl", "poll",
+    "dup", "dup2", "pipe", "pipe2", "unlink", "rename", "mkdir", "rmdir", "opendir", "readdir", "readdir64", "closedir",
+    "realpath", "readlink", "isatty", "fopen", "fclose", "fread", "fwrite", "fflush", "fprintf", "printf", "sprintf",
+    "snprintf", "vsnprintf", "puts", "fputs", "stdout", "stderr", "stdin",
+    // Threads and time
+    "sched_yield", "nanosleep", "clock_gettime", "gettimeofday", "time", "localtime_r", "gmtime_r", "syscall",
+    "sem_wait", "sem_post",
+    // Dynamic loading
+    "dlopen", "dlclose", "dlsym", "dlvsym", "dlerror", "dladdr", "dlinfo",
+    // Networking
+    "socket", "bind", "listen", "accept", "accept4", "connect", "send", "sendto", "recv", "recvfrom", "shutdown",
+    "getaddrinfo", "freeaddrinfo", "setsockopt", "getsockopt",
+    // Math
+    "sin", "cos", "tan", "exp", "exp2", "log", "log2", "log10", "pow", "sqrt", "floor", "ceil", "round", "trunc",
+    "fmod", "fma", "sinf", "cosf", "tanf", "expf", "logf", "powf", "sqrtf", "floorf", "ceilf", "roundf", "truncf",
+    "fmodf", "fmaf",
+    // Windows
+    "GetLastError", "SetLastError", "GetProcAddress", "LoadLibraryA", "LoadLibraryW", "LoadLibraryExW",
+    "FreeLibrary", "GetModuleHandleA", "GetModuleHandleW", "GetModuleHandleExW", "GetModuleFileNameW",
+    "GetCurrentProcess", "GetCurrentThread", "GetCurrentProcessId", "GetCurrentThreadId", "ExitProcess",
+    "VirtualAlloc", "VirtualFree", "VirtualProtect", "VirtualQuery", "HeapAlloc", "HeapFree", "HeapReAlloc",
+    "GetProcessHeap", "CloseHandle", "CreateFileW", "ReadFile", "WriteFile", "WriteConsoleW", "GetStdHandle",
+    "TlsAlloc", "TlsFree", "TlsGetValue", "TlsSetValue", "AcquireSRWLockExclusive", "ReleaseSRWLockExclusive",
+    "AcquireSRWLockShared", "ReleaseSRWLockShared", "WaitForSingleObject", "Sleep", "SwitchToThread",
+    "FormatMessageW", "QueryPerformanceCounter", "QueryPerformanceFrequency", "GetSystemInfo",
+    "AddVectoredExceptionHandler", "SetThreadStackGuarantee", "RtlCaptureContext", "RtlLookupFunctionEntry",
+    "RtlVirtualUnwind", "_tls_index", "_tls_used", "_fltused",
+];
+
+// Returns true for the integer and floating point helpers of compiler-rt and libgcc, e.g. `__udivti3`.
+fn is_compiler_builtin(name: &str) -> bool {
+    #[rustfmt::skip]
+    const OPERATIONS: &[&str] = &[
+        "add", "sub", "mul", "div", "udiv", "mod", "umod", "divmod", "udivmod", "neg", "ashl", "ashr", "lshr", "cmp",
+        "ucmp", "fix", "fixuns", "float", "floatun", "extend", "trunc", "pow", "clz", "ctz", "popcount", "bswap",
+        "mulo", "eq", "ne", "ge", "gt", "le", "lt", "unord",
+    ];
+    const TYPES: &[&str] = &["si", "di", "ti", "sf", "df", "tf", "xf", "hf"];
+
+    let Some(rest) = name.strip_prefix("__") else {
+        return false;
+    };
+    // Strip the operand count suffix, e.g. "3" in "__udivti3"
+    let rest = rest.trim_end_matches(|c: char| c.is_ascii_digit());
+    OPERATIONS.iter().any(|op| {
+        rest.strip_prefix(op).is_some_and(|types| {
+            !types.is_empty()
+                && types.len() % 2 == 0
+                && types.as_bytes().chunks(2).all(|ty| TYPES.iter().any(|known| known.as_bytes() == ty))
+        })
+    })
+}
+
 /// Returns the list of symbols imported by a static library.
+///
+/// With the `parallel` feature, the members of the archive are scanned in parallel.
 pub fn archive_imports(path: &Path) -> Result<Vec<Import>, Error> {
+    let _timing = Timing::start(|| format!("archive_imports({})", path.display()));
     let mut fd = File::open(path)?;
     let mut buffer = Vec::new();
     fd.read_to_end(&mut buffer)?;
 
+    let object = Object::parse(&buffer)?;
+    archive_imports_from(&object, &buffer)
+}
+
+/// Returns the list of symbols imported by a parsed static library or object file.
+///
+/// `buffer` must be the data that `object` was parsed from.  See also
+/// [`ParsedBinary::imports`](crate::ParsedBinary::imports).
+pub fn archive_imports_from(object: &Object, buffer: &[u8]) -> Result<Vec<Import>, Error> {
     let mut imports = HashSet::new();
-    get_unique_imports(&buffer, &mut imports)?;
+    get_unique_imports(object, buffer, &mut imports)?;
     Ok(imports.into_iter().map(|s| Import { name: s }).collect())
 }
 
-fn get_unique_imports(buffer: &[u8], imports: &mut HashSet<String>) -> Result<(), Error> {
-    let object = Object::parse(&buffer)?;
+/// Filters `imports` down to the symbols that may be provided by a plugin, i.e. those classified as
+/// [`ImportCategory::Other`] for the current cargo build target, and not exported by any of `system_libs`.
+///
+/// `system_libs` may list system dynamic libraries that the program links to, beyond the C runtime.
+pub fn plugin_candidates(
+    imports: impl IntoIterator<Item = Import>,
+    system_libs: &[&Path],
+) -> Result<Vec<Import>, Error> {
+    let mut system_exports = HashSet::new();
+    for path in system_libs {
+        system_exports.extend(crate::exports::dylib_exports(path)?.into_iter().map(|export| export.name));
+    }
+    let target = crate::util::current_target();
+    Ok(imports
+        .into_iter()
+        .filter(|import| import.category_for(&target) == ImportCategory::Other)
+        .filter(|import| !system_exports.contains(&import.name))
+        .collect())
+}
+
+// Scans each member of an archive, in parallel with the `parallel` feature, returning the results in member order.
+// Members that can't be extracted are skipped.
+fn scan_members<'a, T: Send>(
+    archive: &archive::Archive<'a>,
+    buffer: &'a [u8],
+    scan: impl Fn(&'a str, &'a [u8]) -> Result<T, Error> + Sync,
+) -> Result<Vec<T>, Error> {
+    let members = archive.members().into_iter();
+    let members = members.filter_map(|mbr_name| Some((mbr_name, archive.extract(mbr_name, buffer).ok()?)));
+    let members = members.collect::<Vec<_>>();
+    // Errors are not `Send`, so they are carried across threads as messages.
+    let scan = |(mbr_name, slice)| scan(mbr_name, slice).map_err(|err| err.to_string());
+    #[cfg(feature = "parallel")]
+    let results = {
+        use rayon::prelude::*;
+        members.into_par_iter().map(scan).collect::<Vec<_>>()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let results = members.into_iter().map(scan).collect::<Vec<_>>();
+    results.into_iter().map(|result| result.map_err(Error::from)).collect()
+}
+
+fn get_unique_imports(object: &Object, buffer: &[u8], imports: &mut HashSet<String>) -> Result<(), Error> {
     match object {
         Object::Archive(archive) => {
-            for mbr_name in archive.members() {
-                if let Ok(slice) = archive.extract(mbr_name, &buffer) {
-                    get_unique_imports(slice, imports)?;
-                }
-            }
+            let member_imports = scan_members(archive, buffer, |_, slice| {
+                let mut imports = HashSet::new();
+                get_unique_imports(&Object::parse(slice)?, slice, &mut imports)?;
+                Ok(imports)
+            })?;
+            imports.extend(member_imports.into_iter().flatten());
             Ok(())
         }
         Object::Elf(elf) => {
@@ -72,11 +265,11 @@ fn get_unique_imports(buffer: &[u8], imports: &mut HashSet<String>) -> Result<()
             }
 
             match mach {
-                mach::Mach::Binary(macho) => macho_imports(&macho, imports),
+                mach::Mach::Binary(macho) => macho_imports(macho, imports),
                 mach::Mach::Fat(multi) => match multi.get(0) {
                     Ok(mach::SingleArch::MachO(macho)) => macho_imports(&macho, imports),
                     Ok(mach::SingleArch::Archive(_)) => {
-                        Err(format!("The first object in a multiarch binary is not MachO").into())
+                        Err("The first object in a multiarch binary is not MachO".to_string().into())
                     }
                     Err(err) => Err(err.to_string().into()),
                 },
@@ -98,3 +291,192 @@ fn get_unique_imports(buffer: &[u8], imports: &mut HashSet<String>) -> Result<()
         _ => Err(format!("Unsupported object type: {object:?}").into()),
     }
 }
+
+/// A site in a static library that references an imported symbol.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ImportSite {
+    /// Name of the archive member containing the reference, or `None` if the scanned file is an object file.
+    pub object: Option<String>,
+    /// Name of the section containing the reference.
+    pub section: String,
+    /// Name of the function (or other symbol) containing the reference, if known.
+    ///
+    /// Names are reported as they appear in the symbol table, i.e. mangled.
+    pub function: Option<String>,
+}
+
+/// Returns the sites referencing each symbol imported by a static library.
+///
+/// This may be used to tell which functions of a program would fail if an imported symbol is missing from the stub
+/// configuration.  With the `parallel` feature, the members of the archive are scanned in parallel.
+pub fn archive_import_sites(path: &Path) -> Result<HashMap<String, Vec<ImportSite>>, Error> {
+    let _timing = Timing::start(|| format!("archive_import_sites({})", path.display()));
+    let buffer = std::fs::read(path)?;
+    let object = Object::parse(&buffer)?;
+    archive_import_sites_from(&object, &buffer)
+}
+
+/// Returns the sites referencing each symbol imported by a parsed static library or object file.
+///
+/// `buffer` must be the data that `object` was parsed from.  See also
+/// [`ParsedBinary::import_sites`](crate::ParsedBinary::import_sites).
+pub fn archive_import_sites_from(object: &Object, buffer: &[u8]) -> Result<HashMap<String, Vec<ImportSite>>, Error> {
+    let mut sites = HashMap::new();
+    get_import_sites(object, buffer, None, &mut sites)?;
+    for sites in sites.values_mut() {
+        sites.sort();
+        sites.dedup();
+    }
+    Ok(sites)
+}
+
+// Symbols defined in a section, as sorted (start, end, name), where `end` is `None` if the size is unknown.
+struct SectionSymbols<'a>(Vec<(u64, Option<u64>, &'a str)>);
+
+impl<'a> SectionSymbols<'a> {
+    fn new(mut symbols: Vec<(u64, Option<u64>, &'a str)>) -> SectionSymbols<'a> {
+        symbols.sort();
+        SectionSymbols(symbols)
+    }
+
+    // Returns the last symbol starting at or before `offset`, if it extends over `offset`.
+    fn lookup(&self, offset: u64) -> Option<String> {
+        let idx = self.0.partition_point(|(start, _, _)| *start <= offset);
+        let (_, end, name) = self.0.get(idx.checked_sub(1)?)?;
+        match end {
+            Some(end) if *end <= offset => None,
+            _ => Some(name.to_string()),
+        }
+    }
+}
+
+fn get_import_sites(
+    object: &Object,
+    buffer: &[u8],
+    member: Option<&str>,
+    sites: &mut HashMap<String, Vec<ImportSite>>,
+) -> Result<(), Error> {
+    let mut add_site = |name: &str, section: &str, function: Option<String>| {
+        sites.entry(name.to_string()).or_default().push(ImportSite {
+            object: member.map(|member| member.to_string()),
+            section: section.to_string(),
+            function,
+        });
+    };
+    match object {
+        Object::Archive(archive) => {
+            let member_sites = scan_members(archive, buffer, |mbr_name, slice| {
+                let mut sites = HashMap::new();
+                get_import_sites(&Object::parse(slice)?, slice, Some(mbr_name), &mut sites)?;
+                Ok(sites)
+            })?;
+            for (name, member_sites) in member_sites.into_iter().flatten() {
+                sites.entry(name).or_default().extend(member_sites);
+            }
+            Ok(())
+        }
+        Object::Elf(elf) => {
+            for (rsec_idx, rsection) in &elf.shdr_relocs {
+                let sec_idx = elf.section_headers[*rsec_idx].sh_info as usize;
+                let Some(section) = elf.section_headers.get(sec_idx) else {
+                    continue;
+                };
+                let sec_name = elf.shdr_strtab.get_at(section.sh_name).unwrap_or_default();
+                let symbols = elf.syms.iter().filter(|sym| sym.st_shndx == sec_idx);
+                let symbols = symbols.filter(|sym| matches!(sym.st_type(), elf::sym::STT_FUNC | elf::sym::STT_OBJECT));
+                let symbols = symbols.filter_map(|sym| {
+                    let name = elf.strtab.get_at(sym.st_name).filter(|name| !name.is_empty())?;
+                    Some((
+                        sym.st_value,
+                        Some(sym.st_value + sym.st_size).filter(|_| sym.st_size > 0),
+                        name,
+                    ))
+                });
+                let symbols = SectionSymbols::new(symbols.collect());
+                for reloc in rsection {
+                    if let Some(sym) = elf.syms.get(reloc.r_sym) {
+                        // check st_shndx as well because of https://github.com/m4b/goblin/issues/288
+                        if sym.is_import() && sym.st_shndx == 0 {
+                            if let Some(sym_name) = elf.strtab.get_at(sym.st_name) {
+                                add_site(sym_name, sec_name, symbols.lookup(reloc.r_offset));
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        Object::Mach(mach) => {
+            let first_arch;
+            let macho = match mach {
+                mach::Mach::Binary(macho) => macho,
+                mach::Mach::Fat(multi) => match multi.get(0) {
+                    Ok(mach::SingleArch::MachO(macho)) => {
+                        first_arch = macho;
+                        &first_arch
+                    }
+                    Ok(mach::SingleArch::Archive(_)) => {
+                        return Err("The first object in a multiarch binary is not MachO".to_string().into())
+                    }
+                    Err(err) => return Err(err.to_string().into()),
+                },
+            };
+            let Some(symbols) = macho.symbols.as_ref() else {
+                return Ok(());
+            };
+            let defined = symbols.iter().filter_map(Result::ok).filter(|(name, nlist)| {
+                nlist.n_type & mach::symbols::N_TYPE == mach::symbols::N_SECT && !name.is_empty()
+            });
+            let defined = defined.collect::<Vec<_>>();
+            let relocations = macho.relocations().map_err(|err| err.to_string())?;
+            for (sec_idx, reloc_iter, section) in relocations {
+                // Sections are numbered from 1 across segments, and objects have a single segment.
+                let sec_symbols = defined.iter().filter(|(_, nlist)| nlist.n_sect == sec_idx + 1);
+                let sec_symbols = sec_symbols.map(|(name, nlist)| (nlist.n_value, None, *name));
+                let sec_symbols = SectionSymbols::new(sec_symbols.collect());
+                let sec_name = format!("{}.{}", section.segname()?, section.name()?);
+                for reloc in reloc_iter {
+                    let reloc = reloc?;
+                    if reloc.is_extern() {
+                        let (name, _) = symbols.get(reloc.r_symbolnum())?;
+                        let function = sec_symbols.lookup(section.addr + reloc.r_address as u64);
+                        add_site(name, &sec_name, function);
+                    }
+                }
+            }
+            Ok(())
+        }
+        Object::COFF(coff) => {
+            if let Ok(Some(strtab)) = coff.header.strings(buffer) {
+                if let Ok(Some(symtab)) = coff.header.symbols(buffer) {
+                    let defined =
+                        symtab.iter().filter(|(_, _, sym)| sym.section_number > 0 && sym.is_function_definition());
+                    let defined = defined.filter_map(|(_, inline_name, sym)| {
+                        // Inline names are borrowed from the symbol table, others from the string table.
+                        let name = inline_name.or_else(|| strtab.get_at(sym.name_offset()? as usize))?;
+                        Some((sym.section_number, sym.value, name))
+                    });
+                    let defined = defined.collect::<Vec<_>>();
+                    for (sec_idx, section) in coff.sections.iter().enumerate() {
+                        // Section numbers are 1-based.
+                        let sec_symbols = defined.iter().filter(|(number, _, _)| *number as usize == sec_idx + 1);
+                        let sec_symbols = sec_symbols.map(|(_, value, name)| (*value as u64, None, *name));
+                        let sec_symbols = SectionSymbols::new(sec_symbols.collect());
+                        let sec_name = section.name()?;
+                        for reloc in section.relocations(buffer)? {
+                            let Some((_, sym)) = symtab.get(reloc.symbol_table_index as usize) else {
+                                continue;
+                            };
+                            if sym.section_number == pe::symbol::IMAGE_SYM_UNDEFINED {
+                                let function = sec_symbols.lookup(reloc.virtual_address as u64);
+                                add_site(sym.name(&strtab)?, sec_name, function);
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        _ => Err(format!("Unsupported object type: {object:?}").into()),
+    }
+}
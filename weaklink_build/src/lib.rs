@@ -5,24 +5,48 @@ macro_rules! write_lines {
     ($dest:expr, $($line:literal)+ $(, $name:ident=$value:expr)*) => (write!($dest, concat!($($line,"\n"),+) $(, $name=$value)*))
 }
 
+mod binary;
+mod bindings;
+mod cache;
+mod call_graph;
+mod candidates;
+mod compat;
+mod deny;
 pub mod exports;
 pub mod imports;
-mod stub_gen;
+mod lint;
+mod machine;
+pub mod locate;
+mod size_report;
+pub mod stub_gen;
 mod util;
 
 use std::borrow::{Cow, ToOwned};
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
 use std::{env, fmt};
 
-use util::iter_fmt;
+use util::{iter_fmt, rust_ident};
 
-use crate::stub_gen::TargetOs;
+use crate::stub_gen::{StubGenerator, StubKind, TargetOs};
+
+pub use binary::ParsedBinary;
+pub use cache::{Cache, CacheKey};
+pub use call_graph::{CallGraph, Caller};
+pub use candidates::dylib_candidates;
+pub use compat::{CompatMatrix, PluginCompat};
+pub use deny::RUNTIME_SYMBOLS;
+pub use goblin;
+pub use lint::Lint;
+pub use machine::Machine;
+pub use size_report::{GroupSize, SizeReport};
+pub use weaklink::loading::LibcFlavor;
 
 type Error = Box<dyn std::error::Error>;
+type ArchMatcher = Box<dyn Fn(&str) -> bool>;
 
 #[derive(Clone, Default, Debug)]
 pub struct SymbolStub {
@@ -32,6 +56,25 @@ pub struct SymbolStub {
     pub export_name: String,
     /// If true, generate a function that returns symbol address when called.
     pub is_data: bool,
+    /// Version of the wrapped library since which the symbol is deprecated, if any.
+    ///
+    /// A warning is issued at runtime when a deprecated symbol is first resolved.
+    /// See [`Library::set_deprecation_handler`](weaklink::Library::set_deprecation_handler).
+    pub deprecated_since: Option<String>,
+    /// Name variant and the symbol's name in dylibs of that variant, for variants in which the name differs from
+    /// `import_name`.  See [`Config::add_name_variant`].
+    pub variant_names: Vec<(String, String)>,
+    /// Export of the wrapped library that returns a struct of pointers, and the index of the symbol's pointer in it,
+    /// if the symbol is obtained from such a struct rather than by its name.  See [`SymbolStub::vtable_slot`].
+    pub vtable_slot: Option<(String, usize)>,
+    /// Factory of a COM-style interface, and the index of the symbol among the interface's methods, if the symbol is
+    /// a method of such an interface.  See [`SymbolStub::interface_slot`].
+    pub interface_slot: Option<(String, usize)>,
+    /// Signature that the host expects the symbol to have, if it should be checked.  See [`SymbolStub::signature`].
+    pub signature: Option<String>,
+    /// Version of the symbol to bind to, such as `LIBFOO_2.0` for `foo@LIBFOO_2.0`, if any.
+    /// See [`SymbolStub::version`].
+    pub version: Option<String>,
 }
 
 impl SymbolStub {
@@ -41,6 +84,12 @@ impl SymbolStub {
             import_name: name.to_string(),
             export_name: name.to_string(),
             is_data: false,
+            deprecated_since: None,
+            variant_names: Vec::new(),
+            vtable_slot: None,
+            interface_slot: None,
+            signature: None,
+            version: None,
         }
     }
 
@@ -51,8 +100,81 @@ impl SymbolStub {
             export_name: exp_name.to_string(),
             import_name: imp_name.to_string(),
             is_data: true,
+            deprecated_since: None,
+            variant_names: Vec::new(),
+            vtable_slot: None,
+            interface_slot: None,
+            signature: None,
+            version: None,
         }
     }
+
+    /// Marks the symbol as deprecated since the specified version of the wrapped library.
+    pub fn deprecated_since(mut self, version: &str) -> SymbolStub {
+        self.deprecated_since = Some(version.to_string());
+        self
+    }
+
+    /// Sets the name of the symbol in dylibs of the specified name variant.
+    pub fn variant(mut self, variant: &str, import_name: &str) -> SymbolStub {
+        self.variant_names.push((variant.to_string(), import_name.to_string()));
+        self
+    }
+
+    /// Obtains the symbol from a struct of pointers, such as a table of functions returned by a plugin's entry point,
+    /// rather than by its name.
+    ///
+    /// `provider` is an export of the wrapped library, with the signature of
+    /// [`VtableProvider`](weaklink::VtableProvider): `extern "C" fn() -> *const *const c_void`.  The symbol's address
+    /// is the pointer at `index` in the struct that it returns, so the struct must consist of pointer-sized members.
+    /// Callers link to the stub as usual.
+    ///
+    /// ```
+    /// # use weaklink_build::SymbolStub;
+    /// let stubs = ["foo_open", "foo_read", "foo_close"]
+    ///     .iter()
+    ///     .enumerate()
+    ///     .map(|(index, name)| SymbolStub::new(name).vtable_slot("foo_get_api", index));
+    /// # assert_eq!(stubs.count(), 3);
+    /// ```
+    pub fn vtable_slot(mut self, provider: &str, index: usize) -> SymbolStub {
+        self.vtable_slot = Some((provider.to_string(), index));
+        self
+    }
+
+    /// Binds the symbol to a method of a COM-style interface, rather than looking it up by its name.
+    ///
+    /// This supports plugins that export only a factory, such as `DllGetClassObject` on Windows.  The interface is
+    /// created at runtime by the callback set for `factory` via
+    /// [`Library::set_factory`](weaklink::Library::set_factory), and the symbol's address is entry `index` of the
+    /// method table that the interface's first member points to.  Callers pass the interface pointer, as returned by
+    /// [`Library::interface`](weaklink::Library::interface), as the first argument, as with any COM method.
+    pub fn interface_slot(mut self, factory: &str, index: usize) -> SymbolStub {
+        self.interface_slot = Some((factory.to_string(), index));
+        self
+    }
+
+    /// Sets the signature that the host expects the symbol to have, e.g. `"fn(u32) -> u32"`.
+    ///
+    /// In debug builds, the runtime compares its [hash](weaklink::signature_hash) with the one reported by the
+    /// dylib's [`Config::signature_query`] export when the symbol is first resolved, and warns on a mismatch, which
+    /// catches ABI drift between the host and the plugin early.  Whitespace in the signature is insignificant.
+    pub fn signature(mut self, signature: &str) -> SymbolStub {
+        self.signature = Some(signature.to_string());
+        self
+    }
+
+    /// Binds the symbol to the specified version of its name, such as `LIBFOO_2.0` for `foo@LIBFOO_2.0`, rather than
+    /// to its default version.
+    ///
+    /// Plugins built with a version script may export several versions of a symbol.  On Linux with glibc, the runtime
+    /// looks the symbol up via `dlvsym`, so that it binds to this version deterministically, and fails to resolve it
+    /// if the dylib doesn't export it.  Elsewhere, the version is ignored.  It doesn't apply to the names of variants,
+    /// nor to symbols looked up through a [`Config::bootstrap_export`].
+    pub fn version(mut self, version: &str) -> SymbolStub {
+        self.version = Some(version.to_string());
+        self
+    }
 }
 
 pub struct Config {
@@ -61,11 +183,166 @@ pub struct Config {
     /// Target triple to generate code for.
     pub target: String,
     /// Dylib names to try when loading implicitly.
+    ///
+    /// On MacOS, names may start with the `@rpath/`, `@loader_path/` or `@executable_path/` tokens, which are
+    /// expanded against the main executable's run paths.  Frameworks may be specified as `framework:<Name>`, in which
+    /// case `<Name>.framework/<Name>` is looked up in the standard framework search paths.
+    ///
+    /// Names may contain placeholders, such as `libplugin-{version}.so`, which are expanded at runtime from the
+    /// values set via [`Library::set_vars`](weaklink::Library::set_vars).
+    ///
+    /// Names may be tagged for a C library, such as `musl:libfoo.so`, in which case they are only tried on systems
+    /// whose C library it is.  See [`Config::add_dylib_names_for`].
+    ///
+    /// See [`Config::add_dylib_candidates`] for the conventional names of a library on the target.
     pub dylib_names: Vec<String>,
+    /// File names of dylibs that [`Library::load_with_dependencies`](weaklink::Library::load_with_dependencies)
+    /// pre-loads from the directory of the main dylib, in the specified order.
+    pub dependencies: Vec<String>,
     /// Whether to perform symbol name adjustment. 
     /// 
     /// Currently this handles a quirk of MacOSX linker, which automatically adds leading underscores to all exports.
     pub adjust_symbol_names: bool,
+    /// Groups whose symbols are called on hot paths.
+    ///
+    /// Symbols of these groups are placed at the start of the symbol table, so that their entries don't share
+    /// cache lines with rarely used symbols.
+    pub hot_groups: Vec<String>,
+    /// Groups whose stubs are patched into direct jumps after the first resolution.
+    ///
+    /// This eliminates the symbol table load from each call, at the cost of the stubs no longer following changes
+    /// of the symbol table, so [`LibraryInstance::with`](weaklink::LibraryInstance::with) rejects the library.
+    /// Patching is best-effort: it is skipped if the code pages can't be made writable, or if the target is out of
+    /// range of a direct jump.  Currently supported on x86_64 and aarch64; not performed in checked mode.
+    pub patched_groups: Vec<String>,
+    /// Groups whose symbols are weakly linked by the OS dynamic linker, rather than called through stubs.
+    ///
+    /// No stubs are generated for these symbols, so callers bind to the wrapped library directly, without any call
+    /// overhead.  Instead, the stubs reference the symbols weakly, which lets the runtime report their availability
+    /// via the [`Group`](weaklink::Group) API as usual.  The host must link against the wrapped library such that
+    /// absent symbols are tolerated, e.g. via `-weak-l` on MacOS, or via weak declarations on ELF platforms.
+    /// Only code symbols whose export name matches their import name are supported.  Not supported on Windows.
+    pub native_weak_groups: Vec<String>,
+    /// Groups that the program can't run without.
+    ///
+    /// If any are listed, the generated source also defines `pub fn init_required() -> Result<(), weaklink::Error>`,
+    /// which loads the library unless it's loaded already, then [resolves](weaklink::Group::resolve_global) these
+    /// groups permanently, each after the required groups it depends on, stopping at the first failure.  This gives
+    /// the host a single call to initialize the library with, instead of resolving each group by hand.
+    pub required_groups: Vec<String>,
+    /// Whether to resolve all symbols as soon as the library is loaded, rather than group by group on first use.
+    ///
+    /// Groups with missing symbols then fail to resolve right away.  This is the default of
+    /// [`Library::set_eager_binding`](weaklink::Library::set_eager_binding), which the host may override.
+    pub eager_binding: bool,
+    /// Whether to place the stubs of each group into a text section of its own.
+    ///
+    /// Sections are named `.text.weaklink.<group>` on ELF targets, so that linker scripts may place them, and
+    /// `--gc-sections` may drop those of groups that are never called.  On Windows, `.text$weaklink_<group>`
+    /// sections are merged into `.text` by group name.  A stub belonging to several groups is placed in the section
+    /// of the first of them by name.  Has no effect on MacOS.
+    pub group_sections: bool,
+    /// Whether to emit a C management API for the library, for hosts written in C.
+    ///
+    /// The generated source then also defines the following `extern "C"` functions, which are declared by the header
+    /// generated by [`Config::generate_c_header`]:
+    /// - `bool <name>_load(void)` and `bool <name>_load_from(const char *path)`, see
+    ///   [`Library::load`](weaklink::Library::load) and [`Library::load_from`](weaklink::Library::load_from).
+    /// - `bool <name>_resolve_<group>(void)` for each group, see
+    ///   [`Group::resolve_global`](weaklink::Group::resolve_global).
+    /// - `const char *<name>_last_error(void)`, which returns the error message of the last failed call on the
+    ///   current thread, or `NULL` if the last call succeeded.
+    /// - `uint32_t <name>_last_error_code(void)`, which returns the [`ErrorCode`](weaklink::ErrorCode) of the last
+    ///   failed call on the current thread, or 0 if the last call succeeded.
+    ///
+    /// The stubs crate must be built as a `staticlib` (or `cdylib`) for linking into the C program.
+    pub c_api: bool,
+    /// Names under which legacy code of the host opens the library itself, e.g. via `dlopen("libfoo.so", ...)`.
+    ///
+    /// If any are listed, the generated source also defines `pub fn register_dlopen_shim()`, which redirects these
+    /// names to the library in the `weaklink::dlopen_shim` module, i.e. its `weaklink_dlopen` and `weaklink_dlsym`
+    /// functions, so that the legacy code shares the library's handle and bookkeeping.  The `dlopen-shim` feature
+    /// of `weaklink` must be enabled.  With [`Config::c_api`], it's also exported as
+    /// `void <name>_register_dlopen_shim(void)`, and declared along with the shim by [`Config::generate_c_header`].
+    pub dlopen_names: Vec<String>,
+    /// Path of a `fn(&mut weaklink::Options)`, which establishes the runtime policies of the library on its first
+    /// use, e.g. `"crate::configure_foo"`.  See [`Library::configure_once`](weaklink::Library::configure_once).
+    pub configure_hook: Option<String>,
+    /// Whether to emit the symbol names of each group with the group, rather than a name table for the whole library.
+    ///
+    /// The runtime then assembles the names from the groups on first use.  Combined with [`Config::group_cfg`], the
+    /// names of groups that are compiled out are dropped along with them, so that binaries embedding a large
+    /// configuration, but compiling in a small subset of it, carry proportionally sized name tables.  Names of
+    /// symbols shared by several groups are emitted with each of them.
+    pub group_name_tables: bool,
+    /// Whether to emit the symbol names of the library in a compressed encoding, rather than as separate literals.
+    ///
+    /// The names are front-coded, so that names sharing long prefixes, such as mangled C++ names, take a fraction
+    /// of the space.  The runtime decodes them on first use.  May not be combined with
+    /// [`Config::group_name_tables`].
+    pub compress_names: bool,
+    /// Whether to emit only hashes of the symbol names, so that the binary doesn't carry a plaintext list of the
+    /// library's API.
+    ///
+    /// The names are then supplied at runtime from the manifest generated by [`Config::generate_manifest`], which is
+    /// shipped separately, via [`Library::set_symbol_names`](weaklink::Library::set_symbol_names) or
+    /// [`Library::load_symbol_names`](weaklink::Library::load_symbol_names).  Until then, symbols can't be resolved,
+    /// but [`Library::index_of`](weaklink::Library::index_of) works.  Typically enabled per build profile:
+    ///
+    /// ```no_run
+    /// # let mut config = weaklink_build::Config::new("foo");
+    /// config.hash_names = std::env::var("PROFILE").unwrap() == "release";
+    /// ```
+    ///
+    /// The hashes only hide the names from casual inspection: names can be confirmed by hashing candidates.  The
+    /// stubs themselves are exported under the symbols' export names, so the host binary should be stripped as well.
+    /// May not be combined with [`Config::group_name_tables`], [`Config::compress_names`] or name variants.
+    pub hash_names: bool,
+    /// A key to obfuscate the symbol names with, so that static analysis of the binary doesn't reveal which symbols
+    /// the host uses.
+    ///
+    /// The names are decrypted transiently whenever a symbol is looked up, and the key is requested from a provider
+    /// set at runtime via [`Library::set_key_provider`](weaklink::Library::set_key_provider), so it isn't part of the
+    /// stubs.  The host should construct it in a way that isn't obvious from its code either.  Names reported by the
+    /// runtime, e.g. to the address filter or in error messages, are empty or replaced by symbol indices.
+    ///
+    /// The obfuscation keeps the names from being read off the binary, but doesn't resist analysis of the running
+    /// process.  May not be combined with other name storage, or with name variants.
+    pub obfuscation_key: Option<u64>,
+    /// Name of an export of the dynamic library, through which symbols are looked up instead of its dynamic symbol
+    /// table, e.g. `"plugin_get_api"`.
+    ///
+    /// This suits libraries that strip their dynamic symbol table, and provide a registration function instead.  The
+    /// export must have the signature of [`BootstrapFn`](weaklink::loading::BootstrapFn):
+    /// `extern "C" fn(name: *const c_char) -> *const c_void`.  It's called with the import name of each symbol that
+    /// is resolved, as well as with the markers of name variants, and returns null for those it doesn't provide.
+    pub bootstrap_export: Option<String>,
+    /// Name of an export of the dynamic library, which reports the signatures of its symbols, e.g.
+    /// `"plugin_signature_hash"`.
+    ///
+    /// The export must have the signature of [`SignatureQuery`](weaklink::SignatureQuery):
+    /// `extern "C" fn(name: *const c_char) -> u64`.  It's called in debug builds with the import name of each symbol
+    /// that has a [`SymbolStub::signature`], and returns the [hash](weaklink::signature_hash) of its signature, or 0
+    /// if it doesn't know the symbol.  See
+    /// [`Library::set_signature_mismatch_handler`](weaklink::Library::set_signature_mismatch_handler).
+    pub signature_query: Option<String>,
+    /// Check the signatures of each group as a whole against those reported by the dynamic library, before the
+    /// group is first resolved.
+    ///
+    /// The stubs embed, for each group with required symbols that have a [`SymbolStub::signature`], the
+    /// [hash](weaklink::group_signature_hash) of their import names and signatures.  The library reports its own in
+    /// the table returned by its `weaklink_sig_hashes` export, which plugins written in Rust produce with
+    /// [`export_signatures!`](weaklink::export_signatures).  Unlike [`Config::signature_query`], this is checked in
+    /// all builds, and a mismatch fails resolution of the group with
+    /// [`ErrorCode::SignatureMismatch`](weaklink::ErrorCode::SignatureMismatch).  Groups that the library doesn't
+    /// list, and libraries without the export, are not checked.
+    pub signature_handshake: bool,
+    /// Template of the names of the accessors of data symbols added by [`Config::add_all_exports_from`], in which
+    /// `{name}` is replaced by the name of the symbol, e.g. `"get_{name}"`.
+    ///
+    /// If `None`, data exports are not added, and have to be added manually via [`SymbolStub::new_data`].  The
+    /// accessors are declared by [`Config::generate_bindings`].
+    pub data_accessor_template: Option<String>,
 
     // The list of symbol stubs created so far.
     stubs: Vec<SymbolStub>,
@@ -73,6 +350,24 @@ pub struct Config {
     stub_by_exp: HashMap<String, usize>,
     // Group name => stub indices in `stubs`.
     groups: HashMap<String, Vec<usize>>,
+    // Group name => indices in `stubs` of symbols that the group may be resolved without.
+    optional_symbols: HashMap<String, Vec<usize>>,
+    // Group name => names of groups that are resolved along with it.
+    group_dependencies: HashMap<String, Vec<String>>,
+    // Group name => minimum version of the wrapped library.
+    min_versions: HashMap<String, String>,
+    // Group name => predicate of the `#[cfg]` attribute of the group's items.
+    group_cfgs: HashMap<String, String>,
+    // Name variants and their marker symbols, in the order they are probed.
+    name_variants: Vec<(String, String)>,
+    // Stub generators registered for targets that they match.
+    generators: Vec<(ArchMatcher, Box<dyn StubGenerator>)>,
+    // Export names of symbols whose table index is frozen, in table order.
+    frozen_indices: Vec<String>,
+    // Patterns of names of symbols in the library that are never stubbed.
+    denied_patterns: Vec<String>,
+    // Names of denied symbols that were left out of groups.
+    skipped_symbols: Vec<String>,
 }
 
 impl Config {
@@ -80,130 +375,596 @@ impl Config {
     /// - [`name`](`Config::name`): The `name` parameter.
     /// - [`target`](`Config::target`): The current cargo build target.
     /// - [`dylib_names`](`Config::dylib_names`): An empty vector.
+    /// - [`dependencies`](`Config::dependencies`): An empty vector.
     /// - [`adjust_symbol_names`](`Config::adjust_symbol_names`): `true`
+    /// - [`hot_groups`](`Config::hot_groups`): An empty vector.
+    /// - [`patched_groups`](`Config::patched_groups`): An empty vector.
+    /// - [`native_weak_groups`](`Config::native_weak_groups`): An empty vector.
+    /// - [`required_groups`](`Config::required_groups`): An empty vector.
+    /// - [`eager_binding`](`Config::eager_binding`): `false`
+    /// - [`group_sections`](`Config::group_sections`): `false`
+    /// - [`c_api`](`Config::c_api`): `false`
+    /// - [`dlopen_names`](`Config::dlopen_names`): An empty vector.
+    /// - [`configure_hook`](`Config::configure_hook`): `None`
+    /// - [`group_name_tables`](`Config::group_name_tables`): `false`
+    /// - [`compress_names`](`Config::compress_names`): `false`
+    /// - [`hash_names`](`Config::hash_names`): `false`
+    /// - [`obfuscation_key`](`Config::obfuscation_key`): `None`
+    /// - [`bootstrap_export`](`Config::bootstrap_export`): `None`
+    /// - [`signature_query`](`Config::signature_query`): `None`
+    /// - [`signature_handshake`](`Config::signature_handshake`): `false`
+    /// - [`data_accessor_template`](`Config::data_accessor_template`): `None`
     pub fn new(name: &str) -> Self {
-        let target = match env::var("TARGET") {
-            Ok(target) => target,
-            Err(_) => env!("TARGET").to_string(), // Fall back to host target
-        };
-
         Config {
             name: name.into(),
-            target: target,
+            target: util::current_target(),
             dylib_names: vec![],
+            dependencies: vec![],
             adjust_symbol_names: true,
+            hot_groups: vec![],
+            patched_groups: vec![],
+            native_weak_groups: vec![],
+            required_groups: vec![],
+            eager_binding: false,
+            group_sections: false,
+            c_api: false,
+            dlopen_names: vec![],
+            configure_hook: None,
+            group_name_tables: false,
+            compress_names: false,
+            hash_names: false,
+            obfuscation_key: None,
+            bootstrap_export: None,
+            signature_query: None,
+            signature_handshake: false,
+            data_accessor_template: None,
             stubs: Vec::new(),
             stub_by_exp: HashMap::new(),
             groups: HashMap::new(),
+            optional_symbols: HashMap::new(),
+            group_dependencies: HashMap::new(),
+            min_versions: HashMap::new(),
+            group_cfgs: HashMap::new(),
+            name_variants: Vec::new(),
+            generators: Vec::new(),
+            frozen_indices: Vec::new(),
+            denied_patterns: Vec::new(),
+            skipped_symbols: Vec::new(),
         }
     }
 
+    /// Create a build configuration for symbols that the main program may or may not provide, e.g. functions of the
+    /// C library that only newer versions of it have, such as `getrandom`.
+    ///
+    /// The library is loaded from the main program (see [`weaklink::loading::main_program`]), rather than a dylib,
+    /// and its groups are resolved like those of any other library.  Otherwise, the defaults are those of
+    /// [`Config::new`].
+    pub fn main_program(name: &str) -> Self {
+        let mut config = Config::new(name);
+        config.dylib_names = vec![weaklink::loading::MAIN_PROGRAM.to_string()];
+        config
+    }
+
+    /// Create a build configuration for symbols of the vDSO that the Linux kernel maps into each process, which may
+    /// or may not provide them depending on the kernel version and architecture, e.g. `__vdso_clock_gettime64`.
+    ///
+    /// The library is loaded from the vDSO (see [`weaklink::loading::vdso`]), rather than a dylib, and its groups
+    /// are resolved like those of any other library.  Otherwise, the defaults are those of [`Config::new`].
+    pub fn vdso(name: &str) -> Self {
+        let mut config = Config::new(name);
+        config.dylib_names = vec![weaklink::loading::VDSO.to_string()];
+        config
+    }
+
     /// Add a group of symbols that may be resolved all at once using the specified group name.  
     /// A symbol may appear in more than one group.
-    pub fn add_symbol_group<'a>(
+    pub fn add_symbol_group(
         &mut self,
         group_name: &str,
         symbols: impl IntoIterator<Item = SymbolStub>,
     ) -> Result<(), Error> {
-        if let Some(_) = self.groups.get(group_name) {
+        if self.groups.contains_key(group_name) {
             Err(format!("Group \"{group_name}\" already exists"))?;
         }
         let mut group_syms = Vec::new();
         for symbol in symbols {
-            let sym_idx = match self.stub_by_exp.entry(symbol.export_name.clone()) {
-                Entry::Occupied(o) => {
-                    let idx = *o.get();
-                    let existing = &self.stubs[idx];
-                    if existing.is_data != symbol.is_data {
-                        return Err(format!(
-                            "Stub for symbol '{}' already exists, but with a different `is_data` value: {}",
-                            existing.export_name, existing.is_data
-                        )
-                        .into());
-                    }
-                    if self.stubs[idx].import_name != symbol.import_name {
-                        return Err(format!(
-                            "Stub for symbol '{}' already exists, but with a different `import_name` value: {}",
-                            existing.export_name, existing.import_name
-                        )
-                        .into());
-                    }
-                    idx
+            if self.is_denied(&symbol.import_name) {
+                continue;
+            }
+            group_syms.push(self.add_stub(symbol)?);
+        }
+        self.groups.insert(group_name.to_string(), group_syms);
+        Ok(())
+    }
+
+    /// Add symbols that don't belong to a specific group.
+    ///
+    /// These are placed in the implicit group "all", which may be resolved via
+    /// [`Library::resolve_ungrouped`](weaklink::Library::resolve_ungrouped).
+    pub fn add_symbols(&mut self, symbols: impl IntoIterator<Item = SymbolStub>) -> Result<(), Error> {
+        for symbol in symbols {
+            self.add_symbol("all", symbol)?;
+        }
+        Ok(())
+    }
+
+    /// Add a single symbol to a group, creating the group if it doesn't exist yet.
+    pub fn add_symbol(&mut self, group_name: &str, symbol: SymbolStub) -> Result<(), Error> {
+        if self.is_denied(&symbol.import_name) {
+            return Ok(());
+        }
+        let sym_idx = self.add_stub(symbol)?;
+        let group_syms = self.groups.entry(group_name.to_string()).or_default();
+        if !group_syms.contains(&sym_idx) {
+            group_syms.push(sym_idx);
+        }
+        Ok(())
+    }
+
+    /// Adds the configured symbols that each static library of client code references to a group, creating the
+    /// groups if they don't exist yet.
+    ///
+    /// This keeps group membership in line with the code when each feature of the program lives in its own
+    /// library: a symbol referenced by several of them is added to each of their groups.  Only symbols that have
+    /// been added to the configuration beforehand are assigned, e.g. the intersection of the library's exports with
+    /// the imports of all client code.
+    ///
+    /// ```no_run
+    /// # use std::path::Path;
+    /// # let mut config = weaklink_build::Config::new("foo");
+    /// let (video, audio) = (Path::new("libvideo.rlib"), Path::new("libaudio.rlib"));
+    /// config.derive_groups_from_archives(&[("video", video), ("audio", audio)])?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn derive_groups_from_archives(&mut self, archives: &[(&str, &Path)]) -> Result<(), Error> {
+        for (group_name, path) in archives {
+            self.check_binary(path)?;
+            let imports = imports::archive_imports(path)?;
+            let imported = imports.iter().map(|import| import.undecorated_name(&self.target)).collect::<HashSet<_>>();
+            let members = self.stubs.iter().enumerate();
+            let members = members.filter(|(_, stub)| imported.contains(stub.export_name.as_str()));
+            let members = members.map(|(idx, _)| idx).collect::<Vec<_>>();
+            let group_syms = self.groups.entry(group_name.to_string()).or_default();
+            for idx in members {
+                if !group_syms.contains(&idx) {
+                    group_syms.push(idx);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a builder that adds symbols to a group, creating the group if it doesn't exist yet.
+    ///
+    /// ```no_run
+    /// # let mut config = weaklink_build::Config::new("foo");
+    /// config.group("base").add("foo_init").add("foo_free").add_data("FOO_TABLE", "get_FOO_TABLE");
+    /// let mut extras = config.group("extras");
+    /// extras.add("foo_extra").depends_on("base");
+    /// if cfg!(feature = "tracing") {
+    ///     extras.add_optional("foo_trace");
+    /// }
+    /// ```
+    pub fn group(&mut self, group_name: &str) -> GroupBuilder<'_> {
+        self.groups.entry(group_name.to_string()).or_default();
+        GroupBuilder {
+            config: self,
+            group_name: group_name.to_string(),
+        }
+    }
+
+    // Returns the index of the stub for `symbol`, adding it if necessary.
+    fn add_stub(&mut self, symbol: SymbolStub) -> Result<usize, Error> {
+        match self.stub_by_exp.entry(symbol.export_name.clone()) {
+            Entry::Occupied(o) => {
+                let idx = *o.get();
+                let existing = &self.stubs[idx];
+                if existing.is_data != symbol.is_data {
+                    return Err(format!(
+                        "Stub for symbol '{}' already exists, but with a different `is_data` value: {}",
+                        existing.export_name, existing.is_data
+                    )
+                    .into());
                 }
-                Entry::Vacant(v) => {
-                    let idx = self.stubs.len();
-                    self.stubs.push(symbol);
-                    v.insert(idx);
-                    idx
+                if self.stubs[idx].import_name != symbol.import_name {
+                    return Err(format!(
+                        "Stub for symbol '{}' already exists, but with a different `import_name` value: {}",
+                        existing.export_name, existing.import_name
+                    )
+                    .into());
                 }
-            };
-            group_syms.push(sym_idx);
+                if self.stubs[idx].version != symbol.version {
+                    return Err(format!(
+                        "Stub for symbol '{}' already exists, but with a different `version` value: {:?}",
+                        existing.export_name, existing.version
+                    )
+                    .into());
+                }
+                Ok(idx)
+            }
+            Entry::Vacant(v) => {
+                let idx = self.stubs.len();
+                self.stubs.push(symbol);
+                v.insert(idx);
+                Ok(idx)
+            }
         }
-        self.groups.insert(group_name.to_string(), group_syms);
+    }
+
+    /// Sets the minimum version of the wrapped library required by a group.
+    ///
+    /// Resolution of the group fails with `weaklink::VersionTooOld` if the library reports an older version, even if
+    /// all of the group's symbols are present.  Versions are compared component-wise, e.g. "2.10" > "2.9".
+    pub fn set_min_version(&mut self, group_name: &str, version: &str) -> Result<(), Error> {
+        if !self.groups.contains_key(group_name) {
+            Err(format!("Group \"{group_name}\" does not exist"))?;
+        }
+        self.min_versions.insert(group_name.to_string(), version.to_string());
         Ok(())
     }
 
+    /// Compiles a group conditionally, as if its generated items were annotated with `#[cfg(<predicate>)]`.
+    ///
+    /// When the predicate is false, the group's `Group` static, its C management API function and the stubs of its
+    /// symbols are compiled out, unless a symbol also belongs to a group that is compiled in.  This lets host crates
+    /// drop optional plugin surfaces via Cargo features, e.g. `config.group_cfg("extras", "feature = \"extras\"")`.
+    /// The symbol table keeps its layout regardless.  Stubs that may be compiled out are not patched (see
+    /// [`Config::patched_groups`]), and a group may only depend on a conditional group with the same predicate.
+    pub fn group_cfg(&mut self, group_name: &str, predicate: &str) -> Result<(), Error> {
+        if !self.groups.contains_key(group_name) {
+            Err(format!("Group \"{group_name}\" does not exist"))?;
+        }
+        self.group_cfgs.insert(group_name.to_string(), predicate.to_string());
+        Ok(())
+    }
+
+    /// Adds a name variant of the wrapped library, e.g. for plugins built against another C++ standard library ABI,
+    /// whose symbol names differ systematically.
+    ///
+    /// When a dylib is loaded, the runtime selects the first variant whose `marker` symbol it exports, and looks up
+    /// symbols by their names for that variant, as set via [`SymbolStub::variant`].  Symbols without a name for the
+    /// variant, and dylibs that don't export any of the markers, use the default names.  The selected variant is
+    /// reported by [`Library::name_variant`](weaklink::Library::name_variant).
+    ///
+    /// ```
+    /// # use weaklink_build::{Config, SymbolStub};
+    /// let mut config = Config::new("plugin_stub");
+    /// config.add_name_variant("cxx11", "_ZNK7Plugin11abi_versionB5cxx11Ev")?;
+    /// let stub = SymbolStub::new("_ZN7Plugin4nameEv").variant("cxx11", "_ZN7Plugin4nameB5cxx11Ev");
+    /// config.add_symbol_group("base", [stub])?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn add_name_variant(&mut self, variant: &str, marker: &str) -> Result<(), Error> {
+        if self.name_variants.iter().any(|(name, _)| name == variant) {
+            Err(format!("Name variant \"{variant}\" already exists"))?;
+        }
+        self.name_variants.push((variant.to_string(), marker.to_string()));
+        Ok(())
+    }
+
+    /// Registers a stub generator for the targets whose triples are accepted by `arch_matcher`.
+    ///
+    /// Registered generators take precedence over the built-in ones, and are tried in the order of registration.
+    ///
+    /// ```
+    /// # use std::io::Write;
+    /// # use weaklink_build::{Config, stub_gen::StubGenerator};
+    /// struct Riscv64StubGenerator;
+    ///
+    /// impl StubGenerator for Riscv64StubGenerator {
+    ///     fn write_fn_stub(&self, text: &mut dyn Write, symtab_base: &str, index: usize) {
+    ///         writeln!(text, "    la t0, {symtab_base}").unwrap();
+    ///         writeln!(text, "    ld t0, {}(t0)", index * 8).unwrap();
+    ///         writeln!(text, "    jr t0").unwrap();
+    ///     }
+    /// }
+    ///
+    /// let mut config = Config::new("foo");
+    /// config.target = "riscv64gc-unknown-linux-gnu".to_string();
+    /// config.register_generator(|target| target.starts_with("riscv64"), Box::new(Riscv64StubGenerator));
+    /// config.group("base").add("foo_init");
+    /// let mut source = Vec::new();
+    /// config.generate_source(&mut source);
+    /// assert!(String::from_utf8(source).unwrap().contains("jr t0"));
+    /// ```
+    pub fn register_generator(
+        &mut self,
+        arch_matcher: impl Fn(&str) -> bool + 'static,
+        generator: Box<dyn StubGenerator>,
+    ) {
+        self.generators.push((Box::new(arch_matcher), generator));
+    }
+
     /// Generate source of the stub crate.
+    ///
+    /// Issues found by [`Config::lint`] are reported as cargo warnings.
     pub fn generate_source(&self, text: &mut dyn Write) {
-        // Adjust names for MacOS ABI
-        let mut stubs = Cow::from(&self.stubs);
-        if self.adjust_symbol_names && self.target.contains("-apple-") {
-            let new_stubs = self
-                .stubs
-                .iter()
-                .map(|stub| {
-                    let mut stub = stub.clone();
-                    if !stub.is_data && stub.export_name == stub.import_name {
-                        if stub.export_name.starts_with('_') {
-                            stub.import_name.remove(0);
-                        } else {
-                            stub.export_name.insert(0, '_');
-                        }
-                    }
-                    stub
-                })
-                .collect::<Vec<_>>();
-            stubs = Cow::from(new_stubs);
+        let _timing = util::Timing::start(|| format!("generate_source({})", self.name));
+        for lint in self.lint() {
+            println!("cargo:warning=weaklink: {lint}");
+        }
+        if !self.skipped_symbols.is_empty() {
+            let skipped = self.skipped_symbols.join(", ");
+            println!("cargo:warning=weaklink: Skipped denied symbols of {}: {skipped}", self.name);
+        }
+
+        if self.compress_names && self.group_name_tables {
+            panic!("Config::compress_names can't be combined with Config::group_name_tables");
+        }
+        if self.hash_names && (self.group_name_tables || self.compress_names || !self.name_variants.is_empty()) {
+            panic!("Config::hash_names can't be combined with other name storage, or with name variants");
+        }
+        let other_names = self.group_name_tables || self.compress_names || self.hash_names;
+        if self.obfuscation_key.is_some() && (other_names || !self.name_variants.is_empty()) {
+            panic!("Config::obfuscation_key can't be combined with other name storage, or with name variants");
+        }
+
+        let (order, new_index) = self.table_order();
+        // Dependencies must exist and must not be cyclic
+        for grp_name in self.group_dependencies.keys() {
+            let mut path = vec![grp_name.as_str()];
+            self.check_group_dependencies(&mut path);
+        }
+        for (grp_name, dep_names) in &self.group_dependencies {
+            for dep_name in dep_names {
+                let dep_cfg = self.group_cfgs.get(dep_name);
+                if dep_cfg.is_some() && dep_cfg != self.group_cfgs.get(grp_name) {
+                    panic!("Group \"{grp_name}\" depends on group \"{dep_name}\", which is compiled conditionally");
+                }
+            }
+        }
+        for grp_name in &self.required_groups {
+            if !self.groups.contains_key(grp_name) {
+                panic!("Required group \"{grp_name}\" does not exist");
+            }
+        }
+        // Name variants must be declared
+        for stub in &self.stubs {
+            if stub.vtable_slot.is_some() && stub.interface_slot.is_some() {
+                panic!("Symbol '{}' can't be both in a vtable and a method of an interface", stub.export_name);
+            }
+            for (variant, _) in &stub.variant_names {
+                if !self.name_variants.iter().any(|(name, _)| name == variant) {
+                    panic!("Symbol '{}' has a name for variant \"{variant}\", which does not exist", stub.export_name);
+                }
+            }
         }
 
+        let ordered_stubs = order.iter().map(|idx| self.stubs[*idx].clone()).collect::<Vec<_>>();
+
+        // Function stubs that may be patched into direct jumps
+        let mut kinds = vec![StubKind::Table; ordered_stubs.len()];
+        for grp_name in &self.patched_groups {
+            let Some(indices) = self.groups.get(grp_name) else {
+                panic!("Patched group \"{grp_name}\" does not exist");
+            };
+            for idx in indices {
+                if !self.stubs[*idx].is_data {
+                    kinds[new_index[*idx]] = StubKind::Patchable;
+                }
+            }
+        }
+
+        // Conditions of stubs that are only compiled in along with their groups
+        let stub_cfgs = self.stub_cfgs(&new_index);
+        for (kind, cfg) in kinds.iter_mut().zip(&stub_cfgs) {
+            if cfg.is_some() && *kind == StubKind::Patchable {
+                *kind = StubKind::Table;
+            }
+        }
+        let has_patch_sites = kinds.contains(&StubKind::Patchable);
+
+        // Symbols bound by the OS dynamic linker instead of stubs
+        for grp_name in &self.native_weak_groups {
+            let Some(indices) = self.groups.get(grp_name) else {
+                panic!("Native weak group \"{grp_name}\" does not exist");
+            };
+            let optional = self.optional_symbols.get(grp_name).into_iter().flatten();
+            for idx in indices.iter().chain(optional) {
+                let stub = &self.stubs[*idx];
+                let renamed = stub.export_name != stub.import_name || !stub.variant_names.is_empty();
+                if stub.is_data || renamed || stub.vtable_slot.is_some() || stub.interface_slot.is_some() {
+                    panic!(
+                        "Symbol '{}' can't be weakly linked natively: only code symbols looked up by their names can",
+                        stub.export_name
+                    );
+                }
+                kinds[new_index[*idx]] = StubKind::NativeWeak;
+            }
+        }
+        let has_native_symbols = kinds.contains(&StubKind::NativeWeak);
+
+        let stubs = self.adjust_names(&ordered_stubs);
+
         // Header
         write_lines!(text,
             "#[allow(unused_imports)]"
             "use weaklink::{{Library, Group, Address}};"
             "use core::arch::global_asm;"
+            "#[allow(unused_imports)]"
             "use std::ffi::CStr;"
+            "use std::sync::atomic::AtomicUsize;"
         );
 
         // Declare symbol table (will be defined by StubGenerator)
         let sym_table = format!("symbol_table_{:08x}", rand::random::<u64>());
         write_lines!(text,
             "extern \"C\" {{"
-            "    static {sym_table}: [Address; {size}];"
+            "    static {sym_table}: [AtomicUsize; {size}];"
             "}}",
             sym_table=sym_table,
             size=stubs.len()
         );
+        if has_patch_sites {
+            write_lines!(text,
+                "extern \"C\" {{"
+                "    static {sym_table}_patch_sites: [Address; {size}];"
+                "}}",
+                sym_table=sym_table,
+                size=stubs.len()
+            );
+        }
+        if has_native_symbols {
+            write_lines!(text,
+                "extern \"C\" {{"
+                "    static {sym_table}_native: [[Address; 2]; {size}];"
+                "}}",
+                sym_table=sym_table,
+                size=kinds.iter().filter(|kind| **kind == StubKind::NativeWeak).count()
+            );
+        }
+
+        // Perfect hash of symbol names, for lookups by name at runtime
+        let names = stubs.iter().map(|stub| stub.import_name.as_str()).collect::<Vec<_>>();
+        let (displacements, slots) = util::perfect_hash(&names);
+
+        // Groups in a stable order, so that runtime iteration over them is deterministic
+        let mut grp_names = self.groups.keys().collect::<Vec<_>>();
+        grp_names.sort();
 
         // Emit library object
         write_lines!(text,
             "#[no_mangle]"
             "#[allow(non_upper_case_globals)]"
-            "pub static {name}: Library = Library::new("
-            "    &[{dylib_names}],"
-            "    unsafe {{ &[\n{symbol_names}] }},"
-            "    unsafe {{ &{sym_table} }},"
-            ");",
+            "pub static {name}: Library = Library::new(weaklink::LibraryDescriptor {{"
+            "    abi_version: {abi_version},"
+            "    name: \"{name}\","
+            "    dylib_names: &[{dylib_names}],"
+            "    dependencies: &[{dependencies}],"
+            "    symbol_names: {symbol_names},"
+            "    compressed_names: {compressed_names},"
+            "    name_hashes: &[{name_hashes}],"
+            "    obfuscated_names: {obfuscated_names},"
+            "    symbol_table: unsafe {{ &{sym_table} }},"
+            "    groups: &[{groups}],"
+            "    name_index: weaklink::NameIndex::new(&[{displacements}], &[{slots}]),"
+            "    patch_sites: {patch_sites},"
+            "    native_symbols: {native_symbols},"
+            "    deprecations: &[{deprecations}],"
+            "    variants: &[{variants}],"
+            "    configure_hook: {configure_hook},"
+            "    bootstrap_export: {bootstrap_export},"
+            "    vtable_slots: &[{vtable_slots}],"
+            "    interface_slots: &[{interface_slots}],"
+            "    signatures: &[{signatures}],"
+            "    signature_query: {signature_query},"
+            "    group_signatures: &[{group_signatures}],"
+            "    eager_binding: {eager_binding},"
+            "    symbol_versions: &[{symbol_versions}],"
+            "}});",
+            abi_version = weaklink::ABI_VERSION,
             name = self.name,
+            eager_binding = self.eager_binding,
             dylib_names = iter_fmt(&self.dylib_names, |f, name| write!(f, "\"{name}\",")),
-            symbol_names = iter_fmt(stubs.as_ref().iter().enumerate(), |f, (i, sym)|
-                writeln!(f, "      CStr::from_bytes_with_nul_unchecked(b\"{}\\0\"), // {i}", sym.import_name)),
-            sym_table=sym_table
+            dependencies = iter_fmt(&self.dependencies, |f, name| write!(f, "\"{name}\",")),
+            symbol_names = match other_names || self.obfuscation_key.is_some() {
+                true => "&[]".to_string(),
+                false => format!("unsafe {{ &[\n{}] }}", iter_fmt(stubs.as_ref().iter().enumerate(), |f, (i, sym)|
+                    writeln!(f, "      CStr::from_bytes_with_nul_unchecked(b\"{}\\0\"), // {i}", sym.import_name))),
+            },
+            compressed_names = {
+                let (blob, order) = match self.compress_names {
+                    true => weaklink::compressed_names::encode(&names),
+                    false => (vec![], vec![]),
+                };
+                format!("weaklink::CompressedNames::new(b\"{}\", &[{}])", blob.escape_ascii(),
+                    iter_fmt(&order, |f, position| write!(f, "{position},")))
+            },
+            name_hashes = match self.hash_names {
+                true => iter_fmt(&names, |f, name| write!(f, "{:#x},", weaklink::name_index::hash(name.as_bytes(), 0)))
+                    .to_string(),
+                false => String::new(),
+            },
+            obfuscated_names = {
+                let (offsets, blob) = match self.obfuscation_key {
+                    Some(key) => weaklink::obfuscated_names::obfuscate(&names, key),
+                    None => (vec![], vec![]),
+                };
+                format!("weaklink::ObfuscatedNames::new(&[{}], b\"{}\")",
+                    iter_fmt(&offsets, |f, offset| write!(f, "{offset},")), blob.escape_ascii())
+            },
+            sym_table=sym_table,
+            groups = iter_fmt(&grp_names, |f, grp_name| write!(f, "{}&{grp_name},", self.cfg_attr(grp_name, " "))),
+            displacements = iter_fmt(&displacements, |f, d| write!(f, "{d},")),
+            slots = iter_fmt(&slots, |f, idx| write!(f, "{idx},")),
+            deprecations = iter_fmt(stubs.iter().enumerate(), |f, (i, stub)| match &stub.deprecated_since {
+                Some(version) => write!(f, "({i}, \"{}\"),", version.escape_default()),
+                None => Ok(()),
+            }),
+            variants = iter_fmt(&self.name_variants, |f, (variant, marker)| {
+                let names = stubs.iter().enumerate().filter_map(|(i, stub)| {
+                    let (_, name) = stub.variant_names.iter().find(|(name_variant, _)| name_variant == variant)?;
+                    Some((i, name))
+                });
+                write!(
+                    f,
+                    "\n    weaklink::NameVariant::new(\"{variant}\", {marker}, &[{names}]),",
+                    marker = c_str(self.adjust_marker(marker)),
+                    names = iter_fmt(names, |f, (i, name)| write!(f, "({i}, {}),", c_str(name)))
+                )
+            }),
+            configure_hook = match &self.configure_hook {
+                Some(path) => format!("Some({path})"),
+                None => "None".to_string(),
+            },
+            vtable_slots = iter_fmt(stubs.iter().enumerate(), |f, (i, stub)| match &stub.vtable_slot {
+                Some((provider, index)) => write!(f, "\n    ({i}, {}, {index}),", c_str(provider)),
+                None => Ok(()),
+            }),
+            interface_slots = iter_fmt(stubs.iter().enumerate(), |f, (i, stub)| match &stub.interface_slot {
+                Some((factory, index)) => write!(f, "\n    ({i}, \"{}\", {index}),", factory.escape_default()),
+                None => Ok(()),
+            }),
+            signatures = iter_fmt(stubs.iter().enumerate(), |f, (i, stub)| match &stub.signature {
+                Some(signature) => write!(f, "({i}, {:#x}),", weaklink::signature_hash(signature)),
+                None => Ok(()),
+            }),
+            symbol_versions = iter_fmt(stubs.iter().enumerate(), |f, (i, stub)| match &stub.version {
+                Some(version) => write!(f, "({i}, {}),", c_str(version)),
+                None => Ok(()),
+            }),
+            signature_query = match &self.signature_query {
+                Some(name) => format!("Some({})", c_str(name)),
+                None => "None".to_string(),
+            },
+            group_signatures = match self.signature_handshake {
+                true => iter_fmt(&grp_names, |f, grp_name| {
+                    let signatures = self.groups[*grp_name].iter().filter_map(|idx| {
+                        let stub = &self.stubs[*idx];
+                        Some((stub.import_name.as_str(), stub.signature.as_deref()?))
+                    });
+                    let signatures = signatures.collect::<Vec<_>>();
+                    match signatures.is_empty() {
+                        true => Ok(()),
+                        false => write!(f, "(\"{grp_name}\", {:#x}),", weaklink::group_signature_hash(&signatures)),
+                    }
+                })
+                .to_string(),
+                false => String::new(),
+            },
+            bootstrap_export = match &self.bootstrap_export {
+                Some(name) => format!("Some({})", c_str(name)),
+                None => "None".to_string(),
+            },
+            patch_sites = match has_patch_sites {
+                true => format!("unsafe {{ &{sym_table}_patch_sites }}"),
+                false => "&[]".to_string(),
+            },
+            native_symbols = match has_native_symbols {
+                true => format!("unsafe {{ &{sym_table}_native }}"),
+                false => "&[]".to_string(),
+            }
         );
 
         // Emit group objects
-        for (grp_name, indices) in &self.groups {
-            let mut indices = indices.clone();
+        for grp_name in grp_names.iter().copied() {
+            let indices = &self.groups[grp_name];
+            let mut indices = indices.iter().map(|idx| new_index[*idx]).collect::<Vec<_>>();
             indices.sort();
+            let optional = self.optional_symbols.get(grp_name).map(Vec::as_slice).unwrap_or_default();
+            let optional = indices_of_optional(optional, &indices, &new_index);
+            let dependencies = self.group_dependencies.get(grp_name).map(Vec::as_slice).unwrap_or_default();
+            write!(text, "{}", self.cfg_attr(grp_name, "\n"));
             write_lines!(text,
                 "#[no_mangle]"
                 "#[allow(non_upper_case_globals)]"
@@ -211,36 +972,516 @@ impl Config {
                 "    \"{grp_name}\","
                 "    &{name},"
                 "    &[{indices}],"
+                "    &[{optional}],"
+                "    &[{names}],"
+                "    &[{dependencies}],"
+                "    {min_version},"
                 ");",
                 name = self.name,
                 grp_name = grp_name,
-                indices = iter_fmt(indices, |f, idx| write!(f, "{idx},"))
+                optional = iter_fmt(&optional, |f, idx| write!(f, "{idx},")),
+                names = match self.group_name_tables {
+                    true => iter_fmt(indices.iter().chain(&optional), |f, idx| {
+                        write!(f, "\n    {},", c_str(&stubs[*idx].import_name))
+                    })
+                    .to_string(),
+                    false => String::new(),
+                },
+                dependencies = iter_fmt(dependencies, |f, dep_name| write!(f, "&{dep_name},")),
+                min_version = match self.min_versions.get(grp_name) {
+                    Some(version) => format!("Some(\"{}\")", version.escape_default()),
+                    None => "None".to_string(),
+                },
+                indices = iter_fmt(&indices, |f, idx| write!(f, "{idx},"))
             );
         }
 
-        let target_os = if self.target.contains("linux") {
-            TargetOs::Linux
-        } else if self.target.contains("apple") {
-            TargetOs::MacOS
-        } else if self.target.contains("windows") {
-            TargetOs::Windows
-        } else {
-            panic!("Unsupported OS");
+        // Emit C management API
+        if self.c_api {
+            write_lines!(text,
+                "#[no_mangle]"
+                "pub extern \"C\" fn {name}_load() -> bool {{"
+                "    weaklink::c_api::report({name}.load())"
+                "}}"
+                "#[no_mangle]"
+                "pub unsafe extern \"C\" fn {name}_load_from(path: *const std::os::raw::c_char) -> bool {{"
+                "    weaklink::c_api::report(weaklink::c_api::path(path).and_then(|path| {name}.load_from(&path)))"
+                "}}"
+                "#[no_mangle]"
+                "pub extern \"C\" fn {name}_last_error() -> *const std::os::raw::c_char {{"
+                "    weaklink::c_api::last_error()"
+                "}}"
+                "#[no_mangle]"
+                "pub extern \"C\" fn {name}_last_error_code() -> u32 {{"
+                "    weaklink::c_api::last_error_code()"
+                "}}",
+                name = self.name
+            );
+            for grp_name in &grp_names {
+                write!(text, "{}", self.cfg_attr(grp_name, "\n"));
+                write_lines!(text,
+                    "#[no_mangle]"
+                    "pub extern \"C\" fn {name}_resolve_{grp_name}() -> bool {{"
+                    "    weaklink::c_api::report({grp_name}.resolve_global())"
+                    "}}",
+                    name = self.name,
+                    grp_name = grp_name
+                );
+            }
+        }
+
+        // Emit registration with the dlopen shim
+        if !self.dlopen_names.is_empty() {
+            write_lines!(text,
+                "/// Redirects opening of the library's names via `weaklink_dlopen` to this library."
+                "#[allow(dead_code)]"
+                "pub fn register_dlopen_shim() {{"
+                "    weaklink::dlopen_shim::register(&{name}, &[{names}]);"
+                "}}",
+                name = self.name,
+                names = iter_fmt(&self.dlopen_names, |f, name| write!(f, "\"{}\",", name.escape_default()))
+            );
+            if self.c_api {
+                write_lines!(text,
+                    "#[no_mangle]"
+                    "pub extern \"C\" fn {name}_register_dlopen_shim() {{"
+                    "    register_dlopen_shim()"
+                    "}}",
+                    name = self.name
+                );
+            }
+        }
+
+        // Emit initialization of the required groups
+        if !self.required_groups.is_empty() {
+            write_lines!(text,
+                "/// Loads the library unless it's loaded already, and permanently resolves its required groups."
+                "#[allow(dead_code)]"
+                "pub fn init_required() -> Result<(), weaklink::Error> {{"
+                "    if {name}.handle().is_none() {{"
+                "        {name}.load()?;"
+                "    }}"
+                "{resolutions}"
+                "    Ok(())"
+                "}}",
+                name = self.name,
+                resolutions = iter_fmt(self.required_order(), |f, grp_name| {
+                    writeln!(f, "    {}{grp_name}.resolve_global()?;", self.cfg_attr(grp_name, "\n    "))
+                })
+            );
+        }
+
+        // Emit symbol index constants (using names before adjustment, so they are the same on all platforms)
+        write_lines!(text,
+            "#[allow(dead_code, non_upper_case_globals)]"
+            "pub mod sym_index {{"
+            "{constants}"
+            "}}",
+            constants = iter_fmt(ordered_stubs.iter().enumerate(), |f, (i, stub)| match rust_ident(&stub.export_name) {
+                Some(ident) => writeln!(f, "    pub const {ident}: u32 = {i};"),
+                None => Ok(()),
+            })
+        );
+
+        let target_os = TargetOs::from_target(&self.target).expect("Unsupported OS");
+
+        // The symbol table occupies whole pages of its own section, so that it can be sealed (see `Library::seal`)
+        let section = match target_os {
+            TargetOs::Linux => ".section .weaklink.got,\\\"aw\\\"",
+            TargetOs::MacOS => ".section __DATA,__weaklink_got",
+            TargetOs::Windows => ".section .wlgot,\\\"dw\\\"",
+        };
+        // Pages are as large as the largest page size the target's kernels may be configured with.
+        let arch = self.target.split('-').next().unwrap_or_default();
+        let p2align = match (&target_os, arch) {
+            (TargetOs::MacOS, "aarch64") => 14,
+            (TargetOs::Linux, "aarch64" | "loongarch64" | "powerpc64" | "powerpc64le") => 16,
+            _ => 12,
+        };
+
+        // Text section of each stub, if placed per group
+        let mut stub_groups = vec![None; ordered_stubs.len()];
+        if self.group_sections {
+            for grp_name in grp_names.iter().rev() {
+                for idx in &self.groups[*grp_name] {
+                    stub_groups[new_index[*idx]] = Some(grp_name.as_str());
+                }
+            }
+        }
+        let layout = stub_gen::Layout {
+            table_section: section,
+            p2align,
+            stub_groups: &stub_groups,
+            stub_cfgs: &stub_cfgs,
         };
 
+        if has_native_symbols && target_os == TargetOs::Windows {
+            panic!("Native weak linkage is not supported for {}", self.target);
+        }
+
         // Emit symbol table and PLT
-        let stub_gen: Box<dyn stub_gen::StubGenerator> = if self.target.starts_with("x86_64-") {
-            Box::new(stub_gen::x64::X64StubGenerator { target_os })
+        self.with_stub_generator(target_os, |stub_gen| {
+            if has_patch_sites && !stub_gen.supports_patching() {
+                panic!("Stub patching is not supported for {}", self.target);
+            }
+            stub_gen::generate(stub_gen, text, &self.name, stubs.as_ref(), &sym_table, &layout, &kinds);
+        });
+    }
+
+    // Calls `f` with the stub generator for the target: the first registered one that matches, or else a built-in one.
+    fn with_stub_generator<R>(&self, target_os: TargetOs, f: impl FnOnce(&dyn StubGenerator) -> R) -> R {
+        let registered = self.generators.iter().find(|(matches, _)| matches(&self.target));
+        let builtin: Box<dyn StubGenerator>;
+        let stub_gen = if let Some((_, stub_gen)) = registered {
+            stub_gen.as_ref()
+        } else if self.target.starts_with("x86_64-") {
+            builtin = Box::new(stub_gen::x64::X64StubGenerator { target_os });
+            builtin.as_ref()
         } else if self.target.starts_with("aarch64-") {
-            Box::new(stub_gen::aarch64::Aarch64StubGenerator { target_os })
+            builtin = Box::new(stub_gen::aarch64::Aarch64StubGenerator { target_os });
+            builtin.as_ref()
+        } else if self.target.starts_with("arm64ec-") {
+            panic!(
+                "ARM64EC is not supported: its stubs may be called from x64 code, which requires entry and exit \
+                 thunks.  Build the program for aarch64-pc-windows-msvc or x86_64-pc-windows-msvc instead, or \
+                 register a generator via Config::register_generator"
+            );
         } else if self.target.starts_with("arm") {
-            Box::new(stub_gen::arm::ArmStubGenerator {})
+            builtin = Box::new(stub_gen::arm::ArmStubGenerator {});
+            builtin.as_ref()
         } else if self.target.starts_with("loongarch") {
-            Box::new(stub_gen::loongarch::LoongArchStubGenerator {})
+            builtin = Box::new(stub_gen::loongarch::LoongArchStubGenerator {});
+            builtin.as_ref()
         } else {
             panic!("Unsupported arch");
         };
+        f(stub_gen)
+    }
+
+    // The `#[cfg]` attribute of a group's items followed by `separator`, or nothing if the group is unconditional.
+    fn cfg_attr(&self, grp_name: &str, separator: &str) -> String {
+        match self.group_cfgs.get(grp_name) {
+            Some(predicate) => format!("#[cfg({predicate})]{separator}"),
+            None => String::new(),
+        }
+    }
+
+    // Returns the `#[cfg]` predicate of each stub in table order: any of the predicates of its groups, or `None` if
+    // it belongs to an unconditional group.
+    fn stub_cfgs(&self, new_index: &[usize]) -> Vec<Option<String>> {
+        let mut predicates = vec![Some(Vec::new()); self.stubs.len()];
+        let members = self.groups.iter().chain(&self.optional_symbols);
+        for (grp_name, indices) in members {
+            for idx in indices {
+                match (&mut predicates[new_index[*idx]], self.group_cfgs.get(grp_name)) {
+                    (Some(stub_predicates), Some(predicate)) => stub_predicates.push(predicate.as_str()),
+                    (stub_predicates, _) => *stub_predicates = None,
+                }
+            }
+        }
+        let predicates = predicates.into_iter().map(|stub_predicates| {
+            let mut stub_predicates = stub_predicates?;
+            stub_predicates.sort();
+            stub_predicates.dedup();
+            match stub_predicates.as_slice() {
+                [] => None,
+                [predicate] => Some(predicate.to_string()),
+                _ => Some(format!("any({})", stub_predicates.join(", "))),
+            }
+        });
+        predicates.collect()
+    }
+
+    // Table layout: symbols with frozen indices first, then those of hot groups, then the rest in the order they were
+    // added.  Returns the stub indices in table order, and the table index of each stub.
+    fn table_order(&self) -> (Vec<usize>, Vec<usize>) {
+        let mut order = Vec::new();
+        let mut new_index = vec![usize::MAX; self.stubs.len()];
+        for exp_name in &self.frozen_indices {
+            let Some(&idx) = self.stub_by_exp.get(exp_name) else {
+                panic!("Symbol '{exp_name}' has a frozen index, but is no longer configured");
+            };
+            new_index[idx] = order.len();
+            order.push(idx);
+        }
+        for grp_name in &self.hot_groups {
+            let Some(indices) = self.groups.get(grp_name) else {
+                panic!("Hot group \"{grp_name}\" does not exist");
+            };
+            let mut indices = indices.clone();
+            indices.sort();
+            for idx in indices {
+                if new_index[idx] == usize::MAX {
+                    new_index[idx] = order.len();
+                    order.push(idx);
+                }
+            }
+        }
+        for (idx, new_idx) in new_index.iter_mut().enumerate() {
+            if *new_idx == usize::MAX {
+                *new_idx = order.len();
+                order.push(idx);
+            }
+        }
+        (order, new_index)
+    }
 
-        stub_gen.generate(text, stubs.as_ref(), &sym_table);
+    // Adjust the name of a marker symbol for MacOS ABI, like those of the symbols.
+    fn adjust_marker<'a>(&self, marker: &'a str) -> &'a str {
+        match self.adjust_symbol_names && self.target.contains("-apple-") {
+            true => marker.strip_prefix('_').unwrap_or(marker),
+            false => marker,
+        }
+    }
+
+    // Adjust names for MacOS ABI
+    fn adjust_names<'a>(&self, ordered_stubs: &'a [SymbolStub]) -> Cow<'a, [SymbolStub]> {
+        if !self.adjust_symbol_names || !self.target.contains("-apple-") {
+            return Cow::from(ordered_stubs);
+        }
+        let new_stubs = ordered_stubs
+            .iter()
+            .map(|stub| {
+                let mut stub = stub.clone();
+                if !stub.is_data && stub.export_name == stub.import_name {
+                    if stub.export_name.starts_with('_') {
+                        stub.import_name.remove(0);
+                        for (_, name) in stub.variant_names.iter_mut().filter(|(_, name)| name.starts_with('_')) {
+                            name.remove(0);
+                        }
+                    } else {
+                        stub.export_name.insert(0, '_');
+                    }
+                }
+                stub
+            })
+            .collect::<Vec<_>>();
+        Cow::from(new_stubs)
+    }
+
+    /// Generate a manifest of the symbol table layout, i.e. the index of each symbol.
+    ///
+    /// Each line holds the index, the export name and the name looked up in the wrapped library, separated by
+    /// spaces.  The manifest may be checked against the compiled stubs at runtime via
+    /// [`Library::verify_layout`](weaklink::Library::verify_layout), and fed back to [`Config::freeze_indices`] to
+    /// keep the indices stable as symbols are added.
+    pub fn generate_manifest(&self, text: &mut dyn Write) {
+        let (order, _) = self.table_order();
+        let ordered_stubs = order.iter().map(|idx| self.stubs[*idx].clone()).collect::<Vec<_>>();
+        let stubs = self.adjust_names(&ordered_stubs);
+        let entries = ordered_stubs.iter().zip(stubs.iter()).enumerate();
+        let entries = iter_fmt(entries, |f, (i, (stub, adjusted))| {
+            writeln!(f, "{i} {} {}", stub.export_name, adjusted.import_name)
+        });
+        write!(text, "# weaklink symbol table of {}\n{entries}", self.name);
+    }
+
+    /// Freezes the symbol table indices recorded in a manifest generated by [`Config::generate_manifest`].
+    ///
+    /// The symbols of the manifest keep their indices, and symbols added since are placed after them, so that a
+    /// newer version of the stubs never renumbers the existing entries.  [`Config::hot_groups`] only affects the
+    /// placement of symbols not in the manifest.  [`Config::generate_source`] panics if a symbol of the manifest is
+    /// no longer configured.
+    ///
+    /// ```
+    /// # use weaklink_build::{Config, SymbolStub};
+    /// let mut config = Config::new("foo_stub");
+    /// config.add_symbols([SymbolStub::new("foo_new"), SymbolStub::new("foo_open")])?;
+    /// config.freeze_indices("# weaklink symbol table of foo_stub\n0 foo_open foo_open\n")?;
+    /// let mut manifest = Vec::new();
+    /// config.generate_manifest(&mut manifest);
+    /// assert!(String::from_utf8(manifest)?.ends_with("0 foo_open foo_open\n1 foo_new foo_new\n"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn freeze_indices(&mut self, manifest: &str) -> Result<(), Error> {
+        let mut frozen = Vec::new();
+        for line in manifest.lines().filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let mut fields = line.split(' ');
+            let (Some(index), Some(exp_name)) = (fields.next(), fields.next()) else {
+                return Err(format!("Malformed manifest line: {line}").into());
+            };
+            if index.parse::<usize>().ok() != Some(frozen.len()) {
+                return Err(format!("Manifest index {index} is out of sequence").into());
+            }
+            if frozen.iter().any(|name| name == exp_name) {
+                return Err(format!("Symbol '{exp_name}' appears in the manifest twice").into());
+            }
+            frozen.push(exp_name.to_string());
+        }
+        self.frozen_indices = frozen;
+        Ok(())
+    }
+
+    /// Generate a C header declaring the management API enabled by [`Config::c_api`], and the data accessors.
+    ///
+    /// Code symbols are called via the stubs under their own names, so their prototypes should be taken from the
+    /// wrapped library's headers.
+    pub fn generate_c_header(&self, text: &mut dyn Write) {
+        let mut grp_names = self.groups.keys().collect::<Vec<_>>();
+        grp_names.sort();
+        let mut accessors = self.stubs.iter().filter(|stub| stub.is_data).collect::<Vec<_>>();
+        accessors.sort_by(|a, b| a.export_name.cmp(&b.export_name));
+
+        write_lines!(text,
+            "/* Generated by weaklink_build: management API of the `{name}` stubs. */"
+            "#ifndef {guard}"
+            "#define {guard}"
+            ""
+            "#include <stdbool.h>"
+            "#include <stdint.h>"
+            ""
+            "#ifdef __cplusplus"
+            "extern \"C\" {{"
+            "#endif"
+            ""
+            "/* Loads the wrapped library, returning false on failure. */"
+            "bool {name}_load(void);"
+            "bool {name}_load_from(const char *path);"
+            ""
+            "/* Resolve a group for the rest of the process lifetime, returning false on failure. */"
+            "{groups}"
+            "/* Returns the error message of the last failed call on this thread, or NULL if the last call succeeded. */"
+            "const char *{name}_last_error(void);"
+            "/* Returns the code of the last error on this thread (a weaklink::ErrorCode), or 0 on success. */"
+            "uint32_t {name}_last_error_code(void);"
+            ""
+            "/* Data accessors, which return the address of a data symbol of the wrapped library. */"
+            "{accessors}{dlopen_shim}"
+            "#ifdef __cplusplus"
+            "}}"
+            "#endif"
+            ""
+            "#endif",
+            name = self.name,
+            guard = format!("{}_WEAKLINK_H", self.name.to_uppercase()),
+            groups = iter_fmt(&grp_names, |f, grp_name| writeln!(f, "bool {}_resolve_{grp_name}(void);", self.name)),
+            accessors = iter_fmt(&accessors, |f, stub| writeln!(f, "const void *{}(void);", stub.export_name)),
+            dlopen_shim = match self.dlopen_names.is_empty() {
+                true => String::new(),
+                false => [
+                    "",
+                    "/* Drop-in replacements of dlopen and friends, to which the library's names are redirected. */",
+                    &format!("void {}_register_dlopen_shim(void);", self.name),
+                    "void *weaklink_dlopen(const char *filename, int flags);",
+                    "void *weaklink_dlsym(void *handle, const char *symbol);",
+                    "int weaklink_dlclose(void *handle);",
+                    "const char *weaklink_dlerror(void);",
+                    "",
+                ]
+                .join("\n"),
+            }
+        );
+    }
+
+    // Returns the required groups, each after the required groups it depends on, directly or indirectly.
+    fn required_order(&self) -> Vec<&str> {
+        fn visit<'a>(config: &'a Config, grp_name: &'a str, visited: &mut HashSet<&'a str>, order: &mut Vec<&'a str>) {
+            if !visited.insert(grp_name) {
+                return;
+            }
+            for dep_name in config.group_dependencies.get(grp_name).into_iter().flatten() {
+                visit(config, dep_name, visited, order);
+            }
+            if config.required_groups.iter().any(|name| name == grp_name) {
+                order.push(grp_name);
+            }
+        }
+
+        let mut visited = HashSet::new();
+        let mut order = Vec::new();
+        for grp_name in &self.required_groups {
+            visit(self, grp_name, &mut visited, &mut order);
+        }
+        order
+    }
+
+    // Panics if a dependency of the last group in `path` doesn't exist, or leads back to a group in `path`.
+    fn check_group_dependencies<'a>(&'a self, path: &mut Vec<&'a str>) {
+        let grp_name = *path.last().unwrap();
+        for dep_name in self.group_dependencies.get(grp_name).into_iter().flatten() {
+            if !self.groups.contains_key(dep_name) {
+                panic!("Group \"{grp_name}\" depends on group \"{dep_name}\", which does not exist");
+            }
+            if path.contains(&dep_name.as_str()) {
+                panic!("Cyclic group dependency: {} -> {dep_name}", path.join(" -> "));
+            }
+            path.push(dep_name);
+            self.check_group_dependencies(path);
+            path.pop();
+        }
+    }
+}
+
+// Returns a `&CStr` constant expression for a symbol name.
+fn c_str(name: &str) -> String {
+    format!("unsafe {{ CStr::from_bytes_with_nul_unchecked(b\"{name}\\0\") }}")
+}
+
+// Returns new indices of the optional symbols of a group that aren't among its required ones, sorted.
+fn indices_of_optional(optional: &[usize], required: &[usize], new_index: &[usize]) -> Vec<usize> {
+    let indices = optional.iter().map(|idx| new_index[*idx]);
+    let mut indices = indices.filter(|idx| !required.contains(idx)).collect::<Vec<_>>();
+    indices.sort();
+    indices.dedup();
+    indices
+}
+
+/// Adds symbols to a group of a [`Config`].  See [`Config::group`].
+///
+/// All methods panic if a symbol conflicts with an existing stub of the same name, as described in
+/// [`Config::add_symbol_group`].
+pub struct GroupBuilder<'a> {
+    config: &'a mut Config,
+    group_name: String,
+}
+
+impl<'a> GroupBuilder<'a> {
+    /// Adds code symbol `name` to the group.
+    pub fn add(&mut self, name: &str) -> &mut Self {
+        self.add_stub(SymbolStub::new(name))
+    }
+
+    /// Adds data symbol `exp_name` to the group, with client-side accessor function `imp_name`.
+    pub fn add_data(&mut self, exp_name: &str, imp_name: &str) -> &mut Self {
+        self.add_stub(SymbolStub::new_data(exp_name, imp_name))
+    }
+
+    /// Adds a symbol stub to the group.
+    pub fn add_stub(&mut self, symbol: SymbolStub) -> &mut Self {
+        if let Err(err) = self.config.add_symbol(&self.group_name, symbol) {
+            panic!("{err}");
+        }
+        self
+    }
+
+    /// Adds code symbol `name` to the group, without requiring it for the group's resolution.
+    ///
+    /// The symbol's address is resolved along with the group if it is present.  Callers must check that it is,
+    /// e.g. via `Library::symbol_address`, before calling it.  If the symbol is also added as a regular member,
+    /// it is required.
+    pub fn add_optional(&mut self, name: &str) -> &mut Self {
+        if self.config.is_denied(name) {
+            return self;
+        }
+        let sym_idx = match self.config.add_stub(SymbolStub::new(name)) {
+            Ok(sym_idx) => sym_idx,
+            Err(err) => panic!("{err}"),
+        };
+        let optional = self.config.optional_symbols.entry(self.group_name.clone()).or_default();
+        if !optional.contains(&sym_idx) {
+            optional.push(sym_idx);
+        }
+        self
+    }
+
+    /// Makes resolution of this group resolve group `group_name` first, and fail if it fails.
+    ///
+    /// The dependency doesn't need to exist yet, but must be added before [`Config::generate_source`] is called.
+    pub fn depends_on(&mut self, group_name: &str) -> &mut Self {
+        let dependencies = self.config.group_dependencies.entry(self.group_name.clone()).or_default();
+        if !dependencies.iter().any(|name| name == group_name) {
+            dependencies.push(group_name.to_string());
+        }
+        self
     }
 }
@@ -0,0 +1,172 @@
+//! Consistency checks of the group setup.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+use crate::imports::{archive_imports, Import, ImportCategory};
+use crate::{Config, Error};
+
+/// A suspicious setup detected by [`Config::lint`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Lint {
+    /// A symbol is required by one group, but optional in another, so the latter's optionality has no effect
+    /// whenever the former is resolved.
+    RequiredAndOptional {
+        symbol: String,
+        required_in: String,
+        optional_in: String,
+    },
+    /// Two groups have identical members, so one of them is probably redundant.
+    DuplicateGroups { first: String, second: String },
+    /// The accessor function of a data stub has the name of a code symbol of the library, which it shadows.
+    AccessorCollision { accessor: String },
+    /// A group has neither members nor dependencies, so it always resolves.
+    EmptyGroup { group: String },
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::RequiredAndOptional {
+                symbol,
+                required_in,
+                optional_in,
+            } => write!(
+                f,
+                "Symbol '{symbol}' is required by group \"{required_in}\", but optional in group \"{optional_in}\""
+            ),
+            Lint::DuplicateGroups { first, second } => {
+                write!(f, "Groups \"{first}\" and \"{second}\" have identical members")
+            }
+            Lint::AccessorCollision { accessor } => {
+                write!(f, "Data accessor '{accessor}' has the same name as a code symbol")
+            }
+            Lint::EmptyGroup { group } => write!(f, "Group \"{group}\" is empty"),
+        }
+    }
+}
+
+impl Config {
+    /// Checks the configuration for suspicious setups, which are likely to cause surprises at runtime.
+    ///
+    /// [`Config::generate_source`] reports these as cargo warnings.
+    ///
+    /// ```
+    /// # use weaklink_build::{Config, Lint};
+    /// let mut config = Config::new("foo");
+    /// config.group("base").add("foo_init");
+    /// config.group("extras");
+    /// assert_eq!(config.lint(), [Lint::EmptyGroup { group: "extras".to_string() }]);
+    /// ```
+    pub fn lint(&self) -> Vec<Lint> {
+        let mut lints = Vec::new();
+        let mut grp_names = self.groups.keys().collect::<Vec<_>>();
+        grp_names.sort();
+
+        // Members of each group, as (required, optional) sorted stub indices
+        let members = grp_names
+            .iter()
+            .map(|grp_name| {
+                let mut required = self.groups[*grp_name].clone();
+                required.sort();
+                let optional = self.optional_symbols.get(*grp_name).map(Vec::as_slice).unwrap_or_default();
+                let mut optional = optional.iter().filter(|idx| !required.contains(idx)).copied().collect::<Vec<_>>();
+                optional.sort();
+                (required, optional)
+            })
+            .collect::<Vec<_>>();
+
+        for (i, (optional_in, (_, optional))) in grp_names.iter().zip(&members).enumerate() {
+            for idx in optional {
+                let required_in = grp_names.iter().zip(&members).find(|(_, (required, _))| required.contains(idx));
+                if let Some((required_in, _)) = required_in {
+                    lints.push(Lint::RequiredAndOptional {
+                        symbol: self.stubs[*idx].export_name.clone(),
+                        required_in: required_in.to_string(),
+                        optional_in: optional_in.to_string(),
+                    });
+                }
+            }
+            for (j, other) in members.iter().enumerate().skip(i + 1) {
+                let (required, optional) = &members[i];
+                if (!required.is_empty() || !optional.is_empty()) && members[i] == *other {
+                    lints.push(Lint::DuplicateGroups {
+                        first: grp_names[i].to_string(),
+                        second: grp_names[j].to_string(),
+                    });
+                }
+            }
+        }
+
+        let code_symbols = self.stubs.iter().filter(|stub| !stub.is_data).map(|stub| stub.import_name.as_str());
+        let code_symbols = code_symbols.collect::<HashSet<_>>();
+        for stub in &self.stubs {
+            if stub.is_data && code_symbols.contains(stub.export_name.as_str()) {
+                lints.push(Lint::AccessorCollision {
+                    accessor: stub.export_name.clone(),
+                });
+            }
+        }
+
+        for (grp_name, (required, optional)) in grp_names.iter().zip(&members) {
+            let has_dependencies = self.group_dependencies.get(*grp_name).is_some_and(|deps| !deps.is_empty());
+            if required.is_empty() && optional.is_empty() && !has_dependencies {
+                lints.push(Lint::EmptyGroup {
+                    group: grp_name.to_string(),
+                });
+            }
+        }
+        lints
+    }
+
+    /// Returns the imports that look like symbols of the wrapped library, i.e. are classified as
+    /// [`ImportCategory::Other`], but have no stub.
+    ///
+    /// This catches calls into the wrapped library that were added without updating the configuration, which would
+    /// otherwise only be noticed when linking, or at runtime if the symbol happens to be provided by another library.
+    ///
+    /// ```
+    /// # use weaklink_build::{Config, imports::Import};
+    /// let mut config = Config::new("foo");
+    /// config.target = "x86_64-unknown-linux-gnu".to_string();
+    /// config.group("base").add("foo_init");
+    /// let imports = ["foo_init", "foo_exit", "memcpy"].map(|name| Import { name: name.to_string() });
+    /// let unstubbed = config.unstubbed_imports(imports);
+    /// assert_eq!(unstubbed.len(), 1);
+    /// assert_eq!(unstubbed[0].name, "foo_exit");
+    /// ```
+    pub fn unstubbed_imports(&self, imports: impl IntoIterator<Item = Import>) -> Vec<Import> {
+        let stubbed = self.stubs.iter().map(|stub| stub.export_name.as_str()).collect::<HashSet<_>>();
+        let mut unstubbed = imports
+            .into_iter()
+            .filter(|import| import.category_for(&self.target) == ImportCategory::Other)
+            .filter(|import| !stubbed.contains(import.undecorated_name(&self.target)))
+            .collect::<Vec<_>>();
+        unstubbed.sort_by(|a, b| a.name.cmp(&b.name));
+        unstubbed
+    }
+
+    /// Scans static libraries that will be linked into the final binary, and reports their
+    /// [unstubbed imports](Config::unstubbed_imports) as cargo warnings.
+    ///
+    /// Returns the unstubbed imports, so that the build script may fail the build instead.
+    pub fn check_imports(&self, archives: &[&Path]) -> Result<Vec<Import>, Error> {
+        let mut imports = Vec::new();
+        for path in archives {
+            self.check_binary(path)?;
+            imports.extend(archive_imports(path)?);
+        }
+        imports.sort_by(|a, b| a.name.cmp(&b.name));
+        imports.dedup_by(|a, b| a.name == b.name);
+
+        let unstubbed = self.unstubbed_imports(imports);
+        for import in &unstubbed {
+            println!(
+                "cargo:warning=weaklink: Symbol '{}' is imported, but has no stub",
+                import.name
+            );
+        }
+        Ok(unstubbed)
+    }
+}
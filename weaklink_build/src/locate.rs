@@ -0,0 +1,192 @@
+//! Discovery of artifacts built by Cargo, e.g. to generate stubs of a plugin built by the same workspace.
+//!
+//! These work both in build scripts, where the artifacts of the target are located from `OUT_DIR`, and in
+//! executables built by Cargo, such as tests, where they are located from the path of the executable.
+//!
+//! Artifacts of [artifact dependencies](https://doc.rust-lang.org/cargo/reference/unstable.html#artifact-dependencies)
+//! are located from the environment variables that Cargo sets for them, which doesn't involve any guessing.
+//!
+//! ```no_run
+//! let plugin = weaklink_build::locate::dylib("plugin")?;
+//! let exports = weaklink_build::exports::dylib_exports(&plugin)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{candidates::dylib_candidates, util, Error};
+
+/// Returns the Cargo target directory, e.g. `target` of the workspace, or the value of `CARGO_TARGET_DIR`.
+pub fn target_dir() -> Result<PathBuf, Error> {
+    if let Some(dir) = env::var_os("CARGO_TARGET_DIR") {
+        let dir = PathBuf::from(dir);
+        // Relative paths are relative to the directory Cargo was invoked from, which is unknown here; the
+        // workspace root is the usual one.
+        return match dir.is_absolute() {
+            true => Ok(dir),
+            false => Ok(workspace_root()?.join(dir)),
+        };
+    }
+    let mut dir = profile_dir()?;
+    dir.pop();
+    // When cross-compiling, profile directories are nested in a directory named after the target triple.
+    if dir.file_name().is_some_and(|name| name == util::current_target().as_str()) {
+        dir.pop();
+    }
+    Ok(dir)
+}
+
+/// Returns the directory of the artifacts of the current profile for the target being built, e.g. `target/debug`,
+/// or `target/<triple>/release` when cross-compiling.
+///
+/// In build scripts, this is derived from `OUT_DIR`, which Cargo places in that directory regardless of the target
+/// directory configuration.  Otherwise, it's derived from the path of the running executable.
+pub fn profile_dir() -> Result<PathBuf, Error> {
+    if let Some(out_dir) = env::var_os("OUT_DIR") {
+        // e.g. target/debug/build/weak_linkage-2156dda05d199c7e/out
+        let out_dir = PathBuf::from(out_dir);
+        let build_dir = out_dir.ancestors().find(|dir| dir.file_name().is_some_and(|name| name == "build"));
+        return match build_dir.and_then(Path::parent) {
+            Some(dir) => Ok(dir.to_path_buf()),
+            None => Err(not_found(format!(
+                "{} is not in a Cargo build directory",
+                out_dir.display()
+            ))),
+        };
+    }
+    // e.g. target/debug/deps/weak_linkage-2156dda05d199c7e, target/debug/examples/foo, or target/debug/foo
+    let mut dir = env::current_exe()?;
+    dir.pop();
+    if dir.ends_with("deps") || dir.ends_with("examples") {
+        dir.pop();
+    }
+    Ok(dir)
+}
+
+/// Returns the directory of the dependencies of the current profile, where Cargo builds libraries, e.g.
+/// `target/debug/deps`.
+pub fn deps_dir() -> Result<PathBuf, Error> {
+    Ok(profile_dir()?.join("deps"))
+}
+
+/// Returns the most recently modified file in the [dependencies directory](deps_dir) whose name matches `pattern`.
+///
+/// Cargo keeps artifacts of builds with other features or dependency versions, which differ only in the hash of
+/// their names, so the most recent one is usually that of the current build.
+pub fn latest_artifact(pattern: impl Fn(&str) -> bool) -> Result<PathBuf, Error> {
+    let deps_dir = deps_dir()?;
+    let mut latest: Option<(SystemTime, PathBuf)> = None;
+    for entry in deps_dir.read_dir()?.filter_map(Result::ok) {
+        let matches = entry.file_name().to_str().is_some_and(&pattern);
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !matches || !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        if latest.as_ref().map_or(true, |(time, _)| modified >= *time) {
+            latest = Some((modified, entry.path()));
+        }
+    }
+    match latest {
+        Some((_, path)) => Ok(path),
+        None => Err(not_found(format!("no matching artifact in {}", deps_dir.display()))),
+    }
+}
+
+/// Kind of the artifact of an artifact dependency, i.e. the value of its `artifact` key in the Cargo manifest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArtifactKind {
+    /// An executable, i.e. `artifact = "bin"`.
+    Bin,
+    /// A dynamic library, e.g. a plugin, i.e. `artifact = "cdylib"`.
+    Cdylib,
+    /// A static library, i.e. `artifact = "staticlib"`, whose imports may be scanned like those of an `.rlib`.
+    Staticlib,
+}
+
+impl ArtifactKind {
+    // Name of the kind in the environment variables set by Cargo.
+    fn env_name(self) -> &'static str {
+        match self {
+            ArtifactKind::Bin => "BIN",
+            ArtifactKind::Cdylib => "CDYLIB",
+            ArtifactKind::Staticlib => "STATICLIB",
+        }
+    }
+}
+
+/// Returns the path of the artifact of an artifact dependency of the package being built, from the
+/// `CARGO_<KIND>_FILE_<DEP>` environment variable that Cargo sets for its build script.
+///
+/// `dep` is the name of the dependency in the manifest, e.g. `plugin` for
+/// `plugin = { path = "../plugin", artifact = "cdylib" }`.  For binaries, it's the dependency whose binary has the
+/// same name as the package.
+///
+/// Note that the `lib` of a dependency, i.e. its `.rlib`, is not an artifact; use [`rlib`] to locate it.
+pub fn artifact(kind: ArtifactKind, dep: &str) -> Result<PathBuf, Error> {
+    let var = format!(
+        "CARGO_{}_FILE_{}",
+        kind.env_name(),
+        dep.to_uppercase().replace('-', "_")
+    );
+    match env::var_os(&var) {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => Err(not_found(format!(
+            "{var} is not set; is {dep} an artifact dependency of this package?"
+        ))),
+    }
+}
+
+/// Returns the path of the dynamic library built from the crate `name`, e.g. a `cdylib` plugin, for the target being
+/// built.
+///
+/// If `name` is a `cdylib` [artifact dependency](artifact), its artifact is returned.  Otherwise, dashes in the
+/// crate name are replaced with underscores, as Cargo does for file names, and the library is looked up under its
+/// [candidate file names](dylib_candidates) for the target, in the dependencies directory, then in the profile
+/// directory.
+pub fn dylib(name: &str) -> Result<PathBuf, Error> {
+    if let Ok(path) = artifact(ArtifactKind::Cdylib, name) {
+        return Ok(path);
+    }
+    let name = name.replace('-', "_");
+    let candidates = dylib_candidates(&util::current_target(), &name, &[]);
+    let profile_dir = profile_dir()?;
+    for dir in [profile_dir.join("deps"), profile_dir] {
+        let path = candidates.iter().map(|file_name| dir.join(file_name)).find(|path| path.is_file());
+        if let Some(path) = path {
+            return Ok(path);
+        }
+    }
+    Err(not_found(format!(
+        "dynamic library {name} was not found; is it a dependency of this package?"
+    )))
+}
+
+/// Returns the path of the most recently built Rust static library (`.rlib`) of the crate `name`.
+pub fn rlib(name: &str) -> Result<PathBuf, Error> {
+    let name = name.replace('-', "_");
+    let prefix = format!("lib{name}-");
+    latest_artifact(|file_name| {
+        file_name.ends_with(".rlib") && (file_name.starts_with(&prefix) || file_name == format!("lib{name}.rlib"))
+    })
+}
+
+// Returns the root of the workspace of the package being built, i.e. the furthest ancestor of its manifest directory
+// with a Cargo manifest.
+fn workspace_root() -> Result<PathBuf, Error> {
+    let manifest_dir = match env::var_os("CARGO_MANIFEST_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => env::current_dir()?,
+    };
+    let root = manifest_dir.ancestors().filter(|dir| dir.join("Cargo.toml").is_file()).last();
+    Ok(root.unwrap_or(&manifest_dir).to_path_buf())
+}
+
+fn not_found(message: String) -> Error {
+    io::Error::new(io::ErrorKind::NotFound, message).into()
+}
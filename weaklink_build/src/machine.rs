@@ -0,0 +1,198 @@
+//! Validation that scanned binaries are built for the configured target.
+
+use std::fmt;
+use std::path::Path;
+
+use goblin::{elf, mach, Object};
+
+use crate::exports::is_arm64ec;
+use crate::{Config, Error};
+
+// Constants missing from Goblin.
+const EM_LOONGARCH: u16 = 258;
+const CPU_SUBTYPE_ARM64E: u32 = 2;
+
+/// Architecture of a binary or of a target: the instruction set, the pointer width and the byte order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Machine {
+    /// Name of the instruction set, as in the first component of target triples, e.g. `x86_64` or `aarch64`.
+    ///
+    /// 32-bit x86 is named `x86`, and variants of 32-bit Arm are all named `arm`.  Arm64 with pointer authentication
+    /// is named `arm64e`.
+    pub arch: &'static str,
+    /// Width of pointers in bits.
+    pub pointer_width: u32,
+    /// Whether the byte order is big-endian.
+    pub big_endian: bool,
+}
+
+impl Machine {
+    /// Returns the architecture of a target triple, or `None` if it's not known.
+    ///
+    /// ```
+    /// use weaklink_build::Machine;
+    /// let machine = Machine::of_target("aarch64-unknown-linux-gnu").unwrap();
+    /// assert_eq!((machine.arch, machine.pointer_width, machine.big_endian), ("aarch64", 64, false));
+    /// ```
+    pub fn of_target(target: &str) -> Option<Machine> {
+        let arch = target.split('-').next().unwrap_or_default();
+        let (name, pointer_width, big_endian) = match arch {
+            "x86_64" | "x86_64h" => ("x86_64", 64, false),
+            "i386" | "i586" | "i686" => ("x86", 32, false),
+            "aarch64" => ("aarch64", 64, false),
+            "aarch64_be" => ("aarch64", 64, true),
+            "arm64e" => ("arm64e", 64, false),
+            "arm64ec" => ("arm64ec", 64, false),
+            "loongarch64" => ("loongarch64", 64, false),
+            "powerpc64le" => ("powerpc64", 64, false),
+            "powerpc64" => ("powerpc64", 64, true),
+            "powerpc" => ("powerpc", 32, true),
+            "s390x" => ("s390x", 64, true),
+            "mips64el" => ("mips64", 64, false),
+            "mips64" => ("mips64", 64, true),
+            "mipsel" => ("mips", 32, false),
+            "mips" => ("mips", 32, true),
+            _ if arch.starts_with("riscv64") => ("riscv64", 64, false),
+            _ if arch.starts_with("riscv32") => ("riscv32", 32, false),
+            _ if arch.starts_with("arm") || arch.starts_with("thumb") => ("arm", 32, arch.contains("eb")),
+            _ => return None,
+        };
+        Some(Machine {
+            arch: name,
+            pointer_width,
+            big_endian,
+        })
+    }
+
+    /// Returns the architectures of a parsed binary: one for most binaries, all of them for universal Mach-O
+    /// binaries, and that of the first object file of static libraries.
+    pub fn of_object(object: &Object, buffer: &[u8]) -> Result<Vec<Machine>, Error> {
+        let mut machines = Vec::new();
+        match object {
+            Object::Elf(elf) => {
+                let arch = match (elf.header.e_machine, elf.is_64) {
+                    (elf::header::EM_X86_64, _) => "x86_64",
+                    (elf::header::EM_386, _) => "x86",
+                    (elf::header::EM_AARCH64, _) => "aarch64",
+                    (elf::header::EM_ARM, _) => "arm",
+                    (EM_LOONGARCH, true) => "loongarch64",
+                    (EM_LOONGARCH, false) => "loongarch32",
+                    (elf::header::EM_RISCV, true) => "riscv64",
+                    (elf::header::EM_RISCV, false) => "riscv32",
+                    (elf::header::EM_PPC64, _) => "powerpc64",
+                    (elf::header::EM_PPC, _) => "powerpc",
+                    (elf::header::EM_S390, _) => "s390x",
+                    (elf::header::EM_MIPS, true) => "mips64",
+                    (elf::header::EM_MIPS, false) => "mips",
+                    (machine, _) => return Err(format!("unknown ELF machine {machine}").into()),
+                };
+                machines.push(Machine {
+                    arch,
+                    pointer_width: if elf.is_64 { 64 } else { 32 },
+                    big_endian: !elf.little_endian,
+                });
+            }
+            Object::Mach(mach::Mach::Binary(macho)) => {
+                machines.push(macho_machine(macho.header.cputype, macho.header.cpusubtype)?);
+            }
+            Object::Mach(mach::Mach::Fat(multi)) => {
+                for arch in multi.iter_arches() {
+                    let arch = arch?;
+                    machines.push(macho_machine(arch.cputype, arch.cpusubtype)?);
+                }
+            }
+            Object::PE(pe) => machines.push(coff_machine(pe.header.coff_header.machine, is_arm64ec(object))?),
+            Object::COFF(coff) => machines.push(coff_machine(coff.header.machine, is_arm64ec(object))?),
+            Object::Archive(archive) => {
+                // Members that aren't object files, e.g. the metadata of rlibs, are skipped.
+                let members = archive
+                    .members()
+                    .into_iter()
+                    .filter_map(|mbr_name| archive.extract(mbr_name, buffer).ok());
+                let mut members = members.filter_map(|slice| Some((Object::parse(slice).ok()?, slice)));
+                if let Some((member, slice)) = members.next() {
+                    machines = Machine::of_object(&member, slice)?;
+                }
+            }
+            _ => return Err(format!("Unsupported object type: {object:?}").into()),
+        }
+        Ok(machines)
+    }
+
+    // Returns true if code of this machine may be used by code of the target's machine.
+    fn runs_on(&self, target: &Machine) -> bool {
+        // Images of ARM64EC programs may be x86_64 or ARM64X (i.e. Arm64) images.
+        self == target || (target.arch == "arm64ec" && matches!(self.arch, "x86_64" | "aarch64" | "arm64ec"))
+    }
+}
+
+impl fmt::Display for Machine {
+    /// Formats the machine as e.g. `aarch64 (64-bit, little-endian)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let byte_order = if self.big_endian { "big-endian" } else { "little-endian" };
+        write!(f, "{} ({}-bit, {byte_order})", self.arch, self.pointer_width)
+    }
+}
+
+fn macho_machine(cputype: u32, cpusubtype: u32) -> Result<Machine, Error> {
+    use mach::cputype::*;
+    let arch = match cputype {
+        CPU_TYPE_X86_64 => "x86_64",
+        CPU_TYPE_X86 => "x86",
+        CPU_TYPE_ARM64 if cpusubtype & !CPU_SUBTYPE_MASK == CPU_SUBTYPE_ARM64E => "arm64e",
+        CPU_TYPE_ARM64 => "aarch64",
+        CPU_TYPE_ARM => "arm",
+        CPU_TYPE_POWERPC64 => "powerpc64",
+        CPU_TYPE_POWERPC => "powerpc",
+        _ => return Err(format!("unknown Mach-O CPU type {cputype:#x}").into()),
+    };
+    Ok(Machine {
+        arch,
+        pointer_width: if cputype & CPU_ARCH_ABI64 != 0 { 64 } else { 32 },
+        big_endian: arch.starts_with("powerpc"),
+    })
+}
+
+fn coff_machine(machine: u16, arm64ec: bool) -> Result<Machine, Error> {
+    use goblin::pe::header::*;
+    let (arch, pointer_width) = match machine {
+        _ if arm64ec => ("arm64ec", 64),
+        COFF_MACHINE_X86_64 => ("x86_64", 64),
+        COFF_MACHINE_X86 => ("x86", 32),
+        COFF_MACHINE_ARM64 => ("aarch64", 64),
+        COFF_MACHINE_ARMNT | COFF_MACHINE_ARM | COFF_MACHINE_THUMB => ("arm", 32),
+        _ => return Err(format!("unknown COFF machine {machine:#x}").into()),
+    };
+    Ok(Machine {
+        arch,
+        pointer_width,
+        big_endian: false,
+    })
+}
+
+impl Config {
+    /// Checks that a scanned binary, e.g. a build of the wrapped library or a static library of client code, is
+    /// built for [`Config::target`], failing with a description of both architectures otherwise.
+    ///
+    /// Symbols of binaries built for another target may well differ, e.g. in their decoration, which otherwise
+    /// leads to confusing failures at runtime.  Binaries whose architecture, or that of the target, is not known
+    /// pass.  This is checked by the methods of `Config` that scan binaries.
+    pub fn check_binary(&self, path: &Path) -> Result<(), Error> {
+        let Some(target) = Machine::of_target(&self.target) else {
+            return Ok(());
+        };
+        let buffer = std::fs::read(path)?;
+        let machines = Machine::of_object(&Object::parse(&buffer)?, &buffer).unwrap_or_default();
+        match machines.iter().find(|machine| !machine.runs_on(&target)) {
+            // Universal binaries need a slice of the target only.
+            Some(_) if machines.iter().any(|machine| machine.runs_on(&target)) => Ok(()),
+            Some(machine) => Err(format!(
+                "{} is built for {machine}, but the target {} is {target}; was it built for another target?",
+                path.display(),
+                self.target
+            )
+            .into()),
+            None => Ok(()),
+        }
+    }
+}
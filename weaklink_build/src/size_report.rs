@@ -0,0 +1,173 @@
+//! Estimates of the size of the generated code and data.
+
+use std::fmt;
+
+use crate::stub_gen::TargetOs;
+use crate::Config;
+
+// Estimated size of the Rust accessor function of a data stub.
+const DATA_ACCESSOR_SIZE: usize = 32;
+
+/// Estimated size in bytes of the code and data generated for a group, or for the whole library.
+/// See [`Config::size_report`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GroupSize {
+    /// Name of the group, or of the library.
+    pub name: String,
+    /// Number of symbols, including optional ones.
+    pub symbols: usize,
+    /// Code of the stubs and of the data accessors.
+    pub stubs: usize,
+    /// Entries of the symbol table and of the auxiliary tables.
+    pub table: usize,
+    /// Symbol names and their lookup tables.
+    pub names: usize,
+}
+
+impl GroupSize {
+    /// Returns the total size.
+    pub fn total(&self) -> usize {
+        self.stubs + self.table + self.names
+    }
+}
+
+/// Estimated size of the code and data generated by [`Config::generate_source`].  See [`Config::size_report`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SizeReport {
+    /// Target triple the estimates are for.
+    pub target: String,
+    /// Size attributable to each group, sorted by name.  Symbols shared by several groups are counted in each.
+    pub groups: Vec<GroupSize>,
+    /// Size of the whole library, counting each symbol once.
+    pub library: GroupSize,
+}
+
+impl fmt::Display for SizeReport {
+    /// Formats the report as a table, with the library total in the last row.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Estimated size of {} for {}:", self.library.name, self.target)?;
+        writeln!(
+            f,
+            "{:<24} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "group", "symbols", "stubs", "table", "names", "total"
+        )?;
+        for size in self.groups.iter().chain([&self.library]) {
+            writeln!(
+                f,
+                "{:<24} {:>8} {:>8} {:>8} {:>8} {:>8}",
+                size.name,
+                size.symbols,
+                size.stubs,
+                size.table,
+                size.names,
+                size.total()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Estimates the size of the code and data that [`Config::generate_source`] would generate for the target,
+    /// broken down by group.
+    ///
+    /// This helps to decide which parts of a large API to trim before wrapping it.  The estimates are based on
+    /// the stub size of the target's [`StubGenerator`](crate::stub_gen::StubGenerator), and on the storage of the
+    /// symbol names as configured by [`Config::group_name_tables`], [`Config::compress_names`],
+    /// [`Config::hash_names`] and [`Config::obfuscation_key`].
+    ///
+    /// ```
+    /// # use weaklink_build::{Config, SymbolStub};
+    /// let mut config = Config::new("foo");
+    /// config.target = "x86_64-unknown-linux-gnu".to_string();
+    /// config.group("base").add("foo_init").add("foo_exit");
+    /// config.group("extras").add("foo_init").add_data("get_foo_version", "foo_version");
+    /// let report = config.size_report();
+    /// assert_eq!(report.groups.len(), 2);
+    /// assert_eq!(report.library.symbols, 3);
+    /// assert!(report.library.total() < report.groups.iter().map(|size| size.total()).sum());
+    /// println!("{report}");
+    /// ```
+    pub fn size_report(&self) -> SizeReport {
+        let target_os = TargetOs::from_target(&self.target).expect("Unsupported OS");
+        let ptr_size = if self.target.starts_with("arm") { 4 } else { 8 };
+        let in_groups = |grp_names: &[String], idx: usize| {
+            grp_names
+                .iter()
+                .any(|grp_name| self.groups.get(grp_name).is_some_and(|indices| indices.contains(&idx)))
+        };
+        let has_patch_sites = (0..self.stubs.len()).any(|idx| in_groups(&self.patched_groups, idx));
+
+        let mut grp_names = self.groups.keys().collect::<Vec<_>>();
+        grp_names.sort();
+        let (groups, library) = self.with_stub_generator(target_os, |stub_gen| {
+            // Size of the given stubs, except for the storage of their names
+            let size_of = |name: &str, indices: &[usize]| {
+                let mut size = GroupSize {
+                    name: name.to_string(),
+                    symbols: indices.len(),
+                    ..GroupSize::default()
+                };
+                for idx in indices {
+                    let stub = &self.stubs[*idx];
+                    size.table += ptr_size;
+                    if has_patch_sites {
+                        size.table += ptr_size;
+                    }
+                    if stub.is_data {
+                        size.stubs += DATA_ACCESSOR_SIZE;
+                    } else if in_groups(&self.native_weak_groups, *idx) {
+                        size.table += 2 * ptr_size;
+                    } else {
+                        size.stubs += stub_gen.stub_size(in_groups(&self.patched_groups, *idx));
+                    }
+                    // A perfect hash slot, and the variant names with their indices
+                    size.names += 4;
+                    for (_, variant_name) in &stub.variant_names {
+                        size.names += variant_name.len() + 1 + 3 * ptr_size;
+                    }
+                }
+                size
+            };
+            // Storage of the names of the given stubs
+            let name_storage = |indices: &[usize]| {
+                let names = indices.iter().map(|idx| self.stubs[*idx].import_name.as_str()).collect::<Vec<_>>();
+                if self.hash_names {
+                    8 * names.len()
+                } else if let Some(key) = self.obfuscation_key {
+                    let (offsets, blob) = weaklink::obfuscated_names::obfuscate(&names, key);
+                    4 * offsets.len() + blob.len()
+                } else if self.compress_names {
+                    let (blob, order) = weaklink::compressed_names::encode(&names);
+                    blob.len() + 4 * order.len()
+                } else {
+                    names.iter().map(|name| name.len() + 1 + 2 * ptr_size).sum::<usize>()
+                }
+            };
+
+            let mut library = size_of(&self.name, &(0..self.stubs.len()).collect::<Vec<_>>());
+            let mut groups = Vec::new();
+            for grp_name in grp_names {
+                let mut indices = self.groups[grp_name].clone();
+                let optional = self.optional_symbols.get(grp_name).into_iter().flatten();
+                indices.extend(optional.filter(|idx| !self.groups[grp_name].contains(idx)));
+                let mut size = size_of(grp_name, &indices);
+                size.names += name_storage(&indices);
+                if self.group_name_tables {
+                    // Names are stored with each group that a symbol belongs to.
+                    library.names += name_storage(&indices);
+                }
+                groups.push(size);
+            }
+            if !self.group_name_tables {
+                library.names += name_storage(&(0..self.stubs.len()).collect::<Vec<_>>());
+            }
+            (groups, library)
+        });
+        SizeReport {
+            target: self.target.clone(),
+            groups,
+            library,
+        }
+    }
+}
@@ -29,6 +29,30 @@ impl super::StubGenerator for Aarch64StubGenerator {
         }
     }
 
+    fn stub_size(&self, patchable: bool) -> usize {
+        if patchable {
+            16
+        } else {
+            12
+        }
+    }
+
+    fn supports_patching(&self) -> bool {
+        true
+    }
+
+    fn text_section_directive(&self, group: Option<&str>) -> String {
+        super::text_section_directive(&self.target_os, group)
+    }
+
+    fn weak_reference_directive(&self) -> &str {
+        if self.target_os == TargetOs::MacOS {
+            ".weak_reference"
+        } else {
+            ".weak"
+        }
+    }
+
     fn asm_symbol_prefix(&self) -> &str {
         if self.target_os == TargetOs::MacOS  {
             "_"
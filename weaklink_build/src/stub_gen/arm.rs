@@ -17,6 +17,19 @@ impl super::StubGenerator for ArmStubGenerator {
         );
     }
 
+    fn stub_size(&self, _patchable: bool) -> usize {
+        // Four instructions and the literal pool.
+        20
+    }
+
+    fn text_section_directive(&self, group: Option<&str>) -> String {
+        // '@' starts a comment on ARM.
+        match group {
+            Some(group) => format!(".section .text.weaklink.{group},\\\"ax\\\",%progbits"),
+            None => ".text".to_string(),
+        }
+    }
+
     fn data_ptr_directive(&self) -> &str {
         ".long"
     }
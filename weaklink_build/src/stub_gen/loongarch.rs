@@ -16,6 +16,10 @@ impl super::StubGenerator for LoongArchStubGenerator {
         );
     }
 
+    fn stub_size(&self, _patchable: bool) -> usize {
+        12
+    }
+
     fn asm_symbol_prefix(&self) -> &str {
             ""
     }
@@ -1,61 +1,230 @@
+//! Generation of stub code for the target architecture.
+//!
+//! Architectures that aren't supported out of the box may be added by implementing [`StubGenerator`], and registering
+//! the implementation with [`Config::register_generator`](crate::Config::register_generator).
+
 use crate::util::iter_fmt;
 use crate::SymbolStub;
 use std::io::Write;
 
-#[derive(PartialEq, Eq)]
-pub(crate) enum TargetOs {
+/// Operating system of the target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TargetOs {
     Linux,
     MacOS,
     Windows,
 }
 
-pub(crate) trait StubGenerator {
-    fn generate(&self, text: &mut dyn Write, symbols: &[SymbolStub], symbol_table: &str) {
+impl TargetOs {
+    /// Returns the operating system of a target triple, if supported.
+    pub fn from_target(target: &str) -> Option<TargetOs> {
+        if target.contains("linux") {
+            Some(TargetOs::Linux)
+        } else if target.contains("apple") {
+            Some(TargetOs::MacOS)
+        } else if target.contains("windows") {
+            Some(TargetOs::Windows)
+        } else {
+            None
+        }
+    }
+}
+
+// Placement of the symbol table and of the stubs.
+pub(crate) struct Layout<'a> {
+    // Section directive of the symbol table.
+    pub table_section: &'a str,
+    // Alignment of the start and of the end of the table.
+    pub p2align: u32,
+    // Group whose text section each stub is placed in, or `None` for the default text section.
+    pub stub_groups: &'a [Option<&'a str>],
+    // Predicate of the `#[cfg]` attribute of each stub, if compiled conditionally.
+    pub stub_cfgs: &'a [Option<String>],
+}
+
+/// Returns the section directive for the stubs of a group on the specified OS, which may be used to implement
+/// [`StubGenerator::text_section_directive`].
+///
+/// Stubs are placed in `.text.weaklink.<group>` on Linux and in `.text$weaklink_<group>` on Windows.
+pub fn text_section_directive(target_os: &TargetOs, group: Option<&str>) -> String {
+    match (target_os, group) {
+        (TargetOs::Linux, Some(group)) => format!(".section .text.weaklink.{group},\\\"ax\\\",@progbits"),
+        // Grouped sections, which the linker merges into .text in the order of their suffixes.
+        (TargetOs::Windows, Some(group)) => format!(".section .text$weaklink_{group},\\\"xr\\\""),
+        // Mach-O section names are too short to embed group names.
+        _ => ".text".to_string(),
+    }
+}
+
+// How calls to a symbol are dispatched.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StubKind {
+    // Via a stub that jumps through the symbol table.
+    Table,
+    // Like `Table`, but the runtime may patch the stub into a direct jump.
+    Patchable,
+    // Without a stub: callers are bound by the OS dynamic linker, and the symbol is weakly referenced.
+    NativeWeak,
+}
+
+// Emit the symbol table, the stubs, and the auxiliary tables used by the runtime.
+pub(crate) fn generate(
+    stub_gen: &dyn StubGenerator,
+    text: &mut dyn Write,
+    library: &str,
+    symbols: &[SymbolStub],
+    symbol_table: &str,
+    layout: &Layout,
+    kinds: &[StubKind],
+) {
+    write_lines!(text,
+        "global_asm!{{\""
+        "{table_section}"
+        ".p2align {table_p2align}, 0x0"
+        "{pfx}{symbol_table}:"
+        "{aliases}"
+        "{entries}"
+        ".p2align {table_p2align}, 0x0"
+        "\"}}",
+        aliases = stub_gen.table_alias_directives(symbol_table),
+        table_section = layout.table_section,
+        table_p2align = layout.p2align,
+        pfx = stub_gen.asm_symbol_prefix(),
+        symbol_table = symbol_table,
+        entries = iter_fmt(symbols.iter().enumerate(), |f, (idx, sym)| {
+            let dir = stub_gen.data_ptr_directive();
+            writeln!(f, "    {dir} 0")
+        }
+    ));
+
+    for (i, symbol) in symbols.iter().enumerate() {
+        if kinds[i] == StubKind::NativeWeak {
+            continue;
+        }
+        let patchable = kinds[i] == StubKind::Patchable;
+        if let Some(predicate) = &layout.stub_cfgs[i] {
+            writeln!(text, "#[cfg({predicate})]");
+        }
+        if !symbol.is_data {
+            write_lines!(text,
+                "global_asm!{{\""
+                "{text_section}"
+                ".p2align {p2align}, 0x0"
+                ".global \\\"{symbol}\\\"" // Will be unescaped the 2nd time when compiling the generated module.
+                //".type   \\\"{symbol}\\\", function"
+                "\\\"{symbol}\\\":",
+                // Patch sites are aligned, so that they can be overwritten atomically
+                text_section = stub_gen.text_section_directive(layout.stub_groups[i]),
+                p2align = if patchable { 4 } else { 2 },
+                symbol = symbol.export_name
+            );
+            stub_gen.write_fn_stub(text, symbol_table, i);
+            if patchable {
+                stub_gen.write_patch_space(text);
+            }
+            writeln!(text, "\"}}");
+        } else {
+            write_lines!(text,
+                "#[no_mangle]"
+                "pub extern \"C\" fn {symbol}() -> Address {{"
+                "    {library}.data_address({index})"
+                "}}",
+                symbol = symbol.export_name,
+                library = library,
+                index = i
+            );
+        }
+    }
+
+    if kinds.contains(&StubKind::Patchable) {
         write_lines!(text,
             "global_asm!{{\""
             ".data"
-            ".p2align 2, 0x0"
-            "{pfx}{symbol_table}:"
+            ".p2align 3, 0x0"
+            "{pfx}{symbol_table}_patch_sites:"
             "{entries}"
             "\"}}",
-            pfx = self.asm_symbol_prefix(),
+            pfx = stub_gen.asm_symbol_prefix(),
             symbol_table = symbol_table,
-            entries = iter_fmt(symbols.iter().enumerate(), |f, (idx, sym)| {
-                let dir = self.data_ptr_directive();
-                writeln!(f, "    {dir} 0")
+            entries = iter_fmt(symbols.iter().enumerate(), |f, (i, sym)| {
+                let dir = stub_gen.data_ptr_directive();
+                match kinds[i] {
+                    StubKind::Patchable => writeln!(f, "    {dir} \\\"{}\\\"", sym.export_name),
+                    _ => writeln!(f, "    {dir} 0"),
+                }
             }
         ));
+    }
 
-        for (i, symbol) in symbols.iter().enumerate() {
-            if !symbol.is_data {
-                write_lines!(text,
-                    "global_asm!{{\""
-                    ".text"
-                    ".p2align 2, 0x0"
-                    ".global \\\"{symbol}\\\"" // Will be unescaped the 2nd time when compiling the generated module.
-                    //".type   \\\"{symbol}\\\", function"
-                    "\\\"{symbol}\\\":",
-                    symbol = symbol.export_name
-                );
-                self.write_fn_stub(text, symbol_table, i);
-                writeln!(text, "\"}}");
-            } else {
-                write_lines!(text,
-                    "#[no_mangle]"
-                    "pub extern \"C\" fn {symbol}() -> Address {{"
-                    "    unsafe {{ {symbol_table}[{index}] as Address }}"
-                    "}}",
-                    symbol = symbol.export_name,
-                    symbol_table = symbol_table,
-                    index = i
-                );
+    // Pairs of symbol index and address, the latter being 0 if the symbol is absent.
+    if kinds.contains(&StubKind::NativeWeak) {
+        write_lines!(text,
+            "global_asm!{{\""
+            ".data"
+            ".p2align 3, 0x0"
+            "{pfx}{symbol_table}_native:"
+            "{entries}"
+            "\"}}",
+            pfx = stub_gen.asm_symbol_prefix(),
+            symbol_table = symbol_table,
+            entries = iter_fmt(symbols.iter().enumerate(), |f, (i, sym)| {
+                let dir = stub_gen.data_ptr_directive();
+                match kinds[i] {
+                    StubKind::NativeWeak => {
+                        writeln!(f, "    {} \\\"{}\\\"", stub_gen.weak_reference_directive(), sym.export_name)?;
+                        writeln!(f, "    {dir} {i}")?;
+                        writeln!(f, "    {dir} \\\"{}\\\"", sym.export_name)
+                    }
+                    _ => Ok(()),
+                }
             }
-        }
+        ));
+    }
+}
+
+/// Emits the architecture-specific parts of the stubs.
+///
+/// All code is emitted into `global_asm!` string literals, so double quotes must be escaped as `\"`.  The symbol
+/// table is an array of pointer-sized entries, which the runtime fills with the addresses of resolved symbols.
+pub trait StubGenerator {
+    /// Whether the runtime can patch stubs of this architecture into direct jumps.
+    ///
+    /// The runtime relies on the layout of the built-in x86_64 and aarch64 stubs, so custom generators should
+    /// keep the default.
+    fn supports_patching(&self) -> bool {
+        false
+    }
+
+    /// Emit space needed by the runtime for patching the preceding stub, if any.
+    fn write_patch_space(&self, _text: &mut dyn Write) {}
+
+    /// Estimated size in bytes of a function stub, including alignment padding, for
+    /// [`Config::size_report`](crate::Config::size_report).
+    fn stub_size(&self, _patchable: bool) -> usize {
+        16
     }
 
     /// Emit code that loads index'th entry from the symbol table and jumps to that address.
+    ///
+    /// The entry must be read with a single pointer-sized load, so that it is atomic with respect to updates
+    /// made by the runtime.
     fn write_fn_stub(&self, text: &mut dyn Write, symtab_base: &str, index: usize);
 
+    /// Directives defining aliases of the symbol table, if the stubs reference it through any.
+    fn table_alias_directives(&self, _symbol_table: &str) -> String {
+        String::new()
+    }
+
+    /// Section directive for the stubs of a group, or for stubs that aren't placed per group.
+    fn text_section_directive(&self, group: Option<&str>) -> String {
+        text_section_directive(&TargetOs::Linux, group)
+    }
+
+    /// Directive declaring a weak reference to a symbol, which is bound to null if the symbol is absent.
+    fn weak_reference_directive(&self) -> &str {
+        ".weak"
+    }
+
     /// Declaration directive for pointer-sized data.
     fn data_ptr_directive(&self) -> &str {
         ".quad"
@@ -8,21 +8,58 @@ pub struct X64StubGenerator {
 
 impl super::StubGenerator for X64StubGenerator {
     fn write_fn_stub(&self, text: &mut dyn Write, symtab_base: &str, index: usize) {
-        if self.target_os == TargetOs::Windows {
-            write_lines!(
-                text,
-                "   jmp qword ptr [rip + {symtab_base} + {offset}]",
-                symtab_base = symtab_base,
-                offset = index * 8
-            );
+        // Under PIC, the hidden alias keeps the reference to the table from going through the GOT.
+        let suffix = if self.target_os == TargetOs::Windows { "" } else { "_local" };
+        write_lines!(
+            text,
+            "    jmp qword ptr [rip + {pfx}{symtab_base}{suffix} + {offset}]",
+            pfx = self.asm_symbol_prefix(),
+            symtab_base = symtab_base,
+            suffix = suffix,
+            offset = index * 8
+        );
+    }
+
+    fn stub_size(&self, patchable: bool) -> usize {
+        // A 6-byte jump, followed by the far jump literal if patchable.
+        if patchable {
+            24
+        } else {
+            8
+        }
+    }
+
+    fn table_alias_directives(&self, symbol_table: &str) -> String {
+        let pfx = self.asm_symbol_prefix();
+        let visibility = match self.target_os {
+            TargetOs::Linux => ".hidden",
+            TargetOs::MacOS => ".private_extern",
+            TargetOs::Windows => return String::new(),
+        };
+        format!("{visibility} {pfx}{symbol_table}_local\n{pfx}{symbol_table}_local:")
+    }
+
+    fn supports_patching(&self) -> bool {
+        true
+    }
+
+    fn write_patch_space(&self, text: &mut dyn Write) {
+        // Literal at offset 16 for far jumps.
+        write_lines!(text,
+            "    .p2align 4, 0xcc"
+            "    .quad 0"
+        );
+    }
+
+    fn text_section_directive(&self, group: Option<&str>) -> String {
+        super::text_section_directive(&self.target_os, group)
+    }
+
+    fn weak_reference_directive(&self) -> &str {
+        if self.target_os == TargetOs::MacOS {
+            ".weak_reference"
         } else {
-            write_lines!(text,
-                "    mov r11, [rip + {pfx}{symtab_base}@GOTPCREL]"
-                "    jmp [r11 + {offset}]",
-                pfx=self.asm_symbol_prefix(),
-                symtab_base = symtab_base,
-                offset = index * 8
-            );
+            ".weak"
         }
     }
 
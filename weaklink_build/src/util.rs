@@ -20,10 +20,103 @@ where
 {
     LazyFmt(move |f| {
         for item in iterable.clone() {
-            if let Err(err) = fmt_fn(f, item) {
-                return Err(err);
-            }
+            fmt_fn(f, item)?;
         }
         Ok(())
     })
 }
+
+// Returns `name` as a Rust identifier, or `None` if it isn't a valid one.
+pub fn rust_ident(name: &str) -> Option<String> {
+    const KEYWORDS: &[&str] = &[
+        "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn", "else", "enum",
+        "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "macro", "match", "mod",
+        "move", "mut", "override", "priv", "pub", "ref", "return", "static", "struct", "trait", "true", "try", "type",
+        "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+    ];
+    const RESERVED: &[&str] = &["_", "crate", "self", "Self", "super"];
+
+    let mut chars = name.chars();
+    let first = chars.next()?;
+    let is_valid =
+        (first.is_ascii_alphabetic() || first == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_valid || RESERVED.contains(&name) {
+        None
+    } else if KEYWORDS.contains(&name) {
+        Some(format!("r#{name}"))
+    } else {
+        Some(name.to_string())
+    }
+}
+
+// Builds a perfect hash of `names` for `weaklink::NameIndex`, returning bucket displacements and slot contents.
+pub fn perfect_hash(names: &[&str]) -> (Vec<u32>, Vec<u32>) {
+    use std::collections::HashSet;
+    use weaklink::name_index::hash;
+
+    // Duplicate names can't be told apart; the first one wins.
+    let mut seen = HashSet::new();
+    let entries = names.iter().enumerate().filter(|(_, name)| seen.insert(*name)).collect::<Vec<_>>();
+
+    let num_buckets = (entries.len() + 3) / 4;
+    let mut buckets = vec![Vec::new(); num_buckets];
+    for (idx, name) in entries {
+        buckets[(hash(name.as_bytes(), 0) % num_buckets as u64) as usize].push((idx, *name));
+    }
+    let mut order = (0..num_buckets).collect::<Vec<_>>();
+    order.sort_by_key(|bucket| std::cmp::Reverse(buckets[*bucket].len()));
+
+    // Place the largest buckets first, while there is the most freedom.
+    let mut displacements = vec![0; num_buckets];
+    let mut slots = vec![None; seen.len()];
+    let mut bucket_slots = Vec::new();
+    for bucket in order {
+        for displacement in 1.. {
+            bucket_slots.clear();
+            for (idx, name) in &buckets[bucket] {
+                let slot = (hash(name.as_bytes(), displacement) % slots.len() as u64) as usize;
+                if slots[slot].is_some() || bucket_slots.iter().any(|(s, _)| *s == slot) {
+                    break;
+                }
+                bucket_slots.push((slot, *idx));
+            }
+            if bucket_slots.len() == buckets[bucket].len() {
+                displacements[bucket] = displacement;
+                break;
+            }
+        }
+        for (slot, idx) in &bucket_slots {
+            slots[*slot] = Some(*idx as u32);
+        }
+    }
+    (displacements, slots.into_iter().map(|idx| idx.unwrap()).collect())
+}
+
+// Returns the current cargo build target, or the host target outside of build scripts.
+pub fn current_target() -> String {
+    match std::env::var("TARGET") {
+        Ok(target) => target,
+        Err(_) => env!("TARGET").to_string(), // Fall back to host target
+    }
+}
+
+// Reports the time taken until it's dropped as a cargo warning, if `WEAKLINK_BUILD_TIMINGS` is set, for profiling
+// build scripts.
+pub struct Timing(Option<(String, std::time::Instant)>);
+
+impl Timing {
+    pub fn start(label: impl FnOnce() -> String) -> Timing {
+        match std::env::var_os("WEAKLINK_BUILD_TIMINGS") {
+            Some(_) => Timing(Some((label(), std::time::Instant::now()))),
+            None => Timing(None),
+        }
+    }
+}
+
+impl Drop for Timing {
+    fn drop(&mut self) {
+        if let Some((label, start)) = &self.0 {
+            println!("cargo:warning=weaklink: {label} took {:.1} ms", start.elapsed().as_secs_f64() * 1000.0);
+        }
+    }
+}
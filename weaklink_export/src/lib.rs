@@ -0,0 +1,80 @@
+//! This crate helps authors of plugins that are loaded by hosts using weaklink stubs.
+//!
+//! It exports what the checks of the host consume, following the conventions of the weaklink runtime:
+//! - [`plugin_version!`] exports the version of the plugin, which groups with a minimum version are checked against
+//!   (see `weaklink_build::Config::set_min_version`).
+//! - [`export_signatures!`] exports the signature hashes of groups, which are checked by stubs generated with
+//!   `weaklink_build::Config::signature_handshake`.
+//! - [`export_manifest!`] exports the [capability manifest](weaklink::PluginManifest) of the plugin.
+//! - [`signature_hash`] computes the hashes reported by the export configured by
+//!   `weaklink_build::Config::signature_query`.
+//!
+//! ## Example
+//! ```rust
+//! weaklink_export::plugin_version!("2.3");
+//!
+//! weaklink_export::export_signatures! {
+//!     "base" => { "foo_init" => "fn() -> i32" },
+//! }
+//!
+//! weaklink_export::export_manifest! {
+//!     version: "2.3",
+//!     groups: ["base"],
+//! }
+//! # fn main() {}
+//! ```
+
+pub use weaklink::{export_signatures, group_signature_hash, signature_hash, GroupSignature};
+
+/// Exports the version of the plugin as `plugin_version`, a NUL-terminated character array.
+///
+/// This is where the runtime detects the version on Linux and macOS.  On Windows, it's detected from the
+/// `VERSIONINFO` resource of the DLL instead.
+#[macro_export]
+macro_rules! plugin_version {
+    ($version:literal) => {
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        pub static plugin_version: [u8; concat!($version, "\0").len()] =
+            $crate::nul_terminated(concat!($version, "\0"));
+    };
+}
+
+/// Exports the capability manifest of the plugin as `weaklink_manifest`: the version of its API, and the groups it
+/// provides.
+///
+/// The manifest is placed in a dedicated section of the binary, so that hosts can check it before loading the plugin
+/// (see [`Library::set_manifest_check`](weaklink::Library::set_manifest_check)).  Once loaded, hosts may read it via
+/// [`PluginManifest::from_module`](weaklink::PluginManifest::from_module).
+#[macro_export]
+macro_rules! export_manifest {
+    (version: $version:literal, groups: [$($group:literal),* $(,)?] $(,)?) => {
+        #[no_mangle]
+        #[allow(non_upper_case_globals)]
+        #[cfg_attr(target_vendor = "apple", link_section = "__DATA,__weaklink")]
+        #[cfg_attr(windows, link_section = ".weaklnk")]
+        #[cfg_attr(not(any(target_vendor = "apple", windows)), link_section = ".weaklink_manifest")]
+        pub static weaklink_manifest: [u8; $crate::manifest_text!($version, $($group),*).len()] =
+            $crate::nul_terminated($crate::manifest_text!($version, $($group),*));
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! manifest_text {
+    ($version:literal, $($group:literal),*) => {
+        concat!("weaklink-manifest 1\nversion ", $version, "\n", $("group ", $group, "\n",)* "\0")
+    };
+}
+
+// Copies a string into an array, which may be exported as a C character array.
+#[doc(hidden)]
+pub const fn nul_terminated<const N: usize>(text: &str) -> [u8; N] {
+    let mut bytes = [0; N];
+    let mut i = 0;
+    while i < N {
+        bytes[i] = text.as_bytes()[i];
+        i += 1;
+    }
+    bytes
+}